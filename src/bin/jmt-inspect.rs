@@ -0,0 +1,9 @@
+//! `jmt-inspect` looks inside a Jellyfish Merkle tree dumped to disk. See [`jmt::inspect`] for
+//! the format of the dumps.
+
+use clap::Parser;
+use jmt::inspect::Cli;
+
+fn main() -> anyhow::Result<()> {
+    Cli::parse().run(&mut std::io::stdout().lock())
+}
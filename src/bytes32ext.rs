@@ -1,5 +1,6 @@
 use core::ops::Index;
 
+#[cfg(feature = "tree")]
 use mirai_annotations::*;
 
 pub trait Bytes32Ext: Index<usize> + Sized {
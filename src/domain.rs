@@ -0,0 +1,80 @@
+//! Domain separation between the trees of different applications.
+//!
+//! Two trees using the same hash function and the same [`Domain`] have interchangeable proofs. An
+//! application can make its proofs meaningless to any other one by hashing its tree with
+//! [`WithDomain`], which replaces the domain separators and the placeholder hash of a
+//! [`SimpleHasher`] by those of a [`Domain`] of its own.
+
+use core::marker::PhantomData;
+
+use crate::{
+    proof::{INTERNAL_DOMAIN_SEPARATOR, LEAF_DOMAIN_SEPARATOR},
+    SimpleHasher, SPARSE_MERKLE_PLACEHOLDER_HASH,
+};
+
+/// The values which set the hashes of a tree apart from those of trees in other domains.
+pub trait Domain {
+    /// Prefixed to the key hash and value hash of a leaf when hashing it.
+    const LEAF_DOMAIN_SEPARATOR: &'static [u8];
+    /// Prefixed to the hashes of the children of an internal node when hashing it.
+    const INTERNAL_DOMAIN_SEPARATOR: &'static [u8];
+    /// The hash of an empty subtree.
+    const SPARSE_MERKLE_PLACEHOLDER_HASH: [u8; 32];
+}
+
+/// The domain of every tree hashed with a plain [`SimpleHasher`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DefaultDomain;
+
+impl Domain for DefaultDomain {
+    const LEAF_DOMAIN_SEPARATOR: &'static [u8] = LEAF_DOMAIN_SEPARATOR;
+    const INTERNAL_DOMAIN_SEPARATOR: &'static [u8] = INTERNAL_DOMAIN_SEPARATOR;
+    const SPARSE_MERKLE_PLACEHOLDER_HASH: [u8; 32] = SPARSE_MERKLE_PLACEHOLDER_HASH;
+}
+
+/// The hash function `H`, hashing trees in the domain `D`.
+///
+/// The domain flows wherever the hasher does, so a `JellyfishMerkleTree<R, WithDomain<H, D>>`
+/// creates, restores and verifies proofs in `D`. Leaves and internal nodes are hashed by `H`, which
+/// only receives the domain separators of `D` instead of its own.
+pub struct WithDomain<H, D> {
+    hasher: H,
+    _phantom_domain: PhantomData<D>,
+}
+
+impl<H: SimpleHasher, D: Domain> SimpleHasher for WithDomain<H, D> {
+    const LEAF_DOMAIN_SEPARATOR: &'static [u8] = D::LEAF_DOMAIN_SEPARATOR;
+    const INTERNAL_DOMAIN_SEPARATOR: &'static [u8] = D::INTERNAL_DOMAIN_SEPARATOR;
+    const SPARSE_MERKLE_PLACEHOLDER_HASH: [u8; 32] = D::SPARSE_MERKLE_PLACEHOLDER_HASH;
+
+    fn new() -> Self {
+        Self {
+            hasher: H::new(),
+            _phantom_domain: PhantomData,
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data)
+    }
+
+    fn finalize(self) -> [u8; 32] {
+        self.hasher.finalize()
+    }
+
+    fn hash_leaf(domain_separator: &[u8], key_hash: &[u8; 32], value_hash: &[u8; 32]) -> [u8; 32] {
+        H::hash_leaf(domain_separator, key_hash, value_hash)
+    }
+
+    fn hash_internal(
+        domain_separator: &[u8],
+        left_child: &[u8; 32],
+        right_child: &[u8; 32],
+    ) -> [u8; 32] {
+        H::hash_internal(domain_separator, left_child, right_child)
+    }
+
+    fn is_canonical_hash(hash: &[u8; 32]) -> bool {
+        H::is_canonical_hash(hash)
+    }
+}
@@ -0,0 +1,286 @@
+//! This module keeps track of forked version histories, where several versions are built on top of
+//! the same base version, for instance to execute competing blocks optimistically before one of
+//! them is selected.
+//!
+//! [`JellyfishMerkleTree::put_value_set_after`](crate::JellyfishMerkleTree::put_value_set_after)
+//! builds a version on top of any earlier one, and nodes are only ever shared between a version and
+//! its descendants, so nodes can be stored as in a linear history. Two children of the same
+//! version must still have different version numbers, as node keys are made of the version which
+//! created each node.
+//!
+//! Values are different: the newest value of a key whose version is not after a given version may
+//! have been written by another branch, so
+//! [`TreeReader::get_value_option`] is not enough to read a branch. A store holding a forked
+//! history opts in to reading branches by implementing [`ForkedTreeReader`], and is read through a
+//! [`BranchReader`], which looks values up with it. Such a store keeps its [`ForkedHistory`] next
+//! to the values, updated with [`ForkedHistory::add_versions`] whenever it writes a branch, and
+//! only returns the values whose version [`ForkedHistory::is_on_branch`] of the requested one.
+//!
+//! Pruning is what differs from a linear history: a node made stale by one branch may still be
+//! part of another one, so the stale node indices of a [`TreeUpdateBatch`] cannot be purged by
+//! version alone. A [`ForkedHistory`] records the base version and the stale nodes of every
+//! version instead, and tells which nodes can be deleted when a branch is abandoned, or when the
+//! versions before the one all remaining branches start from are pruned.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::ops::Deref;
+
+use anyhow::{bail, ensure, Result};
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{
+    node_type::{LeafNode, Node, NodeKey},
+    storage::{TreeReader, TreeUpdateBatch},
+    KeyHash, OwnedValue, Version,
+};
+
+/// Defines the ability for a store holding a forked history to look up values along a single
+/// branch.
+pub trait ForkedTreeReader: TreeReader {
+    /// Gets the newest value of `key_hash` written by `tip` or by one of the versions it was
+    /// built on, skipping the values written by other branches. Returns `None` if the value does
+    /// not exist, and an error if `tip` is not part of the history, as
+    /// [`ForkedHistory::is_on_branch`] does.
+    fn get_value_option_on_branch(
+        &self,
+        tip: Version,
+        key_hash: KeyHash,
+    ) -> Result<Option<OwnedValue>>;
+}
+
+/// A [`TreeReader`] reading the branch of each version of a [`ForkedTreeReader`], held through
+/// a reference or a smart pointer, so that the values written by other branches are never read.
+#[derive(Clone, Debug)]
+pub struct BranchReader<D> {
+    inner: D,
+}
+
+impl<D> BranchReader<D> {
+    /// Wraps `inner`.
+    pub fn new(inner: D) -> Self {
+        Self { inner }
+    }
+
+    /// Returns the wrapped reader.
+    pub fn inner(&self) -> &D {
+        &self.inner
+    }
+}
+
+impl<D> TreeReader for BranchReader<D>
+where
+    D: Deref,
+    D::Target: ForkedTreeReader,
+{
+    fn get_node_option(&self, node_key: &NodeKey) -> Result<Option<Node>> {
+        self.inner.get_node_option(node_key)
+    }
+
+    fn get_value_option(
+        &self,
+        max_version: Version,
+        key_hash: KeyHash,
+    ) -> Result<Option<OwnedValue>> {
+        self.inner.get_value_option_on_branch(max_version, key_hash)
+    }
+
+    fn latest_version_at_or_before(&self, version: Version) -> Result<Option<Version>> {
+        self.inner.latest_version_at_or_before(version)
+    }
+
+    fn latest_version_to_build_on(&self, version: Version) -> Result<Option<Version>> {
+        self.inner.latest_version_to_build_on(version)
+    }
+
+    fn get_rightmost_leaf(&self) -> Result<Option<(NodeKey, LeafNode)>> {
+        self.inner.get_rightmost_leaf()
+    }
+}
+
+/// The base version and the stale nodes of a version of a [`ForkedHistory`].
+#[derive(Clone, Debug, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+struct ForkedVersion {
+    base_version: Option<Version>,
+    stale_nodes: Vec<NodeKey>,
+}
+
+/// The versions of a tree whose history forks, along with the nodes each of them made stale
+/// relative to its own base version.
+#[derive(Clone, Debug, Default, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct ForkedHistory {
+    versions: BTreeMap<Version, ForkedVersion>,
+    /// The oldest readable version, once older versions have been pruned.
+    oldest_readable_version: Option<Version>,
+}
+
+/// The data to delete from storage after abandoning branches or pruning versions of a
+/// [`ForkedHistory`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PrunedNodes {
+    /// Versions whose nodes and values must all be deleted, as no remaining version shares them.
+    pub versions: Vec<Version>,
+    /// Nodes created by remaining versions, but part of none of them anymore.
+    pub stale_nodes: Vec<NodeKey>,
+}
+
+impl ForkedHistory {
+    /// Records the versions written by `batch`, which was created by putting value sets on top of
+    /// `base_version`, the first of them at `first_version` and each following one at the next
+    /// version.
+    pub fn add_versions(
+        &mut self,
+        base_version: Option<Version>,
+        first_version: Version,
+        batch: &TreeUpdateBatch,
+    ) -> Result<()> {
+        if let Some(base_version) = base_version {
+            ensure!(
+                base_version < first_version,
+                "Base version {} is not before version {}.",
+                base_version,
+                first_version
+            );
+            ensure!(
+                self.versions.is_empty() || self.versions.contains_key(&base_version),
+                "Base version {} is not part of the history.",
+                base_version
+            );
+        }
+        let mut new_versions = BTreeMap::new();
+        let mut previous_version = base_version;
+        for version in (first_version..).take(batch.node_stats.len()) {
+            ensure!(
+                !self.versions.contains_key(&version),
+                "Version {} is already part of the history.",
+                version
+            );
+            new_versions.insert(
+                version,
+                ForkedVersion {
+                    base_version: previous_version,
+                    stale_nodes: Vec::new(),
+                },
+            );
+            previous_version = Some(version);
+        }
+        for index in &batch.stale_node_index_batch {
+            match new_versions.get_mut(&index.stale_since_version) {
+                Some(forked_version) => forked_version.stale_nodes.push(index.node_key.clone()),
+                None => bail!(
+                    "Stale node index of version {} outside of the batch.",
+                    index.stale_since_version
+                ),
+            }
+        }
+        self.versions.append(&mut new_versions);
+        Ok(())
+    }
+
+    /// Returns whether no version has been recorded yet.
+    pub fn is_empty(&self) -> bool {
+        self.versions.is_empty() && self.oldest_readable_version.is_none()
+    }
+
+    /// Returns whether `version` is part of the history, that is recorded and neither abandoned
+    /// nor pruned.
+    pub fn contains(&self, version: Version) -> bool {
+        self.versions.contains_key(&version)
+    }
+
+    /// Returns whether the tree at `version` is part of the history of the tree at `tip`, that is
+    /// whether `version` is `tip` or one of its ancestors.
+    ///
+    /// Returns an error if `tip` is not part of the history, because it was never recorded or
+    /// was abandoned, as its branch cannot be told.
+    pub fn is_on_branch(&self, version: Version, tip: Version) -> Result<bool> {
+        ensure!(
+            self.contains(tip),
+            "Version {} is not part of the history.",
+            tip
+        );
+        let mut current = tip;
+        loop {
+            if current == version {
+                return Ok(true);
+            }
+            match self.versions.get(&current).and_then(|v| v.base_version) {
+                Some(base_version) if base_version >= version => current = base_version,
+                // Versions before the oldest readable one were pruned along the single branch
+                // which led to it.
+                _ => {
+                    return Ok(self.oldest_readable_version == Some(current) && version < current);
+                }
+            }
+        }
+    }
+
+    /// Abandons the branch starting at `version`, which removes it from the history along with all
+    /// of its descendants. Every node and value written by these versions can be deleted, while
+    /// the nodes they made stale are left untouched, as they are still part of other versions.
+    pub fn abandon(&mut self, version: Version) -> Result<PrunedNodes> {
+        ensure!(
+            self.versions.contains_key(&version),
+            "Version {} is not part of the history.",
+            version
+        );
+        // A version always comes after its base version, so descendants are found in one pass.
+        let mut abandoned = alloc::vec![version];
+        for (descendant, forked_version) in self.versions.range(version + 1..) {
+            if matches!(forked_version.base_version, Some(base) if abandoned.contains(&base)) {
+                abandoned.push(*descendant);
+            }
+        }
+        for abandoned_version in &abandoned {
+            self.versions.remove(abandoned_version);
+        }
+        Ok(PrunedNodes {
+            versions: abandoned,
+            stale_nodes: Vec::new(),
+        })
+    }
+
+    /// Prunes every version before `oldest_readable_version`, which every remaining branch must
+    /// start from, and returns the nodes which are now part of no remaining version.
+    pub fn prune(&mut self, oldest_readable_version: Version) -> Result<PrunedNodes> {
+        ensure!(
+            self.versions.contains_key(&oldest_readable_version),
+            "Version {} is not part of the history.",
+            oldest_readable_version
+        );
+        for version in self.versions.keys() {
+            ensure!(
+                self.is_on_branch(*version, oldest_readable_version)?
+                    || self.is_on_branch(oldest_readable_version, *version)?,
+                "Version {} is on a branch which does not contain version {}, and must be abandoned first.",
+                version,
+                oldest_readable_version
+            );
+        }
+
+        let mut stale_nodes = Vec::new();
+        let pruned: Vec<_> = self
+            .versions
+            .range(..=oldest_readable_version)
+            .map(|(version, _)| *version)
+            .collect();
+        for version in pruned {
+            let forked_version = self
+                .versions
+                .remove(&version)
+                .expect("version was just listed");
+            stale_nodes.extend(forked_version.stale_nodes);
+        }
+        self.versions.insert(
+            oldest_readable_version,
+            ForkedVersion {
+                base_version: None,
+                stale_nodes: Vec::new(),
+            },
+        );
+        self.oldest_readable_version = Some(oldest_readable_version);
+        Ok(PrunedNodes {
+            versions: Vec::new(),
+            stale_nodes,
+        })
+    }
+}
@@ -0,0 +1,465 @@
+//! The commands of `jmt-inspect`, which looks inside a Jellyfish Merkle tree dumped to disk,
+//! without having to write a program against the storage it came from.
+//!
+//! A dump is a file made of one or more borsh-encoded [`TreeUpdateBatch`]es, one after the other,
+//! such as a single batch written out for debugging, or the log of every batch appended by a
+//! file-backed store. Several dumps can be given, and are applied in order. Stale node indices
+//! are ignored, so every version present in the dumps can be inspected.
+//!
+//! The tree is assumed to be hashed with SHA-256, unless another hasher is picked with `--hasher`.
+//! A tree hashed in a custom [`Domain`](crate::domain::Domain) can be inspected by a binary of its
+//! own, which hands its hasher to [`Cli::run_with`]:
+//!
+//! ```ignore
+//! use clap::Parser;
+//! use jmt::{domain::WithDomain, inspect::Cli};
+//!
+//! fn main() -> anyhow::Result<()> {
+//!     Cli::parse().run_with::<WithDomain<sha2::Sha256, MyDomain>>(&mut std::io::stdout())
+//! }
+//! ```
+
+use alloc::{format, string::String, vec, vec::Vec};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, ensure, format_err, Context, Result};
+use borsh::BorshDeserialize;
+use clap::{Parser, Subcommand, ValueEnum};
+
+use crate::{
+    integrity::verify_tree_integrity,
+    proof::{
+        envelope::{EnvelopedProof, ENVELOPE_MAGIC},
+        SparseMerkleProof,
+    },
+    reader::resolve_root_key,
+    storage::{LeafNode, Node, NodeKey, TreeReader, TreeUpdateBatch},
+    JellyfishMerkleTree, KeyHash, OwnedValue, RootHash, SimpleHasher, Version,
+};
+
+/// The command line of `jmt-inspect`.
+#[derive(Debug, Parser)]
+#[command(name = "jmt-inspect", about = "Inspect a Jellyfish Merkle tree dump")]
+pub struct Cli {
+    /// A file of borsh-encoded `TreeUpdateBatch`es. May be repeated to apply several dumps.
+    #[arg(long = "dump", global = true)]
+    pub dumps: Vec<PathBuf>,
+
+    /// The hash function of the tree.
+    #[arg(long, global = true, value_enum, default_value_t = Hasher::Sha256)]
+    pub hasher: Hasher,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// The hash functions which can be picked with `--hasher`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+#[non_exhaustive]
+pub enum Hasher {
+    /// SHA-256, the hasher of [`Sha256Jmt`](crate::Sha256Jmt).
+    Sha256,
+    /// BLAKE3, the hasher of [`Blake3Jmt`](crate::Blake3Jmt).
+    #[cfg(feature = "blake3")]
+    Blake3,
+    /// Keccak-256, the hasher of [`Keccak256Jmt`](crate::Keccak256Jmt).
+    #[cfg(feature = "keccak")]
+    Keccak256,
+    /// Poseidon, the hasher of [`PoseidonJmt`](crate::PoseidonJmt).
+    #[cfg(feature = "poseidon")]
+    Poseidon,
+}
+
+/// The commands of `jmt-inspect`.
+#[derive(Debug, Subcommand)]
+#[non_exhaustive]
+pub enum Command {
+    /// Print the root hash and leaf count of every version.
+    Roots,
+    /// Print the leaf depth and internal node fan-out histograms of a version.
+    Stats {
+        /// The version to inspect. Defaults to the latest version.
+        #[arg(long)]
+        version: Option<Version>,
+    },
+    /// Check the integrity of a version, printing every inconsistency found.
+    Check {
+        /// The version to check. Defaults to the latest version.
+        #[arg(long)]
+        version: Option<Version>,
+    },
+    /// Print the keys of the nodes on the path from the root to a key hash.
+    Path {
+        /// The key hash, in hex.
+        key_hash: String,
+        /// The version to inspect. Defaults to the latest version.
+        #[arg(long)]
+        version: Option<Version>,
+    },
+    /// Print the proof of a key hash, as hex-encoded borsh or as JSON.
+    Prove {
+        /// The key hash, in hex.
+        key_hash: String,
+        /// The version to prove against. Defaults to the latest version.
+        #[arg(long)]
+        version: Option<Version>,
+        /// Print the proof as JSON instead of hex-encoded borsh.
+        #[arg(long)]
+        json: bool,
+        /// Wrap the hex-encoded borsh proof in a self-describing envelope.
+        #[arg(long, conflicts_with = "json")]
+        envelope: bool,
+    },
+    /// Verify a proof file, in any format printed by `prove`, against a root hash.
+    Verify {
+        /// The proof file.
+        proof: PathBuf,
+        /// The root hash, in hex.
+        #[arg(long)]
+        root_hash: String,
+        /// The key hash, in hex.
+        #[arg(long)]
+        key_hash: String,
+        /// The value, in hex. If omitted, the proof must show that the key is absent.
+        #[arg(long)]
+        value: Option<String>,
+    },
+}
+
+impl Cli {
+    /// Runs the command with the hasher picked by `--hasher`, writing its output to `out`.
+    pub fn run(self, out: &mut dyn Write) -> Result<()> {
+        match self.hasher {
+            Hasher::Sha256 => self.run_with::<sha2::Sha256>(out),
+            #[cfg(feature = "blake3")]
+            Hasher::Blake3 => self.run_with::<crate::Blake3Hasher>(out),
+            #[cfg(feature = "keccak")]
+            Hasher::Keccak256 => self.run_with::<sha3::Keccak256>(out),
+            #[cfg(feature = "poseidon")]
+            Hasher::Poseidon => self.run_with::<crate::poseidon::PoseidonHasher>(out),
+        }
+    }
+
+    /// Runs the command with the hasher `H`, ignoring `--hasher`, writing its output to `out`.
+    pub fn run_with<H: SimpleHasher>(self, out: &mut dyn Write) -> Result<()> {
+        match self.command {
+            Command::Roots => roots::<H>(&DumpStore::load(&self.dumps)?, out),
+            Command::Stats { version } => {
+                let store = DumpStore::load(&self.dumps)?;
+                let version = store.version_or_latest(version)?;
+                stats(&store, version, out)
+            }
+            Command::Check { version } => {
+                let store = DumpStore::load(&self.dumps)?;
+                let version = store.version_or_latest(version)?;
+                check::<H>(&store, version, out)
+            }
+            Command::Path { key_hash, version } => {
+                let store = DumpStore::load(&self.dumps)?;
+                let version = store.version_or_latest(version)?;
+                path::<H>(&store, KeyHash(parse_hash(&key_hash)?), version, out)
+            }
+            Command::Prove {
+                key_hash,
+                version,
+                json,
+                envelope,
+            } => {
+                let store = DumpStore::load(&self.dumps)?;
+                let version = store.version_or_latest(version)?;
+                let key_hash = KeyHash(parse_hash(&key_hash)?);
+                prove::<H>(&store, key_hash, version, json, envelope, out)
+            }
+            Command::Verify {
+                proof,
+                root_hash,
+                key_hash,
+                value,
+            } => {
+                let value = value
+                    .map(hex::decode)
+                    .transpose()
+                    .context("decoding value")?;
+                verify::<H>(
+                    &proof,
+                    RootHash(parse_hash(&root_hash)?),
+                    KeyHash(parse_hash(&key_hash)?),
+                    value,
+                    out,
+                )
+            }
+        }
+    }
+}
+
+/// An in-memory store holding every node and value of the dumps.
+#[derive(Default)]
+struct DumpStore {
+    nodes: BTreeMap<NodeKey, Node>,
+    values: BTreeMap<KeyHash, BTreeMap<Version, Option<OwnedValue>>>,
+}
+
+impl DumpStore {
+    fn load(dumps: &[PathBuf]) -> Result<Self> {
+        ensure!(!dumps.is_empty(), "At least one --dump is required.");
+        let mut store = Self::default();
+        for dump in dumps {
+            let bytes = fs::read(dump).with_context(|| format!("reading {}", dump.display()))?;
+            let mut remaining = bytes.as_slice();
+            while !remaining.is_empty() {
+                let batch = TreeUpdateBatch::deserialize(&mut remaining)
+                    .with_context(|| format!("decoding {}", dump.display()))?;
+                store.apply(batch);
+            }
+        }
+        Ok(store)
+    }
+
+    fn apply(&mut self, batch: TreeUpdateBatch) {
+        let node_batch = batch.node_batch;
+        self.nodes.extend(
+            node_batch
+                .nodes()
+                .iter()
+                .map(|(node_key, node)| (node_key.clone(), node.clone())),
+        );
+        for ((version, key_hash), value) in node_batch.values() {
+            self.values
+                .entry(*key_hash)
+                .or_default()
+                .insert(*version, value.clone());
+        }
+    }
+
+    /// Returns the versions having a root node, in increasing order.
+    fn versions(&self) -> impl Iterator<Item = Version> + '_ {
+        self.nodes
+            .keys()
+            .filter(|node_key| node_key.nibble_path().is_empty())
+            .map(NodeKey::version)
+    }
+
+    fn version_or_latest(&self, version: Option<Version>) -> Result<Version> {
+        match version {
+            Some(version) => Ok(version),
+            None => self
+                .versions()
+                .last()
+                .ok_or_else(|| format_err!("The dumps do not contain any root node.")),
+        }
+    }
+}
+
+impl TreeReader for DumpStore {
+    fn get_node_option(&self, node_key: &NodeKey) -> Result<Option<Node>> {
+        Ok(self.nodes.get(node_key).cloned())
+    }
+
+    fn get_value_option(
+        &self,
+        max_version: Version,
+        key_hash: KeyHash,
+    ) -> Result<Option<OwnedValue>> {
+        Ok(self
+            .values
+            .get(&key_hash)
+            .and_then(|versions| versions.range(..=max_version).next_back())
+            .and_then(|(_version, value)| value.clone()))
+    }
+
+    /// Versions skipped by the dumps resolve to the latest version before them, up to the latest
+    /// version in the dumps.
+    fn latest_version_at_or_before(&self, version: Version) -> Result<Option<Version>> {
+        if self.versions().last() < Some(version) {
+            return Ok(None);
+        }
+        Ok(self
+            .versions()
+            .take_while(|root_version| *root_version <= version)
+            .last())
+    }
+
+    fn get_rightmost_leaf(&self) -> Result<Option<(NodeKey, LeafNode)>> {
+        bail!("Restoring a tree from a dump is not supported.")
+    }
+}
+
+fn parse_hash(hex_hash: &str) -> Result<[u8; 32]> {
+    let bytes = hex::decode(hex_hash.trim()).context("decoding hex")?;
+    bytes
+        .try_into()
+        .map_err(|bytes: Vec<u8>| format_err!("Expected 32 bytes, found {}.", bytes.len()))
+}
+
+fn roots<H: SimpleHasher>(store: &DumpStore, out: &mut dyn Write) -> Result<()> {
+    let tree = JellyfishMerkleTree::<_, H>::new(store);
+    for version in store.versions() {
+        writeln!(
+            out,
+            "version {}\troot {}\tleaves {}",
+            version,
+            hex::encode(tree.get_root_hash(version)?.0),
+            tree.get_leaf_count(version)?,
+        )?;
+    }
+    Ok(())
+}
+
+fn stats(store: &DumpStore, version: Version, out: &mut dyn Write) -> Result<()> {
+    // Leaf depths are counted in nibbles, fan-outs in number of children.
+    let mut depths = BTreeMap::<usize, usize>::new();
+    let mut fan_outs = BTreeMap::<usize, usize>::new();
+
+    let mut pending = vec![resolve_root_key(store, version)?];
+    while let Some(node_key) = pending.pop() {
+        match store.get_node(&node_key)? {
+            Node::Null => {}
+            Node::Leaf(_) => {
+                *depths
+                    .entry(node_key.nibble_path().num_nibbles())
+                    .or_default() += 1
+            }
+            Node::Internal(internal_node) => {
+                let mut num_children = 0;
+                for (nibble, child) in internal_node.children_sorted() {
+                    pending.push(node_key.gen_child_node_key(child.version, nibble));
+                    num_children += 1;
+                }
+                *fan_outs.entry(num_children).or_default() += 1;
+            }
+        }
+    }
+
+    writeln!(out, "leaf depth histogram (nibbles: leaves)")?;
+    for (depth, count) in depths {
+        writeln!(out, "{:>3}: {}", depth, count)?;
+    }
+    writeln!(out, "internal node fan-out histogram (children: nodes)")?;
+    for (fan_out, count) in fan_outs {
+        writeln!(out, "{:>3}: {}", fan_out, count)?;
+    }
+    Ok(())
+}
+
+fn check<H: SimpleHasher>(store: &DumpStore, version: Version, out: &mut dyn Write) -> Result<()> {
+    let errors = verify_tree_integrity::<_, H>(store, version)?;
+    for error in &errors {
+        writeln!(out, "{}", error)?;
+    }
+    ensure!(
+        errors.is_empty(),
+        "Found {} inconsistencies at version {}.",
+        errors.len(),
+        version
+    );
+    writeln!(out, "version {} is intact", version)?;
+    Ok(())
+}
+
+fn path<H: SimpleHasher>(
+    store: &DumpStore,
+    key_hash: KeyHash,
+    version: Version,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let mut node_key = resolve_root_key(store, version)?;
+    loop {
+        let node = store.get_node(&node_key)?;
+        let depth = node_key.nibble_path().num_nibbles();
+        match node {
+            Node::Null => {
+                writeln!(out, "{:?}\tnull", node_key)?;
+                return Ok(());
+            }
+            Node::Leaf(leaf_node) => {
+                let matches = if leaf_node.key_hash() == key_hash {
+                    "the key"
+                } else {
+                    "another key"
+                };
+                writeln!(
+                    out,
+                    "{:?}\tleaf {} ({})",
+                    node_key,
+                    hex::encode(leaf_node.key_hash().0),
+                    matches,
+                )?;
+                return Ok(());
+            }
+            Node::Internal(internal_node) => {
+                writeln!(
+                    out,
+                    "{:?}\tinternal {}\tleaves {}",
+                    node_key,
+                    hex::encode(internal_node.hash::<H>()),
+                    internal_node.leaf_count(),
+                )?;
+                let byte = key_hash.0[depth / 2];
+                // Even depths select the high nibble of a byte, odd depths the low one.
+                let nibble = (byte >> (4 * (1 - depth % 2))) & 0x0f;
+                match internal_node.child(nibble.into()) {
+                    Some(child) => {
+                        node_key = node_key.gen_child_node_key(child.version, nibble.into())
+                    }
+                    None => {
+                        writeln!(out, "no child at nibble {:x}, the key is absent", nibble)?;
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn prove<H: SimpleHasher>(
+    store: &DumpStore,
+    key_hash: KeyHash,
+    version: Version,
+    json: bool,
+    envelope: bool,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let tree = JellyfishMerkleTree::<_, H>::new(store);
+    let (_value, proof) = tree.get_with_proof(key_hash, version)?;
+    if json {
+        writeln!(out, "{}", serde_json::to_string_pretty(&proof)?)?;
+    } else if envelope {
+        writeln!(out, "{}", hex::encode(proof.to_envelope()))?;
+    } else {
+        writeln!(out, "{}", hex::encode(borsh::to_vec(&proof)?))?;
+    }
+    Ok(())
+}
+
+fn read_proof<H: SimpleHasher>(path: &Path) -> Result<SparseMerkleProof<H>> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    if let Ok(proof) = serde_json::from_str(&contents) {
+        return Ok(proof);
+    }
+    let bytes = hex::decode(contents.trim()).context("proof is neither JSON nor hex")?;
+    if bytes.starts_with(&ENVELOPE_MAGIC) {
+        return Ok(SparseMerkleProof::from_envelope(&bytes)?);
+    }
+    Ok(SparseMerkleProof::try_from_slice(&bytes)?)
+}
+
+fn verify<H: SimpleHasher>(
+    path: &Path,
+    root_hash: RootHash,
+    key_hash: KeyHash,
+    value: Option<Vec<u8>>,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let proof = read_proof::<H>(path)?;
+    proof
+        .verify(root_hash, key_hash, value)
+        .context("The proof is invalid.")?;
+    writeln!(out, "proof is valid")?;
+    Ok(())
+}
@@ -0,0 +1,266 @@
+//! This module implements a consistency check of the nodes and values making up a
+//! [`JellyfishMerkleTree`](crate::JellyfishMerkleTree) at some version, to locate the damage
+//! left in storage by disk corruption or by a faulty [`TreeWriter`](crate::storage::TreeWriter).
+
+use alloc::vec::Vec;
+use core::{fmt, marker::PhantomData};
+
+use anyhow::Result;
+
+use crate::{
+    node_type::{Node, NodeKey, NodeType},
+    reader::resolve_root_key,
+    storage::TreeReader,
+    Bytes32Ext, KeyHash, MissingRootError, SimpleHasher, ValueHash, Version,
+};
+
+/// A way in which a node found in storage disagrees with the rest of the tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Inconsistency {
+    /// The node is referenced by its parent but is missing from storage.
+    MissingNode,
+    /// The node is referenced by its parent but is a [`Node::Null`], which may only be found at
+    /// the root of an empty tree.
+    NullNode,
+    /// The node is referenced by its parent with a version newer than the parent's.
+    ChildVersionTooNew { parent_version: Version },
+    /// The hash of the node is not the hash its parent records for it.
+    HashMismatch {
+        expected: [u8; 32],
+        actual: [u8; 32],
+    },
+    /// The node is a leaf but its parent records an internal node, or the other way around.
+    NodeTypeMismatch {
+        expected: NodeType,
+        actual: NodeType,
+    },
+    /// The number of leaves below the node is not the number recorded for it, either by its
+    /// parent or by the node itself.
+    LeafCountMismatch { expected: usize, actual: usize },
+    /// The key hash of the leaf does not start with the nibble path of the leaf.
+    LeafKeyOutsidePath { key_hash: KeyHash },
+    /// The value of the leaf is missing from storage.
+    MissingValue { key_hash: KeyHash },
+    /// The value of the leaf found in storage does not hash to the value hash of the leaf.
+    ValueHashMismatch {
+        key_hash: KeyHash,
+        expected: ValueHash,
+        actual: ValueHash,
+    },
+}
+
+/// An inconsistency found by [`verify_tree_integrity`], along with the key of the offending node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IntegrityError {
+    pub node_key: NodeKey,
+    pub inconsistency: Inconsistency,
+}
+
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Node {:?}: ", self.node_key)?;
+        match &self.inconsistency {
+            Inconsistency::MissingNode => write!(f, "missing from storage."),
+            Inconsistency::NullNode => write!(f, "null node below the root."),
+            Inconsistency::ChildVersionTooNew { parent_version } => {
+                write!(f, "newer than its parent, at version {}.", parent_version)
+            }
+            Inconsistency::HashMismatch { expected, actual } => write!(
+                f,
+                "hash {} does not match the hash {} recorded by its parent.",
+                hex::encode(actual),
+                hex::encode(expected)
+            ),
+            Inconsistency::NodeTypeMismatch { expected, actual } => write!(
+                f,
+                "node type {:?} does not match the node type {:?} recorded by its parent.",
+                actual, expected
+            ),
+            Inconsistency::LeafCountMismatch { expected, actual } => write!(
+                f,
+                "{} leaves found below, but {} recorded.",
+                actual, expected
+            ),
+            Inconsistency::LeafKeyOutsidePath { key_hash } => {
+                write!(
+                    f,
+                    "leaf key hash {:?} is outside the node's path.",
+                    key_hash
+                )
+            }
+            Inconsistency::MissingValue { key_hash } => {
+                write!(
+                    f,
+                    "value of key hash {:?} is missing from storage.",
+                    key_hash
+                )
+            }
+            Inconsistency::ValueHashMismatch {
+                key_hash,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "value of key hash {:?} hashes to {:?}, but the leaf records {:?}.",
+                key_hash, actual, expected
+            ),
+        }
+    }
+}
+
+impl core::error::Error for IntegrityError {}
+
+/// Walks every node reachable from the root of the tree at `version`, and returns every
+/// inconsistency found between the nodes, their parents and the values of the leaves.
+///
+/// An empty result means that the tree at `version` is intact. Failures to read from `reader`,
+/// including a missing root, are returned as errors.
+pub fn verify_tree_integrity<R: TreeReader, H: SimpleHasher>(
+    reader: &R,
+    version: Version,
+) -> Result<Vec<IntegrityError>> {
+    let root_key = resolve_root_key(reader, version)?;
+    let root = reader
+        .get_node_option(&root_key)?
+        .ok_or_else(|| anyhow::anyhow!(MissingRootError { version }))?;
+
+    let mut checker = IntegrityChecker::<R, H> {
+        reader,
+        version,
+        errors: Vec::new(),
+        _phantom_hasher: Default::default(),
+    };
+    if !matches!(root, Node::Null) {
+        checker.check_subtree(root_key, root)?;
+    }
+    Ok(checker.errors)
+}
+
+struct IntegrityChecker<'a, R, H> {
+    reader: &'a R,
+    version: Version,
+    errors: Vec<IntegrityError>,
+    _phantom_hasher: PhantomData<H>,
+}
+
+impl<'a, R: TreeReader, H: SimpleHasher> IntegrityChecker<'a, R, H> {
+    fn report(&mut self, node_key: &NodeKey, inconsistency: Inconsistency) {
+        self.errors.push(IntegrityError {
+            node_key: node_key.clone(),
+            inconsistency,
+        });
+    }
+
+    /// Checks the subtree rooted at `node`, and returns the number of leaves actually found in it.
+    fn check_subtree(&mut self, node_key: NodeKey, node: Node) -> Result<usize> {
+        match node {
+            Node::Null => {
+                self.report(&node_key, Inconsistency::NullNode);
+                Ok(0)
+            }
+            Node::Leaf(leaf_node) => {
+                let key_hash = leaf_node.key_hash();
+                let in_path = node_key
+                    .nibble_path()
+                    .nibbles()
+                    .enumerate()
+                    .all(|(i, nibble)| key_hash.0.get_nibble(i) == nibble);
+                if !in_path {
+                    self.report(&node_key, Inconsistency::LeafKeyOutsidePath { key_hash });
+                }
+
+                match self.reader.get_value_option(self.version, key_hash)? {
+                    None => self.report(&node_key, Inconsistency::MissingValue { key_hash }),
+                    Some(value) => {
+                        let actual = ValueHash::with::<H>(value);
+                        if actual != leaf_node.value_hash() {
+                            self.report(
+                                &node_key,
+                                Inconsistency::ValueHashMismatch {
+                                    key_hash,
+                                    expected: leaf_node.value_hash(),
+                                    actual,
+                                },
+                            );
+                        }
+                    }
+                }
+                Ok(1)
+            }
+            Node::Internal(internal_node) => {
+                let mut leaf_count = 0;
+                for (nibble, child) in internal_node.children_sorted() {
+                    let child_key = node_key.gen_child_node_key(child.version, nibble);
+                    if child.version > node_key.version() {
+                        self.report(
+                            &child_key,
+                            Inconsistency::ChildVersionTooNew {
+                                parent_version: node_key.version(),
+                            },
+                        );
+                    }
+
+                    let child_node = match self.reader.get_node_option(&child_key)? {
+                        Some(child_node) => child_node,
+                        None => {
+                            self.report(&child_key, Inconsistency::MissingNode);
+                            continue;
+                        }
+                    };
+                    let actual_hash = child_node.hash::<H>();
+                    if actual_hash != child.hash {
+                        self.report(
+                            &child_key,
+                            Inconsistency::HashMismatch {
+                                expected: child.hash,
+                                actual: actual_hash,
+                            },
+                        );
+                    }
+
+                    let actual_type = match &child_node {
+                        Node::Null => None,
+                        Node::Leaf(_) => Some(NodeType::Leaf),
+                        Node::Internal(internal_node) => Some(internal_node.node_type()),
+                    };
+                    let child_leaf_count = self.check_subtree(child_key.clone(), child_node)?;
+                    match actual_type {
+                        Some(actual) if child.is_leaf() != (actual == NodeType::Leaf) => self
+                            .report(
+                                &child_key,
+                                Inconsistency::NodeTypeMismatch {
+                                    expected: child.node_type.clone(),
+                                    actual,
+                                },
+                            ),
+                        Some(NodeType::Internal { .. })
+                            if child.leaf_count() != child_leaf_count =>
+                        {
+                            self.report(
+                                &child_key,
+                                Inconsistency::LeafCountMismatch {
+                                    expected: child.leaf_count(),
+                                    actual: child_leaf_count,
+                                },
+                            )
+                        }
+                        _ => {}
+                    }
+                    leaf_count += child_leaf_count;
+                }
+
+                if internal_node.leaf_count() != leaf_count {
+                    self.report(
+                        &node_key,
+                        Inconsistency::LeafCountMismatch {
+                            expected: internal_node.leaf_count(),
+                            actual: leaf_count,
+                        },
+                    );
+                }
+                Ok(leaf_count)
+            }
+        }
+    }
+}
@@ -11,7 +11,9 @@ use alloc::{sync::Arc, vec::Vec};
 use anyhow::{bail, ensure, format_err, Result};
 
 use crate::{
+    metrics::{NoopMetrics, TreeMetrics},
     node_type::{Child, InternalNode, Node, NodeKey},
+    reader::resolve_root_key,
     storage::TreeReader,
     types::{
         nibble::{nibble_path::NibblePath, Nibble, ROOT_NIBBLE_HEIGHT},
@@ -102,6 +104,9 @@ pub struct JellyfishMerkleIterator<R> {
     /// The storage engine from which we can read nodes using node keys.
     reader: Arc<R>,
 
+    /// The observer of the nodes read while iterating.
+    metrics: Arc<dyn TreeMetrics>,
+
     /// The version of the tree this iterator is running on.
     version: Version,
 
@@ -122,10 +127,12 @@ where
     /// following `next` call will yield the smallest key that is greater or equal to
     /// `starting_key`.
     pub fn new(reader: Arc<R>, version: Version, starting_key: KeyHash) -> Result<Self> {
+        let root_key = resolve_root_key(reader.as_ref(), version)?;
+        let version = root_key.version();
         let mut parent_stack = Vec::new();
         let mut done = false;
 
-        let mut current_node_key = NodeKey::new_empty_path(version);
+        let mut current_node_key = root_key;
         let nibble_path = NibblePath::new(starting_key.0.to_vec());
         let mut nibble_iter = nibble_path.nibbles();
 
@@ -159,6 +166,7 @@ where
                     }
                     return Ok(Self {
                         reader,
+                        metrics: Arc::new(NoopMetrics),
                         version,
                         parent_stack,
                         done,
@@ -182,12 +190,26 @@ where
 
         Ok(Self {
             reader,
+            metrics: Arc::new(NoopMetrics),
             version,
             parent_stack,
             done,
         })
     }
 
+    /// Reports the nodes read by the following `next` calls to `metrics`. The nodes read by the
+    /// constructor to position the iterator are not reported.
+    pub fn with_metrics(mut self, metrics: Arc<dyn TreeMetrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    fn get_node(&self, node_key: &NodeKey) -> Result<Node> {
+        let node = self.reader.get_node(node_key);
+        self.metrics.node_read(node.is_ok());
+        node
+    }
+
     fn cleanup_stack(parent_stack: &mut Vec<NodeVisitInfo>) {
         while let Some(info) = parent_stack.last_mut() {
             if info.is_rightmost() {
@@ -202,14 +224,17 @@ where
     /// Constructs a new iterator. This puts the internal state in the correct position, so the
     /// following `next` call will yield the leaf at `start_idx`.
     pub fn new_by_index(reader: Arc<R>, version: Version, start_idx: usize) -> Result<Self> {
+        let root_key = resolve_root_key(reader.as_ref(), version)?;
+        let version = root_key.version();
         let mut parent_stack = Vec::new();
 
-        let mut current_node_key = NodeKey::new_empty_path(version);
+        let mut current_node_key = root_key;
         let mut current_node = reader.get_node(&current_node_key)?;
         let total_leaves = current_node.leaf_count();
         if start_idx >= total_leaves {
             return Ok(Self {
                 reader,
+                metrics: Arc::new(NoopMetrics),
                 version,
                 parent_stack,
                 done: true,
@@ -229,6 +254,7 @@ where
                     );
                     return Ok(Self {
                         reader,
+                        metrics: Arc::new(NoopMetrics),
                         version,
                         parent_stack,
                         done: false,
@@ -284,7 +310,7 @@ where
 
         if self.parent_stack.is_empty() {
             let root_node_key = NodeKey::new_empty_path(self.version);
-            match self.reader.get_node(&root_node_key) {
+            match self.get_node(&root_node_key) {
                 Ok(Node::Leaf(leaf_node)) => {
                     // This means the entire tree has a single leaf node. The key of this leaf node
                     // is greater or equal to `starting_key` (otherwise we would have set `done` to
@@ -324,7 +350,7 @@ where
                     .version,
                 child_index,
             );
-            match self.reader.get_node(&node_key) {
+            match self.get_node(&node_key) {
                 Ok(Node::Internal(internal_node)) => {
                     let visit_info = NodeVisitInfo::new(node_key, internal_node);
                     self.parent_stack.push(visit_info);
@@ -2,6 +2,8 @@
 // SPDX-License-Identifier: Apache-2.0
 #![cfg_attr(not(feature = "std"), no_std)]
 #![forbid(unsafe_code)]
+// Verifier-only builds leave much of the shared path and hashing plumbing unused.
+#![cfg_attr(not(feature = "tree"), allow(dead_code))]
 
 //! This module implements [`JellyfishMerkleTree`] backed by storage module. The tree itself doesn't
 //! persist anything, but realizes the logic of R/W only. The write path will produce all the
@@ -68,50 +70,112 @@
 //! [`TreeUpdateBatch`]: struct.TreeUpdateBatch.html
 //! [`InternalNode`]: node_type/struct.InternalNode.html
 //! [`LeafNode`]: node_type/struct.LeafNode.html
+//!
+//! # Verifier-only builds
+//!
+//! Clients that only need to check proofs against a trusted root hash can disable the default
+//! features of this crate. Without the `tree` feature, only the [`proof`] types and their
+//! verification are compiled, errors are reported as [`proof::VerificationError`], and the crate
+//! is `no_std` with `alloc` and does not depend on `anyhow` or any storage machinery.
 
 extern crate alloc;
 
+#[cfg(feature = "tree")]
 use core::fmt::Debug;
 
 use digest::generic_array::GenericArray;
 use digest::Digest;
 use digest::OutputSizeUser;
 use serde::{Deserialize, Serialize};
-#[cfg(feature = "std")]
+#[cfg(all(feature = "tree", feature = "std"))]
 use thiserror::Error;
 
+// `mirai-annotations` links `std`, so verifier-only builds use these stand-ins in the modules they
+// share with the tree. Like the originals outside of MIRAI, only `checked_precondition!` checks
+// anything.
+#[cfg(not(feature = "tree"))]
+macro_rules! assume {
+    ($condition:expr) => {};
+}
+#[cfg(not(feature = "tree"))]
+macro_rules! precondition {
+    ($condition:expr) => {};
+}
+#[cfg(not(feature = "tree"))]
+macro_rules! checked_precondition {
+    ($condition:expr) => {
+        assert!($condition)
+    };
+}
+
 mod bytes32ext;
+pub mod domain;
+#[cfg(feature = "tree")]
 mod iterator;
+#[cfg(feature = "tree")]
 mod node_type;
+#[cfg(any(test, feature = "poseidon"))]
+pub mod poseidon;
+#[cfg(feature = "tree")]
 mod reader;
+#[cfg(feature = "tree")]
 mod tree;
+#[cfg(feature = "tree")]
 mod tree_cache;
 mod types;
+#[cfg(feature = "tree")]
 mod writer;
 
-#[cfg(any(test, feature = "mocks"))]
+#[cfg(feature = "tree")]
+pub mod fork;
+#[cfg(feature = "cli")]
+pub mod inspect;
+#[cfg(feature = "tree")]
+pub mod integrity;
+#[cfg(feature = "tree")]
+pub mod metrics;
+#[cfg(feature = "migration")]
+pub mod migration;
+#[cfg(any(all(test, feature = "tree"), feature = "mocks"))]
 pub mod mock;
+#[cfg(feature = "tree")]
+pub mod namespace;
+#[cfg(feature = "tree")]
 pub mod restore;
+#[cfg(feature = "tree")]
+pub mod witness;
 
 use bytes32ext::Bytes32Ext;
+#[cfg(feature = "tree")]
 pub use iterator::JellyfishMerkleIterator;
 #[cfg(feature = "ics23")]
-pub use tree::ics23_impl::ics23_spec;
-pub use tree::JellyfishMerkleTree;
-#[cfg(any(test, feature = "sha2"))]
+pub use tree::ics23_impl::{ics23_spec, ics23_spec_in_domain};
+#[cfg(all(feature = "tree", any(test, feature = "blake3")))]
+pub use tree::Blake3Jmt;
+#[cfg(all(feature = "tree", any(test, feature = "keccak")))]
+pub use tree::Keccak256Jmt;
+#[cfg(all(feature = "tree", any(test, feature = "poseidon")))]
+pub use tree::PoseidonJmt;
+#[cfg(all(feature = "tree", any(test, feature = "sha2")))]
 pub use tree::Sha256Jmt;
+#[cfg(feature = "tree")]
+pub use tree::{JellyfishMerkleTree, Jmt};
 
+#[cfg(feature = "tree")]
 use types::nibble::ROOT_NIBBLE_HEIGHT;
 pub use types::proof;
 pub use types::Version;
 
 /// Contains types used to bridge a [`JellyfishMerkleTree`](crate::JellyfishMerkleTree)
 /// to the backing storage recording the tree's internal data.
+#[cfg(feature = "tree")]
 pub mod storage {
-    pub use node_type::{LeafNode, Node, NodeKey};
+    pub use node_type::{LeafNode, Node, NodeDecodeError, NodeEncodingVersion, NodeKey};
     pub use reader::HasPreimage;
+    pub use reader::NodeEncodingReader;
     pub use reader::TreeReader;
-    pub use types::nibble::nibble_path::NibblePath;
+    pub use reader::VersionMetadata;
+    pub use types::nibble::nibble_path::{NibblePath, NibblePathError};
     pub use writer::{
         NodeBatch, NodeStats, StaleNodeIndex, StaleNodeIndexBatch, TreeUpdateBatch, TreeWriter,
     };
@@ -119,10 +183,11 @@ pub mod storage {
     use super::*;
 }
 
-#[cfg(any(test))]
+#[cfg(all(test, feature = "tree"))]
 mod tests;
 
 /// An error that occurs when the state root for a requested version is missing (e.g., because it was pruned).
+#[cfg(feature = "tree")]
 #[derive(Debug)]
 #[cfg_attr(feature = "std", derive(Error))]
 #[cfg_attr(
@@ -133,7 +198,7 @@ pub struct MissingRootError {
     pub version: Version,
 }
 
-#[cfg(not(feature = "std"))]
+#[cfg(all(feature = "tree", not(feature = "std")))]
 impl core::fmt::Display for MissingRootError {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(
@@ -247,7 +312,9 @@ impl KeyHash {
     pub fn with<H: SimpleHasher>(key: impl AsRef<[u8]>) -> Self {
         let key_hash = Self(H::hash(key.as_ref()));
         // Adding a tracing event here allows cross-referencing the key hash
-        // with the original key bytes when looking through logs.
+        // with the original key bytes when looking through logs. With redacted
+        // key hashes, that is exactly what must not be possible.
+        #[cfg(all(feature = "tree", not(feature = "redact_key_hashes")))]
         tracing::debug!(key = ?EscapedByteSlice(key.as_ref()), ?key_hash, "hashed jmt key");
         key_hash
     }
@@ -277,8 +344,10 @@ impl core::fmt::Debug for RootHash {
     }
 }
 
+#[cfg(all(feature = "tree", not(feature = "redact_key_hashes")))]
 struct EscapedByteSlice<'a>(&'a [u8]);
 
+#[cfg(all(feature = "tree", not(feature = "redact_key_hashes")))]
 impl<'a> core::fmt::Debug for EscapedByteSlice<'a> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "b\"")?;
@@ -307,9 +376,53 @@ impl<'a> core::fmt::Debug for EscapedByteSlice<'a> {
     }
 }
 
+/// Formats a [`KeyHash`] in tracing spans and events, unless the `redact_key_hashes` feature is
+/// enabled, in which case the key hash is replaced by a placeholder.
+#[cfg(feature = "tree")]
+pub(crate) struct TracedKeyHash(pub(crate) KeyHash);
+
+#[cfg(feature = "tree")]
+impl core::fmt::Debug for TracedKeyHash {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if cfg!(feature = "redact_key_hashes") {
+            f.write_str("KeyHash(<redacted>)")
+        } else {
+            core::fmt::Debug::fmt(&self.0, f)
+        }
+    }
+}
+
+/// Formats an error in tracing spans, unless the `redact_key_hashes` feature is enabled. Errors
+/// from storage or from the tree may then mention key hashes or the nibble paths of nodes, so only
+/// a [`MissingRootError`] is spelled out, and any other error is replaced by a placeholder.
+#[cfg(feature = "tree")]
+pub(crate) struct TracedError<'a>(pub(crate) &'a anyhow::Error);
+
+#[cfg(feature = "tree")]
+impl core::fmt::Display for TracedError<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if !cfg!(feature = "redact_key_hashes") {
+            core::fmt::Display::fmt(self.0, f)
+        } else if let Some(err) = self.0.downcast_ref::<MissingRootError>() {
+            core::fmt::Display::fmt(err, f)
+        } else {
+            f.write_str("<redacted>")
+        }
+    }
+}
+
 /// A minimal trait representing a hash function. We implement our own
 /// rather than relying on `Digest` for broader compatibility.
 pub trait SimpleHasher: Sized {
+    /// Prefixed to the key hash and value hash of a leaf when hashing it. Hashers only override
+    /// the values of the [`DefaultDomain`](domain::DefaultDomain) to hash trees in another
+    /// [`Domain`](domain::Domain), as [`WithDomain`](domain::WithDomain) does.
+    const LEAF_DOMAIN_SEPARATOR: &'static [u8] = proof::LEAF_DOMAIN_SEPARATOR;
+    /// Prefixed to the hashes of the children of an internal node when hashing it.
+    const INTERNAL_DOMAIN_SEPARATOR: &'static [u8] = proof::INTERNAL_DOMAIN_SEPARATOR;
+    /// The hash of an empty subtree.
+    const SPARSE_MERKLE_PLACEHOLDER_HASH: [u8; 32] = SPARSE_MERKLE_PLACEHOLDER_HASH;
+
     /// Creates a new hasher with default state.
     fn new() -> Self;
     /// Ingests the provided data, updating the hasher's state.
@@ -322,6 +435,36 @@ pub trait SimpleHasher: Sized {
         hasher.update(data.as_ref());
         hasher.finalize()
     }
+    /// Returns the hash of a leaf node with the given key hash and value hash, in the domain of
+    /// `domain_separator`. Hashers which are not byte-oriented, such as
+    /// [`PoseidonHasher`](poseidon::PoseidonHasher), override it along with
+    /// [`hash_internal`](Self::hash_internal).
+    fn hash_leaf(domain_separator: &[u8], key_hash: &[u8; 32], value_hash: &[u8; 32]) -> [u8; 32] {
+        let mut hasher = Self::new();
+        hasher.update(domain_separator);
+        hasher.update(key_hash);
+        hasher.update(value_hash);
+        hasher.finalize()
+    }
+    /// Returns the hash of an internal node with the given child hashes, in the domain of
+    /// `domain_separator`.
+    fn hash_internal(
+        domain_separator: &[u8],
+        left_child: &[u8; 32],
+        right_child: &[u8; 32],
+    ) -> [u8; 32] {
+        let mut hasher = Self::new();
+        hasher.update(domain_separator);
+        hasher.update(left_child);
+        hasher.update(right_child);
+        hasher.finalize()
+    }
+    /// Returns whether `hash` is the canonical encoding of a hash, which every hash output by this
+    /// hasher is. Proofs are rejected when they hash a sibling which is not, so that no two
+    /// encodings of a sibling verify alike. Every 32 bytes are canonical by default.
+    fn is_canonical_hash(_hash: &[u8; 32]) -> bool {
+        true
+    }
 }
 
 impl<T: Digest> SimpleHasher for T
@@ -341,6 +484,26 @@ where
     }
 }
 
+/// The BLAKE3 hash function, as a [`SimpleHasher`]. `blake3::Hasher` implements a newer version
+/// of `Digest` than this crate uses, so it needs this wrapper.
+#[cfg(any(test, feature = "blake3"))]
+pub struct Blake3Hasher(blake3::Hasher);
+
+#[cfg(any(test, feature = "blake3"))]
+impl SimpleHasher for Blake3Hasher {
+    fn new() -> Self {
+        Blake3Hasher(blake3::Hasher::new())
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(self) -> [u8; 32] {
+        *self.0.finalize().as_bytes()
+    }
+}
+
 /// A trivial implementation of [`SimpleHasher`] that simply returns the first 32 bytes of the
 /// provided data. This is useful to avoid hashing data when testing, and facilitate debugging
 /// specific tree configurations.
@@ -0,0 +1,358 @@
+//! This module defines [`TreeMetrics`], through which a
+//! [`JellyfishMerkleTree`](crate::JellyfishMerkleTree), the cache of its pending updates, a
+//! [`JellyfishMerkleRestore`](crate::restore::JellyfishMerkleRestore) and a
+//! [`JellyfishMerkleIterator`](crate::JellyfishMerkleIterator) report what each of their
+//! operations costs.
+//!
+//! Nothing is reported unless an observer is attached with the `with_metrics` method of these
+//! types. [`CountingMetrics`] is a ready-made observer accumulating everything in counters.
+
+use alloc::sync::Arc;
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use anyhow::Result;
+
+use crate::{
+    node_type::{LeafNode, Node, NodeKey},
+    storage::TreeReader,
+    KeyHash, OwnedValue, Version,
+};
+
+/// A phase of the operations on a tree, whose duration is reported through
+/// [`TreeMetrics::phase_completed`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum Phase {
+    /// Applying value sets to the tree, from `put_value_set` and its variants.
+    Update,
+    /// Freezing the updates of a version, which computes its root hash.
+    Freeze,
+    /// Generating a proof, from `get_with_proof` and its variants.
+    Proof,
+    /// Adding a chunk of leaves to a tree being restored.
+    RestoreChunk,
+    /// Finishing the restoration of a tree.
+    RestoreFinish,
+}
+
+impl Phase {
+    const ALL: [Phase; 5] = [
+        Phase::Update,
+        Phase::Freeze,
+        Phase::Proof,
+        Phase::RestoreChunk,
+        Phase::RestoreFinish,
+    ];
+}
+
+/// An observer of the work done by the operations on a tree.
+///
+/// Every method does nothing by default, so that implementations only need to override the
+/// events they are interested in.
+pub trait TreeMetrics: Send + Sync {
+    /// A node was looked up in storage, and was `found` or not.
+    fn node_read(&self, found: bool) {
+        let _ = found;
+    }
+
+    /// A node was looked up among the nodes updated but not written yet. On a miss, it is then
+    /// looked up in storage.
+    fn node_cache_read(&self, hit: bool) {
+        let _ = hit;
+    }
+
+    /// `num_nodes` nodes were added to a batch of updates, or written to storage during a restore.
+    fn nodes_written(&self, num_nodes: usize) {
+        let _ = num_nodes;
+    }
+
+    /// `num_nodes` nodes were hashed, to build new nodes or to get a root hash.
+    ///
+    /// This counts nodes rather than calls to the hash function: hashing an internal node hashes
+    /// every level of its 16-way subtree.
+    fn nodes_hashed(&self, num_nodes: usize) {
+        let _ = num_nodes;
+    }
+
+    /// A proof with `num_siblings` siblings was generated.
+    fn proof_generated(&self, num_siblings: usize) {
+        let _ = num_siblings;
+    }
+
+    /// A lookup of a key stopped at a node `depth` nibbles below the root.
+    fn lookup_depth(&self, depth: usize) {
+        let _ = depth;
+    }
+
+    /// A phase of an operation took `elapsed`.
+    ///
+    /// Durations can only be measured when the `std` feature is enabled, and this is never
+    /// called otherwise.
+    fn phase_completed(&self, phase: Phase, elapsed: Duration) {
+        let _ = (phase, elapsed);
+    }
+}
+
+/// A [`TreeMetrics`] ignoring everything, used when no other observer is attached.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoopMetrics;
+
+impl TreeMetrics for NoopMetrics {}
+
+/// A [`TreeMetrics`] adding up everything it observes into atomic counters, which can be read
+/// at any time with [`snapshot`](Self::snapshot).
+#[derive(Debug, Default)]
+pub struct CountingMetrics {
+    node_reads_found: AtomicU64,
+    node_reads_missing: AtomicU64,
+    node_cache_hits: AtomicU64,
+    node_cache_misses: AtomicU64,
+    nodes_written: AtomicU64,
+    nodes_hashed: AtomicU64,
+    proofs: AtomicU64,
+    proof_siblings: AtomicU64,
+    lookups: AtomicU64,
+    lookup_depths: AtomicU64,
+    max_lookup_depth: AtomicU64,
+    phase_counts: [AtomicU64; Phase::ALL.len()],
+    phase_nanos: [AtomicU64; Phase::ALL.len()],
+}
+
+/// The values of the counters of a [`CountingMetrics`] at some point in time.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    /// The number of nodes looked up in storage and found.
+    pub node_reads_found: u64,
+    /// The number of nodes looked up in storage and not found.
+    pub node_reads_missing: u64,
+    /// The number of nodes found among the pending updates.
+    pub node_cache_hits: u64,
+    /// The number of nodes looked up among the pending updates, and then in storage.
+    pub node_cache_misses: u64,
+    /// The number of nodes added to batches of updates or written during restores.
+    pub nodes_written: u64,
+    /// The number of nodes hashed.
+    pub nodes_hashed: u64,
+    /// The number of proofs generated.
+    pub proofs: u64,
+    /// The total number of siblings in all the proofs generated.
+    pub proof_siblings: u64,
+    /// The number of key lookups.
+    pub lookups: u64,
+    /// The sum of the depths reached by all the key lookups.
+    pub lookup_depths: u64,
+    /// The maximum depth reached by a key lookup.
+    pub max_lookup_depth: u64,
+    phase_counts: [u64; Phase::ALL.len()],
+    phase_nanos: [u64; Phase::ALL.len()],
+}
+
+impl MetricsSnapshot {
+    /// Returns the number of times `phase` was completed.
+    pub fn phase_count(&self, phase: Phase) -> u64 {
+        self.phase_counts[phase as usize]
+    }
+
+    /// Returns the total time spent in `phase`.
+    pub fn phase_time(&self, phase: Phase) -> Duration {
+        Duration::from_nanos(self.phase_nanos[phase as usize])
+    }
+}
+
+impl CountingMetrics {
+    /// Creates a set of counters, all starting at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the current values of the counters.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        MetricsSnapshot {
+            node_reads_found: load(&self.node_reads_found),
+            node_reads_missing: load(&self.node_reads_missing),
+            node_cache_hits: load(&self.node_cache_hits),
+            node_cache_misses: load(&self.node_cache_misses),
+            nodes_written: load(&self.nodes_written),
+            nodes_hashed: load(&self.nodes_hashed),
+            proofs: load(&self.proofs),
+            proof_siblings: load(&self.proof_siblings),
+            lookups: load(&self.lookups),
+            lookup_depths: load(&self.lookup_depths),
+            max_lookup_depth: load(&self.max_lookup_depth),
+            phase_counts: self.phase_counts.each_ref().map(load),
+            phase_nanos: self.phase_nanos.each_ref().map(load),
+        }
+    }
+}
+
+impl TreeMetrics for CountingMetrics {
+    fn node_read(&self, found: bool) {
+        let counter = if found {
+            &self.node_reads_found
+        } else {
+            &self.node_reads_missing
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn node_cache_read(&self, hit: bool) {
+        let counter = if hit {
+            &self.node_cache_hits
+        } else {
+            &self.node_cache_misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn nodes_written(&self, num_nodes: usize) {
+        self.nodes_written
+            .fetch_add(num_nodes as u64, Ordering::Relaxed);
+    }
+
+    fn nodes_hashed(&self, num_nodes: usize) {
+        self.nodes_hashed
+            .fetch_add(num_nodes as u64, Ordering::Relaxed);
+    }
+
+    fn proof_generated(&self, num_siblings: usize) {
+        self.proofs.fetch_add(1, Ordering::Relaxed);
+        self.proof_siblings
+            .fetch_add(num_siblings as u64, Ordering::Relaxed);
+    }
+
+    fn lookup_depth(&self, depth: usize) {
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+        self.lookup_depths
+            .fetch_add(depth as u64, Ordering::Relaxed);
+        self.max_lookup_depth
+            .fetch_max(depth as u64, Ordering::Relaxed);
+    }
+
+    fn phase_completed(&self, phase: Phase, elapsed: Duration) {
+        self.phase_counts[phase as usize].fetch_add(1, Ordering::Relaxed);
+        self.phase_nanos[phase as usize].fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+/// Reports the time elapsed between its creation and its drop as a [`Phase`].
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+pub(crate) struct PhaseTimer<'a> {
+    metrics: &'a dyn TreeMetrics,
+    phase: Phase,
+    #[cfg(feature = "std")]
+    start: std::time::Instant,
+}
+
+impl<'a> PhaseTimer<'a> {
+    pub(crate) fn start(metrics: &'a dyn TreeMetrics, phase: Phase) -> Self {
+        Self {
+            metrics,
+            phase,
+            #[cfg(feature = "std")]
+            start: std::time::Instant::now(),
+        }
+    }
+}
+
+impl Drop for PhaseTimer<'_> {
+    fn drop(&mut self) {
+        #[cfg(feature = "std")]
+        self.metrics
+            .phase_completed(self.phase, self.start.elapsed());
+    }
+}
+
+/// A [`TreeReader`] reporting the nodes read through it to a [`TreeMetrics`].
+pub(crate) struct MeteredReader<'a, R> {
+    reader: &'a R,
+    metrics: &'a dyn TreeMetrics,
+}
+
+impl<'a, R> MeteredReader<'a, R> {
+    pub(crate) fn new(reader: &'a R, metrics: &'a dyn TreeMetrics) -> Self {
+        Self { reader, metrics }
+    }
+}
+
+impl<'a, R: TreeReader> TreeReader for MeteredReader<'a, R> {
+    fn get_node_option(&self, node_key: &NodeKey) -> Result<Option<Node>> {
+        let node = self.reader.get_node_option(node_key)?;
+        self.metrics.node_read(node.is_some());
+        Ok(node)
+    }
+
+    fn get_value_option(
+        &self,
+        max_version: Version,
+        key_hash: KeyHash,
+    ) -> Result<Option<OwnedValue>> {
+        self.reader.get_value_option(max_version, key_hash)
+    }
+
+    fn latest_version_at_or_before(&self, version: Version) -> Result<Option<Version>> {
+        self.reader.latest_version_at_or_before(version)
+    }
+
+    fn latest_version_to_build_on(&self, version: Version) -> Result<Option<Version>> {
+        self.reader.latest_version_to_build_on(version)
+    }
+
+    fn get_rightmost_leaf(&self) -> Result<Option<(NodeKey, LeafNode)>> {
+        self.reader.get_rightmost_leaf()
+    }
+}
+
+/// A [`TreeMetrics`] forwarding everything to another one, while counting the nodes read from
+/// storage, so that the tracing span of a single operation can report them.
+pub(crate) struct NodeReadCounter {
+    inner: Arc<dyn TreeMetrics>,
+    node_reads: AtomicU64,
+}
+
+impl NodeReadCounter {
+    pub(crate) fn new(inner: Arc<dyn TreeMetrics>) -> Self {
+        Self {
+            inner,
+            node_reads: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn node_reads(&self) -> u64 {
+        self.node_reads.load(Ordering::Relaxed)
+    }
+}
+
+impl TreeMetrics for NodeReadCounter {
+    fn node_read(&self, found: bool) {
+        self.node_reads.fetch_add(1, Ordering::Relaxed);
+        self.inner.node_read(found);
+    }
+
+    fn node_cache_read(&self, hit: bool) {
+        self.inner.node_cache_read(hit);
+    }
+
+    fn nodes_written(&self, num_nodes: usize) {
+        self.inner.nodes_written(num_nodes);
+    }
+
+    fn nodes_hashed(&self, num_nodes: usize) {
+        self.inner.nodes_hashed(num_nodes);
+    }
+
+    fn proof_generated(&self, num_siblings: usize) {
+        self.inner.proof_generated(num_siblings);
+    }
+
+    fn lookup_depth(&self, depth: usize) {
+        self.inner.lookup_depth(depth);
+    }
+
+    fn phase_completed(&self, phase: Phase, elapsed: Duration) {
+        self.inner.phase_completed(phase, elapsed);
+    }
+}
@@ -0,0 +1,162 @@
+//! This module migrates stored trees to a new encoding or a new hash function, without exporting
+//! their state and rebuilding them with
+//! [`put_value_set`](crate::JellyfishMerkleTree::put_value_set).
+//!
+//! It is built with the `migration` feature, which also enables
+//! [`append_value_set`](crate::JellyfishMerkleTree::append_value_set) to change the latest version
+//! of a tree in place. Neither is needed to read or update a tree as usual.
+//!
+//! # Node encodings
+//!
+//! A [`NodeEncodingMigration`] rewrites the nodes of a store with a newer [`NodeEncodingVersion`],
+//! a bounded batch at a time, so that the store adopts a new encoding without being rebuilt.
+//!
+//! [`Node::decode`](crate::storage::Node::decode) reads every version of the encoding, so a store
+//! decoding nodes with it stays readable while some of its nodes are written with one version and
+//! others with the next one. Migrating a store to a new version then takes two steps:
+//!
+//! 1. Making the [`TreeWriter`] of the store write nodes with the new version, so that no more
+//!    nodes are written with the old one.
+//! 2. Calling [`NodeEncodingMigration::step`] until the migration is done. Each step lists a batch
+//!    of nodes with [`NodeEncodingReader`], reads those written with an older version through
+//!    [`TreeReader`], and writes them back through the [`TreeWriter`].
+//!
+//! Writing a node back reuses its existing [`NodeKey`], so the [`TreeWriter`] of the store must
+//! accept overwriting a node, which a tree never asks of it otherwise. A writer rejecting
+//! overwrites, like [`MockTreeStore`](crate::mock::MockTreeStore) by default, cannot be migrated.
+//!
+//! The [`cursor`](NodeEncodingMigration::cursor) of a migration can be persisted after each step,
+//! to [`resume`](NodeEncodingMigration::resume) it after a restart.
+//!
+//! Nodes are never modified once written, so rewriting them while the tree is being updated is
+//! harmless. Pruning them is not: a node pruned between the read and the write of a step would be
+//! written back, so nodes must not be pruned while a step runs.
+//!
+//! # Hash functions
+//!
+//! [`rehash_tree`] writes a version of a tree hashed with one [`SimpleHasher`](crate::SimpleHasher)
+//! to another store, hashed with another one, streaming its key-value pairs and writing its nodes
+//! bottom-up.
+
+use anyhow::{ensure, Result};
+
+use crate::{
+    node_type::{NodeEncodingVersion, NodeKey},
+    storage::{NodeBatch, NodeEncodingReader, TreeReader, TreeWriter},
+};
+
+mod rehash;
+
+pub use self::rehash::{rehash_tree, RehashSummary};
+
+/// A migration of the nodes of a store written with an older encoding than its target.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeEncodingMigration {
+    target: NodeEncodingVersion,
+    cursor: Option<NodeKey>,
+    done: bool,
+}
+
+/// What a [`NodeEncodingMigration::step`] did.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MigrationStep {
+    /// The number of nodes listed by the step.
+    pub nodes_scanned: usize,
+    /// The number of nodes rewritten by the step, because they were written with an older
+    /// encoding than the target.
+    pub nodes_rewritten: usize,
+    /// Whether the step reached the last node of the store.
+    pub done: bool,
+}
+
+impl NodeEncodingMigration {
+    /// Starts a migration of every node of a store to the encoding `target`, which the
+    /// [`TreeWriter`] of the store must already write.
+    pub fn new(target: NodeEncodingVersion) -> Self {
+        Self::resume(target, None)
+    }
+
+    /// Resumes a migration to the encoding `target` after the node `cursor`, as returned by
+    /// [`NodeEncodingMigration::cursor`] before the migration was interrupted.
+    pub fn resume(target: NodeEncodingVersion, cursor: Option<NodeKey>) -> Self {
+        Self {
+            target,
+            cursor,
+            done: false,
+        }
+    }
+
+    /// Returns the encoding the nodes are migrated to.
+    pub fn target(&self) -> NodeEncodingVersion {
+        self.target
+    }
+
+    /// Returns the last node listed by the migration, if any.
+    pub fn cursor(&self) -> Option<&NodeKey> {
+        self.cursor.as_ref()
+    }
+
+    /// Returns whether the migration listed the last node of the store.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Lists up to `batch_size` nodes following the cursor, and rewrites those written with an
+    /// older encoding than the target in a single [`NodeBatch`].
+    ///
+    /// The nodes are written back under their existing keys, so the [`TreeWriter`] of `store`
+    /// must overwrite nodes which already exist, rather than reject them.
+    pub fn step<S>(&mut self, store: &S, batch_size: usize) -> Result<MigrationStep>
+    where
+        S: TreeReader + TreeWriter + NodeEncodingReader,
+    {
+        ensure!(
+            batch_size > 0,
+            "the batch size of a migration must not be 0"
+        );
+        let encodings = store.get_node_encodings(self.cursor.as_ref(), batch_size)?;
+        ensure!(
+            encodings.len() <= batch_size,
+            "the store listed {} nodes, more than the {} requested",
+            encodings.len(),
+            batch_size
+        );
+
+        let mut batch = NodeBatch::default();
+        for (node_key, version) in &encodings {
+            if *version >= self.target {
+                continue;
+            }
+            // The node may have been pruned since it was listed.
+            if let Some(node) = store.get_node_option(node_key)? {
+                batch.insert_node(node_key.clone(), node);
+            }
+        }
+        if !batch.nodes().is_empty() {
+            store.write_node_batch(&batch)?;
+        }
+
+        if let Some((node_key, _)) = encodings.last() {
+            self.cursor = Some(node_key.clone());
+        }
+        self.done = encodings.len() < batch_size;
+        Ok(MigrationStep {
+            nodes_scanned: encodings.len(),
+            nodes_rewritten: batch.nodes().len(),
+            done: self.done,
+        })
+    }
+
+    /// Runs steps of up to `batch_size` nodes until the migration is done, and returns the total
+    /// number of nodes rewritten.
+    pub fn run<S>(&mut self, store: &S, batch_size: usize) -> Result<usize>
+    where
+        S: TreeReader + TreeWriter + NodeEncodingReader,
+    {
+        let mut nodes_rewritten = 0;
+        while !self.done {
+            nodes_rewritten += self.step(store, batch_size)?.nodes_rewritten;
+        }
+        Ok(nodes_rewritten)
+    }
+}
@@ -0,0 +1,107 @@
+use alloc::{sync::Arc, vec::Vec};
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    iterator::JellyfishMerkleIterator,
+    node_type::{Node, NodeKey},
+    reader::resolve_root_key,
+    restore::{JellyfishMerkleRestore, StateSnapshotReceiver},
+    storage::{NodeBatch, TreeReader, TreeWriter},
+    KeyHash, MissingRootError, RootHash, SimpleHasher, Version,
+};
+
+/// The outcome of a successful [`rehash_tree`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RehashSummary {
+    /// The root hash of the tree hashed with the old hasher.
+    pub old_root_hash: RootHash,
+    /// The root hash of the tree hashed with the new hasher.
+    pub root_hash: RootHash,
+    /// The number of leaves in the tree.
+    pub num_leaves: usize,
+    /// The number of nodes written to the new store since the most recent restart.
+    pub num_nodes_written: usize,
+}
+
+/// Writes the tree at `version` of `reader`, hashed with `H1`, to `store` at the same version,
+/// hashed with `H2`.
+///
+/// A version skipped by the writer of `reader` is read from the latest version before it, as
+/// [`JellyfishMerkleTree::get`](crate::JellyfishMerkleTree::get) does, but the new tree is still
+/// written at `version` itself, so that `store` holds that version whether or not it resolves
+/// skipped versions.
+///
+/// The new tree holds the same key hashes and values, so it has the same shape, but its value
+/// hashes, leaf hashes and internal hashes are computed with `H2`. Key hashes are kept as they
+/// are, so applications must keep hashing their keys the same way to find them in the new tree.
+///
+/// The key-value pairs are streamed from `reader` with a [`JellyfishMerkleIterator`], in chunks
+/// of `chunk_size`, and each node is written to `store` as soon as its subtree is complete, like a
+/// [`JellyfishMerkleRestore`] does. Calling this function again after an error resumes after the
+/// last leaf written to `store`, which must therefore hold nothing else.
+pub fn rehash_tree<R, H1, S, H2>(
+    reader: Arc<R>,
+    version: Version,
+    store: Arc<S>,
+    chunk_size: usize,
+) -> Result<RehashSummary>
+where
+    R: TreeReader,
+    H1: SimpleHasher,
+    S: 'static + TreeReader + TreeWriter + Send + Sync,
+    H2: SimpleHasher,
+{
+    anyhow::ensure!(chunk_size > 0, "the chunk size of a rehash must not be 0");
+    let old_root_key = resolve_root_key(reader.as_ref(), version)?;
+    let old_root_hash = reader
+        .get_node_option(&old_root_key)?
+        .map(|root| RootHash(root.hash::<H1>()))
+        .ok_or_else(|| anyhow!(MissingRootError { version }))?;
+
+    let resume_after = store
+        .get_rightmost_leaf()?
+        .map(|(_, leaf_node)| leaf_node.key_hash());
+    let mut restore = JellyfishMerkleRestore::<H2>::new_trusted(store.clone(), version)?;
+    let pairs = JellyfishMerkleIterator::new(
+        reader,
+        old_root_key.version(),
+        resume_after.unwrap_or(KeyHash([0; 32])),
+    )?
+    .filter(|pair| !matches!(pair, Ok((key_hash, _)) if Some(*key_hash) == resume_after));
+
+    let mut has_leaves = resume_after.is_some();
+    let mut chunk = Vec::with_capacity(chunk_size);
+    for pair in pairs {
+        chunk.push(pair?);
+        if chunk.len() == chunk_size {
+            restore.add_trusted_chunk(core::mem::take(&mut chunk))?;
+            has_leaves = true;
+        }
+    }
+    if !chunk.is_empty() {
+        restore.add_trusted_chunk(chunk)?;
+        has_leaves = true;
+    }
+
+    if !has_leaves {
+        // A restoration cannot build an empty tree, whose root is a null node.
+        let mut batch = NodeBatch::default();
+        batch.insert_node(NodeKey::new_empty_path(version), Node::Null);
+        store.write_node_batch(&batch)?;
+        return Ok(RehashSummary {
+            old_root_hash,
+            root_hash: RootHash(H2::SPARSE_MERKLE_PLACEHOLDER_HASH),
+            num_leaves: 0,
+            num_nodes_written: 1,
+        });
+    }
+
+    let summary = restore.finish()?;
+    Ok(RehashSummary {
+        old_root_hash,
+        root_hash: summary.root_hash,
+        num_leaves: summary.num_leaves,
+        num_nodes_written: summary.num_nodes_written,
+    })
+}
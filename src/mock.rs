@@ -4,6 +4,7 @@
 //! A mock, in-memory tree store useful for testing.
 
 use alloc::{collections::BTreeSet, vec};
+use core::ops::Bound;
 use parking_lot::RwLock;
 
 use alloc::vec::Vec;
@@ -15,18 +16,55 @@ use hashbrown::{hash_map::Entry, HashMap};
 use std::collections::{hash_map::Entry, HashMap};
 
 use crate::{
+    fork::{ForkedHistory, ForkedTreeReader, PrunedNodes},
+    namespace::{Namespace, NamespacedTreeReader, NamespacedTreeWriter, NamespacedVersionMetadata},
     node_type::{LeafNode, Node, NodeKey},
-    storage::{HasPreimage, NodeBatch, StaleNodeIndex, TreeReader, TreeUpdateBatch, TreeWriter},
-    types::Version,
+    storage::{
+        HasPreimage, NodeBatch, StaleNodeIndex, TreeReader, TreeUpdateBatch, TreeWriter,
+        VersionMetadata,
+    },
+    types::{Version, PRE_GENESIS_VERSION},
     KeyHash, OwnedValue,
 };
 
 #[derive(Default, Debug)]
 struct MockTreeStoreInner {
+    trees: HashMap<Namespace, MockTree>,
+    preimages: HashMap<KeyHash, Vec<u8>>,
+}
+
+/// The nodes, stale node indices and values of the tree in one namespace, along with the history
+/// of the versions written as branches.
+#[derive(Default, Debug)]
+struct MockTree {
     nodes: HashMap<NodeKey, Node>,
     stale_nodes: BTreeSet<StaleNodeIndex>,
     value_history: HashMap<KeyHash, Vec<(Version, Option<OwnedValue>)>>,
-    preimages: HashMap<KeyHash, Vec<u8>>,
+    history: ForkedHistory,
+    /// The versions having a root node, which are the versions of the tree.
+    root_versions: BTreeSet<Version>,
+}
+
+impl MockTree {
+    fn insert_node(&mut self, node_key: NodeKey, node: Node) -> Option<Node> {
+        if is_root(&node_key) {
+            self.root_versions.insert(node_key.version());
+        }
+        self.nodes.insert(node_key, node)
+    }
+
+    fn remove_node(&mut self, node_key: &NodeKey) -> Option<Node> {
+        if is_root(node_key) {
+            self.root_versions.remove(&node_key.version());
+        }
+        self.nodes.remove(node_key)
+    }
+}
+
+/// Returns whether `node_key` is the key of the root of a version, leaving out the root of a tree
+/// being restored.
+fn is_root(node_key: &NodeKey) -> bool {
+    node_key.nibble_path().is_empty() && node_key.version() != PRE_GENESIS_VERSION
 }
 
 /// A mock, in-memory tree store useful for testing.
@@ -34,6 +72,10 @@ struct MockTreeStoreInner {
 /// The tree store is internally represented with a `HashMap`.  This structure
 /// is exposed for use only by downstream crates' tests, and it should obviously
 /// not be used in production.
+///
+/// The store can hold several trees, each in its own [`Namespace`]. Its
+/// [`TreeReader`] and [`TreeWriter`] implementations, like its other methods
+/// without a namespace argument, access the tree in the default namespace.
 pub struct MockTreeStore {
     data: RwLock<MockTreeStoreInner>,
     allow_overwrite: bool,
@@ -48,16 +90,26 @@ impl Default for MockTreeStore {
     }
 }
 
-impl TreeReader for MockTreeStore {
-    fn get_node_option(&self, node_key: &NodeKey) -> Result<Option<Node>> {
-        Ok(self.data.read().nodes.get(node_key).cloned())
+impl NamespacedTreeReader for MockTreeStore {
+    fn get_node_option(&self, namespace: &Namespace, node_key: &NodeKey) -> Result<Option<Node>> {
+        Ok(self
+            .data
+            .read()
+            .trees
+            .get(namespace)
+            .and_then(|tree| tree.nodes.get(node_key).cloned()))
     }
 
-    fn get_rightmost_leaf(&self) -> Result<Option<(NodeKey, LeafNode)>> {
+    fn get_rightmost_leaf(&self, namespace: &Namespace) -> Result<Option<(NodeKey, LeafNode)>> {
         let locked = self.data.read();
         let mut node_key_and_node: Option<(NodeKey, LeafNode)> = None;
 
-        for (key, value) in locked.nodes.iter() {
+        for (key, value) in locked
+            .trees
+            .get(namespace)
+            .into_iter()
+            .flat_map(|tree| &tree.nodes)
+        {
             if let Node::Leaf(leaf_node) = value {
                 if node_key_and_node.is_none()
                     || leaf_node.key_hash() > node_key_and_node.as_ref().unwrap().1.key_hash()
@@ -72,20 +124,143 @@ impl TreeReader for MockTreeStore {
 
     fn get_value_option(
         &self,
+        namespace: &Namespace,
         max_version: Version,
         key_hash: crate::KeyHash,
     ) -> Result<Option<crate::OwnedValue>> {
-        match self.data.read().value_history.get(&key_hash) {
-            Some(version_history) => {
-                for (version, value) in version_history.iter().rev() {
-                    if *version <= max_version {
-                        return Ok(value.clone());
-                    }
-                }
-                Ok(None)
-            }
-            None => Ok(None),
+        self.find_value(namespace, key_hash, |_, version| Ok(version <= max_version))
+    }
+
+    /// The branches of a forked history are not ordered by version, so only the versions written
+    /// resolve there. A linear history resolves the versions skipped up to the latest one.
+    fn latest_version_at_or_before(
+        &self,
+        namespace: &Namespace,
+        version: Version,
+    ) -> Result<Option<Version>> {
+        let locked = self.data.read();
+        let tree = match locked.trees.get(namespace) {
+            Some(tree) => tree,
+            None => return Ok(None),
+        };
+        if !tree.history.is_empty() || tree.root_versions.last() < Some(&version) {
+            return Ok(tree.root_versions.contains(&version).then_some(version));
+        }
+        Ok(tree.root_versions.range(..=version).next_back().copied())
+    }
+
+    /// A linear history builds on the latest version not after `version`, even when it is after
+    /// the latest one. A branch builds on the version it was forked from, which must be written.
+    fn latest_version_to_build_on(
+        &self,
+        namespace: &Namespace,
+        version: Version,
+    ) -> Result<Option<Version>> {
+        let locked = self.data.read();
+        let tree = match locked.trees.get(namespace) {
+            Some(tree) => tree,
+            None => return Ok(None),
+        };
+        if !tree.history.is_empty() {
+            return Ok(tree.root_versions.contains(&version).then_some(version));
         }
+        Ok(tree.root_versions.range(..=version).next_back().copied())
+    }
+}
+
+/// Values written by other branches are not part of the tree at `tip`, and are skipped.
+impl ForkedTreeReader for MockTreeStore {
+    fn get_value_option_on_branch(
+        &self,
+        tip: Version,
+        key_hash: KeyHash,
+    ) -> Result<Option<OwnedValue>> {
+        let namespace = Namespace::default();
+        let known_tip = self
+            .data
+            .read()
+            .trees
+            .get(&namespace)
+            .is_some_and(|tree| tree.history.contains(tip));
+        ensure!(known_tip, "Version {} is not part of the history.", tip);
+        self.find_value(&namespace, key_hash, |tree, version| {
+            tree.history.is_on_branch(version, tip)
+        })
+    }
+}
+
+impl TreeReader for MockTreeStore {
+    fn get_node_option(&self, node_key: &NodeKey) -> Result<Option<Node>> {
+        NamespacedTreeReader::get_node_option(self, &Namespace::default(), node_key)
+    }
+
+    fn get_rightmost_leaf(&self) -> Result<Option<(NodeKey, LeafNode)>> {
+        NamespacedTreeReader::get_rightmost_leaf(self, &Namespace::default())
+    }
+
+    fn get_value_option(
+        &self,
+        max_version: Version,
+        key_hash: crate::KeyHash,
+    ) -> Result<Option<crate::OwnedValue>> {
+        NamespacedTreeReader::get_value_option(self, &Namespace::default(), max_version, key_hash)
+    }
+
+    fn latest_version_at_or_before(&self, version: Version) -> Result<Option<Version>> {
+        NamespacedTreeReader::latest_version_at_or_before(self, &Namespace::default(), version)
+    }
+
+    fn latest_version_to_build_on(&self, version: Version) -> Result<Option<Version>> {
+        NamespacedTreeReader::latest_version_to_build_on(self, &Namespace::default(), version)
+    }
+}
+
+/// The versions of a tree are those of its root nodes, as every version has a root node of its
+/// own, which is purged along with the rest of that version.
+impl NamespacedVersionMetadata for MockTreeStore {
+    fn latest_version(&self, namespace: &Namespace) -> Result<Option<Version>> {
+        Ok(self
+            .data
+            .read()
+            .trees
+            .get(namespace)
+            .and_then(|tree| tree.root_versions.last().copied()))
+    }
+
+    fn oldest_readable_version(&self, namespace: &Namespace) -> Result<Option<Version>> {
+        Ok(self
+            .data
+            .read()
+            .trees
+            .get(namespace)
+            .and_then(|tree| tree.root_versions.first().copied()))
+    }
+
+    fn next_version_after(
+        &self,
+        namespace: &Namespace,
+        version: Version,
+    ) -> Result<Option<Version>> {
+        Ok(self.data.read().trees.get(namespace).and_then(|tree| {
+            tree.root_versions
+                .range((Bound::Excluded(version), Bound::Unbounded))
+                .next()
+                .copied()
+        }))
+    }
+}
+
+impl VersionMetadata for MockTreeStore {
+    fn latest_version(&self) -> Result<Option<Version>> {
+        NamespacedVersionMetadata::latest_version(self, &Namespace::default())
+    }
+
+    fn oldest_readable_version(&self) -> Result<Option<Version>> {
+        NamespacedVersionMetadata::oldest_readable_version(self, &Namespace::default())
+    }
+
+    fn next_version_after(&self, version: Version) -> Result<Option<Version>> {
+        NamespacedVersionMetadata::next_version_after(self, &Namespace::default(), version)
     }
 }
 
@@ -95,27 +270,29 @@ impl HasPreimage for MockTreeStore {
     }
 }
 
-impl TreeWriter for MockTreeStore {
-    fn write_node_batch(&self, node_batch: &NodeBatch) -> Result<()> {
+impl NamespacedTreeWriter for MockTreeStore {
+    fn write_node_batch(&self, namespace: &Namespace, node_batch: &NodeBatch) -> Result<()> {
         let mut locked = self.data.write();
+        let tree = locked.trees.entry(namespace.clone()).or_default();
         for (node_key, node) in node_batch.nodes() {
-            let replaced = locked.nodes.insert(node_key.clone(), node.clone());
+            let replaced = tree.insert_node(node_key.clone(), node.clone());
             if !self.allow_overwrite {
                 assert_eq!(replaced, None);
             }
         }
         for ((version, key_hash), value) in node_batch.values() {
-            put_value(
-                &mut locked.value_history,
-                *version,
-                *key_hash,
-                value.clone(),
-            )?
+            put_value(&mut tree.value_history, *version, *key_hash, value.clone())?
         }
         Ok(())
     }
 }
 
+impl TreeWriter for MockTreeStore {
+    fn write_node_batch(&self, node_batch: &NodeBatch) -> Result<()> {
+        NamespacedTreeWriter::write_node_batch(self, &Namespace::default(), node_batch)
+    }
+}
+
 /// Place a value into the provided value history map. Versions must be pushed in non-decreasing order per key.
 pub fn put_value(
     value_history: &mut HashMap<KeyHash, Vec<(Version, Option<OwnedValue>)>>,
@@ -157,13 +334,12 @@ impl MockTreeStore {
         let key_hash = leaf.key_hash();
         let version = node_key.version();
         let mut locked = self.data.write();
-        match locked.nodes.entry(node_key) {
-            Entry::Occupied(o) => bail!("Key {:?} exists.", o.key()),
-            Entry::Vacant(v) => {
-                v.insert(leaf.into());
-            }
+        let tree = locked.trees.entry(Namespace::default()).or_default();
+        if tree.nodes.contains_key(&node_key) {
+            bail!("Key {:?} exists.", node_key);
         }
-        put_value(&mut locked.value_history, version, key_hash, Some(value))
+        tree.insert_node(node_key, leaf.into());
+        put_value(&mut tree.value_history, version, key_hash, Some(value))
     }
 
     pub fn put_key_preimage(&self, key_hash: KeyHash, preimage: &Vec<u8>) {
@@ -173,28 +349,49 @@ impl MockTreeStore {
             .insert(key_hash, preimage.clone());
     }
 
-    fn put_stale_node_index(&self, index: StaleNodeIndex) -> Result<()> {
-        let is_new_entry = self.data.write().stale_nodes.insert(index);
-        ensure!(is_new_entry, "Duplicated retire log.");
-        Ok(())
+    pub fn write_tree_update_batch(&self, batch: TreeUpdateBatch) -> Result<()> {
+        self.write_tree_update_batch_in(&Namespace::default(), batch)
     }
 
-    pub fn write_tree_update_batch(&self, batch: TreeUpdateBatch) -> Result<()> {
-        self.write_node_batch(&batch.node_batch)?;
-        batch
-            .stale_node_index_batch
-            .into_iter()
-            .map(|i| self.put_stale_node_index(i))
-            .collect::<Result<Vec<_>>>()?;
+    /// Writes the updates of the tree in `namespace`, along with its stale node indices.
+    pub fn write_tree_update_batch_in(
+        &self,
+        namespace: &Namespace,
+        batch: TreeUpdateBatch,
+    ) -> Result<()> {
+        NamespacedTreeWriter::write_node_batch(self, namespace, &batch.node_batch)?;
+        let mut locked = self.data.write();
+        let stale_nodes = &mut locked
+            .trees
+            .entry(namespace.clone())
+            .or_default()
+            .stale_nodes;
+        for index in batch.stale_node_index_batch {
+            ensure!(stale_nodes.insert(index), "Duplicated retire log.");
+        }
         Ok(())
     }
 
     pub fn purge_stale_nodes(&self, least_readable_version: Version) -> Result<()> {
+        self.purge_stale_nodes_in(&Namespace::default(), least_readable_version)
+    }
+
+    /// Purges the stale nodes of the tree in `namespace`, leaving the trees in other namespaces
+    /// untouched whatever their versions.
+    pub fn purge_stale_nodes_in(
+        &self,
+        namespace: &Namespace,
+        least_readable_version: Version,
+    ) -> Result<()> {
         let mut wlocked = self.data.write();
+        let tree = match wlocked.trees.get_mut(namespace) {
+            Some(tree) => tree,
+            None => return Ok(()),
+        };
 
         // Only records retired before or at `least_readable_version` can be purged in order
         // to keep that version still readable.
-        let to_prune = wlocked
+        let to_prune = tree
             .stale_nodes
             .iter()
             .take_while(|log| log.stale_since_version <= least_readable_version)
@@ -202,15 +399,133 @@ impl MockTreeStore {
             .collect::<Vec<_>>();
 
         for log in to_prune {
-            let removed = wlocked.nodes.remove(&log.node_key).is_some();
+            let removed = tree.remove_node(&log.node_key).is_some();
             ensure!(removed, "Stale node index refers to non-existent node.");
-            wlocked.stale_nodes.remove(&log);
+            tree.stale_nodes.remove(&log);
         }
 
         Ok(())
     }
 
+    /// Writes `batch`, created by putting value sets on top of `base_version` from `first_version`
+    /// on, as a branch of the tree in the default namespace. See [`ForkedHistory::add_versions`].
+    pub fn write_branch_update_batch(
+        &self,
+        base_version: Option<Version>,
+        first_version: Version,
+        batch: TreeUpdateBatch,
+    ) -> Result<()> {
+        self.write_branch_update_batch_in(&Namespace::default(), base_version, first_version, batch)
+    }
+
+    /// Writes `batch` as a branch of the tree in `namespace`. Its stale node indices are kept in
+    /// the history of the branches of that tree, to be pruned with
+    /// [`abandon_branch_in`](Self::abandon_branch_in) and
+    /// [`prune_branches_in`](Self::prune_branches_in) rather than with
+    /// [`purge_stale_nodes_in`](Self::purge_stale_nodes_in).
+    pub fn write_branch_update_batch_in(
+        &self,
+        namespace: &Namespace,
+        base_version: Option<Version>,
+        first_version: Version,
+        batch: TreeUpdateBatch,
+    ) -> Result<()> {
+        self.data
+            .write()
+            .trees
+            .entry(namespace.clone())
+            .or_default()
+            .history
+            .add_versions(base_version, first_version, &batch)?;
+        NamespacedTreeWriter::write_node_batch(self, namespace, &batch.node_batch)
+    }
+
+    pub fn abandon_branch(&self, version: Version) -> Result<()> {
+        self.abandon_branch_in(&Namespace::default(), version)
+    }
+
+    /// Deletes the branch of the tree in `namespace` starting at `version`, along with every
+    /// branch built on top of it. See [`ForkedHistory::abandon`].
+    pub fn abandon_branch_in(&self, namespace: &Namespace, version: Version) -> Result<()> {
+        self.prune_history_in(namespace, |history| history.abandon(version))
+    }
+
+    pub fn prune_branches(&self, oldest_readable_version: Version) -> Result<()> {
+        self.prune_branches_in(&Namespace::default(), oldest_readable_version)
+    }
+
+    /// Deletes the versions of the tree in `namespace` before `oldest_readable_version`, once every
+    /// other branch has been abandoned. See [`ForkedHistory::prune`].
+    pub fn prune_branches_in(
+        &self,
+        namespace: &Namespace,
+        oldest_readable_version: Version,
+    ) -> Result<()> {
+        self.prune_history_in(namespace, |history| history.prune(oldest_readable_version))
+    }
+
+    fn prune_history_in(
+        &self,
+        namespace: &Namespace,
+        prune: impl FnOnce(&mut ForkedHistory) -> Result<PrunedNodes>,
+    ) -> Result<()> {
+        let mut wlocked = self.data.write();
+        let tree = wlocked.trees.entry(namespace.clone()).or_default();
+        let pruned = prune(&mut tree.history)?;
+
+        tree.nodes
+            .retain(|node_key, _| !pruned.versions.contains(&node_key.version()));
+        for version in &pruned.versions {
+            tree.root_versions.remove(version);
+        }
+        for node_key in &pruned.stale_nodes {
+            let removed = tree.remove_node(node_key).is_some();
+            ensure!(removed, "Stale node index refers to non-existent node.");
+        }
+        for version_history in tree.value_history.values_mut() {
+            version_history.retain(|(version, _)| !pruned.versions.contains(version));
+        }
+        tree.value_history
+            .retain(|_, version_history| !version_history.is_empty());
+        Ok(())
+    }
+
+    /// Returns the newest value of `key_hash` in the tree in `namespace` whose version is
+    /// `visible`.
+    fn find_value(
+        &self,
+        namespace: &Namespace,
+        key_hash: KeyHash,
+        visible: impl Fn(&MockTree, Version) -> Result<bool>,
+    ) -> Result<Option<OwnedValue>> {
+        let locked = self.data.read();
+        let tree = match locked.trees.get(namespace) {
+            Some(tree) => tree,
+            None => return Ok(None),
+        };
+        match tree.value_history.get(&key_hash) {
+            Some(version_history) => {
+                for (version, value) in version_history.iter().rev() {
+                    if visible(tree, *version)? {
+                        return Ok(value.clone());
+                    }
+                }
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
     pub fn num_nodes(&self) -> usize {
-        self.data.read().nodes.len()
+        self.num_nodes_in(&Namespace::default())
+    }
+
+    /// Returns the number of nodes of the tree in `namespace`.
+    pub fn num_nodes_in(&self, namespace: &Namespace) -> usize {
+        self.data
+            .read()
+            .trees
+            .get(namespace)
+            .map_or(0, |tree| tree.nodes.len())
     }
 }
@@ -0,0 +1,326 @@
+//! This module lets several [`JellyfishMerkleTree`](crate::JellyfishMerkleTree)s, each with its
+//! own version history, share a single store, for instance a main state tree and one tree per
+//! module of an application.
+//!
+//! A store holding several trees implements [`NamespacedTreeReader`] and [`NamespacedTreeWriter`],
+//! which take the [`Namespace`] of the tree along with every node key or value. Wrapping such a
+//! store in a [`NamespacedStore`] gives the plain [`TreeReader`] and [`TreeWriter`] of one of its
+//! trees, to be used with a tree, an iterator or a restore as usual.
+//!
+//! Node keys and versions are only unique within a namespace, so the stale node indices of each
+//! [`TreeUpdateBatch`](crate::storage::TreeUpdateBatch) must be recorded, and pruned, along with
+//! the namespace of the tree which produced them.
+
+use alloc::{sync::Arc, vec::Vec};
+use core::ops::Deref;
+
+use anyhow::Result;
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{
+    node_type::{LeafNode, Node, NodeKey},
+    storage::{HasPreimage, NodeBatch, TreeReader, TreeWriter, VersionMetadata},
+    KeyHash, OwnedValue, Version,
+};
+
+/// The name of one of the trees sharing a store.
+///
+/// The default, empty namespace is the one used by stores which only hold a single tree, so that
+/// such a store can later hold more trees without moving the existing one.
+#[derive(
+    Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, BorshSerialize, BorshDeserialize,
+)]
+pub struct Namespace(Vec<u8>);
+
+impl Namespace {
+    /// Creates the namespace with the given name.
+    pub fn new(name: impl Into<Vec<u8>>) -> Self {
+        Self(name.into())
+    }
+
+    /// Returns the name of this namespace.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Defines the interface between the trees sharing a store and the nodes they hold in it. Each
+/// method is the one of [`TreeReader`] with the same name, restricted to the tree in `namespace`.
+pub trait NamespacedTreeReader {
+    /// Gets the node of the tree in `namespace` with the given node key. Returns `None` if the
+    /// node does not exist.
+    fn get_node_option(&self, namespace: &Namespace, node_key: &NodeKey) -> Result<Option<Node>>;
+
+    /// Gets the newest value of the tree in `namespace` for `key_hash` whose version is *less
+    /// than or equal to* the specified version. Returns `None` if the value does not exist.
+    fn get_value_option(
+        &self,
+        namespace: &Namespace,
+        max_version: Version,
+        key_hash: KeyHash,
+    ) -> Result<Option<OwnedValue>>;
+
+    /// Returns the latest version of the tree in `namespace` which is not after `version`, or
+    /// `None` if there is none. Defaults to `version` itself, like
+    /// [`TreeReader::latest_version_at_or_before`].
+    fn latest_version_at_or_before(
+        &self,
+        _namespace: &Namespace,
+        version: Version,
+    ) -> Result<Option<Version>> {
+        Ok(Some(version))
+    }
+
+    /// Returns the version of the tree in `namespace` which a new version built on top of
+    /// `version` starts from. Defaults to the one `version` resolves to, like
+    /// [`TreeReader::latest_version_to_build_on`].
+    fn latest_version_to_build_on(
+        &self,
+        namespace: &Namespace,
+        version: Version,
+    ) -> Result<Option<Version>> {
+        self.latest_version_at_or_before(namespace, version)
+    }
+
+    /// Gets the rightmost leaf of the tree being restored in `namespace`.
+    fn get_rightmost_leaf(&self, namespace: &Namespace) -> Result<Option<(NodeKey, LeafNode)>>;
+}
+
+/// Defines the interface used to write a batch of updates to one of the trees sharing a store.
+pub trait NamespacedTreeWriter {
+    /// Writes a node batch of the tree in `namespace` into storage.
+    fn write_node_batch(&self, namespace: &Namespace, node_batch: &NodeBatch) -> Result<()>;
+}
+
+/// Defines the ability for a store holding several trees to tell which versions of each tree it
+/// holds. Each method is the one of [`VersionMetadata`] with the same name, restricted to the tree
+/// in `namespace`.
+pub trait NamespacedVersionMetadata {
+    /// Returns the latest version of the tree in `namespace`, or `None` if no version has been
+    /// written yet.
+    fn latest_version(&self, namespace: &Namespace) -> Result<Option<Version>>;
+
+    /// Returns the oldest version of the tree in `namespace` which has not been pruned, or `None`
+    /// if no version has been written yet.
+    fn oldest_readable_version(&self, namespace: &Namespace) -> Result<Option<Version>>;
+
+    /// Returns the first version of the tree in `namespace` written after `version`, or `None` if
+    /// there is none. Defaults to `version + 1` up to the latest version, like
+    /// [`VersionMetadata::next_version_after`].
+    fn next_version_after(
+        &self,
+        namespace: &Namespace,
+        version: Version,
+    ) -> Result<Option<Version>> {
+        Ok(self
+            .latest_version(namespace)?
+            .filter(|latest_version| version < *latest_version)
+            .map(|_| version + 1))
+    }
+}
+
+impl<S: NamespacedTreeReader + ?Sized> NamespacedTreeReader for &S {
+    fn get_node_option(&self, namespace: &Namespace, node_key: &NodeKey) -> Result<Option<Node>> {
+        (**self).get_node_option(namespace, node_key)
+    }
+
+    fn get_value_option(
+        &self,
+        namespace: &Namespace,
+        max_version: Version,
+        key_hash: KeyHash,
+    ) -> Result<Option<OwnedValue>> {
+        (**self).get_value_option(namespace, max_version, key_hash)
+    }
+
+    fn latest_version_at_or_before(
+        &self,
+        namespace: &Namespace,
+        version: Version,
+    ) -> Result<Option<Version>> {
+        (**self).latest_version_at_or_before(namespace, version)
+    }
+
+    fn latest_version_to_build_on(
+        &self,
+        namespace: &Namespace,
+        version: Version,
+    ) -> Result<Option<Version>> {
+        (**self).latest_version_to_build_on(namespace, version)
+    }
+
+    fn get_rightmost_leaf(&self, namespace: &Namespace) -> Result<Option<(NodeKey, LeafNode)>> {
+        (**self).get_rightmost_leaf(namespace)
+    }
+}
+
+impl<S: NamespacedTreeReader + ?Sized> NamespacedTreeReader for Arc<S> {
+    fn get_node_option(&self, namespace: &Namespace, node_key: &NodeKey) -> Result<Option<Node>> {
+        (**self).get_node_option(namespace, node_key)
+    }
+
+    fn get_value_option(
+        &self,
+        namespace: &Namespace,
+        max_version: Version,
+        key_hash: KeyHash,
+    ) -> Result<Option<OwnedValue>> {
+        (**self).get_value_option(namespace, max_version, key_hash)
+    }
+
+    fn latest_version_at_or_before(
+        &self,
+        namespace: &Namespace,
+        version: Version,
+    ) -> Result<Option<Version>> {
+        (**self).latest_version_at_or_before(namespace, version)
+    }
+
+    fn latest_version_to_build_on(
+        &self,
+        namespace: &Namespace,
+        version: Version,
+    ) -> Result<Option<Version>> {
+        (**self).latest_version_to_build_on(namespace, version)
+    }
+
+    fn get_rightmost_leaf(&self, namespace: &Namespace) -> Result<Option<(NodeKey, LeafNode)>> {
+        (**self).get_rightmost_leaf(namespace)
+    }
+}
+
+impl<S: NamespacedVersionMetadata + ?Sized> NamespacedVersionMetadata for &S {
+    fn latest_version(&self, namespace: &Namespace) -> Result<Option<Version>> {
+        (**self).latest_version(namespace)
+    }
+
+    fn oldest_readable_version(&self, namespace: &Namespace) -> Result<Option<Version>> {
+        (**self).oldest_readable_version(namespace)
+    }
+
+    fn next_version_after(
+        &self,
+        namespace: &Namespace,
+        version: Version,
+    ) -> Result<Option<Version>> {
+        (**self).next_version_after(namespace, version)
+    }
+}
+
+impl<S: NamespacedVersionMetadata + ?Sized> NamespacedVersionMetadata for Arc<S> {
+    fn latest_version(&self, namespace: &Namespace) -> Result<Option<Version>> {
+        (**self).latest_version(namespace)
+    }
+
+    fn oldest_readable_version(&self, namespace: &Namespace) -> Result<Option<Version>> {
+        (**self).oldest_readable_version(namespace)
+    }
+
+    fn next_version_after(
+        &self,
+        namespace: &Namespace,
+        version: Version,
+    ) -> Result<Option<Version>> {
+        (**self).next_version_after(namespace, version)
+    }
+}
+
+impl<S: NamespacedTreeWriter + ?Sized> NamespacedTreeWriter for &S {
+    fn write_node_batch(&self, namespace: &Namespace, node_batch: &NodeBatch) -> Result<()> {
+        (**self).write_node_batch(namespace, node_batch)
+    }
+}
+
+impl<S: NamespacedTreeWriter + ?Sized> NamespacedTreeWriter for Arc<S> {
+    fn write_node_batch(&self, namespace: &Namespace, node_batch: &NodeBatch) -> Result<()> {
+        (**self).write_node_batch(namespace, node_batch)
+    }
+}
+
+/// The [`TreeReader`] and [`TreeWriter`] of the tree in one namespace of a shared store.
+///
+/// `S` is a handle to the store, such as a reference to it or an [`Arc`], the latter being needed
+/// to restore a tree with a [`JellyfishMerkleRestore`](crate::restore::JellyfishMerkleRestore).
+#[derive(Clone, Debug)]
+pub struct NamespacedStore<S> {
+    store: S,
+    namespace: Namespace,
+}
+
+impl<S> NamespacedStore<S> {
+    /// Gives access to the tree in `namespace` of `store`.
+    pub fn new(store: S, namespace: Namespace) -> Self {
+        Self { store, namespace }
+    }
+
+    /// Returns the namespace of the tree accessed.
+    pub fn namespace(&self) -> &Namespace {
+        &self.namespace
+    }
+
+    /// Returns the shared store.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+}
+
+impl<S: NamespacedTreeReader> TreeReader for NamespacedStore<S> {
+    fn get_node_option(&self, node_key: &NodeKey) -> Result<Option<Node>> {
+        self.store.get_node_option(&self.namespace, node_key)
+    }
+
+    fn get_value_option(
+        &self,
+        max_version: Version,
+        key_hash: KeyHash,
+    ) -> Result<Option<OwnedValue>> {
+        self.store
+            .get_value_option(&self.namespace, max_version, key_hash)
+    }
+
+    fn latest_version_at_or_before(&self, version: Version) -> Result<Option<Version>> {
+        self.store
+            .latest_version_at_or_before(&self.namespace, version)
+    }
+
+    fn latest_version_to_build_on(&self, version: Version) -> Result<Option<Version>> {
+        self.store
+            .latest_version_to_build_on(&self.namespace, version)
+    }
+
+    fn get_rightmost_leaf(&self) -> Result<Option<(NodeKey, LeafNode)>> {
+        self.store.get_rightmost_leaf(&self.namespace)
+    }
+}
+
+impl<S: NamespacedTreeWriter> TreeWriter for NamespacedStore<S> {
+    fn write_node_batch(&self, node_batch: &NodeBatch) -> Result<()> {
+        self.store.write_node_batch(&self.namespace, node_batch)
+    }
+}
+
+impl<S: NamespacedVersionMetadata> VersionMetadata for NamespacedStore<S> {
+    fn latest_version(&self) -> Result<Option<Version>> {
+        self.store.latest_version(&self.namespace)
+    }
+
+    fn oldest_readable_version(&self) -> Result<Option<Version>> {
+        self.store.oldest_readable_version(&self.namespace)
+    }
+
+    fn next_version_after(&self, version: Version) -> Result<Option<Version>> {
+        self.store.next_version_after(&self.namespace, version)
+    }
+}
+
+/// Key preimages do not depend on the tree holding the keys, so they are shared by all the
+/// namespaces.
+impl<S> HasPreimage for NamespacedStore<S>
+where
+    S: Deref,
+    S::Target: HasPreimage,
+{
+    fn preimage(&self, key_hash: KeyHash) -> Result<Option<Vec<u8>>> {
+        self.store.deref().preimage(key_hash)
+    }
+}
@@ -16,7 +16,6 @@ use alloc::vec::Vec;
 use alloc::{boxed::Box, vec};
 use anyhow::Context;
 use borsh::{BorshDeserialize, BorshSerialize};
-use num_derive::{FromPrimitive, ToPrimitive};
 #[cfg(any(test))]
 use proptest::prelude::*;
 #[cfg(any(test))]
@@ -30,9 +29,13 @@ use crate::{
         proof::{SparseMerkleInternalNode, SparseMerkleLeafNode},
         Version,
     },
-    KeyHash, ValueHash, SPARSE_MERKLE_PLACEHOLDER_HASH,
+    KeyHash, ValueHash,
 };
 
+mod codec;
+
+pub use self::codec::{NodeDecodeError, NodeEncodingVersion};
+
 /// The unique key of each node.
 #[derive(
     Clone,
@@ -65,7 +68,7 @@ impl NodeKey {
     }
 
     /// A shortcut to generate a node key consisting of a version and an empty nibble path.
-    pub(crate) fn new_empty_path(version: Version) -> Self {
+    pub fn new_empty_path(version: Version) -> Self {
         Self::new(version, NibblePath::new(vec![]))
     }
 
@@ -79,8 +82,9 @@ impl NodeKey {
         &self.nibble_path
     }
 
-    /// Generates a child node key based on this node key.
-    pub(crate) fn gen_child_node_key(&self, version: Version, n: Nibble) -> Self {
+    /// Generates the key of the child at nibble `n` of the node with this key, given the version
+    /// of that child.
+    pub fn gen_child_node_key(&self, version: Version, n: Nibble) -> Self {
         let mut node_nibble_path = self.nibble_path().clone();
         node_nibble_path.push(n);
         Self::new(version, node_nibble_path)
@@ -574,7 +578,7 @@ impl InternalNode {
             Self::range_bitmaps(start, width, (existence_bitmap, leaf_bitmap));
         if range_existence_bitmap == 0 {
             // No child under this subtree
-            SPARSE_MERKLE_PLACEHOLDER_HASH
+            H::SPARSE_MERKLE_PLACEHOLDER_HASH
         } else if has_only_child(width, range_existence_bitmap, range_leaf_bitmap) {
             // Only 1 leaf child under this subtree or reach the lowest level
             let only_child_index = Nibble::from(range_existence_bitmap.trailing_zeros() as u8);
@@ -865,15 +869,6 @@ impl From<LeafNode> for SparseMerkleLeafNode {
     }
 }
 
-#[repr(u8)]
-#[derive(FromPrimitive, ToPrimitive, BorshDeserialize, BorshSerialize)]
-#[borsh(use_discriminant = false)]
-enum NodeTag {
-    Null = 0,
-    Leaf = 1,
-    Internal = 2,
-}
-
 /// The concrete node type of [`JellyfishMerkleTree`](crate::JellyfishMerkleTree).
 #[derive(Clone, Debug, Eq, PartialEq, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
 pub enum Node {
@@ -957,7 +952,7 @@ impl Node {
     /// Computes the hash of nodes.
     pub(crate) fn hash<H: SimpleHasher>(&self) -> [u8; 32] {
         match self {
-            Node::Null => SPARSE_MERKLE_PLACEHOLDER_HASH,
+            Node::Null => H::SPARSE_MERKLE_PLACEHOLDER_HASH,
             Node::Internal(internal_node) => internal_node.hash::<H>(),
             Node::Leaf(leaf_node) => leaf_node.hash::<H>(),
         }
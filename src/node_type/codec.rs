@@ -0,0 +1,314 @@
+//! Versioned encodings of [`Node`]s, for storing them.
+//!
+//! Every encoded node starts with a tag byte, whose high nibble is the [`NodeEncodingVersion`] of
+//! the encoding, and whose low nibble is the kind of node, numbered like the variants of [`Node`].
+//!
+//! Version 0 is the borsh encoding derived for [`Node`], which stores used before nodes had an
+//! explicit encoding: it starts with the index of the variant, which reads as a tag of version 0.
+//! It is still decoded, so that the nodes of existing stores can be
+//! [migrated](crate::migration::NodeEncodingMigration) while the tree is in use.
+//!
+//! In version 1, the compact encoding, the tag byte is followed by:
+//!
+//! - Nothing for a null node.
+//! - The key hash and value hash of a leaf node.
+//! - For an internal node, its existence bitmap and leaf bitmap as little-endian `u16`s (see
+//!   [`InternalNode::generate_bitmaps`]), then for each of its children in nibble order: the
+//!   version of the child as a varint, its hash, and its leaf count as a varint if it is an
+//!   internal node.
+//!
+//! Varints are unsigned LEB128: 7 bits per byte from the lowest ones, with the high bit set on all
+//! bytes but the last one. Compared to the derived encodings, where a child takes 42 bytes, or 50
+//! if it is an internal node, a child takes 33 bytes plus its varints, and absent children take no
+//! space at all.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+use super::{Child, Children, InternalNode, LeafNode, Node, NodeType};
+use crate::{types::nibble::Nibble, KeyHash, ValueHash};
+
+/// A version of the encoding of [`Node`]s, as recorded in the high nibble of their first byte.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum NodeEncodingVersion {
+    /// The borsh encoding derived for [`Node`].
+    Borsh = 0,
+    /// The compact encoding, with bitmaps and varints.
+    Compact = 1,
+}
+
+impl NodeEncodingVersion {
+    /// The version written by [`Node::encode`].
+    pub const LATEST: Self = NodeEncodingVersion::Compact;
+
+    /// Returns the version of the encoding of `bytes`, as told by their first byte.
+    pub fn of(bytes: &[u8]) -> Result<Self, NodeDecodeError> {
+        let tag = bytes.first().ok_or(NodeDecodeError::EmptyInput)?;
+        Self::from_u8(tag >> 4).ok_or(NodeDecodeError::UnsupportedVersion { version: tag >> 4 })
+    }
+
+    /// Returns the version following this one, if any.
+    pub fn next(self) -> Option<Self> {
+        Self::from_u8(self as u8 + 1)
+    }
+
+    fn from_u8(version: u8) -> Option<Self> {
+        match version {
+            0 => Some(NodeEncodingVersion::Borsh),
+            1 => Some(NodeEncodingVersion::Compact),
+            _ => None,
+        }
+    }
+}
+
+/// The kind of an encoded node, in the low nibble of its tag byte.
+#[repr(u8)]
+enum NodeTag {
+    Null = 0,
+    Internal = 1,
+    Leaf = 2,
+}
+
+impl NodeTag {
+    fn from_u8(kind: u8) -> Option<Self> {
+        match kind {
+            0 => Some(NodeTag::Null),
+            1 => Some(NodeTag::Internal),
+            2 => Some(NodeTag::Leaf),
+            _ => None,
+        }
+    }
+}
+
+impl Node {
+    /// Encodes the node with the latest encoding, [`NodeEncodingVersion::LATEST`].
+    pub fn encode(&self) -> Vec<u8> {
+        self.encode_as(NodeEncodingVersion::LATEST)
+    }
+
+    /// Encodes the node with the encoding `version`, described in the [module](self)
+    /// documentation.
+    pub fn encode_as(&self, version: NodeEncodingVersion) -> Vec<u8> {
+        if version == NodeEncodingVersion::Borsh {
+            return borsh::to_vec(self).expect("serialization is infallible");
+        }
+
+        let tag = |kind: NodeTag| ((version as u8) << 4) | kind as u8;
+        let mut bytes = Vec::new();
+        match self {
+            Node::Null => bytes.push(tag(NodeTag::Null)),
+            Node::Internal(internal_node) => {
+                bytes.push(tag(NodeTag::Internal));
+                internal_node.encode(&mut bytes);
+            }
+            Node::Leaf(leaf_node) => {
+                bytes.push(tag(NodeTag::Leaf));
+                bytes.extend_from_slice(&leaf_node.key_hash.0);
+                bytes.extend_from_slice(&leaf_node.value_hash.0);
+            }
+        }
+        bytes
+    }
+
+    /// Decodes a node encoded by [`Node::encode_as`], with any version of the encoding.
+    ///
+    /// Only the canonical encoding of a node is accepted: varints must be as short as possible,
+    /// and no bytes may follow the node.
+    pub fn decode(bytes: &[u8]) -> Result<Self, NodeDecodeError> {
+        let version = NodeEncodingVersion::of(bytes)?;
+        let (&tag, rest) = bytes.split_first().expect("bytes have a version");
+        let Some(node_tag) = NodeTag::from_u8(tag & 0x0f) else {
+            return Err(NodeDecodeError::UnknownTag { tag });
+        };
+        if version == NodeEncodingVersion::Borsh {
+            return borsh::from_slice(bytes).map_err(|error| NodeDecodeError::MalformedBorsh {
+                reason: alloc::format!("{}", error),
+            });
+        }
+
+        let mut reader = Reader { bytes: rest };
+        let node = match node_tag {
+            NodeTag::Null => Node::Null,
+            NodeTag::Internal => Node::Internal(InternalNode::decode(&mut reader)?),
+            NodeTag::Leaf => Node::Leaf(LeafNode::new(
+                KeyHash(reader.take()?),
+                ValueHash(reader.take()?),
+            )),
+        };
+        if !reader.bytes.is_empty() {
+            return Err(NodeDecodeError::TrailingBytes {
+                len: reader.bytes.len(),
+            });
+        }
+        Ok(node)
+    }
+}
+
+impl InternalNode {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        let (existence_bitmap, leaf_bitmap) = self.generate_bitmaps();
+        bytes.extend_from_slice(&existence_bitmap.to_le_bytes());
+        bytes.extend_from_slice(&leaf_bitmap.to_le_bytes());
+        for (_, child) in self.children_sorted() {
+            push_varint(bytes, child.version);
+            bytes.extend_from_slice(&child.hash);
+            if let NodeType::Internal { leaf_count } = child.node_type {
+                push_varint(bytes, leaf_count as u64);
+            }
+        }
+    }
+
+    fn decode(reader: &mut Reader) -> Result<Self, NodeDecodeError> {
+        let existence_bitmap = u16::from_le_bytes(reader.take()?);
+        let leaf_bitmap = u16::from_le_bytes(reader.take()?);
+        if existence_bitmap == 0 {
+            return Err(NodeDecodeError::NoChildren);
+        }
+        if leaf_bitmap & !existence_bitmap != 0 {
+            return Err(NodeDecodeError::ExtraLeaves {
+                existence_bitmap,
+                leaf_bitmap,
+            });
+        }
+        if existence_bitmap.count_ones() == 1 && leaf_bitmap != 0 {
+            return Err(NodeDecodeError::OnlyChildIsLeaf);
+        }
+
+        let mut children = Children::new();
+        let mut leaf_count = 0usize;
+        for i in (0..16u8).filter(|i| existence_bitmap & (1 << i) != 0) {
+            let version = reader.varint()?;
+            let hash = reader.take()?;
+            let node_type = if leaf_bitmap & (1 << i) != 0 {
+                NodeType::Leaf
+            } else {
+                let leaf_count = usize::try_from(reader.varint()?)
+                    .map_err(|_| NodeDecodeError::LeafCountOverflow)?;
+                NodeType::Internal { leaf_count }
+            };
+            let child = Child::new(hash, version, node_type);
+            leaf_count = leaf_count
+                .checked_add(child.leaf_count())
+                .ok_or(NodeDecodeError::LeafCountOverflow)?;
+            children.insert(Nibble::from(i), child);
+        }
+        Ok(InternalNode::new(children))
+    }
+}
+
+/// Appends `num` to `bytes` as an unsigned LEB128 varint.
+fn push_varint(bytes: &mut Vec<u8>, mut num: u64) {
+    while num >= 0x80 {
+        bytes.push(num as u8 | 0x80);
+        num >>= 7;
+    }
+    bytes.push(num as u8);
+}
+
+/// Reads the parts of an encoded node from the front of its bytes.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], NodeDecodeError> {
+        if self.bytes.len() < N {
+            return Err(NodeDecodeError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(N);
+        self.bytes = rest;
+        Ok(taken.try_into().expect("slice has N bytes"))
+    }
+
+    fn varint(&mut self) -> Result<u64, NodeDecodeError> {
+        let mut num = 0u64;
+        for shift in (0..64).step_by(7) {
+            let [byte] = self.take()?;
+            let bits = u64::from(byte & 0x7f);
+            if bits << shift >> shift != bits {
+                return Err(NodeDecodeError::InvalidVarint);
+            }
+            num |= bits << shift;
+            if byte & 0x80 == 0 {
+                // A zero last byte means that a shorter encoding of the same number exists.
+                if byte == 0 && shift != 0 {
+                    return Err(NodeDecodeError::InvalidVarint);
+                }
+                return Ok(num);
+            }
+        }
+        Err(NodeDecodeError::InvalidVarint)
+    }
+}
+
+/// The reason bytes were rejected by [`Node::decode`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NodeDecodeError {
+    /// There are no bytes to decode.
+    EmptyInput,
+    /// The node was encoded with a version of the encoding this crate does not know about.
+    UnsupportedVersion { version: u8 },
+    /// The tag byte does not name a kind of node.
+    UnknownTag { tag: u8 },
+    /// The bytes end in the middle of the node.
+    Truncated,
+    /// Bytes are left after the end of the node.
+    TrailingBytes { len: usize },
+    /// A varint is longer than needed, or does not fit a `u64`.
+    InvalidVarint,
+    /// The internal node has no children.
+    NoChildren,
+    /// The leaf bitmap has bits set for children which do not exist.
+    ExtraLeaves {
+        existence_bitmap: u16,
+        leaf_bitmap: u16,
+    },
+    /// The only child of the internal node is a leaf, which should have taken its place.
+    OnlyChildIsLeaf,
+    /// The number of leaves under the internal node does not fit a `usize`.
+    LeafCountOverflow,
+    /// The node could not be decoded with the borsh encoding of version 0.
+    MalformedBorsh { reason: String },
+}
+
+impl fmt::Display for NodeDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeDecodeError::EmptyInput => write!(f, "Missing tag due to empty input."),
+            NodeDecodeError::UnsupportedVersion { version } => write!(
+                f,
+                "Unsupported node encoding version {}. Latest known version is {}.",
+                version,
+                NodeEncodingVersion::LATEST as u8
+            ),
+            NodeDecodeError::UnknownTag { tag } => write!(f, "Unknown node tag {:#04x}.", tag),
+            NodeDecodeError::Truncated => write!(f, "Encoded node is truncated."),
+            NodeDecodeError::TrailingBytes { len } => {
+                write!(f, "Encoded node is followed by {} more bytes.", len)
+            }
+            NodeDecodeError::InvalidVarint => write!(f, "Invalid varint in encoded node."),
+            NodeDecodeError::NoChildren => write!(f, "No children found in internal node."),
+            NodeDecodeError::ExtraLeaves {
+                existence_bitmap,
+                leaf_bitmap,
+            } => write!(
+                f,
+                "Extra leaf bits set: existence: {:#06x}, leaf: {:#06x}.",
+                existence_bitmap, leaf_bitmap
+            ),
+            NodeDecodeError::OnlyChildIsLeaf => {
+                write!(f, "The only child of an internal node is a leaf.")
+            }
+            NodeDecodeError::LeafCountOverflow => {
+                write!(f, "Leaf count of internal node overflows.")
+            }
+            NodeDecodeError::MalformedBorsh { reason } => {
+                write!(f, "Malformed borsh-encoded node: {}", reason)
+            }
+        }
+    }
+}
+
+impl core::error::Error for NodeDecodeError {}
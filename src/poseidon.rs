@@ -0,0 +1,167 @@
+//! A hashing mode whose leaf and internal node hashes are Poseidon hashes over the scalar field of
+//! the BN254 curve, so that proofs of a [`PoseidonJmt`](crate::PoseidonJmt) are cheap to verify in
+//! arithmetic circuits over that field.
+//!
+//! Every hash of this mode is the canonical encoding of a [`FieldElement`], including the
+//! placeholder hash of empty subtrees, which is zero rather than the default
+//! `"SPARSE_MERKLE_PLACEHOLDER_HASH__"`, as the latter is not below the modulus. A
+//! [`Domain`](crate::domain::Domain) used with this hasher should keep it so with a placeholder hash
+//! of its own below the modulus. The hashes are defined on top of a sponge over the width-3
+//! [`permutation`], with one capacity element and a rate of two elements:
+//!
+//! - The sponge of a domain `d` and a list of elements starts from the state `[d, 0, 0]`, then
+//!   adds the elements two by two, the last one padded with zero, to the rate elements and
+//!   permutes the state after each pair. Its output is the first rate element.
+//! - Nodes are hashed in the [`domain`] of the leaf or internal domain separator of the hasher,
+//!   [`LEAF_DOMAIN_SEPARATOR`](crate::proof::LEAF_DOMAIN_SEPARATOR) and
+//!   [`INTERNAL_DOMAIN_SEPARATOR`](crate::proof::INTERNAL_DOMAIN_SEPARATOR) by default, and data
+//!   in [`BYTES_DOMAIN`].
+//! - A leaf hashes in its domain the four elements [`split`] from its key hash and value hash.
+//! - An internal node hashes in its domain its two children, each
+//!   [reduced](FieldElement::from_bytes_reduced) to an element, which leaves the canonical
+//!   encodings of node hashes and of the placeholder hash as they are. As the hashes `x` and
+//!   `x + p` of a child would then hash alike, proofs are rejected when one of their sibling hashes
+//!   is not [canonical](crate::SimpleHasher::is_canonical_hash).
+//! - Data, such as keys and values, hashes in its domain its length in bytes, followed by the
+//!   big-endian integers of its 31-byte chunks, the last one padded with zeros.
+
+use alloc::vec::Vec;
+
+use crate::SimpleHasher;
+
+mod constants;
+mod field;
+
+pub use field::FieldElement;
+
+/// The number of full rounds of the permutation, half of them before the partial rounds.
+const FULL_ROUNDS: usize = 8;
+/// The number of partial rounds of the permutation.
+const PARTIAL_ROUNDS: usize = 57;
+
+/// The Poseidon permutation of width 3 with the S-box `x^5`, 8 full rounds and 57 partial rounds,
+/// using the round constants and MDS matrix of the reference implementation.
+pub fn permutation(mut state: [FieldElement; 3]) -> [FieldElement; 3] {
+    for round in 0..FULL_ROUNDS + PARTIAL_ROUNDS {
+        for (i, element) in state.iter_mut().enumerate() {
+            *element = element.add(&constants::ROUND_CONSTANTS[3 * round + i]);
+        }
+        if (FULL_ROUNDS / 2..FULL_ROUNDS / 2 + PARTIAL_ROUNDS).contains(&round) {
+            state[0] = state[0].pow5();
+        } else {
+            for element in state.iter_mut() {
+                *element = element.pow5();
+            }
+        }
+        state = constants::MDS.map(|row| {
+            row.iter()
+                .zip(&state)
+                .fold(FieldElement::ZERO, |sum, (entry, element)| {
+                    sum.add(&entry.mul(element))
+                })
+        });
+    }
+    state
+}
+
+/// Hashes `elements` in `domain` with the sponge described in the [module](self) documentation.
+pub fn sponge(domain: FieldElement, elements: &[FieldElement]) -> FieldElement {
+    let mut state = [domain, FieldElement::ZERO, FieldElement::ZERO];
+    for pair in elements.chunks(2) {
+        state[1] = state[1].add(&pair[0]);
+        if let Some(second) = pair.get(1) {
+            state[2] = state[2].add(second);
+        }
+        state = permutation(state);
+    }
+    state[1]
+}
+
+/// Splits a key hash or value hash, which may be any 32 bytes, into the elements of its high and
+/// low 128 bits, so that no two of them map to the same elements.
+pub fn split(bytes: &[u8; 32]) -> [FieldElement; 2] {
+    let (high, low) = bytes.split_at(16);
+    [high, low].map(|half| {
+        FieldElement::from_u128(u128::from_be_bytes(
+            half.try_into().expect("halves have 16 bytes"),
+        ))
+    })
+}
+
+/// Returns the domain of the hashes of nodes with the given domain separator: the big-endian
+/// integer of a separator of at most 31 bytes, or else the hash of the separator as data.
+pub fn domain(separator: &[u8]) -> FieldElement {
+    if separator.len() < 32 {
+        let mut bytes = [0; 32];
+        bytes[32 - separator.len()..].copy_from_slice(separator);
+        FieldElement::from_bytes_reduced(&bytes)
+    } else {
+        let mut hasher = PoseidonHasher::new();
+        hasher.update(separator);
+        FieldElement::from_bytes_reduced(&hasher.finalize())
+    }
+}
+
+/// The domain of the hashes of data, such as keys and values, which is the big-endian integer of
+/// `"JMT::Bytes"`.
+pub const BYTES_DOMAIN: FieldElement = FieldElement::from_u128(0x4a4d543a3a4279746573);
+
+/// The [`SimpleHasher`] of the Poseidon hashing mode.
+///
+/// Data is buffered until the hasher is finalized, as its length comes first in its hash.
+#[derive(Clone, Debug, Default)]
+pub struct PoseidonHasher {
+    data: Vec<u8>,
+}
+
+impl SimpleHasher for PoseidonHasher {
+    const SPARSE_MERKLE_PLACEHOLDER_HASH: [u8; 32] = [0; 32];
+
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.data.extend_from_slice(data);
+    }
+
+    fn finalize(self) -> [u8; 32] {
+        let mut elements = Vec::with_capacity(1 + self.data.len() / 31 + 1);
+        elements.push(FieldElement::from_u128(self.data.len() as u128));
+        for chunk in self.data.chunks(31) {
+            let mut bytes = [0; 32];
+            bytes[1..1 + chunk.len()].copy_from_slice(chunk);
+            elements.push(FieldElement::from_bytes_reduced(&bytes));
+        }
+        sponge(BYTES_DOMAIN, &elements).to_bytes()
+    }
+
+    fn hash_leaf(domain_separator: &[u8], key_hash: &[u8; 32], value_hash: &[u8; 32]) -> [u8; 32] {
+        let [key_high, key_low] = split(key_hash);
+        let [value_high, value_low] = split(value_hash);
+        sponge(
+            domain(domain_separator),
+            &[key_high, key_low, value_high, value_low],
+        )
+        .to_bytes()
+    }
+
+    fn hash_internal(
+        domain_separator: &[u8],
+        left_child: &[u8; 32],
+        right_child: &[u8; 32],
+    ) -> [u8; 32] {
+        sponge(
+            domain(domain_separator),
+            &[
+                FieldElement::from_bytes_reduced(left_child),
+                FieldElement::from_bytes_reduced(right_child),
+            ],
+        )
+        .to_bytes()
+    }
+
+    fn is_canonical_hash(hash: &[u8; 32]) -> bool {
+        FieldElement::from_canonical_bytes(hash).is_some()
+    }
+}
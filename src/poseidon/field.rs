@@ -0,0 +1,200 @@
+//! Arithmetic in the scalar field of the BN254 curve, whose elements are kept in Montgomery form.
+
+/// The modulus `p`, as little-endian 64-bit limbs.
+const MODULUS: [u64; 4] = [
+    0x43e1f593f0000001,
+    0x2833e84879b97091,
+    0xb85045b68181585d,
+    0x30644e72e131a029,
+];
+
+/// `-p^-1 mod 2^64`.
+const INV: u64 = 0xc2e1f593efffffff;
+
+/// `2^512 mod p`, which brings an integer into Montgomery form.
+const R2: [u64; 4] = [
+    0x1bb8e645ae216da7,
+    0x53fe3ab1e35c59e3,
+    0x8c49833d53bb8085,
+    0x0216d0b17f4e44a5,
+];
+
+/// An element of the scalar field of the BN254 curve, the prime field of order
+/// `p = 21888242871839275222246405745257275088548364400416034343698204186575808495617`.
+///
+/// Elements are encoded as 32 big-endian bytes. An encoding is canonical when it is less than `p`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FieldElement([u64; 4]);
+
+impl core::fmt::Debug for FieldElement {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "FieldElement({})", hex::encode(self.to_bytes()))
+    }
+}
+
+impl FieldElement {
+    /// The additive identity.
+    pub const ZERO: Self = Self([0; 4]);
+
+    /// Decodes the canonical encoding of an element, or returns `None` if `bytes` is not less than
+    /// `p`.
+    pub fn from_canonical_bytes(bytes: &[u8; 32]) -> Option<Self> {
+        let integer = from_be_bytes(bytes);
+        if less_than_modulus(&integer) {
+            Some(Self::from_integer(integer))
+        } else {
+            None
+        }
+    }
+
+    /// Maps any 32 bytes to an element, by reducing their big-endian integer modulo `p`.
+    pub fn from_bytes_reduced(bytes: &[u8; 32]) -> Self {
+        let mut integer = from_be_bytes(bytes);
+        // 2^256 < 6p, so at most five subtractions are needed.
+        while !less_than_modulus(&integer) {
+            integer = sub(&integer, &MODULUS).0;
+        }
+        Self::from_integer(integer)
+    }
+
+    /// Returns the element equal to `value`.
+    pub const fn from_u128(value: u128) -> Self {
+        Self::from_integer([value as u64, (value >> 64) as u64, 0, 0])
+    }
+
+    /// Returns the canonical encoding of this element.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        let integer = mont_mul(&self.0, &[1, 0, 0, 0]);
+        let mut bytes = [0; 32];
+        for (limb, chunk) in integer.iter().rev().zip(bytes.chunks_exact_mut(8)) {
+            chunk.copy_from_slice(&limb.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Parses the big-endian hexadecimal encoding of an integer less than `p`, which may omit
+    /// leading zeros. Panics, or fails to compile in constants, on any other input.
+    pub(crate) const fn from_hex(hex: &str) -> Self {
+        let hex = hex.as_bytes();
+        let mut integer = [0u64; 4];
+        let mut i = 0;
+        while i < hex.len() {
+            let digit = match hex[i] {
+                b'0'..=b'9' => hex[i] - b'0',
+                b'a'..=b'f' => hex[i] - b'a' + 10,
+                _ => panic!("invalid hexadecimal digit"),
+            } as u64;
+            let position = hex.len() - 1 - i;
+            integer[position / 16] |= digit << (4 * (position % 16));
+            i += 1;
+        }
+        assert!(less_than_modulus(&integer), "integer is not less than p");
+        Self::from_integer(integer)
+    }
+
+    const fn from_integer(integer: [u64; 4]) -> Self {
+        Self(mont_mul(&integer, &R2))
+    }
+
+    pub(crate) fn add(&self, other: &Self) -> Self {
+        // Both elements are less than p < 2^254, so their sum does not overflow.
+        let (sum, _) = add(&self.0, &other.0);
+        if less_than_modulus(&sum) {
+            Self(sum)
+        } else {
+            Self(sub(&sum, &MODULUS).0)
+        }
+    }
+
+    pub(crate) fn mul(&self, other: &Self) -> Self {
+        Self(mont_mul(&self.0, &other.0))
+    }
+
+    /// Returns `self^5`, the S-box of the Poseidon permutation.
+    pub(crate) fn pow5(&self) -> Self {
+        let square = self.mul(self);
+        square.mul(&square).mul(self)
+    }
+}
+
+fn from_be_bytes(bytes: &[u8; 32]) -> [u64; 4] {
+    let mut integer = [0; 4];
+    for (limb, chunk) in integer.iter_mut().rev().zip(bytes.chunks_exact(8)) {
+        *limb = u64::from_be_bytes(chunk.try_into().expect("chunks have 8 bytes"));
+    }
+    integer
+}
+
+const fn less_than_modulus(integer: &[u64; 4]) -> bool {
+    let mut i = 4;
+    while i > 0 {
+        i -= 1;
+        if integer[i] != MODULUS[i] {
+            return integer[i] < MODULUS[i];
+        }
+    }
+    false
+}
+
+fn add(a: &[u64; 4], b: &[u64; 4]) -> ([u64; 4], u64) {
+    let mut sum = [0; 4];
+    let mut carry = 0;
+    for i in 0..4 {
+        let wide = a[i] as u128 + b[i] as u128 + carry as u128;
+        sum[i] = wide as u64;
+        carry = (wide >> 64) as u64;
+    }
+    (sum, carry)
+}
+
+const fn sub(a: &[u64; 4], b: &[u64; 4]) -> ([u64; 4], u64) {
+    let mut difference = [0; 4];
+    let mut borrow = 0;
+    let mut i = 0;
+    while i < 4 {
+        let wide = (a[i] as u128).wrapping_sub(b[i] as u128 + borrow as u128);
+        difference[i] = wide as u64;
+        borrow = (wide >> 127) as u64;
+        i += 1;
+    }
+    (difference, borrow)
+}
+
+/// Returns `a * b / 2^256 mod p`, for `a` and `b` less than `p`.
+const fn mont_mul(a: &[u64; 4], b: &[u64; 4]) -> [u64; 4] {
+    let mut t = [0u64; 5];
+    let mut i = 0;
+    while i < 4 {
+        let mut carry = 0u64;
+        let mut j = 0;
+        while j < 4 {
+            let wide = t[j] as u128 + a[i] as u128 * b[j] as u128 + carry as u128;
+            t[j] = wide as u64;
+            carry = (wide >> 64) as u64;
+            j += 1;
+        }
+        let top = t[4] as u128 + carry as u128;
+
+        let m = t[0].wrapping_mul(INV);
+        let wide = t[0] as u128 + m as u128 * MODULUS[0] as u128;
+        let mut carry = (wide >> 64) as u64;
+        let mut j = 1;
+        while j < 4 {
+            let wide = t[j] as u128 + m as u128 * MODULUS[j] as u128 + carry as u128;
+            t[j - 1] = wide as u64;
+            carry = (wide >> 64) as u64;
+            j += 1;
+        }
+        let wide = top + carry as u128;
+        t[3] = wide as u64;
+        t[4] = (wide >> 64) as u64;
+        i += 1;
+    }
+    // The result is less than 2p < 2^256, so t[4] is zero.
+    let result = [t[0], t[1], t[2], t[3]];
+    if less_than_modulus(&result) {
+        result
+    } else {
+        sub(&result, &MODULUS).0
+    }
+}
@@ -1,7 +1,7 @@
 use alloc::vec::Vec;
 use anyhow::{format_err, Result};
 
-use crate::node_type::{LeafNode, Node, NodeKey};
+use crate::node_type::{LeafNode, Node, NodeEncodingVersion, NodeKey};
 use crate::{KeyHash, OwnedValue, Version};
 
 /// Defines the interface between a
@@ -30,19 +30,104 @@ pub trait TreeReader {
 
     /// Gets a value by identifier, returning the newest value whose version is *less than or
     /// equal to* the specified version.  Returns None if the value does not exist.
+    ///
+    /// On a forked history, that value may have been written by another branch. Stores holding
+    /// one implement [`ForkedTreeReader`](crate::fork::ForkedTreeReader) as well, and are read
+    /// through a [`BranchReader`](crate::fork::BranchReader).
     fn get_value_option(
         &self,
         max_version: Version,
         key_hash: KeyHash,
     ) -> Result<Option<OwnedValue>>;
 
+    /// Returns the latest version of the tree written to storage which is not after `version`,
+    /// or `None` if there is none. Versions need not be contiguous, so this is the version holding
+    /// the tree as it was at `version`. A version after the latest one may still be written, so it
+    /// should not resolve to the latest one.
+    ///
+    /// Defaults to `version` itself, which is right for stores writing every version. Stores
+    /// holding trees written with
+    /// [`put_value_set_after`](crate::JellyfishMerkleTree::put_value_set_after) must override it.
+    fn latest_version_at_or_before(&self, version: Version) -> Result<Option<Version>> {
+        Ok(Some(version))
+    }
+
+    /// Returns the version whose tree a new version built on top of `version` starts from: the
+    /// latest version of the tree written to storage which is not after `version`, or `None` if
+    /// there is none. Unlike [`latest_version_at_or_before`](Self::latest_version_at_or_before),
+    /// a version after the latest one resolves to the latest one, as the next version written
+    /// builds on it.
+    ///
+    /// Defaults to [`latest_version_at_or_before`](Self::latest_version_at_or_before), which is
+    /// right for stores resolving the versions after the latest one too. Stores which leave them
+    /// unresolved must override it.
+    fn latest_version_to_build_on(&self, version: Version) -> Result<Option<Version>> {
+        self.latest_version_at_or_before(version)
+    }
+
     /// Gets the rightmost leaf. Note that this assumes we are in the process of restoring the tree
     /// and all nodes are at the same version.
     fn get_rightmost_leaf(&self) -> Result<Option<(NodeKey, LeafNode)>>;
 }
 
+/// Returns the key of the root node of the tree at `version`, which is the one of the version it
+/// resolves to with [`TreeReader::latest_version_at_or_before`]. A version which does not resolve
+/// keeps its own key, so that reading it fails as a missing root.
+pub(crate) fn resolve_root_key<R: TreeReader + ?Sized>(
+    reader: &R,
+    version: Version,
+) -> Result<NodeKey> {
+    let root_version = reader
+        .latest_version_at_or_before(version)?
+        .unwrap_or(version);
+    Ok(NodeKey::new_empty_path(root_version))
+}
+
+/// Defines the ability for a store to tell which versions of a tree it holds, so that callers do
+/// not have to keep track of them next to the store.
+pub trait VersionMetadata {
+    /// Returns the latest version of the tree written to storage, or `None` if no version has been
+    /// written yet.
+    fn latest_version(&self) -> Result<Option<Version>>;
+
+    /// Returns the oldest version of the tree which can still be read, i.e. which has not been
+    /// pruned, or `None` if no version has been written yet.
+    fn oldest_readable_version(&self) -> Result<Option<Version>>;
+
+    /// Returns the first version of the tree written to storage after `version`, or `None` if
+    /// there is none.
+    ///
+    /// Defaults to `version + 1` up to the latest version, which is right for stores writing every
+    /// version. Stores holding trees written with
+    /// [`put_value_set_after`](crate::JellyfishMerkleTree::put_value_set_after) must override it,
+    /// so that listing the versions written takes one call per version rather than per height.
+    fn next_version_after(&self, version: Version) -> Result<Option<Version>> {
+        Ok(self
+            .latest_version()?
+            .filter(|latest_version| version < *latest_version)
+            .map(|_| version + 1))
+    }
+}
+
 /// Defines the ability for a tree to look up the preimage of its key hashes.
 pub trait HasPreimage {
     /// Gets the preimage of a key hash, if it is present in the tree.
     fn preimage(&self, key_hash: KeyHash) -> Result<Option<Vec<u8>>>;
 }
+
+/// Defines the ability for a store to list the nodes it holds along with the version of the
+/// encoding each of them was written with, so that a
+/// [`NodeEncodingMigration`](crate::migration::NodeEncodingMigration) can rewrite them with a newer
+/// one.
+pub trait NodeEncodingReader {
+    /// Returns up to `limit` nodes following the node `start_after`, or the first nodes if it is
+    /// `None`, along with the version of the encoding of each.
+    ///
+    /// The nodes may be listed in any order, as long as it stays the same between calls, so that
+    /// listing the nodes following the last one returned continues where the call stopped.
+    fn get_node_encodings(
+        &self,
+        start_after: Option<&NodeKey>,
+        limit: usize,
+    ) -> Result<Vec<(NodeKey, NodeEncodingVersion)>>;
+}
@@ -11,10 +11,12 @@ use alloc::boxed::Box;
 use alloc::vec;
 use alloc::{sync::Arc, vec::Vec};
 
-use anyhow::{bail, ensure, Result};
+use anyhow::{bail, ensure, format_err, Result};
 use mirai_annotations::*;
+use tracing::field::{display, Empty};
 
 use crate::{
+    metrics::{NoopMetrics, Phase, PhaseTimer, TreeMetrics},
     node_type::{
         get_child_and_sibling_half_start, Child, Children, InternalNode, LeafNode, Node, NodeKey,
         NodeType,
@@ -28,8 +30,8 @@ use crate::{
         proof::{SparseMerkleInternalNode, SparseMerkleLeafNode, SparseMerkleRangeProof},
         Version,
     },
-    Bytes32Ext, KeyHash, OwnedValue, RootHash, SimpleHasher, ValueHash, ROOT_NIBBLE_HEIGHT,
-    SPARSE_MERKLE_PLACEHOLDER_HASH,
+    Bytes32Ext, KeyHash, OwnedValue, RootHash, SimpleHasher, TracedError, ValueHash,
+    ROOT_NIBBLE_HEIGHT,
 };
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -101,12 +103,63 @@ impl InternalInfo {
     }
 }
 
+/// The outcome of a successful restoration, returned by
+/// [`StateSnapshotReceiver::finish`] once the reconstructed root has been checked against the
+/// expected root hash.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RestoreSummary {
+    /// The root hash of the restored tree, which is the expected root hash.
+    pub root_hash: RootHash,
+    /// The number of leaves in the restored tree.
+    pub num_leaves: usize,
+    /// The number of nodes written to storage since the most recent restart.
+    pub num_nodes_written: usize,
+    /// The number of bytes written to storage since the most recent restart, counting the borsh
+    /// encoding of each node and the length of each value.
+    pub num_bytes_written: usize,
+}
+
+/// How a [`JellyfishMerkleRestore`] holds on to its storage.
+///
+/// This is [`LocalStore`] for a restore created with [`JellyfishMerkleRestore::new`], which
+/// accepts any storage, and [`SharedStore`] for one created with [`Jmt::restore`](crate::Jmt::restore),
+/// which requires `Send + Sync` storage and can then be moved to or shared with other threads.
+pub trait StoreAccess {
+    /// The storage, as written to.
+    type Writer: ?Sized + TreeWriter;
+    /// The storage, as read from.
+    type Reader: ?Sized + TreeReader;
+}
+
+/// The [`StoreAccess`] of a restore over any storage.
+#[derive(Clone, Copy, Debug)]
+pub enum LocalStore {}
+
+impl StoreAccess for LocalStore {
+    type Writer = dyn TreeWriter;
+    type Reader = dyn TreeReader;
+}
+
+/// The [`StoreAccess`] of a restore over `Send + Sync` storage, which is then `Send + Sync`
+/// itself.
+#[derive(Clone, Copy, Debug)]
+pub enum SharedStore {}
+
+impl StoreAccess for SharedStore {
+    type Writer = dyn TreeWriter + Send + Sync;
+    type Reader = dyn TreeReader + Send + Sync;
+}
+
 /// Implements the functionality to restore a
 /// [`JellyfishMerkleTree`](crate::JellyfishMerkleTree) from small chunks of
 /// key-value pairs.
-pub struct JellyfishMerkleRestore<H: SimpleHasher> {
+pub struct JellyfishMerkleRestore<H: SimpleHasher, S: StoreAccess = LocalStore> {
     /// The underlying storage.
-    store: Arc<dyn TreeWriter>,
+    store: Arc<S::Writer>,
+
+    /// The underlying storage, if it can be read back. It is used to check that the root node has
+    /// been written once the restoration finishes.
+    tree_reader: Option<Arc<S::Reader>>,
 
     /// The version of the tree we are restoring.
     version: Version,
@@ -153,8 +206,19 @@ pub struct JellyfishMerkleRestore<H: SimpleHasher> {
     /// The number of keys we have received since the most recent restart.
     num_keys_received: u64,
 
-    /// When the restoration process finishes, we expect the tree to have this root hash.
-    expected_root_hash: RootHash,
+    /// The number of nodes we have written since the most recent restart.
+    num_nodes_written: usize,
+
+    /// The number of bytes we have written since the most recent restart.
+    num_bytes_written: usize,
+
+    /// When the restoration process finishes, we expect the tree to have this root hash. It is
+    /// `None` when the tree is built from trusted key-value pairs, without proofs, as
+    /// [`rehash_tree`](crate::migration::rehash_tree) does.
+    expected_root_hash: Option<RootHash>,
+
+    /// The observer of the work done by the restoration.
+    metrics: Arc<dyn TreeMetrics>,
 
     _phantom_hasher: PhantomData<H>,
 }
@@ -165,7 +229,46 @@ impl<H: SimpleHasher> JellyfishMerkleRestore<H> {
         version: Version,
         expected_root_hash: RootHash,
     ) -> Result<Self> {
-        let tree_reader = Arc::clone(&store);
+        Self::new_impl(store.clone(), store, version, Some(expected_root_hash))
+    }
+
+    /// Starts or resumes building the tree at `version` from trusted key-value pairs, added with
+    /// [`add_trusted_chunk`](Self::add_trusted_chunk), whose root hash is not known in advance.
+    #[cfg(feature = "migration")]
+    pub(crate) fn new_trusted<D: 'static + TreeReader + TreeWriter>(
+        store: Arc<D>,
+        version: Version,
+    ) -> Result<Self> {
+        Self::new_impl(store.clone(), store, version, None)
+    }
+
+    pub fn new_overwrite<D: 'static + TreeWriter>(
+        store: Arc<D>,
+        version: Version,
+        expected_root_hash: RootHash,
+    ) -> Result<Self> {
+        Ok(Self::new_overwrite_impl(store, version, expected_root_hash))
+    }
+}
+
+impl<H: SimpleHasher> JellyfishMerkleRestore<H, SharedStore> {
+    /// Like [`JellyfishMerkleRestore::new`], over `Send + Sync` storage.
+    pub(crate) fn new_shared<D: 'static + TreeReader + TreeWriter + Send + Sync>(
+        store: Arc<D>,
+        version: Version,
+        expected_root_hash: RootHash,
+    ) -> Result<Self> {
+        Self::new_impl(store.clone(), store, version, Some(expected_root_hash))
+    }
+}
+
+impl<H: SimpleHasher, S: StoreAccess> JellyfishMerkleRestore<H, S> {
+    fn new_impl(
+        store: Arc<S::Writer>,
+        tree_reader: Arc<S::Reader>,
+        version: Version,
+        expected_root_hash: Option<RootHash>,
+    ) -> Result<Self> {
         let (partial_nodes, previous_leaf) =
             if let Some((node_key, leaf_node)) = tree_reader.get_rightmost_leaf()? {
                 // TODO: confirm rightmost leaf is at the desired version
@@ -184,37 +287,51 @@ impl<H: SimpleHasher> JellyfishMerkleRestore<H> {
 
         Ok(Self {
             store,
+            tree_reader: Some(tree_reader),
             version,
             partial_nodes,
             frozen_nodes: Default::default(),
             previous_leaf,
             num_keys_received: 0,
+            num_nodes_written: 0,
+            num_bytes_written: 0,
             expected_root_hash,
+            metrics: Arc::new(NoopMetrics),
             _phantom_hasher: Default::default(),
         })
     }
 
-    pub fn new_overwrite<D: 'static + TreeWriter>(
-        store: Arc<D>,
+    fn new_overwrite_impl(
+        store: Arc<S::Writer>,
         version: Version,
         expected_root_hash: RootHash,
-    ) -> Result<Self> {
-        Ok(Self {
+    ) -> Self {
+        Self {
             store,
+            tree_reader: None,
             version,
             partial_nodes: vec![InternalInfo::new_empty(NodeKey::new_empty_path(version))],
             frozen_nodes: Default::default(),
             previous_leaf: None,
             num_keys_received: 0,
-            expected_root_hash,
+            num_nodes_written: 0,
+            num_bytes_written: 0,
+            expected_root_hash: Some(expected_root_hash),
+            metrics: Arc::new(NoopMetrics),
             _phantom_hasher: Default::default(),
-        })
+        }
+    }
+
+    /// Reports the work done by the restoration to `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<dyn TreeMetrics>) -> Self {
+        self.metrics = metrics;
+        self
     }
 
     /// Recovers partial nodes from storage. We do this by looking at all the ancestors of the
     /// rightmost leaf. The ones do not exist in storage are the partial nodes.
-    fn recover_partial_nodes(
-        store: &dyn TreeReader,
+    fn recover_partial_nodes<R: ?Sized + TreeReader>(
+        store: &R,
         version: Version,
         rightmost_leaf_node_key: NodeKey,
     ) -> Result<Vec<InternalInfo>> {
@@ -293,6 +410,29 @@ impl<H: SimpleHasher> JellyfishMerkleRestore<H> {
         chunk: Vec<(KeyHash, OwnedValue)>,
         proof: SparseMerkleRangeProof<H>,
     ) -> Result<()> {
+        let metrics = self.metrics.clone();
+        let _timer = PhaseTimer::start(metrics.as_ref(), Phase::RestoreChunk);
+        self.add_leaves(chunk)?;
+
+        // Verify what we have added so far is all correct.
+        self.verify(proof)?;
+
+        // Write the frozen nodes to storage.
+        self.write_frozen_nodes()
+    }
+
+    /// Adds a chunk of trusted accounts, without a proof to verify them, then writes things to
+    /// storage.
+    #[cfg(feature = "migration")]
+    pub(crate) fn add_trusted_chunk(&mut self, chunk: Vec<(KeyHash, OwnedValue)>) -> Result<()> {
+        let metrics = self.metrics.clone();
+        let _timer = PhaseTimer::start(metrics.as_ref(), Phase::RestoreChunk);
+        self.add_leaves(chunk)?;
+        self.write_frozen_nodes()
+    }
+
+    /// Adds the leaves of a chunk of accounts, which must come after the previous leaf.
+    fn add_leaves(&mut self, chunk: Vec<(KeyHash, OwnedValue)>) -> Result<()> {
         ensure!(!chunk.is_empty(), "Should not add empty chunks.");
 
         for (key, value) in chunk {
@@ -309,12 +449,22 @@ impl<H: SimpleHasher> JellyfishMerkleRestore<H> {
             self.previous_leaf.replace(LeafNode::new(key, value_hash));
             self.num_keys_received += 1;
         }
+        Ok(())
+    }
 
-        // Verify what we have added so far is all correct.
-        self.verify(proof)?;
-
-        // Write the frozen nodes to storage.
+    /// Writes the frozen nodes to storage and keeps track of how much has been written.
+    fn write_frozen_nodes(&mut self) -> Result<()> {
         self.store.write_node_batch(&self.frozen_nodes)?;
+
+        self.num_nodes_written += self.frozen_nodes.nodes().len();
+        self.metrics.nodes_written(self.frozen_nodes.nodes().len());
+        for node in self.frozen_nodes.nodes().values() {
+            self.num_bytes_written +=
+                borsh::object_length(node).map_err(|err| format_err!("{}", err))?;
+        }
+        for value in self.frozen_nodes.values().values().flatten() {
+            self.num_bytes_written += value.len();
+        }
         self.frozen_nodes.clear();
 
         Ok(())
@@ -499,6 +649,7 @@ impl<H: SimpleHasher> JellyfishMerkleRestore<H> {
             let (node_key, internal_node) = last_node.into_internal_node::<H>(self.version);
             // Keep the hash of this node before moving it into `frozen_nodes`, so we can update
             // its parent later.
+            self.metrics.nodes_hashed(1);
             let node_hash = internal_node.hash::<H>();
             let node_leaf_count = internal_node.leaf_count();
             self.frozen_nodes
@@ -556,7 +707,7 @@ impl<H: SimpleHasher> JellyfishMerkleRestore<H> {
             if bit {
                 // This node is a right child and there should be a sibling on the left.
                 let sibling = if i >= self.partial_nodes.len() * 4 {
-                    SPARSE_MERKLE_PLACEHOLDER_HASH
+                    H::SPARSE_MERKLE_PLACEHOLDER_HASH
                 } else {
                     Self::compute_left_sibling(
                         &self.partial_nodes[i / 4],
@@ -580,7 +731,7 @@ impl<H: SimpleHasher> JellyfishMerkleRestore<H> {
         for bit in previous_key.0.iter_bits().rev() {
             if bit {
                 if *left_siblings.last().expect("This sibling must exist.")
-                    == SPARSE_MERKLE_PLACEHOLDER_HASH
+                    == H::SPARSE_MERKLE_PLACEHOLDER_HASH
                 {
                     left_siblings.pop();
                 } else {
@@ -598,10 +749,12 @@ impl<H: SimpleHasher> JellyfishMerkleRestore<H> {
 
         // Verify the proof now that we have all the siblings
         proof.verify(
-            self.expected_root_hash,
+            self.expected_root_hash
+                .expect("Only restorations with an expected root hash verify proofs."),
             SparseMerkleLeafNode::new(previous_key, previous_leaf.value_hash()),
             left_siblings,
-        )
+        )?;
+        Ok(())
     }
 
     /// Computes the sibling on the left for the `n`-th child.
@@ -625,7 +778,7 @@ impl<H: SimpleHasher> JellyfishMerkleRestore<H> {
                     (*hash.as_ref().expect("The hash must be known."), false)
                 }
                 Some(ChildInfo::Leaf { node }) => (node.hash::<H>(), true),
-                None => (SPARSE_MERKLE_PLACEHOLDER_HASH, true),
+                None => (H::SPARSE_MERKLE_PLACEHOLDER_HASH, true),
             }
         } else {
             let (left_hash, left_is_leaf) =
@@ -633,9 +786,9 @@ impl<H: SimpleHasher> JellyfishMerkleRestore<H> {
             let (right_hash, right_is_leaf) =
                 Self::compute_left_sibling_impl(&children[num_children / 2..]);
 
-            if left_hash == SPARSE_MERKLE_PLACEHOLDER_HASH && right_is_leaf {
+            if left_hash == H::SPARSE_MERKLE_PLACEHOLDER_HASH && right_is_leaf {
                 (right_hash, true)
-            } else if left_is_leaf && right_hash == SPARSE_MERKLE_PLACEHOLDER_HASH {
+            } else if left_is_leaf && right_hash == H::SPARSE_MERKLE_PLACEHOLDER_HASH {
                 (left_hash, true)
             } else {
                 (
@@ -648,7 +801,9 @@ impl<H: SimpleHasher> JellyfishMerkleRestore<H> {
 
     /// Finishes the restoration process. This tells the code that there is no more account,
     /// otherwise we can not freeze the rightmost leaf and its ancestors.
-    fn finish_impl(mut self) -> Result<()> {
+    fn finish_impl(mut self) -> Result<RestoreSummary> {
+        let metrics = self.metrics.clone();
+        let _timer = PhaseTimer::start(metrics.as_ref(), Phase::RestoreFinish);
         // Deal with the special case when the entire tree has a single leaf.
         if self.partial_nodes.len() == 1 {
             let mut num_children = 0;
@@ -667,14 +822,60 @@ impl<H: SimpleHasher> JellyfishMerkleRestore<H> {
                     let node_key = NodeKey::new_empty_path(self.version);
                     assert!(self.frozen_nodes.is_empty());
                     self.frozen_nodes.insert_node(node_key, node.into());
-                    self.store.write_node_batch(&self.frozen_nodes)?;
-                    return Ok(());
+                    return self.write_root();
                 }
             }
         }
 
         self.freeze(0);
-        self.store.write_node_batch(&self.frozen_nodes)
+        self.write_root()
+    }
+
+    /// Checks the root node, which must have just been frozen, against the expected root hash,
+    /// then writes it along with the remaining frozen nodes. If storage can be read back, also
+    /// checks that the root node has been written at the version being restored.
+    fn write_root(mut self) -> Result<RestoreSummary> {
+        let root_node_key = NodeKey::new_empty_path(self.version);
+        let root_node = self
+            .frozen_nodes
+            .get_node(&root_node_key)
+            .cloned()
+            .ok_or_else(|| {
+                format_err!(
+                    "Root node at version {} was not reconstructed.",
+                    self.version
+                )
+            })?;
+        self.metrics.nodes_hashed(1);
+        let root_hash = RootHash(root_node.hash::<H>());
+        if let Some(expected_root_hash) = self.expected_root_hash {
+            ensure!(
+                root_hash == expected_root_hash,
+                "Restored root hash {:?} does not match the expected root hash {:?}.",
+                root_hash,
+                expected_root_hash,
+            );
+        }
+
+        self.write_frozen_nodes()?;
+
+        if let Some(tree_reader) = &self.tree_reader {
+            let written_root_hash = tree_reader
+                .get_node_option(&root_node_key)?
+                .map(|node| RootHash(node.hash::<H>()));
+            ensure!(
+                written_root_hash == Some(root_hash),
+                "Root node was not written at version {}.",
+                self.version,
+            );
+        }
+
+        Ok(RestoreSummary {
+            root_hash,
+            num_leaves: root_node.leaf_count(),
+            num_nodes_written: self.num_nodes_written,
+            num_bytes_written: self.num_bytes_written,
+        })
     }
 }
 
@@ -686,25 +887,40 @@ pub trait StateSnapshotReceiver<H: SimpleHasher> {
         proof: SparseMerkleRangeProof<H>,
     ) -> Result<()>;
 
-    fn finish(self) -> Result<()>;
+    /// Finishes the restoration, failing if the restored tree does not have the expected root hash.
+    fn finish(self) -> Result<RestoreSummary>;
 
-    fn finish_box(self: Box<Self>) -> Result<()>;
+    fn finish_box(self: Box<Self>) -> Result<RestoreSummary>;
 }
 
-impl<H: SimpleHasher> StateSnapshotReceiver<H> for JellyfishMerkleRestore<H> {
+impl<H: SimpleHasher, S: StoreAccess> StateSnapshotReceiver<H> for JellyfishMerkleRestore<H, S> {
     fn add_chunk(
         &mut self,
         chunk: Vec<(KeyHash, OwnedValue)>,
         proof: SparseMerkleRangeProof<H>,
     ) -> Result<()> {
-        self.add_chunk_impl(chunk, proof)
+        let span = tracing::debug_span!(
+            "add_chunk",
+            version = self.version,
+            num_keys = chunk.len(),
+            nodes_written = Empty,
+            error = Empty,
+        );
+        let _guard = span.enter();
+        let num_nodes_written = self.num_nodes_written;
+        let result = self.add_chunk_impl(chunk, proof);
+        span.record("nodes_written", self.num_nodes_written - num_nodes_written);
+        if let Err(err) = &result {
+            span.record("error", display(TracedError(err)));
+        }
+        result
     }
 
-    fn finish(self) -> Result<()> {
+    fn finish(self) -> Result<RestoreSummary> {
         self.finish_impl()
     }
 
-    fn finish_box(self: Box<Self>) -> Result<()> {
+    fn finish_box(self: Box<Self>) -> Result<RestoreSummary> {
         self.finish_impl()
     }
 }
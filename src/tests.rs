@@ -1,11 +1,31 @@
 #![cfg(test)]
+mod compact_proof;
 mod compute_vectors;
+mod domain;
+mod envelope;
+mod fork;
 mod helper;
+#[cfg(feature = "cli")]
+mod inspect;
+mod integrity;
 mod iterator;
 mod jellyfish_merkle;
+mod jmt;
+mod metrics;
+#[cfg(feature = "migration")]
+mod migration;
+mod namespace;
 mod nibble_path;
 mod node_type;
+mod partial_tree;
+mod poseidon;
+mod proof_construction;
+mod proof_encoding;
 mod restore;
+#[cfg(feature = "std")]
+mod tracing;
 mod tree_cache;
 mod update_proof;
 mod vectors;
+mod versions;
+mod witness;
@@ -0,0 +1,249 @@
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+
+use proptest::{collection::btree_map, prelude::*};
+use sha2::Sha256;
+
+use crate::{
+    mock::MockTreeStore,
+    proof::{
+        CompactProofError, PartialTree, SparseMerkleInternalNode, SparseMerkleLeafNode,
+        SparseMerkleNode, SparseMerkleProof, UpdateMerkleProof, VerificationError,
+    },
+    tests::helper::init_mock_db,
+    JellyfishMerkleTree, KeyHash, OwnedValue, SimpleHasher, ValueHash,
+};
+
+fn round_trip<H: SimpleHasher>(proof: &SparseMerkleProof<H>) -> SparseMerkleProof<H> {
+    SparseMerkleProof::from_compact_bytes(&proof.to_compact_bytes()).unwrap()
+}
+
+fn test_compact_proofs_verify<H: SimpleHasher>(
+    kvs: BTreeMap<KeyHash, OwnedValue>,
+    absent: Vec<KeyHash>,
+) {
+    let (db, version) = init_mock_db::<H>(&kvs.clone().into_iter().collect());
+    let tree = JellyfishMerkleTree::<_, H>::new(&db);
+    let root_hash = tree.get_root_hash(version).unwrap();
+
+    for key in kvs.keys().chain(&absent) {
+        let (value, proof) = tree.get_with_proof(*key, version).unwrap();
+        let compact = proof.compact();
+        assert_eq!(round_trip(&proof), compact);
+        assert_eq!(round_trip(&compact), compact);
+        assert_eq!(compact.compact(), compact);
+        assert_eq!(compact.to_compact_bytes(), proof.to_compact_bytes());
+        assert!(proof.to_compact_bytes().len() <= borsh::to_vec(&proof).unwrap().len());
+        round_trip(&proof)
+            .verify(root_hash, *key, value.as_ref())
+            .unwrap();
+    }
+}
+
+fn test_compact_proofs_verify_deletions<H: SimpleHasher>(kvs: BTreeMap<KeyHash, OwnedValue>) {
+    let (db, version) = init_mock_db::<H>(&kvs.clone().into_iter().collect());
+    let tree = JellyfishMerkleTree::<_, H>::new(&db);
+    let root_hash = tree.get_root_hash(version).unwrap();
+
+    for key in kvs.keys() {
+        let (_, proof) = tree.get_with_proof(*key, version).unwrap();
+        let update = vec![(*key, None::<OwnedValue>)];
+        let (new_root_hash, _batch) = tree.put_value_set(update.clone(), version + 1).unwrap();
+        UpdateMerkleProof::new(vec![round_trip(&proof)])
+            .verify_update(root_hash, new_root_hash, update)
+            .unwrap();
+    }
+}
+
+fn test_partial_tree_from_compact_proofs<H: SimpleHasher>(kvs: BTreeMap<KeyHash, OwnedValue>) {
+    let (db, version) = init_mock_db::<H>(&kvs.clone().into_iter().collect());
+    let tree = JellyfishMerkleTree::<_, H>::new(&db);
+    let root_hash = tree.get_root_hash(version).unwrap();
+
+    let keys: Vec<KeyHash> = kvs.keys().step_by(3).cloned().collect();
+    let proofs: Vec<_> = keys
+        .iter()
+        .map(|key| {
+            (
+                *key,
+                tree.get_with_proof(*key, version).unwrap().1.compact(),
+            )
+        })
+        .collect();
+    let mut partial_tree =
+        PartialTree::<H>::new(root_hash, proofs.iter().map(|(key, proof)| (*key, proof))).unwrap();
+    for key in &keys {
+        assert_eq!(
+            partial_tree.get(*key).unwrap(),
+            kvs.get(key).map(ValueHash::with::<H>)
+        );
+    }
+
+    // Deletions either succeed with the same root hash as the full tree, or fail without
+    // changing the partial tree.
+    let mut deleted = Vec::new();
+    for key in &keys {
+        let previous_root_hash = partial_tree.root_hash();
+        match partial_tree.delete(*key) {
+            Ok(()) => {
+                deleted.push((*key, None));
+                let (new_root_hash, _batch) =
+                    tree.put_value_set(deleted.clone(), version + 1).unwrap();
+                assert_eq!(partial_tree.root_hash(), new_root_hash);
+            }
+            Err(error) => {
+                assert_eq!(error, VerificationError::UntypedSibling);
+                assert_eq!(partial_tree.root_hash(), previous_root_hash);
+            }
+        }
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(10))]
+
+    #[test]
+    fn test_compact_proofs_verify_sha256(
+        kvs in btree_map(any::<KeyHash>(), any::<OwnedValue>(), 1..100),
+        absent in proptest::collection::vec(any::<KeyHash>(), 10),
+    ) {
+        let absent = absent.into_iter().filter(|key| !kvs.contains_key(key)).collect();
+        test_compact_proofs_verify::<Sha256>(kvs, absent);
+    }
+
+    #[test]
+    fn test_compact_proofs_verify_deletions_sha256(
+        kvs in btree_map(any::<KeyHash>(), any::<OwnedValue>(), 1..50),
+    ) {
+        test_compact_proofs_verify_deletions::<Sha256>(kvs);
+    }
+
+    #[test]
+    fn test_partial_tree_from_compact_proofs_sha256(
+        kvs in btree_map(any::<KeyHash>(), any::<OwnedValue>(), 1..100),
+    ) {
+        test_partial_tree_from_compact_proofs::<Sha256>(kvs);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_compact_encoding_round_trips(proof in any::<SparseMerkleProof<Sha256>>()) {
+        prop_assert_eq!(round_trip(&proof), proof.compact());
+        prop_assert_eq!(round_trip(&proof.compact()), proof.compact());
+    }
+}
+
+#[test]
+fn test_update_rejects_untyped_lowest_sibling() {
+    let db = MockTreeStore::default();
+    let tree = JellyfishMerkleTree::<_, Sha256>::new(&db);
+    let keys = [
+        KeyHash([0x00; 32]),
+        KeyHash([0x80; 32]),
+        KeyHash([0xc0; 32]),
+    ];
+    let (root_hash, batch) = tree
+        .put_value_set(keys.iter().map(|key| (*key, Some(vec![1]))), 0)
+        .unwrap();
+    db.write_tree_update_batch(batch).unwrap();
+
+    // The leaf of the first key is coalesced into the root once the other key of its subtree is
+    // deleted, which the verifier can only tell from the type of its sibling.
+    let (_, proof) = tree.get_with_proof(keys[1], 0).unwrap();
+    let update = vec![(keys[1], None::<OwnedValue>)];
+    let (new_root_hash, _batch) = tree.put_value_set(update.clone(), 1).unwrap();
+    let untyped = SparseMerkleProof::<Sha256>::new(
+        proof.leaf(),
+        proof
+            .siblings()
+            .iter()
+            .map(|sibling| SparseMerkleNode::Untyped(sibling.hash::<Sha256>()))
+            .collect(),
+    );
+    untyped.verify_existence(root_hash, keys[1], [1]).unwrap();
+    assert_eq!(
+        UpdateMerkleProof::new(vec![untyped]).verify_update(root_hash, new_root_hash, &update),
+        Err(VerificationError::UntypedSibling)
+    );
+    UpdateMerkleProof::new(vec![proof.compact()])
+        .verify_update(root_hash, new_root_hash, update)
+        .unwrap();
+}
+
+#[test]
+fn test_compact_encoding_rejects_malformed_bytes() {
+    let leaf = SparseMerkleLeafNode::new(KeyHash([1; 32]), ValueHash([2; 32]));
+    let proof = SparseMerkleProof::<Sha256>::new(
+        Some(leaf),
+        vec![
+            SparseMerkleNode::Null,
+            SparseMerkleNode::Leaf(leaf),
+            SparseMerkleNode::Internal(SparseMerkleInternalNode::new([3; 32], [4; 32])),
+            SparseMerkleNode::Untyped([5; 32]),
+        ],
+    );
+    let bytes = proof.to_compact_bytes();
+    // Flags, leaf, sibling count, the placeholder bitmap, the typed leaf sibling, then one hash
+    // for each of the other non-null siblings.
+    assert_eq!(bytes.len(), 1 + 64 + 2 + 1 + 64 + 32 + 32);
+    assert_eq!(bytes[0], 0b111);
+    assert_eq!(bytes[67], 0b0001);
+    assert_eq!(&bytes[68..132], [[1; 32], [2; 32]].concat());
+    assert_eq!(
+        bytes[132..164],
+        SparseMerkleInternalNode::new([3; 32], [4; 32]).hash::<Sha256>()
+    );
+
+    let decode = |bytes: &[u8]| SparseMerkleProof::<Sha256>::from_compact_bytes(bytes);
+    assert_eq!(
+        decode(&bytes[..bytes.len() - 1]),
+        Err(CompactProofError::Truncated)
+    );
+    assert_eq!(
+        decode(&[bytes.as_slice(), &[0]].concat()),
+        Err(CompactProofError::TrailingBytes { len: 1 })
+    );
+
+    let mut unknown_flags = bytes.clone();
+    unknown_flags[0] |= 0x80;
+    assert_eq!(
+        decode(&unknown_flags),
+        Err(CompactProofError::UnknownFlags { flags: 0x87 })
+    );
+
+    // The typed sibling cannot be a leaf without being typed, nor be typed without existing.
+    let mut untyped_leaf = bytes.clone();
+    untyped_leaf[0] &= !0b010;
+    assert_eq!(
+        decode(&untyped_leaf),
+        Err(CompactProofError::UnknownFlags { flags: 0b101 })
+    );
+    assert_eq!(
+        decode(&[0b010, 0, 0]),
+        Err(CompactProofError::UnknownFlags { flags: 0b010 })
+    );
+
+    let mut too_many_siblings = bytes.clone();
+    too_many_siblings[65..67].copy_from_slice(&257u16.to_le_bytes());
+    assert_eq!(
+        decode(&too_many_siblings),
+        Err(CompactProofError::TooManySiblings { num_siblings: 257 })
+    );
+
+    let mut non_zero_padding = bytes.clone();
+    non_zero_padding[67] |= 0x80;
+    assert_eq!(
+        decode(&non_zero_padding),
+        Err(CompactProofError::NonZeroPadding)
+    );
+
+    let placeholder = SparseMerkleProof::<Sha256>::new(
+        None,
+        vec![SparseMerkleNode::Untyped(
+            Sha256::SPARSE_MERKLE_PLACEHOLDER_HASH,
+        )],
+    );
+    assert_eq!(
+        decode(&placeholder.to_compact_bytes()),
+        Err(CompactProofError::UntypedPlaceholder)
+    );
+}
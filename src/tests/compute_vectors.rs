@@ -1,104 +1,155 @@
 #![cfg(feature = "std")]
-#![allow(unused)]
-use sha2::Sha256;
+//! Computes the test vectors checked by [`super::vectors`] by hand, without using the tree, from
+//! the definition of a sparse Merkle tree whose single-leaf subtrees are replaced by their leaf.
 
-use crate::{
-    proof::{INTERNAL_DOMAIN_SEPARATOR, LEAF_DOMAIN_SEPARATOR},
-    KeyHash, SimpleHasher, ValueHash, SPARSE_MERKLE_PLACEHOLDER_HASH,
-};
+use alloc::{string::ToString, vec, vec::Vec};
 
-const DESCRIPTION: &'static str =  "Manually computed test vectors for a JMT instantiated with the sha2-256 hash function. Keys and values are hex-encoded byte strings. Neither keys nor values have been pre-hashed.";
+use crate::{KeyHash, SimpleHasher, ValueHash};
 
-use super::vectors::{KeyValuePair, TestVector};
+use super::vectors::{KeyValuePair, ProofVector, TestVector, TestVectorWrapper};
 
-fn internal_hash(left_child_hash: [u8; 32], right_child_hash: [u8; 32]) -> [u8; 32] {
-    let mut hasher = Sha256::new();
-    hasher.update(INTERNAL_DOMAIN_SEPARATOR);
+fn internal_hash<H: SimpleHasher>(
+    left_child_hash: [u8; 32],
+    right_child_hash: [u8; 32],
+) -> [u8; 32] {
+    let mut hasher = H::new();
+    hasher.update(H::INTERNAL_DOMAIN_SEPARATOR);
     hasher.update(left_child_hash.as_ref());
     hasher.update(right_child_hash.as_ref());
     hasher.finalize()
 }
 
-fn leaf_hash(key_hash: KeyHash, value_hash: ValueHash) -> [u8; 32] {
-    let mut hasher = Sha256::new();
-    hasher.update(LEAF_DOMAIN_SEPARATOR);
+fn leaf_hash<H: SimpleHasher>(key_hash: KeyHash, value_hash: ValueHash) -> [u8; 32] {
+    let mut hasher = H::new();
+    hasher.update(H::LEAF_DOMAIN_SEPARATOR);
     hasher.update(key_hash.0.as_ref());
     hasher.update(value_hash.0.as_ref());
     hasher.finalize()
 }
 
-fn create_vector_for_empty_trie() -> TestVector {
-    TestVector {
-        expected_root: SPARSE_MERKLE_PLACEHOLDER_HASH,
-        data: vec![],
-    }
+/// Returns the bit of `key_hash` at `depth`, starting from the most significant one.
+fn bit(key_hash: KeyHash, depth: usize) -> bool {
+    key_hash.0[depth / 8] & (0x80 >> (depth % 8)) != 0
 }
 
-fn compute_vector_with_one_leaf() -> TestVector {
-    let key = b"hello";
-    let key_hash = KeyHash::with::<Sha256>(key);
-
-    let value = b"world";
-    let value_hash = ValueHash::with::<Sha256>(value);
-
-    let leaf_hash = leaf_hash(key_hash, value_hash);
-
-    let expected_root = leaf_hash;
-
-    TestVector {
-        expected_root: expected_root.into(),
-        data: vec![KeyValuePair {
-            key: key.to_vec(),
-            value: value.to_vec(),
-        }],
+/// Returns the hash of the subtree at `depth` holding the given leaves.
+fn subtree_hash<H: SimpleHasher>(leaves: &[(KeyHash, [u8; 32])], depth: usize) -> [u8; 32] {
+    match leaves {
+        [] => H::SPARSE_MERKLE_PLACEHOLDER_HASH,
+        [(_, leaf_hash)] => *leaf_hash,
+        _ => {
+            let (right, left): (Vec<_>, Vec<_>) = leaves
+                .iter()
+                .partition(|(key_hash, _)| bit(*key_hash, depth));
+            internal_hash::<H>(
+                subtree_hash::<H>(&left, depth + 1),
+                subtree_hash::<H>(&right, depth + 1),
+            )
+        }
     }
 }
 
-fn compute_vector_with_two_leaves() -> TestVector {
-    let left_key = b"hello";
-    let left_key_hash = KeyHash::with::<Sha256>(left_key);
-
-    let right_key = b"goodbye";
-    let right_key_hash = KeyHash::with::<Sha256>(right_key);
-
-    let value = b"world";
-    let value_hash = ValueHash::with::<Sha256>(value);
-
-    let left_leaf_hash = leaf_hash(left_key_hash, value_hash);
-    let right_leaf_hash = leaf_hash(right_key_hash, value_hash);
+/// Returns the siblings of the leaf of `key_hash` in the subtree at `depth` holding the given
+/// leaves, from the bottom level to the root level.
+fn siblings<H: SimpleHasher>(
+    leaves: &[(KeyHash, [u8; 32])],
+    key_hash: KeyHash,
+    depth: usize,
+) -> Vec<[u8; 32]> {
+    if leaves.len() <= 1 {
+        return vec![];
+    }
+    let (same_side, other_side): (Vec<_>, Vec<_>) = leaves
+        .iter()
+        .partition(|(other_key_hash, _)| bit(*other_key_hash, depth) == bit(key_hash, depth));
+    let mut siblings = siblings::<H>(&same_side, key_hash, depth + 1);
+    siblings.push(subtree_hash::<H>(&other_side, depth + 1));
+    siblings
+}
 
-    let expected_root = internal_hash(left_leaf_hash, right_leaf_hash);
+/// Computes the vector of a tree holding `data`, with a proof for each of its keys.
+fn compute_vector<H: SimpleHasher>(data: &[(&[u8], &[u8])]) -> TestVector {
+    let leaves: Vec<_> = data
+        .iter()
+        .map(|(key, value)| {
+            let key_hash = KeyHash::with::<H>(key);
+            (
+                key_hash,
+                leaf_hash::<H>(key_hash, ValueHash::with::<H>(value)),
+            )
+        })
+        .collect();
 
     TestVector {
-        expected_root: expected_root.into(),
-        data: vec![
-            KeyValuePair {
-                key: left_key.to_vec(),
-                value: value.to_vec(),
-            },
-            KeyValuePair {
-                key: right_key.to_vec(),
+        expected_root: subtree_hash::<H>(&leaves, 0),
+        data: data
+            .iter()
+            .map(|(key, value)| KeyValuePair {
+                key: key.to_vec(),
                 value: value.to_vec(),
-            },
+            })
+            .collect(),
+        proofs: data
+            .iter()
+            .map(|(key, _)| ProofVector {
+                key: key.to_vec(),
+                siblings: siblings::<H>(&leaves, KeyHash::with::<H>(key), 0),
+            })
+            .collect(),
+    }
+}
+
+/// Computes the vectors of the file of `hash_function`, whose description calls it `name`.
+fn compute_vectors<H: SimpleHasher>(hash_function: &str, name: &str) -> TestVectorWrapper {
+    TestVectorWrapper {
+        description: alloc::format!(
+            "Test vectors for a JMT instantiated with the {} hash function. Keys and values are \
+             hex-encoded byte strings. Neither keys nor values have been pre-hashed.",
+            name
+        ),
+        hash_function: hash_function.to_string(),
+        vectors: vec![
+            compute_vector::<H>(&[]),
+            compute_vector::<H>(&[(b"hello", b"world")]),
+            compute_vector::<H>(&[(b"hello", b"world"), (b"goodbye", b"world")]),
         ],
     }
 }
 
+/// Overwrites the checked-in test vectors, indented with tabs like the original ones. Run with
+/// `cargo test generate_vectors -- --ignored`.
+#[test]
+#[ignore]
 fn generate_vectors() {
-    use super::vectors::TestVectorWrapper;
-
-    let vectors = vec![
-        create_vector_for_empty_trie(),
-        compute_vector_with_one_leaf(),
-        compute_vector_with_two_leaves(),
-    ];
-
-    let test_vectors = TestVectorWrapper {
-        description: DESCRIPTION.to_string(),
-        hash_function: "sha2_256".to_string(),
-        vectors,
-    };
-    let file = std::fs::File::create("sha2_256_vectors.json").unwrap();
-    let writer = std::io::BufWriter::new(file);
-    serde_json::to_writer_pretty(writer, &test_vectors).unwrap();
+    use serde::Serialize;
+    use std::io::Write;
+
+    for (file_name, vectors) in [
+        (
+            "sha2_256_vectors.json",
+            compute_vectors::<sha2::Sha256>("sha2", "sha2-256"),
+        ),
+        (
+            "blake3_vectors.json",
+            compute_vectors::<crate::Blake3Hasher>("blake3", "blake3"),
+        ),
+        (
+            "keccak256_vectors.json",
+            compute_vectors::<sha3::Keccak256>("keccak256", "keccak-256"),
+        ),
+    ] {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/tests")
+            .join(file_name);
+        let file = std::fs::File::create(path).unwrap();
+        let mut writer = std::io::BufWriter::new(file);
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(b"\t");
+        vectors
+            .serialize(&mut serde_json::Serializer::with_formatter(
+                &mut writer,
+                formatter,
+            ))
+            .unwrap();
+        writeln!(writer).unwrap();
+    }
 }
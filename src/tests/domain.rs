@@ -0,0 +1,171 @@
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+
+use proptest::{collection::btree_map, prelude::*};
+use sha2::Sha256;
+
+use crate::{
+    domain::{DefaultDomain, Domain, WithDomain},
+    mock::MockTreeStore,
+    proof::SparseMerkleProof,
+    restore::{JellyfishMerkleRestore, StateSnapshotReceiver},
+    JellyfishMerkleTree, KeyHash, OwnedValue, RootHash, SimpleHasher,
+};
+
+/// The domain of a tree which must not share proofs with the trees of other applications.
+struct TestDomain;
+
+impl Domain for TestDomain {
+    const LEAF_DOMAIN_SEPARATOR: &'static [u8] = b"TEST_APP::Leaf";
+    const INTERNAL_DOMAIN_SEPARATOR: &'static [u8] = b"TEST_APP::Internal";
+    const SPARSE_MERKLE_PLACEHOLDER_HASH: [u8; 32] = *b"TEST_APP_EMPTY_SUBTREE_HASH_____";
+}
+
+type TestDomainSha256 = WithDomain<Sha256, TestDomain>;
+
+fn put<H: SimpleHasher>(kvs: &BTreeMap<KeyHash, OwnedValue>) -> (MockTreeStore, RootHash) {
+    let db = MockTreeStore::default();
+    let (root_hash, batch) = JellyfishMerkleTree::<_, H>::new(&db)
+        .put_value_set(kvs.iter().map(|(k, v)| (*k, Some(v.clone()))), 0)
+        .unwrap();
+    db.write_tree_update_batch(batch).unwrap();
+    (db, root_hash)
+}
+
+/// Reads `proof` as a proof of a tree hashed with `H2`.
+fn reinterpret<H1: SimpleHasher, H2: SimpleHasher>(
+    proof: &SparseMerkleProof<H1>,
+) -> SparseMerkleProof<H2> {
+    SparseMerkleProof::new(proof.leaf(), proof.siblings().to_vec())
+}
+
+fn test_domain_separates_proofs(kvs: BTreeMap<KeyHash, OwnedValue>, absent: Vec<KeyHash>) {
+    let (db, root_hash) = put::<TestDomainSha256>(&kvs);
+    let (_, default_root_hash) = put::<Sha256>(&kvs);
+    assert_ne!(root_hash, default_root_hash);
+
+    let tree = JellyfishMerkleTree::<_, TestDomainSha256>::new(&db);
+    for (key, value) in &kvs {
+        let (found, proof) = tree.get_with_proof(*key, 0).unwrap();
+        assert_eq!(found.as_ref(), Some(value));
+        proof.verify_existence(root_hash, *key, value).unwrap();
+        // The same nodes do not prove anything in the default domain.
+        assert!(reinterpret::<_, Sha256>(&proof)
+            .verify_existence(root_hash, *key, value)
+            .is_err());
+    }
+    for key in absent {
+        let (found, proof) = tree.get_with_proof(key, 0).unwrap();
+        assert!(found.is_none());
+        proof.verify_nonexistence(root_hash, key).unwrap();
+    }
+}
+
+fn test_restore_in_domain(kvs: BTreeMap<KeyHash, OwnedValue>) {
+    let (db, root_hash) = put::<TestDomainSha256>(&kvs);
+    let tree = JellyfishMerkleTree::<_, TestDomainSha256>::new(&db);
+
+    let restore_db = Arc::new(MockTreeStore::default());
+    let mut restore =
+        JellyfishMerkleRestore::<TestDomainSha256>::new(Arc::clone(&restore_db), 0, root_hash)
+            .unwrap();
+    let proof = tree
+        .get_range_proof(*kvs.keys().last().unwrap(), 0)
+        .unwrap();
+    restore
+        .add_chunk(kvs.clone().into_iter().collect(), proof)
+        .unwrap();
+    let summary = restore.finish().unwrap();
+    assert_eq!(summary.root_hash, root_hash);
+
+    let restored_tree = JellyfishMerkleTree::<_, TestDomainSha256>::new(&*restore_db);
+    assert_eq!(restored_tree.get_root_hash(0).unwrap(), root_hash);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(10))]
+
+    #[test]
+    fn test_domain_separates_proofs_sha256(
+        kvs in btree_map(any::<KeyHash>(), any::<OwnedValue>(), 1..50),
+        absent in proptest::collection::vec(any::<KeyHash>(), 10),
+    ) {
+        let absent = absent.into_iter().filter(|key| !kvs.contains_key(key)).collect();
+        test_domain_separates_proofs(kvs, absent);
+    }
+
+    #[test]
+    fn test_restore_in_domain_sha256(
+        kvs in btree_map(any::<KeyHash>(), any::<OwnedValue>(), 1..50),
+    ) {
+        test_restore_in_domain(kvs);
+    }
+
+    #[test]
+    fn test_default_domain_matches_plain_hasher(
+        kvs in btree_map(any::<KeyHash>(), any::<OwnedValue>(), 1..50),
+    ) {
+        assert_eq!(
+            put::<WithDomain<Sha256, DefaultDomain>>(&kvs).1,
+            put::<Sha256>(&kvs).1
+        );
+    }
+}
+
+#[test]
+fn test_empty_root_is_domain_placeholder() {
+    let db = MockTreeStore::default();
+    let (root_hash, _batch) = JellyfishMerkleTree::<_, TestDomainSha256>::new(&db)
+        .put_value_set(Vec::new(), 0)
+        .unwrap();
+    assert_eq!(root_hash.0, TestDomain::SPARSE_MERKLE_PLACEHOLDER_HASH);
+}
+
+#[cfg(feature = "ics23")]
+#[test]
+fn test_ics23_proofs_in_domain() {
+    use ics23::HostFunctionsManager;
+
+    use crate::{ics23_spec, ics23_spec_in_domain};
+
+    let kvs: Vec<_> = (0..20u8)
+        .map(|i| (alloc::vec![i], alloc::vec![i; 4]))
+        .collect();
+    let db = MockTreeStore::default();
+    for (key, _) in &kvs {
+        db.put_key_preimage(KeyHash::with::<TestDomainSha256>(key), key);
+    }
+    let tree = JellyfishMerkleTree::<_, TestDomainSha256>::new(&db);
+    let (root_hash, batch) = tree
+        .put_value_set(
+            kvs.iter()
+                .map(|(k, v)| (KeyHash::with::<TestDomainSha256>(k), Some(v.clone()))),
+            0,
+        )
+        .unwrap();
+    db.write_tree_update_batch(batch).unwrap();
+
+    let (key, value) = &kvs[7];
+    let (_, proof) = tree.get_with_ics23_proof(key.clone(), 0).unwrap();
+    assert!(ics23::verify_membership::<HostFunctionsManager>(
+        &proof,
+        &ics23_spec_in_domain::<TestDomain>(),
+        &root_hash.0.to_vec(),
+        key,
+        value,
+    ));
+    assert!(!ics23::verify_membership::<HostFunctionsManager>(
+        &proof,
+        &ics23_spec(),
+        &root_hash.0.to_vec(),
+        key,
+        value,
+    ));
+
+    let (_, proof) = tree.get_with_ics23_proof(b"absent".to_vec(), 0).unwrap();
+    assert!(ics23::verify_non_membership::<HostFunctionsManager>(
+        &proof,
+        &ics23_spec_in_domain::<TestDomain>(),
+        &root_hash.0.to_vec(),
+        b"absent",
+    ));
+}
@@ -0,0 +1,190 @@
+use alloc::vec;
+
+use sha2::Sha256;
+
+use crate::{
+    domain::WithDomain,
+    mock::MockTreeStore,
+    proof::{
+        envelope::{
+            domain_id, hasher_id, EnvelopeError, EnvelopedProof, ProofHeader, ProofKind,
+            ENVELOPE_FORMAT_VERSION, ENVELOPE_HEADER_LEN,
+        },
+        ExclusionProof, SparseMerkleProof, SparseMerkleRangeProof, UpdateMerkleProof,
+    },
+    JellyfishMerkleTree, KeyHash, RootHash, SimpleHasher,
+};
+
+struct TestDomain;
+
+impl crate::domain::Domain for TestDomain {
+    const LEAF_DOMAIN_SEPARATOR: &'static [u8] = b"TEST_APP::Leaf";
+    const INTERNAL_DOMAIN_SEPARATOR: &'static [u8] = b"TEST_APP::Internal";
+    const SPARSE_MERKLE_PLACEHOLDER_HASH: [u8; 32] = [0xaa; 32];
+}
+
+/// Writes a few keys to a new tree, and returns it along with its root hash.
+fn tree_with_keys<H: SimpleHasher>() -> (MockTreeStore, RootHash) {
+    let db = MockTreeStore::default();
+    let (root_hash, batch) = JellyfishMerkleTree::<_, H>::new(&db)
+        .put_value_set(
+            (0..8u8).map(|i| (KeyHash::with::<H>([i]), Some(vec![i]))),
+            0,
+        )
+        .unwrap();
+    db.write_tree_update_batch(batch).unwrap();
+    (db, root_hash)
+}
+
+fn membership_proof<H: SimpleHasher>() -> (SparseMerkleProof<H>, RootHash) {
+    let (db, root_hash) = tree_with_keys::<H>();
+    let (_, proof) = JellyfishMerkleTree::<_, H>::new(&db)
+        .get_with_proof(KeyHash::with::<H>([3]), 0)
+        .unwrap();
+    (proof, root_hash)
+}
+
+#[test]
+fn test_envelope_round_trips_every_proof_kind() {
+    let (db, root_hash) = tree_with_keys::<Sha256>();
+    let tree = JellyfishMerkleTree::<_, Sha256>::new(&db);
+    let key_hash = KeyHash::with::<Sha256>([3]);
+
+    let (_, proof) = tree.get_with_proof(key_hash, 0).unwrap();
+    let decoded = SparseMerkleProof::<Sha256>::from_envelope(&proof.to_envelope()).unwrap();
+    assert_eq!(decoded, proof);
+    decoded.verify_existence(root_hash, key_hash, [3]).unwrap();
+
+    let absent = KeyHash::with::<Sha256>([42]);
+    let proof = tree
+        .get_with_exclusion_proof(absent, 0)
+        .unwrap()
+        .unwrap_err();
+    let decoded = ExclusionProof::<Sha256>::from_envelope(&proof.to_envelope()).unwrap();
+    assert_eq!(decoded, proof);
+    decoded.verify(root_hash, absent).unwrap();
+
+    let proof = tree.get_range_proof(key_hash, 0).unwrap();
+    let decoded = SparseMerkleRangeProof::<Sha256>::from_envelope(&proof.to_envelope()).unwrap();
+    assert_eq!(decoded, proof);
+
+    let update = vec![(absent, Some(vec![42]))];
+    let (new_root_hash, proof, _batch) = tree.put_value_set_with_proof(update.clone(), 1).unwrap();
+    let envelope = proof.to_envelope();
+    assert_eq!(
+        ProofHeader::decode(&envelope).unwrap().0.kind,
+        ProofKind::Update
+    );
+    UpdateMerkleProof::<Sha256>::from_envelope(&envelope)
+        .unwrap()
+        .verify_update(root_hash, new_root_hash, update)
+        .unwrap();
+}
+
+#[test]
+fn test_envelope_header_identifies_hasher_and_domain() {
+    let (proof, _) = membership_proof::<crate::Blake3Hasher>();
+    let envelope = proof.to_envelope();
+    let (header, body) = ProofHeader::decode(&envelope).unwrap();
+    assert_eq!(
+        header,
+        ProofHeader {
+            format_version: ENVELOPE_FORMAT_VERSION,
+            kind: ProofKind::SparseMerkle,
+            hasher_id: hasher_id::<crate::Blake3Hasher>(),
+            domain_id: domain_id::<crate::Blake3Hasher>(),
+        }
+    );
+    assert!(header.is_hashed_with::<crate::Blake3Hasher>());
+    assert!(!header.is_hashed_with::<Sha256>());
+    assert_eq!(body, borsh::to_vec(&proof).unwrap());
+
+    // Hashers are told apart by their hash function, then by their domain.
+    let hasher_ids = [
+        hasher_id::<Sha256>(),
+        hasher_id::<crate::Blake3Hasher>(),
+        hasher_id::<sha3::Keccak256>(),
+        hasher_id::<crate::poseidon::PoseidonHasher>(),
+    ];
+    for (i, id) in hasher_ids.iter().enumerate() {
+        assert!(!hasher_ids[i + 1..].contains(id));
+    }
+    assert_eq!(
+        hasher_id::<WithDomain<Sha256, TestDomain>>(),
+        hasher_id::<Sha256>()
+    );
+    assert_ne!(
+        domain_id::<WithDomain<Sha256, TestDomain>>(),
+        domain_id::<Sha256>()
+    );
+}
+
+#[test]
+fn test_envelope_rejects_other_hasher_domain_and_kind() {
+    let (proof, _) = membership_proof::<crate::Blake3Hasher>();
+    assert!(matches!(
+        SparseMerkleProof::<Sha256>::from_envelope(&proof.to_envelope()),
+        Err(EnvelopeError::HasherMismatch { expected, actual })
+            if expected == hasher_id::<Sha256>() && actual == hasher_id::<crate::Blake3Hasher>()
+    ));
+
+    let (proof, _) = membership_proof::<WithDomain<Sha256, TestDomain>>();
+    assert!(matches!(
+        SparseMerkleProof::<Sha256>::from_envelope(&proof.to_envelope()),
+        Err(EnvelopeError::DomainMismatch { .. })
+    ));
+
+    let (proof, _) = membership_proof::<Sha256>();
+    assert_eq!(
+        SparseMerkleRangeProof::<Sha256>::from_envelope(&proof.to_envelope()),
+        Err(EnvelopeError::ProofKindMismatch {
+            expected: ProofKind::Range,
+            actual: ProofKind::SparseMerkle,
+        })
+    );
+}
+
+#[test]
+fn test_envelope_rejects_malformed_bytes() {
+    let (proof, _) = membership_proof::<Sha256>();
+    let envelope = proof.to_envelope();
+
+    assert_eq!(
+        SparseMerkleProof::<Sha256>::from_envelope(&envelope[..ENVELOPE_HEADER_LEN - 1]),
+        Err(EnvelopeError::Truncated {
+            len: ENVELOPE_HEADER_LEN - 1
+        })
+    );
+    // A bare proof is not an envelope.
+    assert_eq!(
+        SparseMerkleProof::<Sha256>::from_envelope(&envelope[ENVELOPE_HEADER_LEN..]),
+        Err(EnvelopeError::BadMagic)
+    );
+
+    let mut future_version = envelope.clone();
+    future_version[4] = ENVELOPE_FORMAT_VERSION + 1;
+    assert_eq!(
+        SparseMerkleProof::<Sha256>::from_envelope(&future_version),
+        Err(EnvelopeError::UnsupportedFormatVersion {
+            format_version: ENVELOPE_FORMAT_VERSION + 1
+        })
+    );
+
+    let mut unknown_kind = envelope.clone();
+    unknown_kind[5] = 0xff;
+    assert_eq!(
+        SparseMerkleProof::<Sha256>::from_envelope(&unknown_kind),
+        Err(EnvelopeError::UnknownProofKind { tag: 0xff })
+    );
+
+    let mut trailing_bytes = envelope.clone();
+    trailing_bytes.push(0);
+    assert!(matches!(
+        SparseMerkleProof::<Sha256>::from_envelope(&trailing_bytes),
+        Err(EnvelopeError::MalformedProof { .. })
+    ));
+    assert!(matches!(
+        SparseMerkleProof::<Sha256>::from_envelope(&envelope[..envelope.len() - 1]),
+        Err(EnvelopeError::MalformedProof { .. })
+    ));
+}
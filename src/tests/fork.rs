@@ -0,0 +1,240 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::cell::RefCell;
+
+use anyhow::Result;
+use proptest::{collection::btree_map, prelude::*};
+use sha2::Sha256;
+
+use crate::{
+    fork::{BranchReader, ForkedHistory, ForkedTreeReader},
+    integrity::verify_tree_integrity,
+    mock::MockTreeStore,
+    storage::{LeafNode, Node, NodeKey, TreeReader, TreeUpdateBatch},
+    JellyfishMerkleTree, KeyHash, OwnedValue, RootHash, SimpleHasher, Version,
+};
+
+type ValueSet = BTreeMap<KeyHash, Option<OwnedValue>>;
+
+/// Puts `value_set` on top of `base_version` at `version`, as a branch of the tree in `db`.
+fn put_branch<H: SimpleHasher>(
+    db: &MockTreeStore,
+    value_set: &ValueSet,
+    base_version: Option<Version>,
+    version: Version,
+) -> RootHash {
+    let (root_hash, batch) = JellyfishMerkleTree::<_, H>::new(db)
+        .put_value_set_after(value_set.clone(), base_version, version)
+        .unwrap();
+    db.write_branch_update_batch(base_version, version, batch)
+        .unwrap();
+    root_hash
+}
+
+fn assert_intact<H: SimpleHasher>(db: &MockTreeStore, version: Version) {
+    assert_eq!(
+        verify_tree_integrity::<_, H>(&BranchReader::new(db), version).unwrap(),
+        Vec::new()
+    );
+}
+
+fn test_forked_branches<H: SimpleHasher>(base: ValueSet, selected: ValueSet, abandoned: ValueSet) {
+    // The selected branch has a higher version than the abandoned one, so that pruning the
+    // abandoned branch by version alone would break it.
+    let db = MockTreeStore::default();
+    put_branch::<H>(&db, &base, None, 0);
+    let abandoned_root_hash = put_branch::<H>(&db, &abandoned, Some(0), 1);
+    let selected_root_hash = put_branch::<H>(&db, &selected, Some(0), 2);
+
+    // Each branch is the tree its value sets would give in a linear history.
+    let put_linear = |value_sets: [&ValueSet; 2]| {
+        let linear_db = MockTreeStore::default();
+        let (root_hashes, batch) = JellyfishMerkleTree::<_, H>::new(&linear_db)
+            .put_value_sets(value_sets.map(|value_set| value_set.clone()), 0)
+            .unwrap();
+        linear_db.write_tree_update_batch(batch).unwrap();
+        (linear_db, root_hashes[1])
+    };
+    assert_eq!(put_linear([&base, &abandoned]).1, abandoned_root_hash);
+    let (linear_db, linear_root_hash) = put_linear([&base, &selected]);
+    assert_eq!(linear_root_hash, selected_root_hash);
+    for version in 0..=2 {
+        assert_intact::<H>(&db, version);
+    }
+    let reader = BranchReader::new(&db);
+    let tree = JellyfishMerkleTree::<_, H>::new(&reader);
+    for key in abandoned.keys().filter(|key| !selected.contains_key(key)) {
+        assert_eq!(tree.get(*key, 2).unwrap(), base.get(key).cloned().flatten());
+    }
+
+    // Abandoning a branch leaves its base and the other branch intact.
+    db.abandon_branch(1).unwrap();
+    assert!(tree.get_root_hash_option(1).unwrap().is_none());
+    assert_intact::<H>(&db, 0);
+    assert_intact::<H>(&db, 2);
+
+    // Once the selected branch is the only one left, pruning its base keeps exactly its nodes.
+    db.prune_branches(2).unwrap();
+    assert_intact::<H>(&db, 2);
+    linear_db.purge_stale_nodes(1).unwrap();
+    assert_eq!(db.num_nodes(), linear_db.num_nodes());
+}
+
+#[test]
+fn test_prune_requires_abandoning_other_branches() {
+    let db = MockTreeStore::default();
+    let value_set = |byte| ValueSet::from([(KeyHash([byte; 32]), Some(alloc::vec![byte]))]);
+    put_branch::<Sha256>(&db, &value_set(0), None, 0);
+    put_branch::<Sha256>(&db, &value_set(1), Some(0), 1);
+    put_branch::<Sha256>(&db, &value_set(2), Some(0), 2);
+    put_branch::<Sha256>(&db, &value_set(3), Some(2), 3);
+
+    assert!(db.prune_branches(2).is_err());
+    db.prune_branches(0).unwrap();
+    db.abandon_branch(2).unwrap();
+    assert!(JellyfishMerkleTree::<_, Sha256>::new(&db)
+        .get_root_hash_option(3)
+        .unwrap()
+        .is_none());
+    db.prune_branches(1).unwrap();
+    assert_intact::<Sha256>(&db, 1);
+}
+
+/// A store keeping values by key and version, like a database would, and the history of its
+/// branches next to them, so that it can read branches.
+#[derive(Default)]
+struct BranchStore {
+    nodes: RefCell<BTreeMap<NodeKey, Node>>,
+    values: RefCell<BTreeMap<(KeyHash, Version), Option<OwnedValue>>>,
+    history: RefCell<ForkedHistory>,
+}
+
+impl BranchStore {
+    fn write_branch(
+        &self,
+        base_version: Option<Version>,
+        version: Version,
+        batch: TreeUpdateBatch,
+    ) -> Result<()> {
+        self.history
+            .borrow_mut()
+            .add_versions(base_version, version, &batch)?;
+        let node_batch = batch.node_batch;
+        self.nodes.borrow_mut().extend(node_batch.nodes().clone());
+        let mut values = self.values.borrow_mut();
+        for ((version, key_hash), value) in node_batch.values() {
+            values.insert((*key_hash, *version), value.clone());
+        }
+        Ok(())
+    }
+}
+
+impl TreeReader for BranchStore {
+    fn get_node_option(&self, node_key: &NodeKey) -> Result<Option<Node>> {
+        Ok(self.nodes.borrow().get(node_key).cloned())
+    }
+
+    fn get_value_option(
+        &self,
+        max_version: Version,
+        key_hash: KeyHash,
+    ) -> Result<Option<OwnedValue>> {
+        Ok(self
+            .values
+            .borrow()
+            .range((key_hash, 0)..=(key_hash, max_version))
+            .next_back()
+            .and_then(|(_, value)| value.clone()))
+    }
+
+    fn get_rightmost_leaf(&self) -> Result<Option<(NodeKey, LeafNode)>> {
+        unimplemented!("not needed by forked histories")
+    }
+}
+
+impl ForkedTreeReader for BranchStore {
+    fn get_value_option_on_branch(
+        &self,
+        tip: Version,
+        key_hash: KeyHash,
+    ) -> Result<Option<OwnedValue>> {
+        let history = self.history.borrow();
+        for ((_, version), value) in self
+            .values
+            .borrow()
+            .range((key_hash, 0)..=(key_hash, tip))
+            .rev()
+        {
+            if history.is_on_branch(*version, tip)? {
+                return Ok(value.clone());
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[test]
+fn test_values_of_other_branches_are_skipped() {
+    // Version 5 writes `key` on one branch, and version 6 is built on top of version 4 without
+    // touching it, so `key` has its value of version 4 there.
+    let db = BranchStore::default();
+    let key = KeyHash([1; 32]);
+    let other_key = KeyHash([2; 32]);
+    let branches = [
+        (None, 4, alloc::vec![(key, Some(alloc::vec![4]))]),
+        (Some(4), 5, alloc::vec![(key, Some(alloc::vec![5]))]),
+        (Some(4), 6, alloc::vec![(other_key, Some(alloc::vec![6]))]),
+    ];
+    for (base_version, version, value_set) in branches {
+        let (_root_hash, batch) = JellyfishMerkleTree::<_, Sha256>::new(&db)
+            .put_value_set_after(value_set, base_version, version)
+            .unwrap();
+        db.write_branch(base_version, version, batch).unwrap();
+    }
+
+    // Reading the store directly sees the value of the other branch.
+    let linear_tree = JellyfishMerkleTree::<_, Sha256>::new(&db);
+    assert_eq!(linear_tree.get(key, 6).unwrap(), Some(alloc::vec![5]));
+
+    let reader = BranchReader::new(&db);
+    let tree = JellyfishMerkleTree::<_, Sha256>::new(&reader);
+    assert_eq!(tree.get(key, 5).unwrap(), Some(alloc::vec![5]));
+    assert_eq!(tree.get(key, 6).unwrap(), Some(alloc::vec![4]));
+    let (value, proof) = tree.get_with_proof(key, 6).unwrap();
+    assert_eq!(value, Some(alloc::vec![4]));
+    proof
+        .verify_existence(tree.get_root_hash(6).unwrap(), key, alloc::vec![4])
+        .unwrap();
+
+    // A version which is not part of the history has no branch to read.
+    assert!(tree.get(key, 7).is_err());
+}
+
+#[test]
+fn test_branch_of_unknown_tip() {
+    let db = MockTreeStore::default();
+    let value_set = |byte| ValueSet::from([(KeyHash([byte; 32]), Some(alloc::vec![byte]))]);
+    put_branch::<Sha256>(&db, &value_set(0), None, 0);
+    put_branch::<Sha256>(&db, &value_set(1), Some(0), 1);
+    put_branch::<Sha256>(&db, &value_set(2), Some(0), 2);
+
+    let reader = BranchReader::new(&db);
+    let tree = JellyfishMerkleTree::<_, Sha256>::new(&reader);
+    assert_eq!(tree.get(KeyHash([1; 32]), 1).unwrap(), Some(alloc::vec![1]));
+    assert_eq!(tree.get(KeyHash([1; 32]), 2).unwrap(), None);
+    db.abandon_branch(1).unwrap();
+    assert!(tree.get(KeyHash([1; 32]), 1).is_err());
+    assert!(tree.get(KeyHash([1; 32]), 3).is_err());
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(10))]
+
+    #[test]
+    fn test_forked_branches_sha256(
+        base in btree_map(any::<KeyHash>(), any::<OwnedValue>().prop_map(Some), 1..20),
+        selected in btree_map(any::<KeyHash>(), any::<Option<OwnedValue>>(), 1..10),
+        abandoned in btree_map(any::<KeyHash>(), any::<Option<OwnedValue>>(), 1..10),
+    ) {
+        test_forked_branches::<Sha256>(base, selected, abandoned);
+    }
+}
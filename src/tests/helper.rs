@@ -24,7 +24,7 @@ use crate::{
     node_type::LeafNode,
     storage::Node,
     types::{
-        proof::{SparseMerkleInternalNode, SparseMerkleRangeProof},
+        proof::{ExclusionProof, SparseMerkleInternalNode, SparseMerkleRangeProof},
         Version, PRE_GENESIS_VERSION,
     },
     Bytes32Ext, JellyfishMerkleIterator, JellyfishMerkleTree, KeyHash, OwnedValue, RootHash,
@@ -403,6 +403,40 @@ pub fn test_get_with_proof_with_deletions<H: SimpleHasher>(
     test_nonexistent_keys_impl(&tree, version, &nonexistent_keys);
 }
 
+pub fn test_get_with_exclusion_proof<H: SimpleHasher>(
+    (existent_kvs, nonexistent_keys): (HashMap<KeyHash, OwnedValue>, Vec<KeyHash>),
+) {
+    let (db, version) = init_mock_db::<H>(&existent_kvs);
+    let tree = JellyfishMerkleTree::<_, H>::new(&db);
+    let root_hash = tree.get_root_hash(version).unwrap();
+
+    for key in nonexistent_keys {
+        let exclusion_proof = tree
+            .get_with_exclusion_proof(key, version)
+            .unwrap()
+            .expect_err("key must not exist");
+        assert!(exclusion_proof.verify(root_hash, key).is_ok());
+        assert!(exclusion_proof
+            .verify(RootHash(SPARSE_MERKLE_PLACEHOLDER_HASH), key)
+            .is_err());
+    }
+
+    // Neighbors which are not adjacent must be rejected, even though both proofs are valid.
+    let mut sorted_keys: Vec<KeyHash> = existent_kvs.keys().cloned().collect();
+    sorted_keys.sort();
+    for window in sorted_keys.windows(3) {
+        let key = plus_one(window[0]);
+        if key == window[1] {
+            continue;
+        }
+        let skipping_proof = ExclusionProof::<H>::Middle {
+            rightmost_left_proof: tree.get_with_proof(window[0], version).unwrap().1,
+            leftmost_right_proof: tree.get_with_proof(window[2], version).unwrap().1,
+        };
+        assert!(skipping_proof.verify(root_hash, key).is_err());
+    }
+}
+
 /// A very general test that demonstrates that given a sequence of insertions and deletions, batched
 /// by version, the end result of having performed those operations is identical to having *already
 /// known* what the end result would be, and only performing the insertions necessary to get there,
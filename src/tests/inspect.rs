@@ -0,0 +1,154 @@
+use alloc::{format, string::String, vec, vec::Vec};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use clap::Parser;
+use sha2::Sha256;
+
+use crate::{
+    inspect::Cli, mock::MockTreeStore, JellyfishMerkleTree, KeyHash, RootHash, SimpleHasher,
+};
+
+/// Writes a dump of a small tree, in which the keys `[i; 32]` hold the values `[i]`, to a file
+/// named after `name`, returning its path and the root hash of the tree.
+fn write_dump<H: SimpleHasher>(name: &str) -> (PathBuf, RootHash) {
+    let db = MockTreeStore::default();
+    let tree = JellyfishMerkleTree::<_, H>::new(&db);
+    let mut dump = Vec::new();
+    let mut root_hash = RootHash([0; 32]);
+    for (version, i) in (0u8..8).enumerate() {
+        let (new_root_hash, batch) = tree
+            .put_value_set(vec![(KeyHash([i; 32]), Some(vec![i]))], version as u64)
+            .unwrap();
+        dump.extend(borsh::to_vec(&batch).unwrap());
+        db.write_tree_update_batch(batch).unwrap();
+        root_hash = new_root_hash;
+    }
+    let path = std::env::temp_dir().join(format!("jmt-inspect-{}-{}", std::process::id(), name));
+    fs::write(&path, dump).unwrap();
+    (path, root_hash)
+}
+
+fn run(args: &[&str]) -> anyhow::Result<String> {
+    let mut out = Vec::new();
+    Cli::try_parse_from(core::iter::once("jmt-inspect").chain(args.iter().copied()))?
+        .run(&mut out)?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+/// Proves `[3; 32]` with `prove` in the format selected by `format_args`, and checks that
+/// `verify` accepts it with the right value only.
+fn prove_and_verify(hasher: &str, dump: &Path, root_hash: RootHash, format_args: &[&str]) {
+    let dump = dump.to_str().unwrap();
+    let key_hash = hex::encode([3; 32]);
+    let mut prove_args = vec!["--hasher", hasher, "--dump", dump, "prove", &key_hash];
+    prove_args.extend_from_slice(format_args);
+    let proof = run(&prove_args).unwrap();
+
+    let proof_path = PathBuf::from(format!("{}-proof{}", dump, format_args.join("")));
+    fs::write(&proof_path, proof).unwrap();
+    let root_hash = hex::encode(root_hash.0);
+    let verify = |value: &str| {
+        run(&[
+            "--hasher",
+            hasher,
+            "verify",
+            proof_path.to_str().unwrap(),
+            "--root-hash",
+            &root_hash,
+            "--key-hash",
+            &key_hash,
+            "--value",
+            value,
+        ])
+    };
+    assert_eq!(verify("03").unwrap(), "proof is valid\n");
+    assert!(verify("04").is_err());
+    fs::remove_file(proof_path).unwrap();
+}
+
+#[test]
+fn test_inspect_prove_then_verify() {
+    let (dump, root_hash) = write_dump::<sha2::Sha256>("prove-then-verify");
+    for format_args in [&[][..], &["--json"], &["--envelope"]] {
+        prove_and_verify("sha256", &dump, root_hash, format_args);
+    }
+
+    let roots = run(&["--dump", dump.to_str().unwrap(), "roots"]).unwrap();
+    assert!(roots.ends_with(&format!(
+        "version 7\troot {}\tleaves 8\n",
+        hex::encode(root_hash.0)
+    )));
+    fs::remove_file(dump).unwrap();
+}
+
+#[cfg(feature = "blake3")]
+#[test]
+fn test_inspect_with_another_hasher() {
+    let (dump, root_hash) = write_dump::<crate::Blake3Hasher>("another-hasher");
+    prove_and_verify("blake3", &dump, root_hash, &[]);
+
+    // The root hashes of a tree hashed with BLAKE3 are wrong when it is inspected as SHA-256.
+    let check = |hasher| {
+        run(&[
+            "--hasher",
+            hasher,
+            "--dump",
+            dump.to_str().unwrap(),
+            "check",
+        ])
+    };
+    check("blake3").unwrap();
+    assert!(check("sha256").is_err());
+    fs::remove_file(dump).unwrap();
+}
+
+#[test]
+fn test_inspect_skipped_version() {
+    // Versions 0 and 5, so that version 3 holds the tree of version 0.
+    let db = MockTreeStore::default();
+    let tree = JellyfishMerkleTree::<_, Sha256>::new(&db);
+    let mut dump = Vec::new();
+    for (base_version, version) in [(None, 0), (Some(0), 5)] {
+        let (_root_hash, batch) = tree
+            .put_value_set_after(
+                vec![(KeyHash([version as u8; 32]), Some(vec![version as u8]))],
+                base_version,
+                version,
+            )
+            .unwrap();
+        dump.extend(borsh::to_vec(&batch).unwrap());
+        db.write_tree_update_batch(batch).unwrap();
+    }
+    let path = std::env::temp_dir().join(format!("jmt-inspect-{}-skipped", std::process::id()));
+    fs::write(&path, dump).unwrap();
+    let dump = path.to_str().unwrap();
+
+    let key_hash = hex::encode([0; 32]);
+    for (skipped, written) in [
+        (
+            vec!["stats", "--version", "3"],
+            vec!["stats", "--version", "0"],
+        ),
+        (
+            vec!["path", &key_hash, "--version", "3"],
+            vec!["path", &key_hash, "--version", "0"],
+        ),
+    ] {
+        assert_eq!(
+            run(&[&["--dump", dump][..], &skipped].concat()).unwrap(),
+            run(&[&["--dump", dump][..], &written].concat()).unwrap()
+        );
+    }
+    run(&["--dump", dump, "check", "--version", "3"]).unwrap();
+    // A version after the latest one in the dumps does not resolve.
+    assert!(run(&["--dump", dump, "stats", "--version", "9"]).is_err());
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_inspect_requires_a_dump() {
+    assert!(run(&["roots"]).is_err());
+}
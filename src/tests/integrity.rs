@@ -0,0 +1,151 @@
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+
+use proptest::{collection::btree_map, prelude::*};
+use sha2::Sha256;
+
+use crate::{
+    integrity::{verify_tree_integrity, Inconsistency, IntegrityError},
+    mock::MockTreeStore,
+    node_type::{Child, Children, InternalNode, LeafNode, Node, NodeKey, NodeType},
+    storage::{NodeBatch, TreeWriter},
+    tests::helper::init_mock_db_with_deletions_afterwards,
+    types::nibble::{nibble_path::NibblePath, Nibble},
+    JellyfishMerkleTree, KeyHash, OwnedValue, SimpleHasher, ValueHash, Version,
+};
+
+fn test_intact_tree_has_no_inconsistencies<H: SimpleHasher>(
+    kvs: BTreeMap<KeyHash, OwnedValue>,
+    deletions: Vec<prop::sample::Index>,
+) {
+    let deletions = deletions
+        .into_iter()
+        .map(|index| **index.get(&kvs.keys().collect::<Vec<_>>()))
+        .collect();
+    let (db, version) =
+        init_mock_db_with_deletions_afterwards::<H>(&kvs.into_iter().collect(), deletions);
+    for version in 0..=version {
+        assert_eq!(verify_tree_integrity::<_, H>(&db, version).unwrap(), vec![]);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(10))]
+
+    #[test]
+    fn test_intact_tree_has_no_inconsistencies_sha256(
+        kvs in btree_map(any::<KeyHash>(), any::<OwnedValue>(), 1..50),
+        deletions in prop::collection::vec(any::<prop::sample::Index>(), 0..10),
+    ) {
+        test_intact_tree_has_no_inconsistencies::<Sha256>(kvs, deletions);
+    }
+}
+
+/// Builds a tree with an internal node at `0` holding two leaves, and a leaf at `1`, then
+/// overwrites the node at `node_key` with `node`.
+fn corrupted_db(node_key: &NodeKey, node: Node) -> MockTreeStore {
+    let db = MockTreeStore::new(true /* allow_overwrite */);
+    let tree = JellyfishMerkleTree::<_, Sha256>::new(&db);
+    let (_root_hash, batch) = tree
+        .put_value_set(
+            vec![
+                (KeyHash([0x00; 32]), Some(vec![1])),
+                (KeyHash([0x01; 32]), Some(vec![2])),
+                (KeyHash([0x10; 32]), Some(vec![3])),
+            ],
+            0, /* version */
+        )
+        .unwrap();
+    db.write_tree_update_batch(batch).unwrap();
+    assert_eq!(verify_tree_integrity::<_, Sha256>(&db, 0).unwrap(), vec![]);
+
+    let mut node_batch = NodeBatch::default();
+    node_batch.insert_node(node_key.clone(), node);
+    db.write_node_batch(&node_batch).unwrap();
+    db
+}
+
+fn inconsistencies_at(errors: &[IntegrityError], node_key: &NodeKey) -> Vec<Inconsistency> {
+    errors
+        .iter()
+        .filter(|error| &error.node_key == node_key)
+        .map(|error| error.inconsistency.clone())
+        .collect()
+}
+
+#[test]
+fn test_corrupted_leaf_value_hash() {
+    let key_hash = KeyHash([0x00; 32]);
+    let node_key = NodeKey::new(0, NibblePath::new(vec![0x00]));
+    let value_hash = ValueHash::with::<Sha256>(b"other");
+    let db = corrupted_db(&node_key, LeafNode::new(key_hash, value_hash).into());
+
+    let errors = verify_tree_integrity::<_, Sha256>(&db, 0).unwrap();
+    assert_eq!(errors.len(), 2);
+    assert!(matches!(
+        errors[0].inconsistency,
+        Inconsistency::HashMismatch { .. }
+    ));
+    assert_eq!(
+        errors[1],
+        IntegrityError {
+            node_key,
+            inconsistency: Inconsistency::ValueHashMismatch {
+                key_hash,
+                expected: value_hash,
+                actual: ValueHash::with::<Sha256>([1]),
+            },
+        }
+    );
+}
+
+#[test]
+fn test_corrupted_leaf_key_hash() {
+    let key_hash = KeyHash([0x20; 32]);
+    let node_key = NodeKey::new(0, NibblePath::new_odd(vec![0x10]));
+    let leaf_node = LeafNode::new(key_hash, ValueHash::with::<Sha256>([3]));
+    let db = corrupted_db(&node_key, leaf_node.into());
+
+    let errors = verify_tree_integrity::<_, Sha256>(&db, 0).unwrap();
+    let inconsistencies = inconsistencies_at(&errors, &node_key);
+    assert_eq!(inconsistencies.len(), errors.len());
+    assert!(inconsistencies.contains(&Inconsistency::LeafKeyOutsidePath { key_hash }));
+    assert!(inconsistencies.contains(&Inconsistency::MissingValue { key_hash }));
+}
+
+#[test]
+fn test_corrupted_internal_node() {
+    // Replace the internal node at `0` by one whose second child points to a later version.
+    let node_key = NodeKey::new(0, NibblePath::new_odd(vec![0x00]));
+    let leaf_hash =
+        LeafNode::new(KeyHash([0x00; 32]), ValueHash::with::<Sha256>([1])).hash::<Sha256>();
+    let bad_version: Version = 5;
+    let mut children = Children::new();
+    children.insert(Nibble::from(0), Child::new(leaf_hash, 0, NodeType::Leaf));
+    children.insert(
+        Nibble::from(1),
+        Child::new([0; 32], bad_version, NodeType::Leaf),
+    );
+    let db = corrupted_db(&node_key, InternalNode::new(children).into());
+
+    let errors = verify_tree_integrity::<_, Sha256>(&db, 0).unwrap();
+    let missing_child_key = node_key.gen_child_node_key(bad_version, Nibble::from(1));
+    assert_eq!(
+        inconsistencies_at(&errors, &missing_child_key),
+        vec![
+            Inconsistency::ChildVersionTooNew { parent_version: 0 },
+            Inconsistency::MissingNode
+        ]
+    );
+    let inconsistencies = inconsistencies_at(&errors, &node_key);
+    assert!(matches!(
+        inconsistencies[0],
+        Inconsistency::HashMismatch { .. }
+    ));
+    assert!(inconsistencies.contains(&Inconsistency::LeafCountMismatch {
+        expected: 2,
+        actual: 1
+    }));
+
+    // A missing root is an error rather than an inconsistency.
+    assert!(verify_tree_integrity::<_, Sha256>(&db, 1).is_err());
+}
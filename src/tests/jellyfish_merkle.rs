@@ -17,8 +17,8 @@ use crate::{
         arb_interleaved_insertions_and_deletions, arb_kv_pair_with_distinct_last_nibble,
         arb_partitions, arb_tree_with_index,
         test_clairvoyant_construction_matches_interleaved_construction, test_get_leaf_count,
-        test_get_range_proof, test_get_with_proof, test_get_with_proof_with_deletions,
-        test_get_with_proof_with_distinct_last_nibble,
+        test_get_range_proof, test_get_with_exclusion_proof, test_get_with_proof,
+        test_get_with_proof_with_deletions, test_get_with_proof_with_distinct_last_nibble,
     },
     types::{
         nibble::{nibble_path::NibblePath, Nibble},
@@ -57,6 +57,7 @@ macro_rules! impl_jellyfish_tests_for_hasher {
             instantiate_test_for_hasher!(test_1000_versions, $hasher);
             instantiate_test_for_hasher!(test_delete_then_get_in_one, $hasher);
             instantiate_test_for_hasher!(test_two_gets_then_delete, $hasher);
+            instantiate_test_for_hasher!(test_to_dot, $hasher);
 
 
             proptest! {
@@ -65,6 +66,11 @@ macro_rules! impl_jellyfish_tests_for_hasher {
                     super::test_get_with_proof::<$hasher>((existent_kvs, nonexistent_keys))
                 }
 
+                #[test]
+                fn proptest_get_with_exclusion_proof((existent_kvs, nonexistent_keys) in super::arb_existent_kvs_and_nonexistent_keys(100, 20)) {
+                    super::test_get_with_exclusion_proof::<$hasher>((existent_kvs, nonexistent_keys))
+                }
+
                 #[test]
                 fn proptest_get_with_proof_with_deletions((existent_kvs, deletions, nonexistent_keys) in super::arb_existent_kvs_and_deletions_and_nonexistent_keys(1000, 100)) {
                     super::test_get_with_proof_with_deletions::<$hasher>((existent_kvs, deletions, nonexistent_keys))
@@ -785,9 +791,57 @@ fn test_two_gets_then_delete<H: SimpleHasher>() {
     db.write_tree_update_batch(batch).unwrap();
 }
 
+fn test_to_dot<H: SimpleHasher>() {
+    let db = MockTreeStore::default();
+    let tree = JellyfishMerkleTree::<_, H>::new(&db);
+
+    // The first two keys share their first nibble, so they sit under an internal node at `0`.
+    let key1 = KeyHash([0x00; 32]);
+    let key2 = KeyHash([0x01; 32]);
+    let key3 = KeyHash([0x10; 32]);
+    let (_root, batch) = tree
+        .put_value_set(
+            vec![
+                (key1, Some(vec![1])),
+                (key2, Some(vec![2])),
+                (key3, Some(vec![3])),
+            ],
+            0, /* version */
+        )
+        .unwrap();
+    db.write_tree_update_batch(batch).unwrap();
+
+    let dot = tree.to_dot(0, &NibblePath::new(vec![]), None).unwrap();
+    assert!(dot.starts_with("digraph jmt {"));
+    assert_eq!(dot.matches("{internal ").count(), 2);
+    assert_eq!(dot.matches("{leaf ").count(), 3);
+    assert!(dot.contains("n0:c0 -> n1;"));
+    assert!(!dot.contains("salmon"));
+
+    // A subtree can be a single leaf, found above the path it was asked for.
+    let dot = tree.to_dot(0, &NibblePath::new(vec![0x10]), None).unwrap();
+    assert_eq!(dot.matches("[label=").count(), 1);
+    assert!(dot.contains(&format!("key {}", hex::encode(&key3.0[..4]))));
+    let dot = tree.to_dot(0, &NibblePath::new(vec![0x00]), None).unwrap();
+    assert_eq!(dot.matches("{leaf ").count(), 1);
+    let dot = tree.to_dot(0, &NibblePath::new(vec![0x20]), None).unwrap();
+    assert_eq!(dot.matches("[label=").count(), 0);
+
+    // Updating the first key makes its leaf and both internal nodes above it stale.
+    let (_root, batch) = tree
+        .put_value_set(vec![(key1, Some(vec![4]))], 1 /* version */)
+        .unwrap();
+    let dot = tree
+        .to_dot(0, &NibblePath::new(vec![]), Some(&batch))
+        .unwrap();
+    assert_eq!(dot.matches("salmon").count(), 3);
+
+    assert!(tree.to_dot(1, &NibblePath::new(vec![]), None).is_err());
+}
+
 // Implement the test suite for sha256
 impl_jellyfish_tests_for_hasher!(sha256_tests, sha2::Sha256);
 
 // Optionally implement the test suite for blake3
 #[cfg(feature = "blake3_tests")]
-impl_jellyfish_tests_for_hasher!(blake3_tests, blake3::Hasher);
+impl_jellyfish_tests_for_hasher!(blake3_tests, crate::Blake3Hasher);
@@ -0,0 +1,84 @@
+use alloc::{sync::Arc, vec::Vec};
+
+use proptest::{collection::btree_map, prelude::*};
+use sha2::Sha256;
+
+use crate::{
+    mock::MockTreeStore,
+    restore::{JellyfishMerkleRestore, SharedStore, StateSnapshotReceiver},
+    JellyfishMerkleIterator, JellyfishMerkleTree, Jmt, KeyHash, OwnedValue,
+};
+
+fn assert_send_sync_clone<T: Send + Sync + Clone>() {}
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn test_handles_are_send_and_sync() {
+    assert_send_sync_clone::<Jmt<MockTreeStore, Sha256>>();
+    assert_send_sync::<JellyfishMerkleIterator<MockTreeStore>>();
+    assert_send_sync::<JellyfishMerkleRestore<Sha256, SharedStore>>();
+}
+
+fn test_jmt_matches_borrowed_tree(kvs: Vec<(KeyHash, OwnedValue)>) {
+    let db = Arc::new(MockTreeStore::default());
+    let jmt = Jmt::<_, Sha256>::new(db.clone());
+
+    // Write from a clone of the handle, possibly on another thread.
+    let writer = jmt.clone();
+    let write = move || {
+        let (_root_hash, batch) = writer
+            .put_value_set(kvs.iter().map(|(k, v)| (*k, Some(v.clone()))), 0)
+            .unwrap();
+        writer.reader().write_tree_update_batch(batch).unwrap();
+        kvs
+    };
+    #[cfg(feature = "std")]
+    let kvs = std::thread::spawn(write).join().unwrap();
+    #[cfg(not(feature = "std"))]
+    let kvs = write();
+
+    let tree = JellyfishMerkleTree::<_, Sha256>::new(db.as_ref());
+    assert_eq!(
+        jmt.get_root_hash(0).unwrap(),
+        tree.get_root_hash(0).unwrap()
+    );
+    for (key, value) in &kvs {
+        let (jmt_value, proof) = jmt.get_with_proof(*key, 0).unwrap();
+        assert_eq!(jmt_value.as_ref(), Some(value));
+        assert_eq!(proof, tree.get_with_proof(*key, 0).unwrap().1);
+    }
+    let entries = jmt
+        .iter(0, KeyHash([0; 32]))
+        .unwrap()
+        .collect::<anyhow::Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(entries, kvs);
+    assert_eq!(jmt.iter_by_index(0, kvs.len() - 1).unwrap().count(), 1);
+
+    // Restore the tree into another store, possibly on another thread.
+    let root_hash = jmt.get_root_hash(0).unwrap();
+    let restore_db = Jmt::<_, Sha256>::new(Arc::new(MockTreeStore::default()));
+    let mut restore = restore_db.restore(0, root_hash).unwrap();
+    let proof = jmt.get_range_proof(kvs.last().unwrap().0, 0).unwrap();
+    let restore = move || {
+        restore.add_chunk(kvs, proof).unwrap();
+        restore.finish().unwrap()
+    };
+    #[cfg(feature = "std")]
+    let summary = std::thread::spawn(restore).join().unwrap();
+    #[cfg(not(feature = "std"))]
+    let summary = restore();
+    assert_eq!(summary.root_hash, root_hash);
+    assert_eq!(restore_db.get_root_hash(0).unwrap(), root_hash);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(10))]
+
+    #[test]
+    fn test_jmt_matches_borrowed_tree_sha256(
+        kvs in btree_map(any::<KeyHash>(), any::<OwnedValue>(), 1..50),
+    ) {
+        test_jmt_matches_borrowed_tree(kvs.into_iter().collect());
+    }
+}
@@ -0,0 +1,111 @@
+use alloc::{sync::Arc, vec::Vec};
+
+use proptest::{collection::btree_map, prelude::*};
+use sha2::Sha256;
+
+use crate::{
+    metrics::{CountingMetrics, Phase},
+    mock::MockTreeStore,
+    restore::{JellyfishMerkleRestore, StateSnapshotReceiver},
+    JellyfishMerkleIterator, JellyfishMerkleTree, KeyHash, OwnedValue, SimpleHasher,
+};
+
+fn test_metrics_count_tree_operations<H: SimpleHasher>(
+    value_sets: Vec<Vec<(KeyHash, OwnedValue)>>,
+) {
+    let db = Arc::new(MockTreeStore::default());
+    let metrics = Arc::new(CountingMetrics::new());
+    let tree = JellyfishMerkleTree::<_, H>::new(db.as_ref()).with_metrics(metrics.clone());
+
+    let num_versions = value_sets.len();
+    let last_version = num_versions as u64 - 1;
+    let (_root_hashes, batch) = tree
+        .put_value_sets(
+            value_sets
+                .iter()
+                .map(|value_set| value_set.iter().map(|(k, v)| (*k, Some(v.clone())))),
+            0, /* first_version */
+        )
+        .unwrap();
+    let snapshot = metrics.snapshot();
+    assert_eq!(
+        snapshot.nodes_written,
+        batch.node_batch.nodes().len() as u64
+    );
+    assert!(snapshot.nodes_hashed >= num_versions as u64);
+    assert!(snapshot.node_cache_hits > 0);
+    if cfg!(feature = "std") {
+        assert_eq!(snapshot.phase_count(Phase::Update), 1);
+        assert_eq!(snapshot.phase_count(Phase::Freeze), num_versions as u64);
+    }
+    db.write_tree_update_batch(batch).unwrap();
+
+    // Every lookup reads at least the root, and reports the size of its proof.
+    let keys: Vec<KeyHash> = value_sets.iter().flatten().map(|(k, _v)| *k).collect();
+    let mut num_siblings = 0;
+    for key in &keys {
+        let (_value, proof) = tree.get_with_proof(*key, last_version).unwrap();
+        num_siblings += proof.siblings().len() as u64;
+    }
+    let after_proofs = metrics.snapshot();
+    assert_eq!(after_proofs.proofs, keys.len() as u64);
+    assert_eq!(after_proofs.proof_siblings, num_siblings);
+    assert_eq!(after_proofs.lookups, keys.len() as u64);
+    assert!(after_proofs.node_reads_found >= snapshot.node_reads_found + keys.len() as u64);
+    assert!(after_proofs.max_lookup_depth <= 64);
+    if cfg!(feature = "std") {
+        assert_eq!(after_proofs.phase_count(Phase::Proof), keys.len() as u64);
+    }
+
+    // Iterating reads every node of the tree below the root.
+    let num_leaves = tree.get_leaf_count(last_version).unwrap();
+    let iterator_metrics = Arc::new(CountingMetrics::new());
+    let iterator = JellyfishMerkleIterator::new(db.clone(), last_version, KeyHash([0; 32]))
+        .unwrap()
+        .with_metrics(iterator_metrics.clone());
+    assert_eq!(iterator.count(), num_leaves);
+    assert!(iterator_metrics.snapshot().node_reads_found >= num_leaves as u64);
+
+    // Restoring the tree writes as many nodes as the summary reports.
+    let restore_db = Arc::new(MockTreeStore::default());
+    let restore_metrics = Arc::new(CountingMetrics::new());
+    let mut restore = JellyfishMerkleRestore::<H>::new(
+        restore_db,
+        last_version,
+        tree.get_root_hash(last_version).unwrap(),
+    )
+    .unwrap()
+    .with_metrics(restore_metrics.clone());
+    let entries: Vec<_> = JellyfishMerkleIterator::new(db.clone(), last_version, KeyHash([0; 32]))
+        .unwrap()
+        .collect::<anyhow::Result<_>>()
+        .unwrap();
+    let rightmost_key = entries.last().unwrap().0;
+    let proof = tree.get_range_proof(rightmost_key, last_version).unwrap();
+    restore.add_chunk(entries, proof).unwrap();
+    let summary = restore.finish().unwrap();
+    let restore_snapshot = restore_metrics.snapshot();
+    assert_eq!(
+        restore_snapshot.nodes_written,
+        summary.num_nodes_written as u64
+    );
+    if cfg!(feature = "std") {
+        assert_eq!(restore_snapshot.phase_count(Phase::RestoreChunk), 1);
+        assert_eq!(restore_snapshot.phase_count(Phase::RestoreFinish), 1);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(10))]
+
+    #[test]
+    fn test_metrics_count_tree_operations_sha256(
+        value_sets in prop::collection::vec(
+            btree_map(any::<KeyHash>(), any::<OwnedValue>(), 1..20)
+                .prop_map(|value_set| value_set.into_iter().collect()),
+            1..5,
+        ),
+    ) {
+        test_metrics_count_tree_operations::<Sha256>(value_sets);
+    }
+}
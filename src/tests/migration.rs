@@ -0,0 +1,406 @@
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use core::{
+    cell::{Cell, RefCell},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use anyhow::{bail, Result};
+use proptest::{
+    collection::{btree_map, vec},
+    prelude::*,
+};
+use sha2::Sha256;
+
+use crate::{
+    migration::{rehash_tree, NodeEncodingMigration, RehashSummary},
+    mock::MockTreeStore,
+    storage::{
+        LeafNode, Node, NodeBatch, NodeEncodingReader, NodeEncodingVersion, NodeKey, TreeReader,
+        TreeWriter,
+    },
+    Blake3Hasher, JellyfishMerkleTree, KeyHash, OwnedValue, RootHash, SimpleHasher, Version,
+};
+
+/// A store keeping nodes encoded, like a database would, and writing them with the encoding of
+/// its choice.
+struct EncodedTreeStore {
+    nodes: RefCell<BTreeMap<NodeKey, Vec<u8>>>,
+    values: RefCell<BTreeMap<(KeyHash, Version), Option<OwnedValue>>>,
+    encoding: Cell<NodeEncodingVersion>,
+}
+
+impl EncodedTreeStore {
+    fn new(encoding: NodeEncodingVersion) -> Self {
+        Self {
+            nodes: Default::default(),
+            values: Default::default(),
+            encoding: Cell::new(encoding),
+        }
+    }
+
+    fn count_nodes_with(&self, encoding: NodeEncodingVersion) -> usize {
+        self.nodes
+            .borrow()
+            .values()
+            .filter(|bytes| NodeEncodingVersion::of(bytes).unwrap() == encoding)
+            .count()
+    }
+}
+
+impl TreeReader for EncodedTreeStore {
+    fn get_node_option(&self, node_key: &NodeKey) -> Result<Option<Node>> {
+        Ok(match self.nodes.borrow().get(node_key) {
+            Some(bytes) => Some(Node::decode(bytes)?),
+            None => None,
+        })
+    }
+
+    fn get_value_option(
+        &self,
+        max_version: Version,
+        key_hash: KeyHash,
+    ) -> Result<Option<OwnedValue>> {
+        Ok(self
+            .values
+            .borrow()
+            .range((key_hash, 0)..=(key_hash, max_version))
+            .next_back()
+            .and_then(|(_, value)| value.clone()))
+    }
+
+    fn get_rightmost_leaf(&self) -> Result<Option<(NodeKey, LeafNode)>> {
+        let mut rightmost: Option<(NodeKey, LeafNode)> = None;
+        for (node_key, bytes) in self.nodes.borrow().iter() {
+            if let Node::Leaf(leaf) = Node::decode(bytes)? {
+                let is_rightmost = match &rightmost {
+                    Some((_, rightmost)) => leaf.key_hash() > rightmost.key_hash(),
+                    None => true,
+                };
+                if is_rightmost {
+                    rightmost = Some((node_key.clone(), leaf));
+                }
+            }
+        }
+        Ok(rightmost)
+    }
+}
+
+impl TreeWriter for EncodedTreeStore {
+    fn write_node_batch(&self, node_batch: &NodeBatch) -> Result<()> {
+        let mut nodes = self.nodes.borrow_mut();
+        for (node_key, node) in node_batch.nodes() {
+            nodes.insert(node_key.clone(), node.encode_as(self.encoding.get()));
+        }
+        let mut values = self.values.borrow_mut();
+        for ((version, key_hash), value) in node_batch.values() {
+            values.insert((*key_hash, *version), value.clone());
+        }
+        Ok(())
+    }
+}
+
+impl NodeEncodingReader for EncodedTreeStore {
+    fn get_node_encodings(
+        &self,
+        start_after: Option<&NodeKey>,
+        limit: usize,
+    ) -> Result<Vec<(NodeKey, NodeEncodingVersion)>> {
+        let nodes = self.nodes.borrow();
+        let nodes: Vec<_> = match start_after {
+            Some(start_after) => nodes
+                .range(start_after..)
+                .skip_while(|(node_key, _)| *node_key == start_after)
+                .take(limit)
+                .collect(),
+            None => nodes.iter().take(limit).collect(),
+        };
+        nodes
+            .into_iter()
+            .map(|(node_key, bytes)| Ok((node_key.clone(), NodeEncodingVersion::of(bytes)?)))
+            .collect()
+    }
+}
+
+/// Writes each value set as a new version, and returns the root hash of each version.
+fn put_versions(
+    store: &EncodedTreeStore,
+    first_version: Version,
+    value_sets: &[Vec<(KeyHash, OwnedValue)>],
+) -> Vec<RootHash> {
+    let tree = JellyfishMerkleTree::<_, Sha256>::new(store);
+    let mut root_hashes = Vec::new();
+    for (i, value_set) in value_sets.iter().enumerate() {
+        let (root_hash, batch) = tree
+            .put_value_set(
+                value_set.iter().map(|(k, v)| (*k, Some(v.clone()))),
+                first_version + i as Version,
+            )
+            .unwrap();
+        store.write_node_batch(&batch.node_batch).unwrap();
+        root_hashes.push(root_hash);
+    }
+    root_hashes
+}
+
+/// Checks that every version still has its root hash, and proves every key written so far.
+fn check_versions(
+    store: &EncodedTreeStore,
+    root_hashes: &[RootHash],
+    value_sets: &[Vec<(KeyHash, OwnedValue)>],
+) {
+    let tree = JellyfishMerkleTree::<_, Sha256>::new(store);
+    for (version, root_hash) in root_hashes.iter().enumerate() {
+        let version = version as Version;
+        assert_eq!(tree.get_root_hash(version).unwrap(), *root_hash);
+        for (key, _) in value_sets[..=version as usize].iter().flatten() {
+            let (value, proof) = tree.get_with_proof(*key, version).unwrap();
+            proof
+                .verify_existence(*root_hash, *key, value.unwrap())
+                .unwrap();
+        }
+    }
+}
+
+fn test_online_migration(value_sets: Vec<Vec<(KeyHash, OwnedValue)>>, batch_size: usize) {
+    let (old_sets, new_sets) = value_sets.split_at(value_sets.len() / 2);
+    let store = EncodedTreeStore::new(NodeEncodingVersion::Borsh);
+    let mut root_hashes = put_versions(&store, 0, old_sets);
+    let num_borsh_nodes = store.count_nodes_with(NodeEncodingVersion::Borsh);
+
+    // Once the store writes the new encoding, versions are added while the migration goes on,
+    // and the tree stays readable in between.
+    store.encoding.set(NodeEncodingVersion::Compact);
+    let mut migration = NodeEncodingMigration::new(NodeEncodingVersion::Compact);
+    let mut nodes_rewritten = 0;
+    for value_set in new_sets {
+        let step = migration.step(&store, batch_size).unwrap();
+        assert!(step.nodes_scanned <= batch_size);
+        nodes_rewritten += step.nodes_rewritten;
+        root_hashes.extend(put_versions(
+            &store,
+            root_hashes.len() as Version,
+            core::slice::from_ref(value_set),
+        ));
+        check_versions(&store, &root_hashes, &value_sets);
+    }
+
+    // Resuming the migration from its cursor finishes it.
+    let mut migration =
+        NodeEncodingMigration::resume(NodeEncodingVersion::Compact, migration.cursor().cloned());
+    nodes_rewritten += migration.run(&store, batch_size).unwrap();
+    assert!(migration.is_done());
+    assert_eq!(store.count_nodes_with(NodeEncodingVersion::Borsh), 0);
+    assert_eq!(nodes_rewritten, num_borsh_nodes);
+    check_versions(&store, &root_hashes, &value_sets);
+
+    // Migrating again rewrites nothing.
+    assert_eq!(
+        NodeEncodingMigration::new(NodeEncodingVersion::Compact)
+            .run(&store, batch_size)
+            .unwrap(),
+        0
+    );
+}
+
+/// A store which fails to write once it has written a given number of batches.
+struct FlakyTreeStore {
+    store: MockTreeStore,
+    writes_left: AtomicUsize,
+}
+
+impl TreeReader for FlakyTreeStore {
+    fn get_node_option(&self, node_key: &NodeKey) -> Result<Option<Node>> {
+        self.store.get_node_option(node_key)
+    }
+
+    fn get_value_option(
+        &self,
+        max_version: Version,
+        key_hash: KeyHash,
+    ) -> Result<Option<OwnedValue>> {
+        self.store.get_value_option(max_version, key_hash)
+    }
+
+    fn get_rightmost_leaf(&self) -> Result<Option<(NodeKey, LeafNode)>> {
+        self.store.get_rightmost_leaf()
+    }
+}
+
+impl TreeWriter for FlakyTreeStore {
+    fn write_node_batch(&self, node_batch: &NodeBatch) -> Result<()> {
+        if self.writes_left.load(Ordering::SeqCst) == 0 {
+            bail!("out of writes");
+        }
+        self.writes_left.fetch_sub(1, Ordering::SeqCst);
+        self.store.write_node_batch(node_batch)
+    }
+}
+
+/// Writes `kvs` to a new tree hashed with `H`, at version 0.
+fn put_tree<H: SimpleHasher>(kvs: &BTreeMap<KeyHash, OwnedValue>) -> (MockTreeStore, RootHash) {
+    let db = MockTreeStore::default();
+    let (root_hash, batch) = JellyfishMerkleTree::<_, H>::new(&db)
+        .put_value_set(kvs.iter().map(|(k, v)| (*k, Some(v.clone()))), 0)
+        .unwrap();
+    db.write_tree_update_batch(batch).unwrap();
+    (db, root_hash)
+}
+
+fn test_rehash(
+    kvs: BTreeMap<KeyHash, OwnedValue>,
+    updates: BTreeMap<KeyHash, Option<OwnedValue>>,
+    chunk_size: usize,
+) {
+    let (db, old_root_hash) = put_tree::<Sha256>(&kvs);
+    let (new_old_root_hash, batch) = JellyfishMerkleTree::<_, Sha256>::new(&db)
+        .put_value_set(updates.clone(), 1)
+        .unwrap();
+    db.write_tree_update_batch(batch).unwrap();
+    let db = Arc::new(db);
+
+    let mut new_kvs = kvs.clone();
+    for (key, value) in updates {
+        match value {
+            Some(value) => new_kvs.insert(key, value),
+            None => new_kvs.remove(&key),
+        };
+    }
+
+    for (version, kvs, old_root_hash) in [(0, kvs, old_root_hash), (1, new_kvs, new_old_root_hash)]
+    {
+        let store = Arc::new(MockTreeStore::default());
+        let summary = rehash_tree::<_, Sha256, _, Blake3Hasher>(
+            db.clone(),
+            version,
+            store.clone(),
+            chunk_size,
+        )
+        .unwrap();
+        // The rehashed tree is the tree built from scratch with the new hasher.
+        let (_, root_hash) = put_tree::<Blake3Hasher>(&kvs);
+        assert_eq!(summary.old_root_hash, old_root_hash);
+        assert_eq!(summary.root_hash, root_hash);
+        assert_eq!(summary.num_leaves, kvs.len());
+
+        let tree = JellyfishMerkleTree::<_, Blake3Hasher>::new(&*store);
+        assert_eq!(tree.get_root_hash(version).unwrap(), root_hash);
+        for (key, value) in &kvs {
+            let (found, proof) = tree.get_with_proof(*key, version).unwrap();
+            assert_eq!(found.as_ref(), Some(value));
+            proof.verify_existence(root_hash, *key, value).unwrap();
+        }
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(10))]
+
+    #[test]
+    fn test_rehash_sha256_to_blake3(
+        kvs in btree_map(any::<KeyHash>(), any::<OwnedValue>(), 1..100),
+        updates in btree_map(any::<KeyHash>(), any::<Option<OwnedValue>>(), 0..20),
+        chunk_size in 1..50usize,
+    ) {
+        test_rehash(kvs, updates, chunk_size);
+    }
+
+    #[test]
+    fn test_online_migration_to_compact_encoding(
+        value_sets in vec(vec(any::<(KeyHash, OwnedValue)>(), 1..10), 2..8),
+        batch_size in 1..20usize,
+    ) {
+        test_online_migration(value_sets, batch_size);
+    }
+}
+
+#[test]
+fn test_migration_rejects_empty_batches() {
+    let store = EncodedTreeStore::new(NodeEncodingVersion::Compact);
+    let mut migration = NodeEncodingMigration::new(NodeEncodingVersion::Compact);
+    assert!(migration.step(&store, 0).is_err());
+
+    let step = migration.step(&store, 1).unwrap();
+    assert_eq!(step.nodes_scanned, 0);
+    assert!(step.done);
+    assert_eq!(migration.cursor(), None);
+}
+
+#[test]
+fn test_rehash_empty_tree() {
+    let db = MockTreeStore::default();
+    let (old_root_hash, batch) = JellyfishMerkleTree::<_, Sha256>::new(&db)
+        .put_value_set(Vec::new(), 0)
+        .unwrap();
+    db.write_tree_update_batch(batch).unwrap();
+
+    let store = Arc::new(MockTreeStore::default());
+    let summary =
+        rehash_tree::<_, Sha256, _, Blake3Hasher>(Arc::new(db), 0, store.clone(), 10).unwrap();
+    assert_eq!(
+        summary,
+        RehashSummary {
+            old_root_hash,
+            root_hash: RootHash(Blake3Hasher::SPARSE_MERKLE_PLACEHOLDER_HASH),
+            num_leaves: 0,
+            num_nodes_written: 1,
+        }
+    );
+    assert_eq!(
+        JellyfishMerkleTree::<_, Blake3Hasher>::new(&*store)
+            .get_root_hash(0)
+            .unwrap(),
+        summary.root_hash
+    );
+    assert!(
+        rehash_tree::<_, Sha256, MockTreeStore, Blake3Hasher>(store, 1, Arc::default(), 10)
+            .is_err()
+    );
+}
+
+#[test]
+fn test_rehash_skipped_version() {
+    // Version 3 is skipped, so it holds the tree of version 0.
+    let db = MockTreeStore::default();
+    let tree = JellyfishMerkleTree::<_, Sha256>::new(&db);
+    let key = KeyHash([1; 32]);
+    for (base_version, version) in [(None, 0), (Some(0), 5)] {
+        let (_root_hash, batch) = tree
+            .put_value_set_after(
+                alloc::vec![(key, Some(alloc::vec![version as u8]))],
+                base_version,
+                version,
+            )
+            .unwrap();
+        db.write_tree_update_batch(batch).unwrap();
+    }
+    let old_root_hash = tree.get_root_hash(0).unwrap();
+
+    let store = Arc::new(MockTreeStore::default());
+    let summary =
+        rehash_tree::<_, Sha256, _, Blake3Hasher>(Arc::new(db), 3, store.clone(), 10).unwrap();
+    assert_eq!(summary.old_root_hash, old_root_hash);
+    assert_eq!(summary.num_leaves, 1);
+    // The new tree is written at the version which was asked for.
+    let new_tree = JellyfishMerkleTree::<_, Blake3Hasher>::new(&*store);
+    assert_eq!(new_tree.get_root_hash(3).unwrap(), summary.root_hash);
+    assert_eq!(new_tree.get(key, 3).unwrap(), Some(alloc::vec![0]));
+}
+
+#[test]
+fn test_rehash_resumes_after_error() {
+    let kvs: BTreeMap<_, _> = (0..100u8)
+        .map(|i| (KeyHash::with::<Sha256>([i]), alloc::vec![i]))
+        .collect();
+    let (db, _) = put_tree::<Sha256>(&kvs);
+    let db = Arc::new(db);
+    let store = Arc::new(FlakyTreeStore {
+        store: MockTreeStore::default(),
+        writes_left: AtomicUsize::new(3),
+    });
+
+    assert!(rehash_tree::<_, Sha256, _, Blake3Hasher>(db.clone(), 0, store.clone(), 10).is_err());
+    store.writes_left.store(usize::MAX, Ordering::SeqCst);
+    let summary = rehash_tree::<_, Sha256, _, Blake3Hasher>(db, 0, store.clone(), 10).unwrap();
+    assert_eq!(summary.root_hash, put_tree::<Blake3Hasher>(&kvs).1);
+    assert_eq!(summary.num_leaves, kvs.len());
+}
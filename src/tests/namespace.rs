@@ -0,0 +1,114 @@
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+
+use proptest::{collection::btree_map, prelude::*};
+use sha2::Sha256;
+
+use crate::{
+    mock::MockTreeStore,
+    namespace::{Namespace, NamespacedStore},
+    restore::{JellyfishMerkleRestore, StateSnapshotReceiver},
+    JellyfishMerkleIterator, JellyfishMerkleTree, KeyHash, OwnedValue, SimpleHasher,
+};
+
+/// Puts every value set in `value_sets` at a version of its own in the tree in `namespace`, and
+/// returns the root hashes.
+fn put_versions<H: SimpleHasher>(
+    db: &MockTreeStore,
+    namespace: &Namespace,
+    value_sets: &[Vec<(KeyHash, OwnedValue)>],
+) -> Vec<crate::RootHash> {
+    let store = NamespacedStore::new(db, namespace.clone());
+    let tree = JellyfishMerkleTree::<_, H>::new(&store);
+    let mut root_hashes = Vec::new();
+    for (version, value_set) in value_sets.iter().enumerate() {
+        let (root_hash, batch) = tree
+            .put_value_set(
+                value_set.iter().map(|(k, v)| (*k, Some(v.clone()))),
+                version as u64,
+            )
+            .unwrap();
+        db.write_tree_update_batch_in(namespace, batch).unwrap();
+        root_hashes.push(root_hash);
+    }
+    root_hashes
+}
+
+fn test_namespaces_are_independent<H: SimpleHasher>(
+    main_sets: Vec<Vec<(KeyHash, OwnedValue)>>,
+    module_sets: Vec<Vec<(KeyHash, OwnedValue)>>,
+) {
+    let main = Namespace::default();
+    let module = Namespace::new("module");
+
+    // Each tree has the same root hashes as it would have alone in a store.
+    let db = MockTreeStore::default();
+    let main_root_hashes = put_versions::<H>(&db, &main, &main_sets);
+    let module_root_hashes = put_versions::<H>(&db, &module, &module_sets);
+    assert_eq!(
+        main_root_hashes,
+        put_versions::<H>(&MockTreeStore::default(), &main, &main_sets)
+    );
+    assert_eq!(
+        module_root_hashes,
+        put_versions::<H>(&MockTreeStore::default(), &module, &module_sets)
+    );
+
+    // Pruning every old version of the main tree leaves the module tree fully readable.
+    let module_num_nodes = db.num_nodes_in(&module);
+    db.purge_stale_nodes_in(&main, main_sets.len() as u64 - 1)
+        .unwrap();
+    assert_eq!(db.num_nodes_in(&module), module_num_nodes);
+    let module_store = NamespacedStore::new(&db, module.clone());
+    let module_tree = JellyfishMerkleTree::<_, H>::new(&module_store);
+    for (version, root_hash) in module_root_hashes.iter().enumerate() {
+        assert_eq!(
+            module_tree.get_root_hash(version as u64).unwrap(),
+            *root_hash
+        );
+    }
+
+    // The module tree can be restored next to another tree in a new store.
+    let last_version = module_sets.len() as u64 - 1;
+    let entries: Vec<_> = JellyfishMerkleIterator::new(
+        Arc::new(NamespacedStore::new(&db, module.clone())),
+        last_version,
+        KeyHash([0; 32]),
+    )
+    .unwrap()
+    .collect::<anyhow::Result<_>>()
+    .unwrap();
+    let proof = module_tree
+        .get_range_proof(entries.last().unwrap().0, last_version)
+        .unwrap();
+    let restore_db = Arc::new(MockTreeStore::default());
+    put_versions::<H>(&restore_db, &main, &main_sets);
+    let mut restore = JellyfishMerkleRestore::<H>::new(
+        Arc::new(NamespacedStore::new(restore_db.clone(), module.clone())),
+        last_version,
+        *module_root_hashes.last().unwrap(),
+    )
+    .unwrap();
+    restore.add_chunk(entries, proof).unwrap();
+    restore.finish().unwrap();
+    let restored_store = NamespacedStore::new(restore_db.as_ref(), module);
+    let restored_tree = JellyfishMerkleTree::<_, H>::new(&restored_store);
+    assert_eq!(
+        restored_tree.get_root_hash(last_version).unwrap(),
+        *module_root_hashes.last().unwrap()
+    );
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(10))]
+
+    #[test]
+    fn test_namespaces_are_independent_sha256(
+        main_sets in prop::collection::vec(btree_map(any::<KeyHash>(), any::<OwnedValue>(), 1..10), 1..5),
+        module_sets in prop::collection::vec(btree_map(any::<KeyHash>(), any::<OwnedValue>(), 1..10), 1..5),
+    ) {
+        let into_vec = |sets: Vec<BTreeMap<_, _>>| -> Vec<Vec<_>> {
+            sets.into_iter().map(|set| set.into_iter().collect()).collect()
+        };
+        test_namespaces_are_independent::<Sha256>(into_vec(main_sets), into_vec(module_sets));
+    }
+}
@@ -6,7 +6,7 @@ use alloc::{format, vec};
 use proptest::prelude::*;
 
 use crate::types::nibble::{
-    nibble_path::{arb_internal_nibble_path, skip_common_prefix, NibblePath},
+    nibble_path::{arb_internal_nibble_path, skip_common_prefix, NibblePath, NibblePathError},
     Nibble,
 };
 
@@ -50,6 +50,36 @@ fn test_empty_nibble_path() {
     NibblePath::new_odd(vec![]);
 }
 
+#[test]
+fn test_nibble_path_try_from_nibbles() {
+    assert_eq!(
+        NibblePath::try_from_nibbles(vec![0x12, 0x34, 0x50], 5).unwrap(),
+        NibblePath::new_odd(vec![0x12, 0x34, 0x50])
+    );
+    assert_eq!(
+        NibblePath::try_from_nibbles(vec![0x12, 0x34], 4).unwrap(),
+        NibblePath::new(vec![0x12, 0x34])
+    );
+    assert!(NibblePath::try_from_nibbles(vec![], 0).unwrap().is_empty());
+    NibblePath::try_from_nibbles(vec![0xff; 32], 64).unwrap();
+
+    assert_eq!(
+        NibblePath::try_from_nibbles(vec![0x12, 0x34, 0x56], 5),
+        Err(NibblePathError::NonZeroPadding)
+    );
+    assert_eq!(
+        NibblePath::try_from_nibbles(vec![0x12, 0x34, 0x50], 4),
+        Err(NibblePathError::LengthMismatch {
+            num_nibbles: 4,
+            num_bytes: 3
+        })
+    );
+    assert_eq!(
+        NibblePath::try_from_nibbles(vec![0; 33], 65),
+        Err(NibblePathError::TooManyNibbles { num_nibbles: 65 })
+    );
+}
+
 #[test]
 fn test_get_nibble() {
     let bytes = vec![0x12, 0x34];
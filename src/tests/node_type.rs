@@ -11,7 +11,7 @@ use rand::rngs::OsRng;
 use sha2::Sha256;
 
 use crate::{
-    node_type::{Child, Children, InternalNode, Node, NodeKey, NodeType},
+    node_type::{Child, Children, InternalNode, Node, NodeEncodingVersion, NodeKey, NodeType},
     storage::TreeReader,
     types::{
         nibble::{nibble_path::NibblePath, Nibble},
@@ -120,6 +120,129 @@ proptest! {
         let deserialized = InternalNode::deserialize(&mut vec.as_ref()).unwrap();
         assert_eq!(deserialized, input.clone());
     }
+
+    #[test]
+    fn test_node_encoding_roundtrip(input in any::<InternalNode>(), leaf in any::<(KeyHash, ValueHash)>()) {
+        let encoded = Node::Internal(input.clone()).encode();
+        assert_eq!(Node::decode(&encoded).unwrap(), Node::Internal(input.clone()));
+        assert!(encoded.len() < borsh::to_vec(&Node::Internal(input.clone())).unwrap().len());
+
+        for node in [Node::Null, Node::new_leaf(leaf.0, leaf.1), Node::Internal(input)] {
+            for version in [NodeEncodingVersion::Borsh, NodeEncodingVersion::Compact] {
+                let encoded = node.encode_as(version);
+                assert_eq!(NodeEncodingVersion::of(&encoded).unwrap(), version);
+                assert_eq!(Node::decode(&encoded).unwrap(), node);
+            }
+        }
+    }
+}
+
+/// Pins the node encoding, so that nodes written by earlier releases stay readable.
+#[test]
+fn test_node_encoding_is_stable() {
+    assert_eq!(Node::Null.encode(), [0x10]);
+
+    let leaf = Node::new_leaf(KeyHash([0xaa; 32]), ValueHash([0xbb; 32]));
+    let expected: Vec<u8> = [&[0x12][..], &[0xaa; 32], &[0xbb; 32]].concat();
+    assert_eq!(leaf.encode(), expected);
+    assert_eq!(Node::decode(&expected).unwrap(), leaf);
+
+    let mut children = Children::new();
+    children.insert(Nibble::from(1), Child::new([0x11; 32], 5, NodeType::Leaf));
+    children.insert(
+        Nibble::from(3),
+        Child::new([0x22; 32], 300, NodeType::Internal { leaf_count: 2 }),
+    );
+    let internal = Node::new_internal(children);
+    let expected: Vec<u8> = [
+        // Tag, existence bitmap and leaf bitmap.
+        &[0x11, 0x0a, 0x00, 0x02, 0x00][..],
+        // The leaf child at nibble 1: its version and hash.
+        &[0x05],
+        &[0x11; 32],
+        // The internal child at nibble 3: its version, hash and leaf count.
+        &[0xac, 0x02],
+        &[0x22; 32],
+        &[0x02],
+    ]
+    .concat();
+    assert_eq!(internal.encode(), expected);
+    assert_eq!(Node::decode(&expected).unwrap(), internal);
+
+    // Nodes written before encodings were versioned are borsh-encoded, and start with the index of
+    // their variant.
+    assert_eq!(
+        Node::decode(&borsh::to_vec(&internal).unwrap()).unwrap(),
+        internal
+    );
+    assert_eq!(
+        NodeEncodingVersion::of(&borsh::to_vec(&leaf).unwrap()).unwrap(),
+        NodeEncodingVersion::Borsh
+    );
+}
+
+#[test]
+fn test_node_decoding_rejects_malformed_bytes() {
+    use crate::storage::NodeDecodeError;
+
+    let mut children = Children::new();
+    children.insert(Nibble::from(0), Child::new([1; 32], 0, NodeType::Leaf));
+    children.insert(Nibble::from(2), Child::new([2; 32], 1, NodeType::Leaf));
+    let bytes = Node::new_internal(children).encode();
+
+    assert_eq!(Node::decode(&[]), Err(NodeDecodeError::EmptyInput));
+    assert_eq!(
+        Node::decode(&[0x20]),
+        Err(NodeDecodeError::UnsupportedVersion { version: 2 })
+    );
+    assert!(matches!(
+        Node::decode(&[0x01]),
+        Err(NodeDecodeError::MalformedBorsh { .. })
+    ));
+    assert_eq!(
+        Node::decode(&[0x03]),
+        Err(NodeDecodeError::UnknownTag { tag: 0x03 })
+    );
+    assert_eq!(
+        Node::decode(&[0x13]),
+        Err(NodeDecodeError::UnknownTag { tag: 0x13 })
+    );
+    assert_eq!(
+        Node::decode(&bytes[..bytes.len() - 1]),
+        Err(NodeDecodeError::Truncated)
+    );
+    assert_eq!(
+        Node::decode(&[bytes.as_slice(), &[0]].concat()),
+        Err(NodeDecodeError::TrailingBytes { len: 1 })
+    );
+    assert_eq!(
+        Node::decode(&[0x11, 0x00, 0x00, 0x00, 0x00]),
+        Err(NodeDecodeError::NoChildren)
+    );
+    assert_eq!(
+        Node::decode(&[0x11, 0x05, 0x00, 0x03, 0x00]),
+        Err(NodeDecodeError::ExtraLeaves {
+            existence_bitmap: 0x0005,
+            leaf_bitmap: 0x0003,
+        })
+    );
+    assert_eq!(
+        Node::decode(&[0x11, 0x01, 0x00, 0x01, 0x00]),
+        Err(NodeDecodeError::OnlyChildIsLeaf)
+    );
+
+    // The version of the first child, 0, encoded on two bytes instead of one.
+    let overlong_varint = [&bytes[..5], &[0x80, 0x00], &bytes[6..]].concat();
+    assert_eq!(
+        Node::decode(&overlong_varint),
+        Err(NodeDecodeError::InvalidVarint)
+    );
+    // A version of 2^64.
+    let overflowing_varint = [&bytes[..5], &[0x80; 9], &[0x02], &bytes[6..]].concat();
+    assert_eq!(
+        Node::decode(&overflowing_varint),
+        Err(NodeDecodeError::InvalidVarint)
+    );
 }
 
 #[test]
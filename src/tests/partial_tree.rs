@@ -0,0 +1,99 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use proptest::{collection::btree_map, prelude::*};
+use sha2::Sha256;
+
+use crate::{
+    proof::{PartialTree, VerificationError},
+    tests::helper::init_mock_db,
+    JellyfishMerkleTree, KeyHash, OwnedValue, SimpleHasher, ValueHash,
+};
+
+fn test_partial_tree_matches_full_tree<H: SimpleHasher>(
+    kvs: BTreeMap<KeyHash, OwnedValue>,
+    new_keys: Vec<KeyHash>,
+    updates: Vec<(prop::sample::Index, Option<OwnedValue>)>,
+) {
+    let (db, version) = init_mock_db::<H>(&kvs.clone().into_iter().collect());
+    let tree = JellyfishMerkleTree::<_, H>::new(&db);
+    let root_hash = tree.get_root_hash(version).unwrap();
+
+    // Prove a few existing keys and a few keys which are not in the tree.
+    let keys: Vec<KeyHash> = kvs.keys().step_by(7).cloned().chain(new_keys).collect();
+    let proofs: Vec<_> = keys
+        .iter()
+        .map(|key| (*key, tree.get_with_proof(*key, version).unwrap().1))
+        .collect();
+    let mut partial_tree =
+        PartialTree::<H>::new(root_hash, proofs.iter().map(|(key, proof)| (*key, proof))).unwrap();
+    assert_eq!(partial_tree.root_hash(), root_hash);
+    for key in &keys {
+        assert_eq!(
+            partial_tree.get(*key).unwrap(),
+            kvs.get(key).map(ValueHash::with::<H>)
+        );
+    }
+
+    let value_set: BTreeMap<_, _> = updates
+        .into_iter()
+        .map(|(index, value)| (*index.get(&keys), value))
+        .collect();
+    for (key, value) in &value_set {
+        match value {
+            Some(value) => partial_tree.put(*key, value).unwrap(),
+            None => partial_tree.delete(*key).unwrap(),
+        }
+    }
+    let (new_root_hash, _batch) = tree
+        .put_value_set(value_set.into_iter().collect::<Vec<_>>(), version + 1)
+        .unwrap();
+    assert_eq!(partial_tree.root_hash(), new_root_hash);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(100))]
+
+    #[test]
+    fn test_partial_tree_matches_full_tree_sha256(
+        kvs in btree_map(any::<KeyHash>(), any::<OwnedValue>(), 1..100),
+        new_keys in prop::collection::vec(any::<KeyHash>(), 1..5),
+        updates in prop::collection::vec(
+            (any::<prop::sample::Index>(), any::<Option<OwnedValue>>()),
+            1..20,
+        ),
+    ) {
+        test_partial_tree_matches_full_tree::<Sha256>(kvs, new_keys, updates);
+    }
+}
+
+#[test]
+fn test_partial_tree_rejects_uncovered_keys() {
+    let kvs: BTreeMap<KeyHash, OwnedValue> = (0u8..16)
+        .map(|i| (KeyHash([i << 4; 32]), alloc::vec![i]))
+        .collect();
+    let (db, version) = init_mock_db::<Sha256>(&kvs.clone().into_iter().collect());
+    let tree = JellyfishMerkleTree::<_, Sha256>::new(&db);
+    let root_hash = tree.get_root_hash(version).unwrap();
+
+    let proven_key = KeyHash([0; 32]);
+    let (_value, proof) = tree.get_with_proof(proven_key, version).unwrap();
+    let mut partial_tree = PartialTree::<Sha256>::new(root_hash, [(proven_key, &proof)]).unwrap();
+
+    let other_key = KeyHash([0xf0; 32]);
+    let not_covered = VerificationError::KeyNotCovered {
+        key_hash: other_key,
+    };
+    assert_eq!(partial_tree.get(other_key), Err(not_covered.clone()));
+    assert_eq!(
+        partial_tree.put(other_key, b"value"),
+        Err(not_covered.clone())
+    );
+    assert_eq!(partial_tree.delete(other_key), Err(not_covered));
+
+    // Proofs against another root are rejected.
+    assert!(PartialTree::<Sha256>::new(
+        tree.get_root_hash(version - 1).unwrap(),
+        [(proven_key, &proof)]
+    )
+    .is_err());
+}
@@ -0,0 +1,192 @@
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+
+use proptest::{collection::btree_map, prelude::*};
+
+use crate::{
+    mock::MockTreeStore,
+    poseidon::{permutation, FieldElement, PoseidonHasher},
+    proof::{
+        SparseMerkleNode, SparseMerkleProof, UpdateMerkleProof, VerificationError,
+        INTERNAL_DOMAIN_SEPARATOR, LEAF_DOMAIN_SEPARATOR,
+    },
+    KeyHash, OwnedValue, PoseidonJmt, SimpleHasher, ValueHash,
+};
+
+fn from_hex(hex: &str) -> [u8; 32] {
+    hex::decode(hex).unwrap().try_into().unwrap()
+}
+
+#[test]
+fn test_permutation_matches_reference() {
+    // The test vector of the reference implementation for this instance.
+    let state = permutation([0, 1, 2].map(FieldElement::from_u128));
+    assert_eq!(
+        state.map(|element| element.to_bytes()),
+        [
+            "115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a",
+            "0fca49b798923ab0239de1c9e7a4a9a2210312b6a2f616d18b5a87f9b628ae29",
+            "0e7ae82e40091e63cbd4f16a6d16310b3729d4b6e138fcf54110e2867045a30c",
+        ]
+        .map(from_hex)
+    );
+}
+
+#[test]
+fn test_field_element_encoding() {
+    let modulus = from_hex("30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001");
+    assert_eq!(FieldElement::from_canonical_bytes(&modulus), None);
+    assert_eq!(
+        FieldElement::from_bytes_reduced(&modulus),
+        FieldElement::ZERO
+    );
+    assert_eq!(
+        FieldElement::from_bytes_reduced(&[0xff; 32]).to_bytes(),
+        from_hex("0e0a77c19a07df2f666ea36f7879462e36fc76959f60cd29ac96341c4ffffffa")
+    );
+    let element = FieldElement::from_bytes_reduced(&[0x12; 32]);
+    assert_eq!(
+        FieldElement::from_canonical_bytes(&element.to_bytes()),
+        Some(element)
+    );
+}
+
+#[test]
+fn test_hashes_match_specification() {
+    // Computed independently from the specification in the module documentation.
+    let key_hash = KeyHash::with::<PoseidonHasher>(b"hello");
+    let value_hash = ValueHash::with::<PoseidonHasher>(b"world");
+    assert_eq!(
+        key_hash.0,
+        from_hex("15bfa440644bf3cb40238ab6a54957b004b14cfb08a750358860aa53695666ed")
+    );
+    assert_eq!(
+        value_hash.0,
+        from_hex("0c370b9d32f8ea96b8cc1302d4331a4fa6c417b3d4ecf138f6e2feb48f5f522c")
+    );
+    assert_eq!(
+        PoseidonHasher::hash([]),
+        from_hex("2b4ade70f43877b79b0d6bdddd0725a141858f241aa1db482f11c99d89c6548e")
+    );
+    let leaf_hash = PoseidonHasher::hash_leaf(LEAF_DOMAIN_SEPARATOR, &key_hash.0, &value_hash.0);
+    assert_eq!(
+        leaf_hash,
+        from_hex("0f8ef2685211fbc88a219b76e78441612424564531071e99a890e07148d6fa96")
+    );
+    assert_eq!(
+        PoseidonHasher::hash_internal(
+            INTERNAL_DOMAIN_SEPARATOR,
+            &PoseidonHasher::SPARSE_MERKLE_PLACEHOLDER_HASH,
+            &leaf_hash
+        ),
+        from_hex("206a3b075759024444f754afdc4fb8be847ec72655febf6140d2bca8b1c090c9")
+    );
+}
+
+#[test]
+fn test_leaf_hash_commits_to_every_key_bit() {
+    // Key hashes which are equal modulo the field order still have different leaves.
+    let value_hash = [0; 32];
+    let key_hash = [0; 32];
+    let modulus = from_hex("30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001");
+    assert_ne!(
+        PoseidonHasher::hash_leaf(LEAF_DOMAIN_SEPARATOR, &key_hash, &value_hash),
+        PoseidonHasher::hash_leaf(LEAF_DOMAIN_SEPARATOR, &modulus, &value_hash)
+    );
+}
+
+#[test]
+fn test_empty_root_is_canonical() {
+    let db = MockTreeStore::default();
+    let (root_hash, _batch) = PoseidonJmt::new(&db).put_value_set(Vec::new(), 0).unwrap();
+    assert_eq!(root_hash.0, PoseidonHasher::SPARSE_MERKLE_PLACEHOLDER_HASH);
+    assert_eq!(
+        FieldElement::from_canonical_bytes(&root_hash.0),
+        Some(FieldElement::ZERO)
+    );
+}
+
+#[test]
+fn test_proofs_reject_non_canonical_siblings() {
+    let db = MockTreeStore::default();
+    let tree = PoseidonJmt::new(&db);
+    let keys = [KeyHash([0x00; 32]), KeyHash([0x80; 32])];
+    let (root_hash, batch) = tree
+        .put_value_set(keys.iter().map(|key| (*key, Some(vec![1]))), 0)
+        .unwrap();
+    db.write_tree_update_batch(batch).unwrap();
+    let (_, proof) = tree.get_with_proof(keys[0], 0).unwrap();
+    proof.verify_existence(root_hash, keys[0], [1]).unwrap();
+
+    // Adding the modulus to the sibling hash, which is less than it, gives 32 bytes which reduce
+    // to the same element, and so hash alike.
+    let modulus = from_hex("30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001");
+    let sibling_hash = proof.siblings()[0].hash::<PoseidonHasher>();
+    let mut non_canonical = [0; 32];
+    let mut carry = 0;
+    for i in (0..32).rev() {
+        let sum = u16::from(sibling_hash[i]) + u16::from(modulus[i]) + carry;
+        non_canonical[i] = sum as u8;
+        carry = sum >> 8;
+    }
+    assert_eq!(carry, 0);
+    assert!(!PoseidonHasher::is_canonical_hash(&non_canonical));
+    let leaf_hash = proof.leaf().unwrap().hash::<PoseidonHasher>();
+    assert_eq!(
+        PoseidonHasher::hash_internal(INTERNAL_DOMAIN_SEPARATOR, &leaf_hash, &non_canonical),
+        root_hash.0
+    );
+
+    let malleated = SparseMerkleProof::<PoseidonHasher>::new(
+        proof.leaf(),
+        vec![SparseMerkleNode::Untyped(non_canonical)],
+    );
+    assert_eq!(
+        malleated.verify_existence(root_hash, keys[0], [1]),
+        Err(VerificationError::NonCanonicalHash)
+    );
+    let (new_root_hash, _batch) = tree
+        .put_value_set(vec![(keys[0], Some(vec![2]))], 1)
+        .unwrap();
+    assert_eq!(
+        UpdateMerkleProof::new(vec![malleated]).verify_update(
+            root_hash,
+            new_root_hash,
+            vec![(keys[0], Some(vec![2]))]
+        ),
+        Err(VerificationError::NonCanonicalHash)
+    );
+}
+
+fn test_poseidon_proofs(kvs: BTreeMap<KeyHash, OwnedValue>, absent: Vec<KeyHash>) {
+    let db = MockTreeStore::default();
+    let tree = PoseidonJmt::new(&db);
+    let (root_hash, batch) = tree
+        .put_value_set(kvs.iter().map(|(k, v)| (*k, Some(v.clone()))), 0)
+        .unwrap();
+    db.write_tree_update_batch(batch).unwrap();
+    assert!(FieldElement::from_canonical_bytes(&root_hash.0).is_some());
+
+    for (key, value) in &kvs {
+        let (found, proof) = tree.get_with_proof(*key, 0).unwrap();
+        assert_eq!(found.as_ref(), Some(value));
+        proof.verify_existence(root_hash, *key, value).unwrap();
+        assert!(proof.verify_existence(root_hash, *key, b"other").is_err());
+    }
+    for key in absent.into_iter().filter(|key| !kvs.contains_key(key)) {
+        let (found, proof) = tree.get_with_proof(key, 0).unwrap();
+        assert_eq!(found, None);
+        proof.verify_nonexistence(root_hash, key).unwrap();
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(10))]
+
+    #[test]
+    fn test_poseidon_proofs_verify(
+        kvs in btree_map(any::<KeyHash>(), any::<OwnedValue>(), 1..20),
+        absent in prop::collection::vec(any::<KeyHash>(), 1..5),
+    ) {
+        test_poseidon_proofs(kvs, absent);
+    }
+}
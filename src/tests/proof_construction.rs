@@ -0,0 +1,204 @@
+use alloc::{collections::BTreeMap, string::ToString, vec, vec::Vec};
+
+use proptest::{collection::btree_map, prelude::*};
+use sha2::Sha256;
+
+use crate::{
+    proof::{
+        envelope::{EnvelopeError, EnvelopedProof},
+        CompactProofError, MalformedProofError, SparseMerkleInternalNode, SparseMerkleLeafNode,
+        SparseMerkleNode, SparseMerkleProof, SparseMerkleRangeProof, VerificationError,
+    },
+    tests::helper::init_mock_db,
+    JellyfishMerkleTree, KeyHash, OwnedValue, RootHash, SimpleHasher, ValueHash,
+};
+
+fn test_tree_proofs_can_be_rebuilt<H: SimpleHasher>(
+    kvs: BTreeMap<KeyHash, OwnedValue>,
+    absent: Vec<KeyHash>,
+) {
+    let (db, version) = init_mock_db::<H>(&kvs.clone().into_iter().collect());
+    let tree = JellyfishMerkleTree::<_, H>::new(&db);
+    let root_hash = tree.get_root_hash(version).unwrap();
+
+    for key in kvs.keys().chain(&absent) {
+        let (value, proof) = tree.get_with_proof(*key, version).unwrap();
+        for proof in [proof.clone(), proof.compact()] {
+            let rebuilt =
+                SparseMerkleProof::<H>::try_new(proof.leaf(), proof.siblings().to_vec()).unwrap();
+            assert_eq!(rebuilt, proof);
+            rebuilt.verify(root_hash, *key, value.as_ref()).unwrap();
+        }
+    }
+
+    for key in kvs.keys() {
+        let proof = tree.get_range_proof(*key, version).unwrap();
+        let rebuilt =
+            SparseMerkleRangeProof::<H>::try_new(proof.right_siblings().to_vec()).unwrap();
+        assert_eq!(rebuilt, proof);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(10))]
+
+    #[test]
+    fn test_tree_proofs_can_be_rebuilt_sha256(
+        kvs in btree_map(any::<KeyHash>(), any::<OwnedValue>(), 1..100),
+        absent in proptest::collection::vec(any::<KeyHash>(), 10),
+    ) {
+        let absent = absent.into_iter().filter(|key| !kvs.contains_key(key)).collect();
+        test_tree_proofs_can_be_rebuilt::<Sha256>(kvs, absent);
+    }
+}
+
+#[test]
+fn test_proof_construction_rejects_malformed_parts() {
+    let leaf = SparseMerkleLeafNode::new(KeyHash([0x00; 32]), ValueHash([1; 32]));
+    let try_new = |leaf, siblings| SparseMerkleProof::<Sha256>::try_new(leaf, siblings);
+
+    assert_eq!(
+        try_new(Some(leaf), vec![SparseMerkleNode::Untyped([2; 32]); 257]),
+        Err(MalformedProofError::TooManySiblings { num_siblings: 257 })
+    );
+    assert_eq!(
+        try_new(
+            None,
+            vec![SparseMerkleNode::Null, SparseMerkleNode::Untyped([2; 32])]
+        ),
+        Err(MalformedProofError::PlaceholderAtBottom)
+    );
+    assert_eq!(
+        try_new(
+            Some(leaf),
+            vec![
+                SparseMerkleNode::Untyped([2; 32]),
+                SparseMerkleNode::Untyped(Sha256::SPARSE_MERKLE_PLACEHOLDER_HASH),
+            ]
+        ),
+        Err(MalformedProofError::UntypedPlaceholder { index: 1 })
+    );
+
+    // With two siblings, the leaf is at depth 2: the lowest sibling must branch off its path at
+    // the second bit, and the highest one at the first bit.
+    let sibling = |key: u8| {
+        SparseMerkleNode::Leaf(SparseMerkleLeafNode::new(
+            KeyHash([key; 32]),
+            ValueHash([3; 32]),
+        ))
+    };
+    try_new(Some(leaf), vec![sibling(0x40), sibling(0x80)]).unwrap();
+    assert_eq!(
+        try_new(Some(leaf), vec![sibling(0x80), sibling(0x40)]),
+        Err(MalformedProofError::MisplacedLeafSibling { index: 0 })
+    );
+    assert_eq!(
+        try_new(Some(leaf), vec![sibling(0x40), sibling(0x00)]),
+        Err(MalformedProofError::MisplacedLeafSibling { index: 1 })
+    );
+    // Without a leaf, the path is only known from the key being proved.
+    try_new(None, vec![sibling(0x80), sibling(0x40)]).unwrap();
+
+    assert_eq!(
+        SparseMerkleRangeProof::<Sha256>::try_new(vec![SparseMerkleNode::Null; 257]),
+        Err(MalformedProofError::TooManySiblings { num_siblings: 257 })
+    );
+    assert_eq!(
+        SparseMerkleRangeProof::<Sha256>::try_new(vec![
+            SparseMerkleNode::Null,
+            SparseMerkleNode::Untyped(Sha256::SPARSE_MERKLE_PLACEHOLDER_HASH),
+        ]),
+        Err(MalformedProofError::UntypedPlaceholder { index: 1 })
+    );
+}
+
+#[test]
+fn test_decoders_reject_malformed_proofs() {
+    // The proof of the leaf at depth 2 whose siblings are swapped, which only the crate itself
+    // can make without checking it.
+    let leaf = SparseMerkleLeafNode::new(KeyHash([0x00; 32]), ValueHash([1; 32]));
+    let sibling = |key: u8| {
+        SparseMerkleNode::Leaf(SparseMerkleLeafNode::new(
+            KeyHash([key; 32]),
+            ValueHash([3; 32]),
+        ))
+    };
+    let proof = SparseMerkleProof::<Sha256>::new(Some(leaf), vec![sibling(0x80), sibling(0x40)]);
+    let reason = MalformedProofError::MisplacedLeafSibling { index: 0 }.to_string();
+
+    let json = serde_json::to_string(&proof).unwrap();
+    let error = serde_json::from_str::<SparseMerkleProof<Sha256>>(&json).unwrap_err();
+    assert!(error.to_string().contains(&reason));
+    let bytes = borsh::to_vec(&proof).unwrap();
+    let error = borsh::from_slice::<SparseMerkleProof<Sha256>>(&bytes).unwrap_err();
+    assert_eq!(error.to_string(), reason);
+    assert_eq!(
+        SparseMerkleProof::<Sha256>::from_envelope(&proof.to_envelope()),
+        Err(EnvelopeError::MalformedProof { reason })
+    );
+    assert_eq!(
+        SparseMerkleProof::<Sha256>::from_compact_bytes(&proof.to_compact_bytes()),
+        Err(CompactProofError::MalformedProof(
+            MalformedProofError::MisplacedLeafSibling { index: 0 }
+        ))
+    );
+
+    let range_proof = SparseMerkleRangeProof::<Sha256>::new(vec![SparseMerkleNode::Untyped(
+        Sha256::SPARSE_MERKLE_PLACEHOLDER_HASH,
+    )]);
+    let reason = MalformedProofError::UntypedPlaceholder { index: 0 }.to_string();
+    let json = serde_json::to_string(&range_proof).unwrap();
+    let error = serde_json::from_str::<SparseMerkleRangeProof<Sha256>>(&json).unwrap_err();
+    assert!(error.to_string().contains(&reason));
+    let bytes = borsh::to_vec(&range_proof).unwrap();
+    let error = borsh::from_slice::<SparseMerkleRangeProof<Sha256>>(&bytes).unwrap_err();
+    assert_eq!(error.to_string(), reason);
+    assert_eq!(
+        SparseMerkleRangeProof::<Sha256>::from_envelope(&range_proof.to_envelope()),
+        Err(EnvelopeError::MalformedProof { reason })
+    );
+}
+
+#[test]
+fn test_constructed_proofs_cannot_retype_siblings() {
+    let db = crate::mock::MockTreeStore::default();
+    let tree = JellyfishMerkleTree::<_, Sha256>::new(&db);
+    let keys = [KeyHash([0x00; 32]), KeyHash([0x80; 32])];
+    let (root_hash, batch) = tree
+        .put_value_set(keys.iter().map(|key| (*key, Some(vec![1]))), 0)
+        .unwrap();
+    db.write_tree_update_batch(batch).unwrap();
+
+    let (_, proof) = tree.get_with_proof(keys[0], 0).unwrap();
+    let [SparseMerkleNode::Leaf(sibling)] = proof.siblings() else {
+        panic!("the only sibling is the other leaf");
+    };
+    assert_eq!(sibling.key_hash(), keys[1]);
+
+    // Typing the sibling as an internal node takes children which hash to it, so that claiming
+    // the leaf is an internal node changes the root hash.
+    let retyped = SparseMerkleProof::<Sha256>::try_new(
+        proof.leaf(),
+        vec![SparseMerkleNode::Internal(SparseMerkleInternalNode::new(
+            sibling.key_hash().0,
+            sibling.value_hash().0,
+        ))],
+    )
+    .unwrap();
+    assert!(matches!(
+        retyped.verify_existence(root_hash, keys[0], [1]),
+        Err(VerificationError::RootHashMismatch { .. })
+    ));
+    proof.verify_existence(root_hash, keys[0], [1]).unwrap();
+}
+
+#[test]
+fn test_range_proof_verification_rejects_too_many_siblings() {
+    let proof =
+        SparseMerkleRangeProof::<Sha256>::try_new(vec![SparseMerkleNode::Null; 200]).unwrap();
+    let leaf = SparseMerkleLeafNode::new(KeyHash([0; 32]), ValueHash([1; 32]));
+    assert_eq!(
+        proof.verify(RootHash([0; 32]), leaf, vec![[0; 32]; 100]),
+        Err(VerificationError::TooManySiblings { num_siblings: 300 })
+    );
+}
@@ -0,0 +1,544 @@
+use alloc::{format, string::String, vec, vec::Vec};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::{
+    proof::{
+        json::{
+            JsonExclusionProof, JsonSparseMerkleLeafNode, JsonSparseMerkleNode,
+            JsonSparseMerkleProof, JsonSparseMerkleRangeProof, JsonUpdateMerkleProof,
+        },
+        ExclusionProof, MalformedProofError, SparseMerkleLeafNode, SparseMerkleProof,
+        SparseMerkleRangeProof, UpdateMerkleProof,
+    },
+    KeyHash, RootHash,
+};
+
+/// Proofs of a single tree in every encoding, for testing verifiers written in other languages.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub(crate) struct ProofFixtures {
+    pub description: String,
+    pub hash_function: String,
+    #[serde(with = "hex::serde")]
+    pub root_hash: [u8; 32],
+    pub fixtures: Vec<ProofFixture>,
+}
+
+/// A proof in its JSON form and as the hex-encoded bytes of its protobuf message, along with what
+/// it proves.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum ProofFixture {
+    SparseMerkle {
+        description: String,
+        #[serde(with = "hex::serde")]
+        key_hash: [u8; 32],
+        #[serde(with = "hex_option")]
+        value: Option<Vec<u8>>,
+        proof: JsonSparseMerkleProof,
+        #[serde(with = "hex::serde")]
+        protobuf: Vec<u8>,
+    },
+    Exclusion {
+        description: String,
+        #[serde(with = "hex::serde")]
+        key_hash: [u8; 32],
+        proof: JsonExclusionProof,
+        #[serde(with = "hex::serde")]
+        protobuf: Vec<u8>,
+    },
+    Range {
+        description: String,
+        rightmost_leaf: JsonSparseMerkleLeafNode,
+        #[serde(with = "hex_hashes")]
+        left_siblings: Vec<[u8; 32]>,
+        proof: JsonSparseMerkleRangeProof,
+        #[serde(with = "hex::serde")]
+        protobuf: Vec<u8>,
+    },
+    Update {
+        description: String,
+        #[serde(with = "hex::serde")]
+        new_root_hash: [u8; 32],
+        updates: Vec<FixtureUpdate>,
+        proof: JsonUpdateMerkleProof,
+        #[serde(with = "hex::serde")]
+        protobuf: Vec<u8>,
+    },
+}
+
+/// A key and its new value, or `null` if it is deleted.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub(crate) struct FixtureUpdate {
+    #[serde(with = "hex::serde")]
+    pub key_hash: [u8; 32],
+    #[serde(with = "hex_option")]
+    pub value: Option<Vec<u8>>,
+}
+
+mod hex_option {
+    use alloc::{string::String, vec::Vec};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        bytes: &Option<Vec<u8>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match bytes {
+            Some(bytes) => serializer.serialize_some(&hex::encode(bytes)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Vec<u8>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|bytes| hex::decode(bytes).map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+mod hex_hashes {
+    use alloc::{string::String, vec::Vec};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(hashes: &[[u8; 32]], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(hashes.iter().map(hex::encode))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<[u8; 32]>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .into_iter()
+            .map(|hash| {
+                let mut bytes = [0; 32];
+                hex::decode_to_slice(hash, &mut bytes).map_err(D::Error::custom)?;
+                Ok(bytes)
+            })
+            .collect()
+    }
+}
+
+/// Checks a fixture from its JSON form only, like a verifier in another language would.
+fn verify_fixture(root_hash: RootHash, fixture: &ProofFixture) {
+    match fixture {
+        ProofFixture::SparseMerkle {
+            key_hash,
+            value,
+            proof,
+            ..
+        } => {
+            let decoded = SparseMerkleProof::<Sha256>::try_from(proof.clone()).unwrap();
+            assert_eq!(&JsonSparseMerkleProof::from(&decoded), proof);
+            decoded
+                .verify(root_hash, KeyHash(*key_hash), value.as_ref())
+                .unwrap();
+        }
+        ProofFixture::Exclusion {
+            key_hash, proof, ..
+        } => {
+            let decoded = ExclusionProof::<Sha256>::try_from(proof.clone()).unwrap();
+            assert_eq!(&JsonExclusionProof::from(&decoded), proof);
+            decoded.verify(root_hash, KeyHash(*key_hash)).unwrap();
+        }
+        ProofFixture::Range {
+            rightmost_leaf,
+            left_siblings,
+            proof,
+            ..
+        } => {
+            let decoded = SparseMerkleRangeProof::<Sha256>::try_from(proof.clone()).unwrap();
+            assert_eq!(&JsonSparseMerkleRangeProof::from(&decoded), proof);
+            decoded
+                .verify(
+                    root_hash,
+                    SparseMerkleLeafNode::from(*rightmost_leaf),
+                    left_siblings.clone(),
+                )
+                .unwrap();
+        }
+        ProofFixture::Update {
+            new_root_hash,
+            updates,
+            proof,
+            ..
+        } => {
+            let decoded = UpdateMerkleProof::<Sha256>::try_from(proof.clone()).unwrap();
+            assert_eq!(&JsonUpdateMerkleProof::from(&decoded), proof);
+            let updates: Vec<_> = updates
+                .iter()
+                .map(|update| (KeyHash(update.key_hash), update.value.clone()))
+                .collect();
+            decoded
+                .verify_update(root_hash, RootHash(*new_root_hash), updates)
+                .unwrap();
+        }
+    }
+}
+
+#[test]
+fn test_sha2_256_proof_fixtures() {
+    let fixtures: ProofFixtures =
+        serde_json::from_str(include_str!("sha2_256_proof_fixtures.json"))
+            .expect("fixtures must be valid json");
+    let kinds = ["sparse_merkle", "exclusion", "range", "update"];
+    for kind in kinds {
+        assert!(
+            fixtures
+                .fixtures
+                .iter()
+                .any(|fixture| serde_json::to_value(fixture).unwrap()["kind"] == kind),
+            "no {} fixture",
+            kind
+        );
+    }
+    for fixture in &fixtures.fixtures {
+        verify_fixture(RootHash(fixtures.root_hash), fixture);
+        #[cfg(feature = "proto")]
+        check_protobuf(fixture);
+    }
+}
+
+/// Checks that the protobuf message of a fixture decodes to the proof of its JSON form.
+#[cfg(feature = "proto")]
+fn check_protobuf(fixture: &ProofFixture) {
+    use crate::proof::proto;
+    use prost::Message;
+
+    match fixture {
+        ProofFixture::SparseMerkle {
+            proof, protobuf, ..
+        } => assert_eq!(
+            SparseMerkleProof::<Sha256>::try_from(
+                proto::SparseMerkleProof::decode(protobuf.as_slice()).unwrap()
+            ),
+            SparseMerkleProof::try_from(proof.clone()).map_err(Into::into)
+        ),
+        ProofFixture::Exclusion {
+            proof, protobuf, ..
+        } => assert_eq!(
+            ExclusionProof::<Sha256>::try_from(
+                proto::ExclusionProof::decode(protobuf.as_slice()).unwrap()
+            ),
+            ExclusionProof::try_from(proof.clone()).map_err(Into::into)
+        ),
+        ProofFixture::Range {
+            proof, protobuf, ..
+        } => assert_eq!(
+            SparseMerkleRangeProof::<Sha256>::try_from(
+                proto::SparseMerkleRangeProof::decode(protobuf.as_slice()).unwrap()
+            ),
+            SparseMerkleRangeProof::try_from(proof.clone()).map_err(Into::into)
+        ),
+        ProofFixture::Update {
+            proof, protobuf, ..
+        } => {
+            let decoded = UpdateMerkleProof::<Sha256>::try_from(
+                proto::UpdateMerkleProof::decode(protobuf.as_slice()).unwrap(),
+            )
+            .unwrap();
+            assert_eq!(&JsonUpdateMerkleProof::from(&decoded), proof);
+        }
+    }
+}
+
+#[cfg(feature = "proto")]
+#[test]
+fn test_sha2_256_proof_fixtures_are_up_to_date() {
+    let fixtures: ProofFixtures =
+        serde_json::from_str(include_str!("sha2_256_proof_fixtures.json"))
+            .expect("fixtures must be valid json");
+    assert_eq!(fixtures, generate::proof_fixtures());
+}
+
+#[test]
+fn test_json_form_is_canonical() {
+    let node = |json: &str| serde_json::from_str::<JsonSparseMerkleNode>(json);
+    let hash = "00".repeat(32);
+
+    let untyped = JsonSparseMerkleNode::Untyped { hash: [0xab; 32] };
+    let json = serde_json::to_string(&untyped).unwrap();
+    assert_eq!(
+        json,
+        format!(r#"{{"type":"untyped","hash":"{}"}}"#, "ab".repeat(32))
+    );
+    assert_eq!(node(&json).unwrap(), untyped);
+    assert_eq!(
+        serde_json::to_string(&JsonSparseMerkleNode::Null).unwrap(),
+        r#"{"type":"null"}"#
+    );
+
+    // Uppercase digits, hashes of the wrong length, unknown types and unknown fields are rejected.
+    assert!(node(&format!(
+        r#"{{"type":"untyped","hash":"{}"}}"#,
+        "AB".repeat(32)
+    ))
+    .is_err());
+    assert!(node(&format!(
+        r#"{{"type":"untyped","hash":"{}"}}"#,
+        "ab".repeat(31)
+    ))
+    .is_err());
+    assert!(node(&format!(r#"{{"type":"subtree","hash":"{}"}}"#, hash)).is_err());
+    assert!(node(&format!(
+        r#"{{"type":"untyped","hash":"{}","extra":"{}"}}"#,
+        hash, hash
+    ))
+    .is_err());
+
+    // Proofs are checked when converted from their JSON form.
+    let proof = JsonSparseMerkleProof {
+        leaf: None,
+        siblings: vec![JsonSparseMerkleNode::Null],
+    };
+    assert_eq!(
+        SparseMerkleProof::<Sha256>::try_from(proof),
+        Err(MalformedProofError::PlaceholderAtBottom)
+    );
+}
+
+#[cfg(feature = "proto")]
+#[test]
+fn test_protobuf_conversion_rejects_malformed_messages() {
+    use crate::proof::proto::{self, ProtoProofError};
+
+    let leaf = proto::SparseMerkleLeafNode {
+        key_hash: vec![1; 32],
+        value_hash: vec![2; 31],
+    };
+    assert_eq!(
+        SparseMerkleProof::<Sha256>::try_from(proto::SparseMerkleProof {
+            leaf: Some(leaf),
+            siblings: vec![],
+        }),
+        Err(ProtoProofError::InvalidHashLength { len: 31 })
+    );
+    assert_eq!(
+        SparseMerkleProof::<Sha256>::try_from(proto::SparseMerkleProof {
+            leaf: None,
+            siblings: vec![proto::SparseMerkleNode { node: None }],
+        }),
+        Err(ProtoProofError::MissingField { field: "node" })
+    );
+    assert_eq!(
+        ExclusionProof::<Sha256>::try_from(proto::ExclusionProof {
+            proof: Some(proto::exclusion_proof::Proof::Middle(
+                proto::exclusion_proof::Middle {
+                    leftmost_right_proof: Some(proto::SparseMerkleProof::default()),
+                    rightmost_left_proof: None,
+                }
+            )),
+        }),
+        Err(ProtoProofError::MissingField {
+            field: "rightmost_left_proof"
+        })
+    );
+}
+
+#[cfg(feature = "proto")]
+mod generate {
+    use alloc::{string::ToString, vec, vec::Vec};
+    use prost::Message;
+    use sha2::Sha256;
+
+    use super::{FixtureUpdate, ProofFixture, ProofFixtures};
+    use crate::{
+        mock::MockTreeStore, proof::proto, Bytes32Ext, JellyfishMerkleTree, KeyHash, OwnedValue,
+    };
+
+    type Tree<'a> = JellyfishMerkleTree<'a, MockTreeStore, Sha256>;
+
+    const DESCRIPTION: &str = "Proofs of a JMT instantiated with the sha2_256 hash function, in \
+        their canonical JSON form and as the hex-encoded bytes of their jmt.proof.v1 protobuf \
+        message (proto/jmt/proof/v1/proof.proto). Hashes and values are hex-encoded. Value \
+        hashes are the hash of the value, leaf hashes the hash of \"JMT::LeafNode\", the key \
+        hash and the value hash, internal hashes the hash of \"JMT::IntrnalNode\" and the hashes \
+        of the children, and empty subtrees hash to \"SPARSE_MERKLE_PLACEHOLDER_HASH__\". \
+        Siblings are ordered from the bottom level to the root level. Update proofs go from \
+        root_hash to new_root_hash.";
+
+    fn key(i: u8) -> KeyHash {
+        KeyHash::with::<Sha256>([i])
+    }
+
+    fn value(i: u8) -> OwnedValue {
+        vec![i; usize::from(i % 4) + 1]
+    }
+
+    fn sparse_merkle(tree: &Tree, description: &str, key_hash: KeyHash) -> ProofFixture {
+        let (value, proof) = tree.get_with_proof(key_hash, 0).unwrap();
+        sparse_merkle_fixture(description, key_hash, value, &proof)
+    }
+
+    fn sparse_merkle_fixture(
+        description: &str,
+        key_hash: KeyHash,
+        value: Option<OwnedValue>,
+        proof: &crate::proof::SparseMerkleProof<Sha256>,
+    ) -> ProofFixture {
+        ProofFixture::SparseMerkle {
+            description: description.to_string(),
+            key_hash: key_hash.0,
+            value,
+            proof: proof.into(),
+            protobuf: proto::SparseMerkleProof::from(proof).encode_to_vec(),
+        }
+    }
+
+    fn exclusion(tree: &Tree, description: &str, key_hash: KeyHash) -> ProofFixture {
+        let proof = tree
+            .get_with_exclusion_proof(key_hash, 0)
+            .unwrap()
+            .unwrap_err();
+        ProofFixture::Exclusion {
+            description: description.to_string(),
+            key_hash: key_hash.0,
+            proof: (&proof).into(),
+            protobuf: proto::ExclusionProof::from(&proof).encode_to_vec(),
+        }
+    }
+
+    fn range(tree: &Tree, description: &str, rightmost_key_hash: KeyHash) -> ProofFixture {
+        let proof = tree.get_range_proof(rightmost_key_hash, 0).unwrap();
+        // The siblings on the left of the path to the rightmost leaf are the ones of its own
+        // proof where the path goes right.
+        let (_, leaf_proof) = tree.get_with_proof(rightmost_key_hash, 0).unwrap();
+        let num_siblings = leaf_proof.siblings().len();
+        let left_siblings = leaf_proof
+            .siblings()
+            .iter()
+            .zip(
+                rightmost_key_hash
+                    .0
+                    .iter_bits()
+                    .rev()
+                    .skip(256 - num_siblings),
+            )
+            .filter(|(_, bit)| *bit)
+            .map(|(sibling, _)| sibling.hash::<Sha256>())
+            .collect();
+        ProofFixture::Range {
+            description: description.to_string(),
+            rightmost_leaf: leaf_proof.leaf().unwrap().into(),
+            left_siblings,
+            proof: (&proof).into(),
+            protobuf: proto::SparseMerkleRangeProof::from(&proof).encode_to_vec(),
+        }
+    }
+
+    fn update(
+        tree: &Tree,
+        description: &str,
+        updates: Vec<(KeyHash, Option<OwnedValue>)>,
+    ) -> ProofFixture {
+        let (new_root_hash, proof, _batch) =
+            tree.put_value_set_with_proof(updates.clone(), 1).unwrap();
+        ProofFixture::Update {
+            description: description.to_string(),
+            new_root_hash: new_root_hash.0,
+            updates: updates
+                .into_iter()
+                .map(|(key_hash, value)| FixtureUpdate {
+                    key_hash: key_hash.0,
+                    value,
+                })
+                .collect(),
+            proof: (&proof).into(),
+            protobuf: proto::UpdateMerkleProof::from(&proof).encode_to_vec(),
+        }
+    }
+
+    /// Returns the fixtures of a tree holding the keys `0` to `15`, hashed.
+    pub(crate) fn proof_fixtures() -> ProofFixtures {
+        let db = MockTreeStore::default();
+        let tree = Tree::new(&db);
+        let (root_hash, batch) = tree
+            .put_value_set((0..16).map(|i| (key(i), Some(value(i)))), 0)
+            .unwrap();
+        db.write_tree_update_batch(batch).unwrap();
+
+        let mut keys: Vec<_> = (0..16).map(key).collect();
+        keys.sort();
+        // Absent keys whose subtree holds a leaf, and whose subtree is empty.
+        let (absent_with_leaf, absent_without_leaf) = {
+            let proofs: Vec<_> = (16..=255)
+                .map(|i| (key(i), tree.get_with_proof(key(i), 0).unwrap().1))
+                .collect();
+            let find = |has_leaf: bool| {
+                proofs
+                    .iter()
+                    .find(|(_, proof)| proof.leaf().is_some() == has_leaf)
+                    .unwrap()
+                    .0
+            };
+            (find(true), find(false))
+        };
+        let mut between = keys[7];
+        between.0[31] += 1;
+        assert!(between < keys[8]);
+        let (_, proof) = tree.get_with_proof(key(3), 0).unwrap();
+
+        let fixtures = vec![
+            sparse_merkle(&tree, "Inclusion of the key 3.", key(3)),
+            sparse_merkle_fixture(
+                "Compacted inclusion proof of the key 3, with untyped siblings.",
+                key(3),
+                Some(value(3)),
+                &proof.compact(),
+            ),
+            sparse_merkle(
+                &tree,
+                "Non-inclusion of a key whose subtree holds another leaf.",
+                absent_with_leaf,
+            ),
+            sparse_merkle(
+                &tree,
+                "Non-inclusion of a key whose subtree is empty.",
+                absent_without_leaf,
+            ),
+            exclusion(
+                &tree,
+                "Exclusion of a key smaller than every key.",
+                KeyHash([0x00; 32]),
+            ),
+            exclusion(&tree, "Exclusion of a key between two keys.", between),
+            exclusion(
+                &tree,
+                "Exclusion of a key greater than every key.",
+                KeyHash([0xff; 32]),
+            ),
+            range(&tree, "Range proof of the 8 smallest keys.", keys[7]),
+            update(
+                &tree,
+                "Insertion of a key, update of the key 3 and deletion of the key 5.",
+                vec![
+                    (absent_with_leaf, Some(vec![0x42])),
+                    (key(3), Some(vec![0x43])),
+                    (key(5), None),
+                ],
+            ),
+        ];
+
+        ProofFixtures {
+            description: DESCRIPTION.to_string(),
+            hash_function: "sha2_256".to_string(),
+            root_hash: root_hash.0,
+            fixtures,
+        }
+    }
+
+    /// Overwrites the checked-in proof fixtures. Run with
+    /// `cargo test --features proto generate_proof_fixtures -- --ignored`.
+    #[cfg(feature = "std")]
+    #[test]
+    #[ignore]
+    fn generate_proof_fixtures() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/tests/sha2_256_proof_fixtures.json");
+        let file = std::fs::File::create(path).unwrap();
+        let writer = std::io::BufWriter::new(file);
+        serde_json::to_writer_pretty(writer, &proof_fixtures()).unwrap();
+    }
+}
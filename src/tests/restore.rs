@@ -63,7 +63,9 @@ fn test_restore_with_interruption<H: SimpleHasher>(
         restore
             .add_chunk(remaining_accounts.into_iter().collect(), proof)
             .unwrap();
-        restore.finish().unwrap();
+        let summary = restore.finish().unwrap();
+        assert_eq!(summary.root_hash, expected_root_hash);
+        assert_eq!(summary.num_leaves, entries.len());
     }
 
     assert_success::<H>(&restore_db, expected_root_hash, &entries, version);
@@ -119,7 +121,7 @@ proptest! {
     ) {
         let restore_db = Arc::new(MockTreeStore::default());
         // For this test, restore everything without interruption.
-        restore_without_interruption::<blake3::Hasher>(&btree, target_version, &restore_db, true);
+        restore_without_interruption::<crate::Blake3Hasher>(&btree, target_version, &restore_db, true);
     }
 
     #[test]
@@ -130,7 +132,7 @@ proptest! {
                 (Just(btree), 1..len)
             })
     ) {
-        test_restore_with_interruption::<blake3::Hasher>(entries, first_batch_size )
+        test_restore_with_interruption::<crate::Blake3Hasher>(entries, first_batch_size )
     }
 
 
@@ -142,12 +144,39 @@ proptest! {
         target_version in 0u64..2000,
     ) {
         let restore_db = Arc::new(MockTreeStore::new(true /* allow_overwrite */));
-        restore_without_interruption::<blake3::Hasher>(&btree1, target_version, &restore_db, true);
+        restore_without_interruption::<crate::Blake3Hasher>(&btree1, target_version, &restore_db, true);
         // overwrite, an entirely different tree
-        restore_without_interruption::<blake3::Hasher>(&btree2, target_version, &restore_db, false);
+        restore_without_interruption::<crate::Blake3Hasher>(&btree2, target_version, &restore_db, false);
     }
 }
 
+#[test]
+fn test_restore_finish_rejects_incomplete_tree() {
+    let btree: BTreeMap<KeyHash, OwnedValue> = (0u8..10)
+        .map(|i| (KeyHash([i; 32]), alloc::vec![i]))
+        .collect();
+    let (db, version) = init_mock_db::<Sha256>(&btree.clone().into_iter().collect());
+    let tree = JellyfishMerkleTree::<_, Sha256>::new(&db);
+    let expected_root_hash = tree.get_root_hash(version).unwrap();
+
+    // Only the first half of the leaves is sent before finishing, so the reconstructed root
+    // cannot match, and it must not be written.
+    let restore_db = Arc::new(MockTreeStore::default());
+    let mut restore =
+        JellyfishMerkleRestore::<Sha256>::new(Arc::clone(&restore_db), version, expected_root_hash)
+            .unwrap();
+    let chunk: Vec<_> = btree.clone().into_iter().take(5).collect();
+    let proof = tree
+        .get_range_proof(chunk.last().unwrap().0, version)
+        .unwrap();
+    restore.add_chunk(chunk, proof).unwrap();
+    assert!(restore.finish().is_err());
+    assert!(JellyfishMerkleTree::<_, Sha256>::new(restore_db.as_ref())
+        .get_root_hash_option(version)
+        .unwrap()
+        .is_none());
+}
+
 fn assert_success<H: SimpleHasher>(
     db: &MockTreeStore,
     expected_root_hash: RootHash,
@@ -190,7 +219,12 @@ fn restore_without_interruption<H: SimpleHasher>(
             .add_chunk(alloc::vec![(*key, value.clone())], proof)
             .unwrap();
     }
-    Box::new(restore).finish().unwrap();
+    let summary = Box::new(restore).finish().unwrap();
+    assert_eq!(summary.root_hash, expected_root_hash);
+    assert_eq!(summary.num_leaves, btree.len());
+    // Every leaf is written, along with at least the root node unless the tree has a single leaf.
+    assert!(summary.num_nodes_written >= btree.len());
+    assert!(summary.num_bytes_written >= btree.values().map(|value| value.len()).sum());
 
     assert_success::<H>(target_db, expected_root_hash, btree, target_version);
 }
@@ -0,0 +1,208 @@
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use std::sync::Mutex;
+
+use sha2::Sha256;
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Metadata, Subscriber,
+};
+
+use crate::{
+    mock::MockTreeStore,
+    storage::{LeafNode, Node, NodeKey, TreeReader},
+    JellyfishMerkleTree, KeyHash, OwnedValue, Version,
+};
+
+/// The name and the fields of a span, as recorded by [`SpanRecorder`].
+#[derive(Debug, Default)]
+struct RecordedSpan {
+    name: &'static str,
+    fields: BTreeMap<&'static str, String>,
+}
+
+impl Visit for RecordedSpan {
+    fn record_debug(&mut self, field: &Field, value: &dyn core::fmt::Debug) {
+        self.fields.insert(field.name(), format!("{:?}", value));
+    }
+}
+
+/// A subscriber keeping every span created, along with the values later recorded in it.
+#[derive(Default)]
+struct SpanRecorder {
+    spans: Mutex<Vec<RecordedSpan>>,
+}
+
+impl SpanRecorder {
+    /// Returns the fields of the spans named `name`, in order of creation.
+    fn spans(&self, name: &str) -> Vec<BTreeMap<&'static str, String>> {
+        self.spans
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|span| span.name == name)
+            .map(|span| span.fields.clone())
+            .collect()
+    }
+}
+
+impl Subscriber for SpanRecorder {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attributes: &span::Attributes<'_>) -> span::Id {
+        let mut span = RecordedSpan {
+            name: attributes.metadata().name(),
+            ..Default::default()
+        };
+        attributes.record(&mut span);
+        let mut spans = self.spans.lock().unwrap();
+        spans.push(span);
+        span::Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, id: &span::Id, values: &span::Record<'_>) {
+        let mut spans = self.spans.lock().unwrap();
+        values.record(&mut spans[id.into_u64() as usize - 1]);
+    }
+
+    fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+    fn event(&self, _event: &Event<'_>) {}
+
+    fn enter(&self, _span: &span::Id) {}
+
+    fn exit(&self, _span: &span::Id) {}
+}
+
+#[test]
+fn test_spans_record_operations() {
+    let recorder = Arc::new(SpanRecorder::default());
+    let db = MockTreeStore::default();
+    let tree = JellyfishMerkleTree::<_, Sha256>::new(&db);
+    let key = KeyHash([0x12; 32]);
+
+    let root_hashes = tracing::subscriber::with_default(recorder.clone(), || {
+        let (root_hashes, batch) = tree
+            .put_value_sets(
+                vec![
+                    vec![(key, Some(vec![1])), (KeyHash([0x56; 32]), Some(vec![2]))],
+                    vec![(key, Some(vec![3]))],
+                ],
+                0, /* first_version */
+            )
+            .unwrap();
+        db.write_tree_update_batch(batch).unwrap();
+        tree.get_with_proof(key, 1).unwrap();
+        tree.get_with_exclusion_proof(KeyHash([0x34; 32]), 1)
+            .unwrap()
+            .unwrap_err();
+        tree.get_with_proof(key, 2).unwrap_err();
+        tree.put_value_set(vec![(key, None)], 2).unwrap();
+        root_hashes
+    });
+
+    let put_spans = recorder.spans("put_value_sets");
+    assert_eq!(put_spans.len(), 2);
+    assert_eq!(put_spans[0]["first_version"], "0");
+    assert_eq!(put_spans[0]["num_versions"], "2");
+    assert_eq!(put_spans[0]["num_keys"], "3");
+    assert_eq!(
+        put_spans[0]["root_hash"],
+        format!("{:?}", root_hashes.last())
+    );
+    // Only the second update finds nodes in storage.
+    assert_eq!(put_spans[0]["node_reads"], "0");
+    assert_ne!(put_spans[1]["node_reads"], "0");
+
+    let freeze_spans = recorder.spans("freeze");
+    assert_eq!(freeze_spans.len(), 3);
+    assert_eq!(freeze_spans[1]["version"], "1");
+    assert_eq!(
+        freeze_spans[1]["root_hash"],
+        format!("{:?}", root_hashes[1])
+    );
+
+    let proof_spans = recorder.spans("get_with_proof");
+    assert_eq!(proof_spans[0]["version"], "1");
+    assert_eq!(proof_spans[0]["found"], "true");
+    assert_ne!(proof_spans[0]["node_reads"], "0");
+    let expected_key_hash = if cfg!(feature = "redact_key_hashes") {
+        "KeyHash(<redacted>)".to_string()
+    } else {
+        format!("{:?}", key)
+    };
+    assert_eq!(proof_spans[0]["key_hash"], expected_key_hash);
+    let failed_span = proof_spans.last().unwrap();
+    assert!(failed_span.contains_key("error"));
+    assert!(!failed_span.contains_key("found"));
+
+    // The exclusion proof also counts the reads of the proofs generated for the key and for the
+    // keys bounding it.
+    let exclusion_spans = recorder.spans("get_with_exclusion_proof");
+    assert_eq!(exclusion_spans.len(), 1);
+    assert_eq!(exclusion_spans[0]["found"], "false");
+    let nested_reads: u64 = proof_spans[1..proof_spans.len() - 1]
+        .iter()
+        .map(|span| span["node_reads"].parse::<u64>().unwrap())
+        .sum();
+    let exclusion_reads: u64 = exclusion_spans[0]["node_reads"].parse().unwrap();
+    assert!(exclusion_reads > nested_reads);
+}
+
+/// A store which lost every value, while keeping the nodes of the tree.
+struct MissingValues(MockTreeStore);
+
+impl TreeReader for MissingValues {
+    fn get_node_option(&self, node_key: &NodeKey) -> anyhow::Result<Option<Node>> {
+        self.0.get_node_option(node_key)
+    }
+
+    fn get_value_option(
+        &self,
+        _max_version: Version,
+        _key_hash: KeyHash,
+    ) -> anyhow::Result<Option<OwnedValue>> {
+        Ok(None)
+    }
+
+    fn get_rightmost_leaf(&self) -> anyhow::Result<Option<(NodeKey, LeafNode)>> {
+        self.0.get_rightmost_leaf()
+    }
+}
+
+#[test]
+fn test_spans_record_errors() {
+    let db = MockTreeStore::default();
+    let key = KeyHash([0xab; 32]);
+    let (_root_hash, batch) = JellyfishMerkleTree::<_, Sha256>::new(&db)
+        .put_value_set(vec![(key, Some(vec![1]))], 0)
+        .unwrap();
+    db.write_tree_update_batch(batch).unwrap();
+
+    let recorder = Arc::new(SpanRecorder::default());
+    let store = MissingValues(db);
+    let tree = JellyfishMerkleTree::<_, Sha256>::new(&store);
+    tracing::subscriber::with_default(recorder.clone(), || {
+        tree.get_with_proof(key, 0).unwrap_err();
+        tree.get_with_proof(key, 1).unwrap_err();
+    });
+
+    let proof_spans = recorder.spans("get_with_proof");
+    let missing_value = &proof_spans[0]["error"];
+    let missing_root = &proof_spans[1]["error"];
+    assert!(missing_root.contains("Missing state root node at version 1"));
+    if cfg!(feature = "redact_key_hashes") {
+        assert_eq!(missing_value, "<redacted>");
+    } else {
+        assert!(missing_value.contains("Missing value"));
+        assert!(missing_value.contains(&format!("{:?}", key)));
+    }
+}
@@ -1,8 +1,7 @@
 use alloc::{string::String, vec::Vec};
 use serde::{Deserialize, Serialize};
-use sha2::Sha256;
 
-use crate::{mock::MockTreeStore, KeyHash, Sha256Jmt};
+use crate::{mock::MockTreeStore, JellyfishMerkleTree, KeyHash, SimpleHasher};
 
 #[derive(Serialize, Deserialize)]
 pub(crate) struct TestVectorWrapper {
@@ -16,6 +15,8 @@ pub(crate) struct TestVector {
     #[serde(with = "hex::serde")]
     pub expected_root: [u8; 32],
     pub data: Vec<KeyValuePair>,
+    #[serde(default)]
+    pub proofs: Vec<ProofVector>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -26,27 +27,88 @@ pub(crate) struct KeyValuePair {
     pub value: Vec<u8>,
 }
 
-#[test]
-fn test_with_vectors() {
-    let test_vectors = include_str!("sha2_256_vectors.json");
+/// The existence proof of a key of a [`TestVector`], with its siblings ordered from the bottom
+/// level to the root level.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ProofVector {
+    #[serde(with = "hex::serde")]
+    pub key: Vec<u8>,
+    #[serde(with = "hex_siblings")]
+    pub siblings: Vec<[u8; 32]>,
+}
+
+mod hex_siblings {
+    use alloc::{string::String, vec::Vec};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
 
+    pub fn serialize<S: Serializer>(
+        siblings: &[[u8; 32]],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(siblings.iter().map(hex::encode))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<[u8; 32]>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .into_iter()
+            .map(|sibling| {
+                let mut bytes = [0; 32];
+                hex::decode_to_slice(sibling, &mut bytes).map_err(D::Error::custom)?;
+                Ok(bytes)
+            })
+            .collect()
+    }
+}
+
+fn test_with_vectors<H: SimpleHasher>(hash_function: &str, test_vectors: &str) {
     let test_file: TestVectorWrapper =
         serde_json::from_str(test_vectors).expect("test vectors must be valid json");
+    assert_eq!(test_file.hash_function, hash_function);
 
-    let store = &MockTreeStore::default();
-
-    let jmt = Sha256Jmt::new(store);
     for vector in test_file.vectors {
+        let store = MockTreeStore::default();
+        let jmt = JellyfishMerkleTree::<_, H>::new(&store);
         let mut key_value_pairs = Vec::new();
-        for pair in vector.data {
-            let key_hash = KeyHash::with::<Sha256>(&pair.key);
-            key_value_pairs.push((key_hash, Some(pair.value)));
+        for pair in &vector.data {
+            let key_hash = KeyHash::with::<H>(&pair.key);
+            key_value_pairs.push((key_hash, Some(pair.value.clone())));
         }
 
-        let root = jmt
+        let (root, batch) = jmt
             .put_value_set(key_value_pairs, 0)
-            .expect("tree update must not fail")
-            .0;
+            .expect("tree update must not fail");
         assert_eq!(root.0, vector.expected_root);
+        store.write_tree_update_batch(batch).unwrap();
+
+        for proof_vector in vector.proofs {
+            let key_hash = KeyHash::with::<H>(&proof_vector.key);
+            let (value, proof) = jmt.get_with_proof(key_hash, 0).unwrap();
+            let siblings: Vec<_> = proof
+                .siblings()
+                .iter()
+                .map(|sibling| sibling.hash::<H>())
+                .collect();
+            assert_eq!(siblings, proof_vector.siblings);
+            proof
+                .verify_existence(root, key_hash, value.unwrap())
+                .unwrap();
+        }
     }
 }
+
+#[test]
+fn test_with_sha2_256_vectors() {
+    test_with_vectors::<sha2::Sha256>("sha2", include_str!("sha2_256_vectors.json"));
+}
+
+#[test]
+fn test_with_blake3_vectors() {
+    test_with_vectors::<crate::Blake3Hasher>("blake3", include_str!("blake3_vectors.json"));
+}
+
+#[test]
+fn test_with_keccak256_vectors() {
+    test_with_vectors::<sha3::Keccak256>("keccak256", include_str!("keccak256_vectors.json"));
+}
@@ -0,0 +1,231 @@
+use alloc::{sync::Arc, vec, vec::Vec};
+
+use sha2::Sha256;
+
+use crate::{
+    integrity::verify_tree_integrity,
+    mock::MockTreeStore,
+    namespace::{Namespace, NamespacedStore},
+    storage::VersionMetadata,
+    types::nibble::nibble_path::NibblePath,
+    JellyfishMerkleTree, Jmt, KeyHash,
+};
+
+#[test]
+fn test_latest_version_apis() {
+    let db = MockTreeStore::default();
+    let tree = JellyfishMerkleTree::<_, Sha256>::new(&db);
+    let key = KeyHash([0x12; 32]);
+    assert_eq!(db.latest_version().unwrap(), None);
+    assert_eq!(tree.get_latest(key).unwrap(), None);
+    assert_eq!(tree.root_hash_history().unwrap().count(), 0);
+
+    let mut root_hashes = Vec::new();
+    for i in 0..3u8 {
+        let (version, root_hash, batch) = tree
+            .put_value_set_latest(vec![(key, Some(vec![i]))])
+            .unwrap();
+        assert_eq!(version, i as u64);
+        db.write_tree_update_batch(batch).unwrap();
+        root_hashes.push((version, root_hash));
+        assert_eq!(tree.get_latest(key).unwrap(), Some(vec![i]));
+    }
+    assert_eq!(db.latest_version().unwrap(), Some(2));
+    assert_eq!(
+        tree.root_hash_history()
+            .unwrap()
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap(),
+        root_hashes
+    );
+
+    // Pruning moves the oldest readable version, and the history along with it.
+    db.purge_stale_nodes(1).unwrap();
+    assert_eq!(db.oldest_readable_version().unwrap(), Some(1));
+    let jmt = Jmt::<_, Sha256>::new(Arc::new(db));
+    assert_eq!(
+        jmt.root_hash_history()
+            .unwrap()
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap(),
+        root_hashes[1..]
+    );
+
+    // Each namespace has its own versions.
+    let module = NamespacedStore::new(jmt.reader().clone(), Namespace::new("module"));
+    assert_eq!(module.latest_version().unwrap(), None);
+    let module_tree = JellyfishMerkleTree::<_, Sha256>::new(&module);
+    let (version, _root_hash, _batch) = module_tree
+        .put_value_set_latest(vec![(key, Some(vec![3]))])
+        .unwrap();
+    assert_eq!(version, 0);
+}
+
+#[test]
+fn test_non_contiguous_versions() {
+    let db = MockTreeStore::default();
+    let tree = JellyfishMerkleTree::<_, Sha256>::new(&db);
+    let key1 = KeyHash([0x12; 32]);
+    let key2 = KeyHash([0x34; 32]);
+
+    // Versions 0, 5 and 9, each on top of the previous one.
+    let mut root_hashes = Vec::new();
+    let mut base_version = None;
+    for (version, value_set) in [
+        (0, vec![(key1, Some(vec![0]))]),
+        (5, vec![(key2, Some(vec![5]))]),
+        (9, vec![(key1, None)]),
+    ] {
+        let (root_hash, batch) = tree
+            .put_value_set_after(value_set, base_version, version)
+            .unwrap();
+        db.write_tree_update_batch(batch).unwrap();
+        root_hashes.push((version, root_hash));
+        base_version = Some(version);
+    }
+    assert!(tree
+        .put_value_set_after(vec![(key1, None)], Some(9), 9)
+        .is_err());
+
+    assert_eq!(tree.resolve_version(3).unwrap(), Some(0));
+    assert_eq!(tree.resolve_version(5).unwrap(), Some(5));
+    assert_eq!(tree.resolve_version(9).unwrap(), Some(9));
+    // A version after the latest one may still be written, so it does not resolve yet.
+    assert_eq!(tree.resolve_version(100).unwrap(), None);
+    assert!(tree.get_root_hash_option(100).unwrap().is_none());
+    assert_eq!(tree.get(key1, 7).unwrap(), Some(vec![0]));
+    assert_eq!(tree.get(key2, 7).unwrap(), Some(vec![5]));
+
+    // Every read at a skipped height sees the tree of the latest version before it.
+    assert_eq!(tree.get_root_hash(7).unwrap(), root_hashes[1].1);
+    assert_eq!(
+        tree.get_root_hash_option(3).unwrap(),
+        Some(root_hashes[0].1)
+    );
+    assert_eq!(tree.get_leaf_count(7).unwrap(), 2);
+    let (value, proof) = tree.get_with_proof(key2, 7).unwrap();
+    assert_eq!(value, Some(vec![5]));
+    proof
+        .verify_existence(tree.get_root_hash(7).unwrap(), key2, vec![5])
+        .unwrap();
+    let absent_key = KeyHash([0x56; 32]);
+    let (value, proof) = tree.get_with_proof(absent_key, 7).unwrap();
+    assert_eq!(value, None);
+    proof
+        .verify_nonexistence(root_hashes[1].1, absent_key)
+        .unwrap();
+    let proof = tree
+        .get_with_exclusion_proof(absent_key, 7)
+        .unwrap()
+        .unwrap_err();
+    proof.verify(root_hashes[1].1, absent_key).unwrap();
+    let range_proof = tree.get_range_proof(key2, 7).unwrap();
+    assert_eq!(
+        range_proof.right_siblings(),
+        tree.get_range_proof(key2, 5).unwrap().right_siblings()
+    );
+    assert_eq!(
+        tree.root_hash_history()
+            .unwrap()
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap(),
+        root_hashes
+    );
+    assert_eq!(
+        verify_tree_integrity::<_, Sha256>(&db, 7).unwrap(),
+        Vec::new()
+    );
+    assert_eq!(
+        tree.to_dot(7, &NibblePath::new(vec![]), None).unwrap(),
+        tree.to_dot(5, &NibblePath::new(vec![]), None).unwrap()
+    );
+
+    // The tree at height 7 is the one at version 5, which stays readable until version 9 is the
+    // oldest one kept.
+    db.purge_stale_nodes(7).unwrap();
+    assert_eq!(db.oldest_readable_version().unwrap(), Some(5));
+    assert_eq!(tree.resolve_version(7).unwrap(), Some(5));
+    assert_eq!(tree.get_root_hash(7).unwrap(), root_hashes[1].1);
+
+    let jmt = Jmt::<_, Sha256>::new(Arc::new(db));
+    assert_eq!(
+        jmt.iter(7, KeyHash([0; 32]))
+            .unwrap()
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap(),
+        vec![(key1, vec![0]), (key2, vec![5])]
+    );
+}
+
+#[test]
+fn test_commit_after_gap() {
+    let db = MockTreeStore::default();
+    let tree = JellyfishMerkleTree::<_, Sha256>::new(&db);
+    let key1 = KeyHash([0x12; 32]);
+    let key2 = KeyHash([0x34; 32]);
+    for (base_version, version, key) in [(None, 0, key1), (Some(0), 5, key2)] {
+        let (_root_hash, batch) = tree
+            .put_value_set_after(
+                vec![(key, Some(vec![version as u8]))],
+                base_version,
+                version,
+            )
+            .unwrap();
+        db.write_tree_update_batch(batch).unwrap();
+    }
+
+    // Neither commit passes version 5: the previous height and a skipped base both resolve to it.
+    let (root_hash, batch) = tree
+        .put_value_set(vec![(key1, Some(vec![10]))], 10)
+        .unwrap();
+    let (root_hash_after, _batch) = tree
+        .put_value_set_after(vec![(key1, Some(vec![10]))], Some(7), 10)
+        .unwrap();
+    assert_eq!(root_hash, root_hash_after);
+    db.write_tree_update_batch(batch).unwrap();
+    assert_eq!(tree.get(key1, 10).unwrap(), Some(vec![10]));
+    assert_eq!(tree.get(key2, 10).unwrap(), Some(vec![5]));
+
+    // Building on a version before the first one written has nothing to start from, unless the
+    // base version is only the previous height, which the first version of a tree may have.
+    let db = MockTreeStore::default();
+    let (root_hash, batch) = JellyfishMerkleTree::<_, Sha256>::new(&db)
+        .put_value_set(vec![(key1, Some(vec![3]))], 3)
+        .unwrap();
+    let (root_hash_after, _batch) = JellyfishMerkleTree::<_, Sha256>::new(&db)
+        .put_value_set_after(vec![(key1, Some(vec![3]))], None, 3)
+        .unwrap();
+    assert_eq!(root_hash, root_hash_after);
+    db.write_tree_update_batch(batch).unwrap();
+    assert!(JellyfishMerkleTree::<_, Sha256>::new(&db)
+        .put_value_set_after(vec![(key1, None)], Some(2), 4)
+        .is_err());
+}
+
+#[test]
+fn test_root_hash_history_skips_gaps() {
+    // Walking every height between these versions would never finish.
+    let db = MockTreeStore::default();
+    let tree = JellyfishMerkleTree::<_, Sha256>::new(&db);
+    let key = KeyHash([0x12; 32]);
+    let mut root_hashes = Vec::new();
+    let mut base_version = None;
+    for version in [0, 1 << 40, 1 << 50] {
+        let (root_hash, batch) = tree
+            .put_value_set_after(vec![(key, Some(vec![1]))], base_version, version)
+            .unwrap();
+        db.write_tree_update_batch(batch).unwrap();
+        root_hashes.push((version, root_hash));
+        base_version = Some(version);
+    }
+
+    assert_eq!(db.next_version_after(0).unwrap(), Some(1 << 40));
+    assert_eq!(db.next_version_after(1 << 50).unwrap(), None);
+    assert_eq!(
+        tree.root_hash_history()
+            .unwrap()
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap(),
+        root_hashes
+    );
+}
@@ -0,0 +1,208 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use proptest::{collection::btree_map, prelude::*};
+use sha2::Sha256;
+
+use crate::{
+    mock::MockTreeStore,
+    node_type::{Node, NodeKey},
+    storage::TreeReader,
+    tests::helper::init_mock_db,
+    witness::{RecordingTreeReader, Witness, WitnessTreeReader},
+    JellyfishMerkleTree, KeyHash, OwnedValue, RootHash, SimpleHasher, ValueHash, Version,
+};
+
+/// Reads `reads` and writes `writes` on top of `version`, returning the values read and the new
+/// root hash.
+fn execute<R: TreeReader, H: SimpleHasher>(
+    reader: &R,
+    version: Version,
+    reads: &[KeyHash],
+    writes: &[(KeyHash, Option<OwnedValue>)],
+) -> anyhow::Result<(Vec<Option<OwnedValue>>, [u8; 32])> {
+    let tree = JellyfishMerkleTree::<_, H>::new(reader);
+    let mut values = Vec::new();
+    for key in reads {
+        let (value, proof) = tree.get_with_proof(*key, version)?;
+        assert_eq!(tree.get(*key, version)?, value);
+        proof.verify(tree.get_root_hash(version)?, *key, value.as_ref())?;
+        values.push(value);
+    }
+    let (new_root_hash, _batch) = tree.put_value_set(writes.to_vec(), version + 1)?;
+    Ok((values, new_root_hash.0))
+}
+
+fn test_replay_from_witness<H: SimpleHasher>(
+    kvs: BTreeMap<KeyHash, OwnedValue>,
+    reads: Vec<KeyHash>,
+    writes: Vec<(KeyHash, Option<OwnedValue>)>,
+) {
+    let (db, version) = init_mock_db::<H>(&kvs.clone().into_iter().collect());
+    let reads: Vec<_> = reads
+        .into_iter()
+        .chain(kvs.keys().take(3).cloned())
+        .collect();
+
+    let recording_reader = RecordingTreeReader::new(db);
+    let expected = execute::<_, H>(&recording_reader, version, &reads, &writes).unwrap();
+    let (db, witness) = recording_reader.into_parts();
+    let root_hash = JellyfishMerkleTree::<_, H>::new(&db)
+        .get_root_hash(version)
+        .unwrap();
+
+    // The witness is much smaller than the whole tree, but enough to replay the same operations.
+    assert!(witness.nodes().len() <= db.num_nodes());
+    let witness_reader = WitnessTreeReader::new::<H>(witness, root_hash, version).unwrap();
+    let replayed = execute::<_, H>(&witness_reader, version, &reads, &writes).unwrap();
+    assert_eq!(replayed, expected);
+
+    // Replaying against an empty witness must fail instead of pretending the tree is empty.
+    let empty_reader = WitnessTreeReader::new::<H>(Witness::default(), root_hash, version).unwrap();
+    assert!(execute::<_, H>(&empty_reader, version, &reads, &writes).is_err());
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(10))]
+
+    #[test]
+    fn test_replay_from_witness_sha256(
+        kvs in btree_map(any::<KeyHash>(), any::<OwnedValue>(), 1..200),
+        reads in prop::collection::vec(any::<KeyHash>(), 0..10),
+        writes in btree_map(any::<KeyHash>(), any::<Option<OwnedValue>>(), 1..10),
+    ) {
+        test_replay_from_witness::<Sha256>(kvs, reads, writes.into_iter().collect());
+    }
+}
+
+#[test]
+fn test_witness_roundtrip_borsh() {
+    use borsh::BorshDeserialize;
+
+    let kvs = (0u8..10)
+        .map(|i| (KeyHash([i; 32]), alloc::vec![i]))
+        .collect();
+    let (db, version) = init_mock_db::<Sha256>(&kvs);
+    let recording_reader = RecordingTreeReader::new(db);
+    execute::<_, Sha256>(&recording_reader, version, &[KeyHash([3; 32])], &[]).unwrap();
+
+    let witness = recording_reader.take_witness();
+    assert!(!witness.is_empty());
+    assert!(recording_reader.witness().is_empty());
+    let serialized = borsh::to_vec(&witness).unwrap();
+    let deserialized = Witness::deserialize(&mut serialized.as_slice()).unwrap();
+    assert_eq!(witness, deserialized);
+}
+
+/// Records the witness of reading `keys` at the latest version of a small tree.
+fn record_witness(keys: &[KeyHash]) -> (Witness, RootHash, Version) {
+    let kvs = (0u8..10)
+        .map(|i| (KeyHash([i * 16; 32]), alloc::vec![i]))
+        .collect();
+    let (db, version) = init_mock_db::<Sha256>(&kvs);
+    let recording_reader = RecordingTreeReader::new(db);
+    let tree = JellyfishMerkleTree::<_, Sha256>::new(&recording_reader);
+    for key in keys {
+        tree.get_with_proof(*key, version).unwrap();
+        tree.get(*key, version).unwrap();
+    }
+    let root_hash = tree.get_root_hash(version).unwrap();
+    (recording_reader.take_witness(), root_hash, version)
+}
+
+#[test]
+fn test_witness_verification() {
+    let keys = [KeyHash([0x10; 32]), KeyHash([0x15; 32])];
+    let (witness, root_hash, version) = record_witness(&keys);
+    witness.verify::<Sha256>(root_hash, version).unwrap();
+    assert!(witness
+        .verify::<Sha256>(RootHash([0; 32]), version)
+        .is_err());
+    assert!(witness.verify::<Sha256>(root_hash, version + 1).is_err());
+
+    // Swapping a value for another one breaks the link to its leaf.
+    let mut tampered = witness.clone();
+    let present = tampered
+        .values
+        .get_mut(&(version, keys[0]))
+        .unwrap()
+        .as_mut()
+        .unwrap();
+    present[0] ^= 1;
+    assert!(tampered.verify::<Sha256>(root_hash, version).is_err());
+
+    // An absent key cannot be given a value, and a present key cannot be hidden.
+    let mut tampered = witness.clone();
+    *tampered.values.get_mut(&(version, keys[1])).unwrap() = Some(alloc::vec![1]);
+    assert!(tampered.verify::<Sha256>(root_hash, version).is_err());
+    let mut tampered = witness.clone();
+    *tampered.values.get_mut(&(version, keys[0])).unwrap() = None;
+    assert!(tampered.verify::<Sha256>(root_hash, version).is_err());
+
+    // Replacing a leaf changes its hash, which no longer matches its parent.
+    let mut tampered = witness.clone();
+    let leaf = tampered
+        .nodes
+        .values_mut()
+        .flatten()
+        .find(|node| matches!(node, Node::Leaf(_)))
+        .unwrap();
+    *leaf = Node::new_leaf(KeyHash([0x10; 32]), ValueHash([0; 32]));
+    assert!(tampered.verify::<Sha256>(root_hash, version).is_err());
+
+    // Nodes which are not part of the tree are rejected too.
+    let mut tampered = witness.clone();
+    tampered.nodes.insert(
+        NodeKey::new_empty_path(version + 1),
+        Some(Node::new_leaf(keys[0], ValueHash([0; 32]))),
+    );
+    assert!(tampered.verify::<Sha256>(root_hash, version).is_err());
+
+    assert!(WitnessTreeReader::new::<Sha256>(witness, RootHash([0; 32]), version).is_err());
+}
+
+#[test]
+fn test_witness_reader_does_not_restore() {
+    let (witness, root_hash, version) = record_witness(&[]);
+    assert!(
+        WitnessTreeReader::new::<Sha256>(witness, root_hash, version)
+            .unwrap()
+            .get_rightmost_leaf()
+            .is_err()
+    );
+}
+
+#[test]
+fn test_replay_at_skipped_version() {
+    // Version 1 is skipped, so reading it reads the tree of version 0.
+    let db = MockTreeStore::default();
+    let key = KeyHash([0x10; 32]);
+    let absent_key = KeyHash([0x20; 32]);
+    let tree = JellyfishMerkleTree::<_, Sha256>::new(&db);
+    let (_root_hash, batch) = tree
+        .put_value_set_after(alloc::vec![(key, Some(alloc::vec![0]))], None, 0)
+        .unwrap();
+    db.write_tree_update_batch(batch).unwrap();
+    let (_root_hash, batch) = tree
+        .put_value_set_after(alloc::vec![(key, Some(alloc::vec![2]))], Some(0), 2)
+        .unwrap();
+    db.write_tree_update_batch(batch).unwrap();
+
+    let recording_reader = RecordingTreeReader::new(db);
+    let expected = execute::<_, Sha256>(&recording_reader, 1, &[key, absent_key], &[]).unwrap();
+    let (db, witness) = recording_reader.into_parts();
+    assert_eq!(witness.resolved_versions().get(&1), Some(&Some(0)));
+    let root_hash = JellyfishMerkleTree::<_, Sha256>::new(&db)
+        .get_root_hash(1)
+        .unwrap();
+
+    let witness_reader = WitnessTreeReader::new::<Sha256>(witness.clone(), root_hash, 1).unwrap();
+    let replayed = execute::<_, Sha256>(&witness_reader, 1, &[key, absent_key], &[]).unwrap();
+    assert_eq!(replayed, expected);
+    assert_eq!(replayed.0, alloc::vec![Some(alloc::vec![0]), None]);
+
+    // The witness vouches for the version it was recorded at only.
+    assert!(witness.verify::<Sha256>(root_hash, 0).is_err());
+    let mut tampered = witness;
+    tampered.resolved_versions.insert(1, Some(2));
+    assert!(tampered.verify::<Sha256>(root_hash, 1).is_err());
+}
@@ -1,5 +1,5 @@
 use crate::storage::Node::Leaf;
-use alloc::{collections::BTreeMap, vec::Vec};
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
 use alloc::{format, vec};
 use anyhow::{bail, ensure, format_err, Context, Result};
 use core::marker::PhantomData;
@@ -12,7 +12,9 @@ use std::collections::HashMap;
 use crate::proof::definition::UpdateMerkleProof;
 use crate::proof::{SparseMerkleLeafNode, SparseMerkleNode};
 use crate::{
+    metrics::{MeteredReader, NodeReadCounter, NoopMetrics, Phase, PhaseTimer, TreeMetrics},
     node_type::{Child, Children, InternalNode, LeafNode, Node, NodeKey, NodeType},
+    reader::resolve_root_key,
     storage::{TreeReader, TreeUpdateBatch},
     tree_cache::TreeCache,
     types::{
@@ -20,24 +22,47 @@ use crate::{
             nibble_path::{skip_common_prefix, NibbleIterator, NibblePath},
             Nibble, NibbleRangeIterator, ROOT_NIBBLE_HEIGHT,
         },
-        proof::{SparseMerkleProof, SparseMerkleRangeProof},
+        proof::{ExclusionProof, SparseMerkleProof, SparseMerkleRangeProof},
         Version,
     },
-    Bytes32Ext, KeyHash, MissingRootError, OwnedValue, RootHash, SimpleHasher, ValueHash,
+    Bytes32Ext, KeyHash, MissingRootError, OwnedValue, RootHash, SimpleHasher, TracedError,
+    TracedKeyHash, ValueHash,
 };
+use tracing::field::{debug, display, Empty};
 
 /// A [`JellyfishMerkleTree`] instantiated using the `sha2::Sha256` hasher.
 /// This is a sensible default choice for most applications.
 #[cfg(any(test, feature = "sha2"))]
 pub type Sha256Jmt<'a, R> = JellyfishMerkleTree<'a, R, sha2::Sha256>;
 
+/// A [`JellyfishMerkleTree`] instantiated using the [`Blake3Hasher`](crate::Blake3Hasher), which is faster than
+/// SHA-256 on most platforms.
+#[cfg(any(test, feature = "blake3"))]
+pub type Blake3Jmt<'a, R> = JellyfishMerkleTree<'a, R, crate::Blake3Hasher>;
+
+/// A [`JellyfishMerkleTree`] instantiated using the `sha3::Keccak256` hasher, whose proofs can be
+/// verified cheaply on the EVM.
+#[cfg(any(test, feature = "keccak"))]
+pub type Keccak256Jmt<'a, R> = JellyfishMerkleTree<'a, R, sha3::Keccak256>;
+
+/// A [`JellyfishMerkleTree`] instantiated using the [`PoseidonHasher`](crate::poseidon::PoseidonHasher),
+/// whose proofs can be verified cheaply in arithmetic circuits.
+#[cfg(any(test, feature = "poseidon"))]
+pub type PoseidonJmt<'a, R> = JellyfishMerkleTree<'a, R, crate::poseidon::PoseidonHasher>;
+
 /// A Jellyfish Merkle tree data structure, parameterized by a [`TreeReader`] `R`
 /// and a [`SimpleHasher`] `H`. See [`crate`] for description.
 pub struct JellyfishMerkleTree<'a, R, H: SimpleHasher> {
     reader: &'a R,
+    metrics: Arc<dyn TreeMetrics>,
     _phantom_hasher: PhantomData<H>,
 }
 
+mod dot;
+mod owned;
+mod versions;
+pub use owned::Jmt;
+
 #[cfg(feature = "ics23")]
 pub mod ics23_impl;
 
@@ -50,12 +75,46 @@ where
     pub fn new(reader: &'a R) -> Self {
         Self {
             reader,
+            metrics: Arc::new(NoopMetrics),
             _phantom_hasher: Default::default(),
         }
     }
 
+    /// Reports the work done by the operations on this tree to `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<dyn TreeMetrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Returns the reader, reporting the nodes read through it to the metrics of this tree.
+    fn metered_reader(&self) -> MeteredReader<'_, R> {
+        MeteredReader::new(self.reader, self.metrics.as_ref())
+    }
+
+    /// Runs `operation` on a view of this tree which counts the nodes read from storage, then
+    /// records that count in the `node_reads` field of `span`, and the error in its `error` field.
+    fn traced<T>(
+        &self,
+        span: &tracing::Span,
+        operation: impl FnOnce(&JellyfishMerkleTree<'_, R, H>) -> Result<T>,
+    ) -> Result<T> {
+        let node_reads = Arc::new(NodeReadCounter::new(self.metrics.clone()));
+        let tree = JellyfishMerkleTree {
+            reader: self.reader,
+            metrics: node_reads.clone(),
+            _phantom_hasher: PhantomData,
+        };
+        let result = operation(&tree);
+        span.record("node_reads", node_reads.node_reads());
+        if let Err(err) = &result {
+            span.record("error", display(TracedError(err)));
+        }
+        result
+    }
+
     /// Get the node hash from the cache if exists, otherwise compute it.
     fn get_hash(
+        &self,
         node_key: &NodeKey,
         node: &Node,
         hash_cache: &Option<&HashMap<NibblePath, [u8; 32]>>,
@@ -66,6 +125,7 @@ where
                 None => unreachable!("{:?} can not be found in hash cache", node_key),
             }
         } else {
+            self.metrics.nodes_hashed(1);
             node.hash::<H>()
         }
     }
@@ -77,7 +137,32 @@ where
         node_hashes: Option<Vec<&HashMap<NibblePath, [u8; 32]>>>,
         first_version: Version,
     ) -> Result<(Vec<RootHash>, TreeUpdateBatch)> {
-        let mut tree_cache = TreeCache::new(self.reader, first_version)?;
+        let span = tracing::debug_span!(
+            "batch_put_value_sets",
+            first_version,
+            num_versions = value_sets.len(),
+            num_keys = value_sets.iter().map(Vec::len).sum::<usize>(),
+            node_reads = Empty,
+            root_hash = Empty,
+            error = Empty,
+        );
+        let _guard = span.enter();
+        let (root_hashes, batch) = self.traced(&span, |tree| {
+            tree.batch_put_value_sets_impl(value_sets, node_hashes, first_version)
+        })?;
+        span.record("root_hash", debug(root_hashes.last()));
+        Ok((root_hashes, batch))
+    }
+
+    fn batch_put_value_sets_impl(
+        &self,
+        value_sets: Vec<Vec<(KeyHash, OwnedValue)>>,
+        node_hashes: Option<Vec<&HashMap<NibblePath, [u8; 32]>>>,
+        first_version: Version,
+    ) -> Result<(Vec<RootHash>, TreeUpdateBatch)> {
+        let _timer = PhaseTimer::start(self.metrics.as_ref(), Phase::Update);
+        let mut tree_cache =
+            TreeCache::new(self.reader, first_version)?.with_metrics(self.metrics.as_ref());
         let hash_sets: Vec<_> = match node_hashes {
             Some(hashes) => hashes.into_iter().map(Some).collect(),
             None => (0..value_sets.len()).map(|_| None).collect(),
@@ -177,7 +262,7 @@ where
                     children.insert(
                         child_index,
                         Child::new(
-                            Self::get_hash(&new_child_node_key, &new_child_node, hash_cache),
+                            self.get_hash(&new_child_node_key, &new_child_node, hash_cache),
                             version,
                             new_child_node.node_type(),
                         ),
@@ -272,7 +357,7 @@ where
                 children.insert(
                     child_index,
                     Child::new(
-                        Self::get_hash(&new_child_node_key, &new_child_node, hash_cache),
+                        self.get_hash(&new_child_node_key, &new_child_node, hash_cache),
                         version,
                         new_child_node.node_type(),
                     ),
@@ -281,6 +366,7 @@ where
             if isolated_existing_leaf {
                 let existing_leaf_node_key =
                     node_key.gen_child_node_key(version, existing_leaf_bucket);
+                self.metrics.nodes_hashed(1);
                 children.insert(
                     existing_leaf_bucket,
                     Child::new(existing_leaf_node.hash::<H>(), version, NodeType::Leaf),
@@ -325,7 +411,7 @@ where
                 children.insert(
                     child_index,
                     Child::new(
-                        Self::get_hash(&new_child_node_key, &new_child_node, hash_cache),
+                        self.get_hash(&new_child_node_key, &new_child_node, hash_cache),
                         version,
                         new_child_node.node_type(),
                     ),
@@ -422,7 +508,71 @@ where
         value_sets: impl IntoIterator<Item = impl IntoIterator<Item = (KeyHash, Option<OwnedValue>)>>,
         first_version: Version,
     ) -> Result<(Vec<RootHash>, TreeUpdateBatch)> {
-        let mut tree_cache = TreeCache::new(self.reader, first_version)?;
+        let span = tracing::debug_span!(
+            "put_value_sets",
+            first_version,
+            num_versions = Empty,
+            num_keys = Empty,
+            node_reads = Empty,
+            root_hash = Empty,
+            error = Empty,
+        );
+        let _guard = span.enter();
+        let (root_hashes, batch) = self.traced(&span, |tree| {
+            tree.put_value_sets_impl(value_sets, first_version, || {
+                TreeCache::new(tree.reader, first_version)
+            })
+        })?;
+        // The value sets are only known once consumed, and the batch holds one value per key
+        // updated at each version.
+        span.record("num_versions", root_hashes.len());
+        span.record("num_keys", batch.node_batch.values().len());
+        span.record("root_hash", debug(root_hashes.last()));
+        Ok((root_hashes, batch))
+    }
+
+    /// Same as [`put_value_set`](Self::put_value_set), but applies `value_set` to the tree at
+    /// `base_version`, or to an empty tree if it is `None`, rather than to the tree at
+    /// `version - 1`. `base_version` must be before `version`.
+    ///
+    /// This lets versions skip the heights at which the tree does not change, instead of writing
+    /// a copy of the root node for each of them. The tree at such a height is the one at the
+    /// latest version before it, which reads [resolve](Self::resolve_version) to, provided the
+    /// store overrides [`TreeReader::latest_version_at_or_before`].
+    pub fn put_value_set_after(
+        &self,
+        value_set: impl IntoIterator<Item = (KeyHash, Option<OwnedValue>)>,
+        base_version: Option<Version>,
+        version: Version,
+    ) -> Result<(RootHash, TreeUpdateBatch)> {
+        let span = tracing::debug_span!(
+            "put_value_set_after",
+            base_version,
+            version,
+            num_keys = Empty,
+            node_reads = Empty,
+            root_hash = Empty,
+            error = Empty,
+        );
+        let _guard = span.enter();
+        let (root_hashes, batch) = self.traced(&span, |tree| {
+            tree.put_value_sets_impl(vec![value_set], version, || {
+                TreeCache::new_after(tree.reader, base_version, version)
+            })
+        })?;
+        span.record("num_keys", batch.node_batch.values().len());
+        span.record("root_hash", debug(root_hashes[0]));
+        Ok((root_hashes[0], batch))
+    }
+
+    fn put_value_sets_impl(
+        &self,
+        value_sets: impl IntoIterator<Item = impl IntoIterator<Item = (KeyHash, Option<OwnedValue>)>>,
+        first_version: Version,
+        new_tree_cache: impl FnOnce() -> Result<TreeCache<'a, R>>,
+    ) -> Result<(Vec<RootHash>, TreeUpdateBatch)> {
+        let _timer = PhaseTimer::start(self.metrics.as_ref(), Phase::Update);
+        let mut tree_cache = new_tree_cache()?.with_metrics(self.metrics.as_ref());
         for (idx, value_set) in value_sets.into_iter().enumerate() {
             let version = first_version + idx as u64;
             for (i, (key, value)) in value_set.into_iter().enumerate() {
@@ -452,7 +602,9 @@ where
         value_set: impl IntoIterator<Item = (KeyHash, Option<OwnedValue>)>,
         latest_version: Version,
     ) -> Result<(RootHash, TreeUpdateBatch)> {
-        let mut tree_cache = TreeCache::new_overwrite(self.reader, latest_version)?;
+        let _timer = PhaseTimer::start(self.metrics.as_ref(), Phase::Update);
+        let mut tree_cache = TreeCache::new_overwrite(self.reader, latest_version)?
+            .with_metrics(self.metrics.as_ref());
         for (i, (key, value)) in value_set.into_iter().enumerate() {
             let action = if value.is_some() { "insert" } else { "delete" };
             let value_hash = value.as_ref().map(|v| ValueHash::with::<H>(v));
@@ -486,7 +638,9 @@ where
         value_sets: impl IntoIterator<Item = impl IntoIterator<Item = (KeyHash, Option<OwnedValue>)>>,
         first_version: Version,
     ) -> Result<(Vec<(RootHash, UpdateMerkleProof<H>)>, TreeUpdateBatch)> {
-        let mut tree_cache = TreeCache::new(self.reader, first_version)?;
+        let _timer = PhaseTimer::start(self.metrics.as_ref(), Phase::Update);
+        let mut tree_cache =
+            TreeCache::new(self.reader, first_version)?.with_metrics(self.metrics.as_ref());
         let mut batch_proofs = Vec::new();
         for (idx, value_set) in value_sets.into_iter().enumerate() {
             let version = first_version + idx as u64;
@@ -772,6 +926,7 @@ where
             }
             PutResult::Updated((_, new_node)) => {
                 // update child
+                self.metrics.nodes_hashed(1);
                 children.insert(
                     child_index,
                     Child::new(new_node.hash::<H>(), version, new_node.node_type()),
@@ -909,6 +1064,7 @@ where
             assert_ne!(existing_leaf_index, new_leaf_index);
 
             let mut children = Children::new();
+            self.metrics.nodes_hashed(1);
             children.insert(
                 existing_leaf_index,
                 Child::new(existing_leaf_node.hash::<H>(), version, NodeType::Leaf),
@@ -925,6 +1081,7 @@ where
                 value,
                 tree_cache,
             )?;
+            self.metrics.nodes_hashed(1);
             children.insert(
                 new_leaf_index,
                 Child::new(new_leaf_node.hash::<H>(), version, NodeType::Leaf),
@@ -941,6 +1098,7 @@ where
                     .expect("Common nibble_path below internal node ran out of nibble");
                 node_key = NodeKey::new(version, common_nibble_path.clone());
                 let mut children = Children::new();
+                self.metrics.nodes_hashed(1);
                 children.insert(
                     nibble,
                     Child::new(
@@ -1007,9 +1165,32 @@ where
         &self,
         key: KeyHash,
         version: Version,
+    ) -> Result<(Option<OwnedValue>, SparseMerkleProof<H>)> {
+        let span = tracing::debug_span!(
+            "get_with_proof",
+            key_hash = ?TracedKeyHash(key),
+            version,
+            node_reads = Empty,
+            found = Empty,
+            num_siblings = Empty,
+            error = Empty,
+        );
+        let _guard = span.enter();
+        let _timer = PhaseTimer::start(self.metrics.as_ref(), Phase::Proof);
+        let (value, proof) = self.traced(&span, |tree| tree.lookup_with_proof(key, version))?;
+        span.record("found", value.is_some());
+        span.record("num_siblings", proof.siblings().len());
+        self.metrics.proof_generated(proof.siblings().len());
+        Ok((value, proof))
+    }
+
+    fn lookup_with_proof(
+        &self,
+        key: KeyHash,
+        version: Version,
     ) -> Result<(Option<OwnedValue>, SparseMerkleProof<H>)> {
         // Empty tree just returns proof with no sibling hash.
-        let mut next_node_key = NodeKey::new_empty_path(version);
+        let mut next_node_key = self.root_node_key(version)?;
         let mut siblings: Vec<SparseMerkleNode> = vec![];
         let nibble_path = NibblePath::new(key.0.to_vec());
         let mut nibble_iter = nibble_path.nibbles();
@@ -1017,13 +1198,16 @@ where
         // We limit the number of loops here deliberately to avoid potential cyclic graph bugs
         // in the tree structure.
         for nibble_depth in 0..=ROOT_NIBBLE_HEIGHT {
-            let next_node = self.reader.get_node(&next_node_key).map_err(|err| {
-                if nibble_depth == 0 {
-                    anyhow::anyhow!(MissingRootError { version })
-                } else {
-                    err
-                }
-            })?;
+            let next_node = self
+                .metered_reader()
+                .get_node(&next_node_key)
+                .map_err(|err| {
+                    if nibble_depth == 0 {
+                        anyhow::anyhow!(MissingRootError { version })
+                    } else {
+                        err
+                    }
+                })?;
             match next_node {
                 Node::Internal(internal_node) => {
                     let queried_child_index = nibble_iter
@@ -1032,7 +1216,7 @@ where
 
                     let (child_node_key, mut siblings_in_internal) = internal_node
                         .get_only_child_with_siblings::<H>(
-                            self.reader,
+                            &self.metered_reader(),
                             &next_node_key,
                             queried_child_index,
                         );
@@ -1041,17 +1225,19 @@ where
                     next_node_key = match child_node_key {
                         Some(node_key) => node_key,
                         None => {
+                            self.metrics.lookup_depth(nibble_depth);
                             return Ok((
                                 None,
                                 SparseMerkleProof::new(None, {
                                     siblings.reverse();
                                     siblings
                                 }),
-                            ))
+                            ));
                         }
                     };
                 }
                 Node::Leaf(leaf_node) => {
+                    self.metrics.lookup_depth(nibble_depth);
                     return Ok((
                         if leaf_node.key_hash() == key {
                             Some(self.reader.get_value(version, leaf_node.key_hash())?)
@@ -1066,6 +1252,7 @@ where
                 }
                 Node::Null => {
                     if nibble_depth == 0 {
+                        self.metrics.lookup_depth(nibble_depth);
                         return Ok((None, SparseMerkleProof::new(None, vec![])));
                     } else {
                         bail!(
@@ -1133,17 +1320,20 @@ where
     ) -> Result<SearchResult> {
         let search_path = NibblePath::new(search_key.0.to_vec());
         let mut search_nibbles = search_path.nibbles();
-        let mut next_node_key = NodeKey::new_empty_path(version);
+        let mut next_node_key = self.root_node_key(version)?;
         let mut internal_nodes = vec![];
 
         for nibble_depth in 0..=ROOT_NIBBLE_HEIGHT {
-            let next_node = self.reader.get_node(&next_node_key).map_err(|err| {
-                if nibble_depth == 0 {
-                    anyhow::anyhow!(MissingRootError { version })
-                } else {
-                    err
-                }
-            })?;
+            let next_node = self
+                .metered_reader()
+                .get_node(&next_node_key)
+                .map_err(|err| {
+                    if nibble_depth == 0 {
+                        anyhow::anyhow!(MissingRootError { version })
+                    } else {
+                        err
+                    }
+                })?;
 
             match next_node {
                 Node::Internal(node) => {
@@ -1266,6 +1456,28 @@ where
         &self,
         key_hash: KeyHash,
         version: Version,
+    ) -> Result<Result<(OwnedValue, SparseMerkleProof<H>), ExclusionProof<H>>> {
+        let span = tracing::debug_span!(
+            "get_with_exclusion_proof",
+            key_hash = ?TracedKeyHash(key_hash),
+            version,
+            node_reads = Empty,
+            found = Empty,
+            error = Empty,
+        );
+        let _guard = span.enter();
+        let proof_or_exclusion = self.traced(&span, |tree| {
+            tree.lookup_with_exclusion_proof(key_hash, version)
+        })?;
+        span.record("found", proof_or_exclusion.is_ok());
+        Ok(proof_or_exclusion)
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn lookup_with_exclusion_proof(
+        &self,
+        key_hash: KeyHash,
+        version: Version,
     ) -> Result<Result<(OwnedValue, SparseMerkleProof<H>), ExclusionProof<H>>> {
         // Optimistically attempt get_with_proof, if that succeeds, we're done.
         if let (Some(value), proof) = self.get_with_proof(key_hash, version)? {
@@ -1322,7 +1534,7 @@ where
         };
 
         for nibble_depth in nibble_depth..=ROOT_NIBBLE_HEIGHT {
-            let node = self.reader.get_node(&node_key).map_err(|err| {
+            let node = self.metered_reader().get_node(&node_key).map_err(|err| {
                 if nibble_depth == 0 {
                     anyhow::anyhow!(MissingRootError { version })
                 } else {
@@ -1398,18 +1610,39 @@ where
     }
 
     pub(crate) fn get_root_node_option(&self, version: Version) -> Result<Option<Node>> {
-        let root_node_key = NodeKey::new_empty_path(version);
-        self.reader.get_node_option(&root_node_key)
+        let root_node_key = self.root_node_key(version)?;
+        self.metered_reader().get_node_option(&root_node_key)
+    }
+
+    /// Returns the version holding the tree as it was at `version`: `version` itself if it was
+    /// written, or else the latest version written before it, as versions written with
+    /// [`put_value_set_after`](Self::put_value_set_after) may skip some. Returns `None` if there
+    /// is no such version.
+    ///
+    /// Reading the tree at any version resolves it first, so this is only needed to tell which
+    /// version was actually read.
+    pub fn resolve_version(&self, version: Version) -> Result<Option<Version>> {
+        self.reader.latest_version_at_or_before(version)
+    }
+
+    /// Returns the key of the root node of the tree at `version`, which is the one of the version
+    /// it resolves to. A version which does not resolve keeps its own key, so that reading it
+    /// fails as a missing root.
+    fn root_node_key(&self, version: Version) -> Result<NodeKey> {
+        resolve_root_key(self.reader, version)
     }
 
     pub fn get_root_hash(&self, version: Version) -> Result<RootHash> {
-        self.get_root_node(version).map(|n| RootHash(n.hash::<H>()))
+        let root_node = self.get_root_node(version)?;
+        self.metrics.nodes_hashed(1);
+        Ok(RootHash(root_node.hash::<H>()))
     }
 
     pub fn get_root_hash_option(&self, version: Version) -> Result<Option<RootHash>> {
-        Ok(self
-            .get_root_node_option(version)?
-            .map(|n| RootHash(n.hash::<H>())))
+        Ok(self.get_root_node_option(version)?.map(|root_node| {
+            self.metrics.nodes_hashed(1);
+            RootHash(root_node.hash::<H>())
+        }))
     }
 
     // TODO: should this be public? seems coupled to tests?
@@ -1428,21 +1661,6 @@ enum PutResult<T> {
     NotChanged,
 }
 
-/// A proof of non-existence by exclusion between two adjacent neighbors.
-#[derive(Debug)]
-pub enum ExclusionProof<H: SimpleHasher> {
-    Leftmost {
-        leftmost_right_proof: SparseMerkleProof<H>,
-    },
-    Middle {
-        leftmost_right_proof: SparseMerkleProof<H>,
-        rightmost_left_proof: SparseMerkleProof<H>,
-    },
-    Rightmost {
-        rightmost_left_proof: SparseMerkleProof<H>,
-    },
-}
-
 #[derive(Debug, Clone, Copy)]
 enum Extreme {
     Left,
@@ -0,0 +1,144 @@
+use alloc::{collections::BTreeSet, format, string::String, vec, vec::Vec};
+use core::fmt::Write;
+
+use anyhow::Result;
+
+use crate::{
+    node_type::{Node, NodeKey},
+    reader::resolve_root_key,
+    storage::{TreeReader, TreeUpdateBatch},
+    types::nibble::nibble_path::NibblePath,
+    Bytes32Ext, JellyfishMerkleTree, SimpleHasher, Version,
+};
+
+/// Returns the first 4 bytes of `hash` in hex, which is enough to tell nodes apart in a drawing.
+fn short_hash(hash: &[u8; 32]) -> String {
+    hex::encode(&hash[..4])
+}
+
+impl<'a, R, H> JellyfishMerkleTree<'a, R, H>
+where
+    R: 'a + TreeReader,
+    H: SimpleHasher,
+{
+    /// Renders the subtree under `nibble_path` at `version` in the
+    /// [DOT language](https://graphviz.org/doc/info/lang.html), for instance to be drawn with
+    /// `dot -Tsvg`. An empty `nibble_path` renders the whole tree.
+    ///
+    /// Internal nodes are drawn with their 16 child slots, each occupied slot showing the version
+    /// and the short hash of its child. Leaves show their key hash and value hash.
+    ///
+    /// If `stale_nodes` is given, the nodes it marks as stale are highlighted. Passing the batch
+    /// produced by the update to `version + 1` shows which nodes that update replaced.
+    pub fn to_dot(
+        &self,
+        version: Version,
+        nibble_path: &NibblePath,
+        stale_nodes: Option<&TreeUpdateBatch>,
+    ) -> Result<String> {
+        let stale_node_keys: BTreeSet<&NodeKey> = stale_nodes
+            .into_iter()
+            .flat_map(|batch| batch.stale_node_index_batch.iter())
+            .map(|stale_node_index| &stale_node_index.node_key)
+            .collect();
+
+        let mut dot = String::new();
+        writeln!(dot, "digraph jmt {{")?;
+        writeln!(dot, "  node [shape=record, fontname=monospace];")?;
+
+        if let Some(subtree_root) = self.find_subtree_root(version, nibble_path)? {
+            let mut pending = vec![(subtree_root, None)];
+            let mut next_id = 0;
+            while let Some((node_key, parent)) = pending.pop() {
+                let id = next_id;
+                next_id += 1;
+                let label = match self.reader.get_node(&node_key)? {
+                    Node::Null => String::from("null"),
+                    Node::Leaf(leaf_node) => format!(
+                        "{{leaf {:?} | v{} | key {} | value {}}}",
+                        node_key.nibble_path(),
+                        node_key.version(),
+                        short_hash(&leaf_node.key_hash().0),
+                        short_hash(&leaf_node.value_hash().0),
+                    ),
+                    Node::Internal(internal_node) => {
+                        let mut slots = Vec::with_capacity(16);
+                        for nibble in 0..16u8 {
+                            match internal_node.child(nibble.into()) {
+                                Some(child) => {
+                                    slots.push(format!(
+                                        "<c{:x}> {:x}: v{} {}",
+                                        nibble,
+                                        nibble,
+                                        child.version,
+                                        short_hash(&child.hash),
+                                    ));
+                                }
+                                None => slots.push(format!("<c{:x}> {:x}: -", nibble, nibble)),
+                            }
+                        }
+                        // Children are pushed in reverse so that they are numbered left to right.
+                        let children: Vec<_> = internal_node.children_sorted().collect();
+                        for (nibble, child) in children.into_iter().rev() {
+                            pending.push((
+                                node_key.gen_child_node_key(child.version, nibble),
+                                Some((id, nibble)),
+                            ));
+                        }
+                        format!(
+                            "{{internal {:?} | v{} | {} | leaves {} | {{{}}}}}",
+                            node_key.nibble_path(),
+                            node_key.version(),
+                            short_hash(&internal_node.hash::<H>()),
+                            internal_node.leaf_count(),
+                            slots.join(" | "),
+                        )
+                    }
+                };
+
+                let style = if stale_node_keys.contains(&node_key) {
+                    ", style=filled, fillcolor=salmon"
+                } else {
+                    ""
+                };
+                writeln!(dot, "  n{} [label=\"{}\"{}];", id, label, style)?;
+                if let Some((parent_id, nibble)) = parent {
+                    writeln!(dot, "  n{}:c{:x} -> n{};", parent_id, nibble, id)?;
+                }
+            }
+        }
+
+        writeln!(dot, "}}")?;
+        Ok(dot)
+    }
+
+    /// Returns the key of the node at the top of the subtree under `nibble_path` at `version`,
+    /// or `None` if that subtree is empty.
+    fn find_subtree_root(
+        &self,
+        version: Version,
+        nibble_path: &NibblePath,
+    ) -> Result<Option<NodeKey>> {
+        let mut node_key = resolve_root_key(self.reader, version)?;
+        for (depth, nibble) in nibble_path.nibbles().enumerate() {
+            match self.reader.get_node(&node_key)? {
+                Node::Null => return Ok(None),
+                Node::Leaf(leaf_node) => {
+                    // A leaf stands for the whole subtree it is in, as long as its key is in the
+                    // subtree under `nibble_path`.
+                    let in_subtree = nibble_path
+                        .nibbles()
+                        .enumerate()
+                        .skip(depth)
+                        .all(|(i, nibble)| leaf_node.key_hash().0.get_nibble(i) == nibble);
+                    return Ok(in_subtree.then_some(node_key));
+                }
+                Node::Internal(internal_node) => match internal_node.child(nibble) {
+                    Some(child) => node_key = node_key.gen_child_node_key(child.version, nibble),
+                    None => return Ok(None),
+                },
+            }
+        }
+        Ok(Some(node_key))
+    }
+}
@@ -1,14 +1,14 @@
 use alloc::vec;
 use alloc::vec::Vec;
 use anyhow::Result;
+use tracing::field::Empty;
 
 use crate::{
-    proof::{SparseMerkleProof, INTERNAL_DOMAIN_SEPARATOR, LEAF_DOMAIN_SEPARATOR},
+    domain::{DefaultDomain, Domain},
+    proof::{ExclusionProof, SparseMerkleProof},
     storage::HasPreimage,
     storage::TreeReader,
-    tree::ExclusionProof,
-    JellyfishMerkleTree, KeyHash, OwnedValue, SimpleHasher, Version,
-    SPARSE_MERKLE_PLACEHOLDER_HASH,
+    JellyfishMerkleTree, KeyHash, OwnedValue, SimpleHasher, TracedKeyHash, Version,
 };
 
 fn sparse_merkle_proof_to_ics23_existence_proof<H: SimpleHasher>(
@@ -38,14 +38,14 @@ fn sparse_merkle_proof_to_ics23_existence_proof<H: SimpleHasher>(
                     // so prefix = domsep || sibling
                     //    suffix = (empty)
                     let mut prefix = Vec::with_capacity(16 + 32);
-                    prefix.extend_from_slice(INTERNAL_DOMAIN_SEPARATOR);
+                    prefix.extend_from_slice(H::INTERNAL_DOMAIN_SEPARATOR);
                     prefix.extend_from_slice(&proof.siblings()[sibling_idx].hash::<H>());
                     (prefix, Vec::new())
                 } else {
                     // We want hash( domsep || current || sibling )
                     // so prefix = domsep
                     //    suffix = sibling
-                    let prefix = INTERNAL_DOMAIN_SEPARATOR.to_vec();
+                    let prefix = H::INTERNAL_DOMAIN_SEPARATOR.to_vec();
                     let suffix = proof.siblings()[sibling_idx].hash::<H>().to_vec();
                     (prefix, suffix)
                 };
@@ -68,7 +68,7 @@ fn sparse_merkle_proof_to_ics23_existence_proof<H: SimpleHasher>(
             prehash_key: ics23::HashOp::Sha256.into(),
             prehash_value: ics23::HashOp::Sha256.into(),
             length: ics23::LengthOp::NoPrefix.into(),
-            prefix: LEAF_DOMAIN_SEPARATOR.to_vec(),
+            prefix: H::LEAF_DOMAIN_SEPARATOR.to_vec(),
         }),
     }
 }
@@ -195,6 +195,28 @@ where
         version: Version,
     ) -> Result<(Option<OwnedValue>, ics23::CommitmentProof)> {
         let key_hash: KeyHash = KeyHash::with::<H>(key.as_slice());
+        let span = tracing::debug_span!(
+            "get_with_ics23_proof",
+            key_hash = ?TracedKeyHash(key_hash),
+            version,
+            node_reads = Empty,
+            found = Empty,
+            error = Empty,
+        );
+        let _guard = span.enter();
+        let (value, proof) = self.traced(&span, |tree| {
+            tree.lookup_with_ics23_proof(key, key_hash, version)
+        })?;
+        span.record("found", value.is_some());
+        Ok((value, proof))
+    }
+
+    fn lookup_with_ics23_proof(
+        &self,
+        key: Vec<u8>,
+        key_hash: KeyHash,
+        version: Version,
+    ) -> Result<(Option<OwnedValue>, ics23::CommitmentProof)> {
         let proof_or_exclusion = self.get_with_exclusion_proof(key_hash, version)?;
 
         match proof_or_exclusion {
@@ -228,21 +250,27 @@ where
 }
 
 pub fn ics23_spec() -> ics23::ProofSpec {
+    ics23_spec_in_domain::<DefaultDomain>()
+}
+
+/// The spec of the ICS23 proofs of trees hashed in the domain `D`, such as the trees of a
+/// [`WithDomain<H, D>`](crate::domain::WithDomain) hasher.
+pub fn ics23_spec_in_domain<D: Domain>() -> ics23::ProofSpec {
     ics23::ProofSpec {
         leaf_spec: Some(ics23::LeafOp {
             hash: ics23::HashOp::Sha256.into(),
             prehash_key: ics23::HashOp::Sha256.into(),
             prehash_value: ics23::HashOp::Sha256.into(),
             length: ics23::LengthOp::NoPrefix.into(),
-            prefix: LEAF_DOMAIN_SEPARATOR.to_vec(),
+            prefix: D::LEAF_DOMAIN_SEPARATOR.to_vec(),
         }),
         inner_spec: Some(ics23::InnerSpec {
             hash: ics23::HashOp::Sha256.into(),
             child_order: vec![0, 1],
-            min_prefix_length: INTERNAL_DOMAIN_SEPARATOR.len() as i32,
-            max_prefix_length: INTERNAL_DOMAIN_SEPARATOR.len() as i32,
+            min_prefix_length: D::INTERNAL_DOMAIN_SEPARATOR.len() as i32,
+            max_prefix_length: D::INTERNAL_DOMAIN_SEPARATOR.len() as i32,
             child_size: 32,
-            empty_child: SPARSE_MERKLE_PLACEHOLDER_HASH.to_vec(),
+            empty_child: D::SPARSE_MERKLE_PLACEHOLDER_HASH.to_vec(),
         }),
         min_depth: 0,
         max_depth: 64,
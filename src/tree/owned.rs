@@ -0,0 +1,299 @@
+use alloc::{sync::Arc, vec::Vec};
+use core::marker::PhantomData;
+
+#[cfg(not(feature = "std"))]
+use hashbrown::HashMap;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use super::versions::readable_versions;
+use crate::{
+    metrics::{NoopMetrics, TreeMetrics},
+    proof::{definition::UpdateMerkleProof, ExclusionProof, SparseMerkleProof},
+    restore::{JellyfishMerkleRestore, SharedStore},
+    storage::{TreeReader, TreeUpdateBatch, TreeWriter, VersionMetadata},
+    types::{nibble::nibble_path::NibblePath, proof::SparseMerkleRangeProof},
+    JellyfishMerkleIterator, JellyfishMerkleTree, KeyHash, OwnedValue, RootHash, SimpleHasher,
+    Version,
+};
+
+/// An owned handle to a Jellyfish Merkle tree, sharing its [`TreeReader`] `R` through an [`Arc`].
+///
+/// Unlike a [`JellyfishMerkleTree`], which borrows its reader, a `Jmt` can be kept in a
+/// long-lived struct, cloned cheaply, and moved to other threads or tasks whenever `R` is
+/// `Send + Sync`. Every operation borrows a [`JellyfishMerkleTree`] for its duration, which
+/// [`tree`](Self::tree) also gives access to.
+pub struct Jmt<R, H: SimpleHasher> {
+    reader: Arc<R>,
+    metrics: Arc<dyn TreeMetrics>,
+    _phantom_hasher: PhantomData<H>,
+}
+
+// Manually implement Clone to circumvent [incorrect auto-bounds](https://github.com/rust-lang/rust/issues/26925)
+// TODO: Switch back to #[derive] once the perfect_derive feature lands
+impl<R, H: SimpleHasher> Clone for Jmt<R, H> {
+    fn clone(&self) -> Self {
+        Self {
+            reader: self.reader.clone(),
+            metrics: self.metrics.clone(),
+            _phantom_hasher: Default::default(),
+        }
+    }
+}
+
+// Manually implement Debug to circumvent [incorrect auto-bounds](https://github.com/rust-lang/rust/issues/26925)
+// TODO: Switch back to #[derive] once the perfect_derive feature lands
+impl<R: core::fmt::Debug, H: SimpleHasher> core::fmt::Debug for Jmt<R, H> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Jmt")
+            .field("reader", &self.reader)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<R, H> Jmt<R, H>
+where
+    R: TreeReader,
+    H: SimpleHasher,
+{
+    /// Creates a `Jmt` backed by the given [`TreeReader`].
+    pub fn new(reader: Arc<R>) -> Self {
+        Self {
+            reader,
+            metrics: Arc::new(NoopMetrics),
+            _phantom_hasher: Default::default(),
+        }
+    }
+
+    /// Reports the work done by the operations on this tree, and by the iterators it creates, to
+    /// `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<dyn TreeMetrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Returns the reader backing this tree.
+    pub fn reader(&self) -> &Arc<R> {
+        &self.reader
+    }
+
+    /// Borrows this tree as a [`JellyfishMerkleTree`].
+    pub fn tree(&self) -> JellyfishMerkleTree<'_, R, H> {
+        JellyfishMerkleTree::new(self.reader.as_ref()).with_metrics(self.metrics.clone())
+    }
+
+    /// See [`JellyfishMerkleTree::put_value_set`].
+    pub fn put_value_set(
+        &self,
+        value_set: impl IntoIterator<Item = (KeyHash, Option<OwnedValue>)>,
+        version: Version,
+    ) -> Result<(RootHash, TreeUpdateBatch)> {
+        self.tree().put_value_set(value_set, version)
+    }
+
+    /// See [`JellyfishMerkleTree::put_value_set_after`].
+    pub fn put_value_set_after(
+        &self,
+        value_set: impl IntoIterator<Item = (KeyHash, Option<OwnedValue>)>,
+        base_version: Option<Version>,
+        version: Version,
+    ) -> Result<(RootHash, TreeUpdateBatch)> {
+        self.tree()
+            .put_value_set_after(value_set, base_version, version)
+    }
+
+    /// See [`JellyfishMerkleTree::put_value_set_with_proof`].
+    pub fn put_value_set_with_proof(
+        &self,
+        value_set: impl IntoIterator<Item = (KeyHash, Option<OwnedValue>)>,
+        version: Version,
+    ) -> Result<(RootHash, UpdateMerkleProof<H>, TreeUpdateBatch)> {
+        self.tree().put_value_set_with_proof(value_set, version)
+    }
+
+    /// See [`JellyfishMerkleTree::put_value_sets`].
+    pub fn put_value_sets(
+        &self,
+        value_sets: impl IntoIterator<Item = impl IntoIterator<Item = (KeyHash, Option<OwnedValue>)>>,
+        first_version: Version,
+    ) -> Result<(Vec<RootHash>, TreeUpdateBatch)> {
+        self.tree().put_value_sets(value_sets, first_version)
+    }
+
+    /// See [`JellyfishMerkleTree::put_value_sets_with_proof`].
+    #[allow(clippy::type_complexity)]
+    pub fn put_value_sets_with_proof(
+        &self,
+        value_sets: impl IntoIterator<Item = impl IntoIterator<Item = (KeyHash, Option<OwnedValue>)>>,
+        first_version: Version,
+    ) -> Result<(Vec<(RootHash, UpdateMerkleProof<H>)>, TreeUpdateBatch)> {
+        self.tree()
+            .put_value_sets_with_proof(value_sets, first_version)
+    }
+
+    /// See [`JellyfishMerkleTree::batch_put_value_sets`].
+    pub fn batch_put_value_sets(
+        &self,
+        value_sets: Vec<Vec<(KeyHash, OwnedValue)>>,
+        node_hashes: Option<Vec<&HashMap<NibblePath, [u8; 32]>>>,
+        first_version: Version,
+    ) -> Result<(Vec<RootHash>, TreeUpdateBatch)> {
+        self.tree()
+            .batch_put_value_sets(value_sets, node_hashes, first_version)
+    }
+
+    #[cfg(feature = "migration")]
+    /// See [`JellyfishMerkleTree::append_value_set`].
+    pub fn append_value_set(
+        &self,
+        value_set: impl IntoIterator<Item = (KeyHash, Option<OwnedValue>)>,
+        latest_version: Version,
+    ) -> Result<(RootHash, TreeUpdateBatch)> {
+        self.tree().append_value_set(value_set, latest_version)
+    }
+
+    /// See [`JellyfishMerkleTree::get`].
+    pub fn get(&self, key: KeyHash, version: Version) -> Result<Option<OwnedValue>> {
+        self.tree().get(key, version)
+    }
+
+    /// See [`JellyfishMerkleTree::get_with_proof`].
+    pub fn get_with_proof(
+        &self,
+        key: KeyHash,
+        version: Version,
+    ) -> Result<(Option<OwnedValue>, SparseMerkleProof<H>)> {
+        self.tree().get_with_proof(key, version)
+    }
+
+    /// See [`JellyfishMerkleTree::get_with_exclusion_proof`].
+    #[allow(clippy::type_complexity)]
+    pub fn get_with_exclusion_proof(
+        &self,
+        key_hash: KeyHash,
+        version: Version,
+    ) -> Result<Result<(OwnedValue, SparseMerkleProof<H>), ExclusionProof<H>>> {
+        self.tree().get_with_exclusion_proof(key_hash, version)
+    }
+
+    /// See [`JellyfishMerkleTree::get_range_proof`].
+    pub fn get_range_proof(
+        &self,
+        rightmost_key_to_prove: KeyHash,
+        version: Version,
+    ) -> Result<SparseMerkleRangeProof<H>> {
+        self.tree().get_range_proof(rightmost_key_to_prove, version)
+    }
+
+    /// See [`JellyfishMerkleTree::get_root_hash`].
+    pub fn get_root_hash(&self, version: Version) -> Result<RootHash> {
+        self.tree().get_root_hash(version)
+    }
+
+    /// See [`JellyfishMerkleTree::resolve_version`].
+    pub fn resolve_version(&self, version: Version) -> Result<Option<Version>> {
+        self.tree().resolve_version(version)
+    }
+
+    /// See [`JellyfishMerkleTree::get_root_hash_option`].
+    pub fn get_root_hash_option(&self, version: Version) -> Result<Option<RootHash>> {
+        self.tree().get_root_hash_option(version)
+    }
+
+    /// See [`JellyfishMerkleTree::get_leaf_count`].
+    pub fn get_leaf_count(&self, version: Version) -> Result<usize> {
+        self.tree().get_leaf_count(version)
+    }
+
+    /// Returns an iterator over the key-value pairs of the tree at `version`, starting from the
+    /// smallest key greater than or equal to `starting_key`.
+    pub fn iter(
+        &self,
+        version: Version,
+        starting_key: KeyHash,
+    ) -> Result<JellyfishMerkleIterator<R>> {
+        Ok(
+            JellyfishMerkleIterator::new(self.reader.clone(), version, starting_key)?
+                .with_metrics(self.metrics.clone()),
+        )
+    }
+
+    /// Returns an iterator over the key-value pairs of the tree at `version`, starting from the
+    /// `start_idx`-th smallest key.
+    pub fn iter_by_index(
+        &self,
+        version: Version,
+        start_idx: usize,
+    ) -> Result<JellyfishMerkleIterator<R>> {
+        Ok(
+            JellyfishMerkleIterator::new_by_index(self.reader.clone(), version, start_idx)?
+                .with_metrics(self.metrics.clone()),
+        )
+    }
+}
+
+impl<R, H> Jmt<R, H>
+where
+    R: TreeReader + VersionMetadata,
+    H: SimpleHasher,
+{
+    /// See [`JellyfishMerkleTree::put_value_set_latest`].
+    pub fn put_value_set_latest(
+        &self,
+        value_set: impl IntoIterator<Item = (KeyHash, Option<OwnedValue>)>,
+    ) -> Result<(Version, RootHash, TreeUpdateBatch)> {
+        self.tree().put_value_set_latest(value_set)
+    }
+
+    /// See [`JellyfishMerkleTree::get_latest`].
+    pub fn get_latest(&self, key: KeyHash) -> Result<Option<OwnedValue>> {
+        self.tree().get_latest(key)
+    }
+
+    /// See [`JellyfishMerkleTree::root_hash_history`]. The iterator holds a clone of this handle.
+    pub fn root_hash_history(&self) -> Result<impl Iterator<Item = Result<(Version, RootHash)>>> {
+        let jmt = self.clone();
+        Ok(readable_versions(self.reader.clone())?.map(move |version| {
+            let version = version?;
+            Ok((version, jmt.get_root_hash(version)?))
+        }))
+    }
+}
+
+impl<R, H> Jmt<R, H>
+where
+    R: 'static + TreeReader + TreeWriter + Send + Sync,
+    H: SimpleHasher,
+{
+    /// Starts or resumes restoring the tree at `version` into the storage of this tree, like
+    /// [`JellyfishMerkleRestore::new`]. As the storage is `Send + Sync`, so is the restore, which
+    /// reports its work to the metrics of this tree.
+    pub fn restore(
+        &self,
+        version: Version,
+        expected_root_hash: RootHash,
+    ) -> Result<JellyfishMerkleRestore<H, SharedStore>> {
+        Ok(
+            JellyfishMerkleRestore::new_shared(self.reader.clone(), version, expected_root_hash)?
+                .with_metrics(self.metrics.clone()),
+        )
+    }
+}
+
+#[cfg(feature = "ics23")]
+impl<R, H> Jmt<R, H>
+where
+    R: TreeReader + crate::storage::HasPreimage,
+    H: SimpleHasher,
+{
+    /// See [`JellyfishMerkleTree::get_with_ics23_proof`].
+    pub fn get_with_ics23_proof(
+        &self,
+        key: Vec<u8>,
+        version: Version,
+    ) -> Result<(Option<OwnedValue>, ics23::CommitmentProof)> {
+        self.tree().get_with_ics23_proof(key, version)
+    }
+}
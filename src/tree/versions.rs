@@ -0,0 +1,67 @@
+use core::ops::Deref;
+
+use anyhow::Result;
+
+use crate::{
+    storage::{TreeReader, TreeUpdateBatch, VersionMetadata},
+    JellyfishMerkleTree, KeyHash, OwnedValue, RootHash, SimpleHasher, Version,
+};
+
+impl<'a, R, H> JellyfishMerkleTree<'a, R, H>
+where
+    R: 'a + TreeReader + VersionMetadata,
+    H: SimpleHasher,
+{
+    /// Applies `value_set` on top of the latest version in storage, and returns the new version
+    /// along with the values returned by [`put_value_set`](Self::put_value_set). The first value
+    /// set put in an empty store gets version 0.
+    pub fn put_value_set_latest(
+        &self,
+        value_set: impl IntoIterator<Item = (KeyHash, Option<OwnedValue>)>,
+    ) -> Result<(Version, RootHash, TreeUpdateBatch)> {
+        let version = match self.reader.latest_version()? {
+            Some(latest_version) => latest_version + 1,
+            None => 0,
+        };
+        let (root_hash, batch) = self.put_value_set(value_set, version)?;
+        Ok((version, root_hash, batch))
+    }
+
+    /// Returns the value of `key` at the latest version in storage, without any proof, or `None`
+    /// if there is no such value or no version at all.
+    pub fn get_latest(&self, key: KeyHash) -> Result<Option<OwnedValue>> {
+        match self.reader.latest_version()? {
+            Some(latest_version) => self.get(key, latest_version),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the root hash of every readable version in storage, from the oldest to the latest.
+    pub fn root_hash_history(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<(Version, RootHash)>> + '_> {
+        Ok(readable_versions(self.reader)?.map(move |version| {
+            let version = version?;
+            Ok((version, self.get_root_hash(version)?))
+        }))
+    }
+}
+
+/// Returns the versions of the tree which can be read from `reader`, from the oldest to the
+/// latest.
+pub(crate) fn readable_versions<D, R>(reader: D) -> Result<impl Iterator<Item = Result<Version>>>
+where
+    D: Deref<Target = R>,
+    R: VersionMetadata + ?Sized,
+{
+    // Only the versions written are listed, as the versions skipped by put_value_set_after hold
+    // the tree of an earlier one. Listing stops at the first error.
+    let oldest_version = reader.oldest_readable_version()?;
+    Ok(core::iter::successors(
+        oldest_version.map(Ok),
+        move |version: &Result<Version>| match version {
+            Ok(version) => reader.next_version_after(*version).transpose(),
+            Err(_) => None,
+        },
+    ))
+}
@@ -72,9 +72,11 @@ use hashbrown::{hash_map::Entry, HashMap, HashSet};
 #[cfg(feature = "std")]
 use std::collections::{hash_map::Entry, HashMap, HashSet};
 
-use anyhow::{bail, Result};
+use anyhow::{bail, ensure, format_err, Result};
+use tracing::field::{debug, Empty};
 
 use crate::{
+    metrics::{NoopMetrics, Phase, PhaseTimer, TreeMetrics},
     node_type::{Node, NodeKey},
     storage::{
         NodeBatch, NodeStats, StaleNodeIndex, StaleNodeIndexBatch, TreeReader, TreeUpdateBatch,
@@ -144,6 +146,9 @@ pub struct TreeCache<'a, R> {
 
     /// The underlying persistent storage.
     reader: &'a R,
+
+    /// The observer of the nodes read and written through this cache.
+    metrics: &'a dyn TreeMetrics,
 }
 
 impl<'a, R> TreeCache<'a, R>
@@ -151,29 +156,78 @@ where
     R: 'a + TreeReader,
 {
     /// Constructs a new `TreeCache` instance.
+    ///
+    /// Updates apply to the tree of the latest version written before `next_version`, or to an
+    /// empty tree if there is none, as the first version of a tree may be any.
     pub fn new(reader: &'a R, next_version: Version) -> Result<Self> {
+        let root_version = match next_version.checked_sub(1) {
+            Some(previous_version) => reader.latest_version_to_build_on(previous_version)?,
+            None => None,
+        };
+        Self::new_on(reader, root_version, next_version)
+    }
+
+    /// Constructs a new `TreeCache` instance whose updates apply to the tree at `base_version`,
+    /// or to an empty tree if it is `None`, rather than to the tree at `next_version - 1`.
+    ///
+    /// A base version skipped by the writer is resolved with
+    /// [`TreeReader::latest_version_to_build_on`], so that updates apply to the tree of the
+    /// latest version written before it. It is an error if there is no such version.
+    pub fn new_after(
+        reader: &'a R,
+        base_version: Option<Version>,
+        next_version: Version,
+    ) -> Result<Self> {
+        let root_version = match base_version {
+            Some(base_version) => {
+                ensure!(
+                    base_version < next_version,
+                    "base version {} is not before version {}",
+                    base_version,
+                    next_version
+                );
+                let root_version = reader
+                    .latest_version_to_build_on(base_version)?
+                    .ok_or_else(|| {
+                        format_err!(
+                            "no version at or before base version {} to build version {} on",
+                            base_version,
+                            next_version
+                        )
+                    })?;
+                Some(root_version)
+            }
+            None => None,
+        };
+        Self::new_on(reader, root_version, next_version)
+    }
+
+    /// Constructs a new `TreeCache` instance whose updates apply to the tree whose root was
+    /// written at `root_version`, or to an empty tree if it is `None`.
+    fn new_on(reader: &'a R, root_version: Option<Version>, next_version: Version) -> Result<Self> {
         let mut node_cache = HashMap::new();
-        let root_node_key = if next_version == 0 {
-            let pre_genesis_root_key = NodeKey::new_empty_path(PRE_GENESIS_VERSION);
-            let pre_genesis_root = reader.get_node_option(&pre_genesis_root_key)?;
-
-            match pre_genesis_root {
-                Some(_) => {
-                    // This is to support the extreme case where things really went wild,
-                    // and we need to ditch the transaction history and apply a new
-                    // genesis on top of an existing state db.
-                    pre_genesis_root_key
-                }
-                None => {
-                    // Hack: We need to start from an empty tree, so we insert
-                    // a null node beforehand deliberately to deal with this corner case.
-                    let genesis_root_key = NodeKey::new_empty_path(0);
-                    node_cache.insert(genesis_root_key.clone(), Node::new_null());
-                    genesis_root_key
+        let root_node_key = match root_version {
+            Some(root_version) => NodeKey::new_empty_path(root_version),
+            None => {
+                let pre_genesis_root_key = NodeKey::new_empty_path(PRE_GENESIS_VERSION);
+                let pre_genesis_root = reader.get_node_option(&pre_genesis_root_key)?;
+
+                match pre_genesis_root {
+                    Some(_) => {
+                        // This is to support the extreme case where things really went wild,
+                        // and we need to ditch the transaction history and apply a new
+                        // genesis on top of an existing state db.
+                        pre_genesis_root_key
+                    }
+                    None => {
+                        // Hack: We need to start from an empty tree, so we insert
+                        // a null node beforehand deliberately to deal with this corner case.
+                        let genesis_root_key = NodeKey::new_empty_path(next_version);
+                        node_cache.insert(genesis_root_key.clone(), Node::new_null());
+                        genesis_root_key
+                    }
                 }
             }
-        } else {
-            NodeKey::new_empty_path(next_version - 1)
         };
         Ok(Self {
             node_cache,
@@ -182,6 +236,7 @@ where
             root_node_key,
             next_version,
             reader,
+            metrics: &NoopMetrics,
             num_stale_leaves: 0,
             num_new_leaves: 0,
             value_cache: Default::default(),
@@ -214,33 +269,52 @@ where
             root_node_key,
             next_version: current_version,
             reader,
+            metrics: &NoopMetrics,
             num_stale_leaves: 0,
             num_new_leaves: 0,
             value_cache: Default::default(),
         })
     }
 
+    /// Reports the nodes read and written through this cache to `metrics`.
+    pub fn with_metrics(mut self, metrics: &'a dyn TreeMetrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
     /// Gets a node with given node key. If it doesn't exist in node cache, read from `reader`.
     pub fn get_node(&self, node_key: &NodeKey) -> Result<Node> {
-        Ok(if let Some(node) = self.node_cache.get(node_key) {
-            node.clone()
-        } else if let Some(node) = self.frozen_cache.node_cache.nodes().get(node_key) {
-            node.clone()
-        } else {
-            self.reader.get_node(node_key)?
-        })
+        match self.get_cached_node(node_key) {
+            Some(node) => Ok(node.clone()),
+            None => {
+                let node = self.reader.get_node(node_key);
+                self.metrics.node_read(node.is_ok());
+                node
+            }
+        }
     }
 
     /// Gets a node with the given node key. If it doesn't exist in node cache, read from `reader`
     /// If it doesn't exist anywhere, return `None`.
     pub fn get_node_option(&self, node_key: &NodeKey) -> Result<Option<Node>> {
-        Ok(if let Some(node) = self.node_cache.get(node_key) {
-            Some(node.clone())
-        } else if let Some(node) = self.frozen_cache.node_cache.nodes().get(node_key) {
-            Some(node.clone())
-        } else {
-            self.reader.get_node_option(node_key)?
-        })
+        match self.get_cached_node(node_key) {
+            Some(node) => Ok(Some(node.clone())),
+            None => {
+                let node = self.reader.get_node_option(node_key)?;
+                self.metrics.node_read(node.is_some());
+                Ok(node)
+            }
+        }
+    }
+
+    /// Gets a node from the nodes updated but not written yet.
+    fn get_cached_node(&self, node_key: &NodeKey) -> Option<&Node> {
+        let cached_node = self
+            .node_cache
+            .get(node_key)
+            .or_else(|| self.frozen_cache.node_cache.nodes().get(node_key));
+        self.metrics.node_cache_read(cached_node.is_some());
+        cached_node
     }
 
     /// Gets the current root node key.
@@ -288,6 +362,16 @@ where
 
     /// Freezes all the contents in cache to be immutable and clear `node_cache`.
     pub fn freeze<H: SimpleHasher>(&mut self) -> Result<()> {
+        let metrics = self.metrics;
+        let _timer = PhaseTimer::start(metrics, Phase::Freeze);
+        let span = tracing::debug_span!(
+            "freeze",
+            version = self.next_version,
+            new_nodes = Empty,
+            stale_nodes = Empty,
+            root_hash = Empty,
+        );
+        let _guard = span.enter();
         let mut root_node_key = self.get_root_node_key().clone();
 
         let root_node = if let Some(root_node) = self.get_node_option(&root_node_key)? {
@@ -303,9 +387,10 @@ where
 
         // Insert the root node's hash into the list of root hashes in the frozen cache, so that
         // they can be extracted later after a sequence of transactions:
-        self.frozen_cache
-            .root_hashes
-            .push(RootHash(root_node.hash::<H>()));
+        self.metrics.nodes_hashed(1);
+        let root_hash = RootHash(root_node.hash::<H>());
+        span.record("root_hash", debug(root_hash));
+        self.frozen_cache.root_hashes.push(root_hash);
 
         // If the effect of this set of changes has been to do nothing, we still need to create a
         // new root node that matches the anticipated version; we do this by copying the previous
@@ -330,6 +415,9 @@ where
             stale_nodes: self.stale_node_index_cache.len(),
             stale_leaves: self.num_stale_leaves,
         };
+        self.metrics.nodes_written(node_stats.new_nodes);
+        span.record("new_nodes", node_stats.new_nodes);
+        span.record("stale_nodes", node_stats.stale_nodes);
         self.frozen_cache.node_stats.push(node_stats);
         self.frozen_cache
             .node_cache
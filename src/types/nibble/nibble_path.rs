@@ -8,6 +8,7 @@ use alloc::vec;
 use core::{fmt, iter::FromIterator};
 
 use alloc::vec::Vec;
+#[cfg(feature = "tree")]
 use mirai_annotations::*;
 #[cfg(any(test))]
 use proptest::{collection::vec, prelude::*};
@@ -96,6 +97,48 @@ prop_compose! {
     }
 }
 
+/// The reason [`NibblePath::try_from_nibbles`] rejected its input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NibblePathError {
+    /// The path is longer than a path from the root to a leaf at the bottom of the tree.
+    TooManyNibbles { num_nibbles: usize },
+    /// The bytes are not exactly enough to hold the nibbles.
+    LengthMismatch {
+        num_nibbles: usize,
+        num_bytes: usize,
+    },
+    /// The path has an odd number of nibbles, but the low half of its last byte is not 0.
+    NonZeroPadding,
+}
+
+impl fmt::Display for NibblePathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NibblePathError::TooManyNibbles { num_nibbles } => write!(
+                f,
+                "A nibble path has at most {} nibbles, found {}.",
+                ROOT_NIBBLE_HEIGHT, num_nibbles
+            ),
+            NibblePathError::LengthMismatch {
+                num_nibbles,
+                num_bytes,
+            } => write!(
+                f,
+                "{} nibbles take {} bytes, found {}.",
+                num_nibbles,
+                num_nibbles.div_ceil(2),
+                num_bytes
+            ),
+            NibblePathError::NonZeroPadding => {
+                write!(f, "The last nibble of the last byte must be 0.")
+            }
+        }
+    }
+}
+
+impl core::error::Error for NibblePathError {}
+
 impl NibblePath {
     /// Creates a new `NibblePath` from a vector of bytes assuming each byte has 2 nibbles.
     pub(crate) fn new(bytes: Vec<u8>) -> Self {
@@ -119,6 +162,27 @@ impl NibblePath {
         NibblePath { num_nibbles, bytes }
     }
 
+    /// Creates a `NibblePath` of the first `num_nibbles` nibbles of `bytes`, two nibbles per byte
+    /// starting from the high half of the first byte.
+    ///
+    /// `bytes` must hold exactly `num_nibbles` nibbles, and if there is an odd number of them, the
+    /// unused low half of the last byte must be 0.
+    pub fn try_from_nibbles(bytes: Vec<u8>, num_nibbles: usize) -> Result<Self, NibblePathError> {
+        if num_nibbles > ROOT_NIBBLE_HEIGHT {
+            return Err(NibblePathError::TooManyNibbles { num_nibbles });
+        }
+        if bytes.len() != num_nibbles.div_ceil(2) {
+            return Err(NibblePathError::LengthMismatch {
+                num_nibbles,
+                num_bytes: bytes.len(),
+            });
+        }
+        if num_nibbles % 2 == 1 && bytes[bytes.len() - 1] & 0x0f != 0 {
+            return Err(NibblePathError::NonZeroPadding);
+        }
+        Ok(NibblePath { num_nibbles, bytes })
+    }
+
     /// Adds a nibble to the end of the nibble path.
     pub(crate) fn push(&mut self, nibble: Nibble) {
         assert!(ROOT_NIBBLE_HEIGHT > self.num_nibbles);
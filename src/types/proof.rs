@@ -3,9 +3,16 @@
 
 //! Merkle proof types.
 
+mod compact;
 pub(crate) mod definition;
+pub mod envelope;
+mod error;
+pub mod json;
+mod partial_tree;
 #[cfg(all(test, feature = "std"))]
 pub(crate) mod proptest_proof;
+#[cfg(feature = "proto")]
+pub mod proto;
 
 use crate::{
     proof::SparseMerkleNode::{Internal, Leaf},
@@ -15,12 +22,18 @@ use crate::{
 #[cfg(all(test, feature = "std"))]
 use proptest_derive::Arbitrary;
 
-pub use self::definition::{SparseMerkleProof, SparseMerkleRangeProof, UpdateMerkleProof};
-use crate::{KeyHash, ValueHash, SPARSE_MERKLE_PLACEHOLDER_HASH};
+pub use self::compact::CompactProofError;
+pub use self::definition::{
+    ExclusionProof, SparseMerkleProof, SparseMerkleRangeProof, UpdateMerkleProof,
+};
+pub use self::error::{MalformedProofError, VerificationError};
+pub use self::partial_tree::PartialTree;
+use crate::{KeyHash, ValueHash};
 use borsh::{BorshDeserialize, BorshSerialize};
 use serde::{Deserialize, Serialize};
 
 pub const LEAF_DOMAIN_SEPARATOR: &[u8] = b"JMT::LeafNode";
+// chop a vowel to fit in 16 bytes
 pub const INTERNAL_DOMAIN_SEPARATOR: &[u8] = b"JMT::IntrnalNode";
 
 #[cfg_attr(all(test, feature = "std"), derive(Arbitrary))]
@@ -35,21 +48,46 @@ pub const INTERNAL_DOMAIN_SEPARATOR: &[u8] = b"JMT::IntrnalNode";
 /// be able to forge the node/leaf type, as this assertion wouldn't be checked.
 /// Providing a [`SparseMerkleInternalNode`] or a [`SparseMerkleLeafNode`] structure is sufficient to
 /// prove the node type as one would need to reverse the hash function to forge them.
-pub(crate) enum SparseMerkleNode {
-    // The default sparse node
+/// Siblings whose type is never needed can be replaced by their hash alone, in which case they
+/// are [`SparseMerkleNode::Untyped`] and nothing may rely on their type.
+#[non_exhaustive]
+pub enum SparseMerkleNode {
+    /// The default sparse node, standing for an empty subtree.
     Null,
-    // The internal sparse merkle tree node
+    /// The internal sparse merkle tree node.
     Internal(SparseMerkleInternalNode),
-    // The leaf sparse merkle tree node
+    /// The leaf sparse merkle tree node.
     Leaf(SparseMerkleLeafNode),
+    /// A non-empty node of which only the hash is known.
+    Untyped([u8; 32]),
 }
 
 impl SparseMerkleNode {
-    pub(crate) fn hash<H: SimpleHasher>(&self) -> [u8; 32] {
+    /// Returns the hash of the node, which is the placeholder hash for [`SparseMerkleNode::Null`].
+    pub fn hash<H: SimpleHasher>(&self) -> [u8; 32] {
         match self {
-            SparseMerkleNode::Null => SPARSE_MERKLE_PLACEHOLDER_HASH,
+            SparseMerkleNode::Null => H::SPARSE_MERKLE_PLACEHOLDER_HASH,
             Internal(node) => node.hash::<H>(),
             Leaf(node) => node.hash::<H>(),
+            SparseMerkleNode::Untyped(hash) => *hash,
+        }
+    }
+
+    /// Checks that the hashes this node is made of are canonical for `H`, as verifiers hash them
+    /// with [`SimpleHasher::hash_internal`]. The placeholder hash of `H` is always accepted.
+    pub(crate) fn check_canonical<H: SimpleHasher>(&self) -> Result<(), VerificationError> {
+        let is_canonical = |hash: &[u8; 32]| {
+            *hash == H::SPARSE_MERKLE_PLACEHOLDER_HASH || H::is_canonical_hash(hash)
+        };
+        let canonical = match self {
+            SparseMerkleNode::Null | Leaf(_) => true,
+            Internal(node) => is_canonical(&node.left_child) && is_canonical(&node.right_child),
+            SparseMerkleNode::Untyped(hash) => is_canonical(hash),
+        };
+        if canonical {
+            Ok(())
+        } else {
+            Err(VerificationError::NonCanonicalHash)
         }
     }
 }
@@ -58,7 +96,8 @@ impl SparseMerkleNode {
     Serialize, Deserialize, Clone, Copy, Eq, PartialEq, BorshSerialize, BorshDeserialize, Debug,
 )]
 #[cfg_attr(all(test, feature = "std"), derive(Arbitrary))]
-pub(crate) struct SparseMerkleInternalNode {
+/// An internal node of the binary sparse Merkle tree, made of the hashes of its two children.
+pub struct SparseMerkleInternalNode {
     left_child: [u8; 32],
     right_child: [u8; 32],
 }
@@ -71,16 +110,26 @@ impl SparseMerkleInternalNode {
         }
     }
 
+    /// Returns the hash of the left child.
+    pub fn left_child(&self) -> [u8; 32] {
+        self.left_child
+    }
+
+    /// Returns the hash of the right child.
+    pub fn right_child(&self) -> [u8; 32] {
+        self.right_child
+    }
+
     pub fn hash<H: SimpleHasher>(&self) -> [u8; 32] {
-        let mut hasher = H::new();
-        // chop a vowel to fit in 16 bytes
-        hasher.update(INTERNAL_DOMAIN_SEPARATOR);
-        hasher.update(&self.left_child);
-        hasher.update(&self.right_child);
-        hasher.finalize()
+        H::hash_internal(
+            H::INTERNAL_DOMAIN_SEPARATOR,
+            &self.left_child,
+            &self.right_child,
+        )
     }
 }
 
+/// A leaf of the binary sparse Merkle tree, made of a key hash and the hash of its value.
 #[derive(Eq, Copy, Serialize, Deserialize, borsh::BorshSerialize, borsh::BorshDeserialize)]
 pub struct SparseMerkleLeafNode {
     key_hash: KeyHash,
@@ -136,22 +185,26 @@ impl PartialEq for SparseMerkleLeafNode {
 }
 
 impl SparseMerkleLeafNode {
-    pub(crate) fn new(key_hash: KeyHash, value_hash: ValueHash) -> Self {
+    pub fn new(key_hash: KeyHash, value_hash: ValueHash) -> Self {
         SparseMerkleLeafNode {
             key_hash,
             value_hash,
         }
     }
 
-    pub(crate) fn key_hash(&self) -> KeyHash {
+    pub fn key_hash(&self) -> KeyHash {
         self.key_hash
     }
 
-    pub(crate) fn hash<H: SimpleHasher>(&self) -> [u8; 32] {
-        let mut hasher = H::new();
-        hasher.update(LEAF_DOMAIN_SEPARATOR);
-        hasher.update(&self.key_hash.0);
-        hasher.update(&self.value_hash.0);
-        hasher.finalize()
+    pub fn value_hash(&self) -> ValueHash {
+        self.value_hash
+    }
+
+    pub fn hash<H: SimpleHasher>(&self) -> [u8; 32] {
+        H::hash_leaf(
+            H::LEAF_DOMAIN_SEPARATOR,
+            &self.key_hash.0,
+            &self.value_hash.0,
+        )
     }
 }
@@ -0,0 +1,270 @@
+//! A compact binary encoding of [`SparseMerkleProof`]s, for clients which pay for every byte.
+//!
+//! The encoding is made of:
+//!
+//! - A flags byte. Its lowest bit tells whether the proof has a leaf, the next one whether the
+//!   lowest sibling which is not a placeholder is typed, and the next one whether that sibling is
+//!   a leaf. Other bits are zero.
+//! - The key hash and value hash of the leaf, if any.
+//! - The number of siblings, as a little-endian `u16` of at most 256.
+//! - A bitmap of the siblings which are placeholders, starting from its lowest bit and padded with
+//!   zeros to whole bytes.
+//! - For every sibling which is not a placeholder, from the bottom level to the root level: the
+//!   hashes of its children if it is the typed internal node, its key hash and value hash if it is
+//!   the typed leaf, or its 32-byte hash otherwise.
+//!
+//! Verifying an update only needs the type of the lowest sibling which is not a placeholder, as
+//! it tells whether deleting the key coalesces that sibling. Every other sibling is written as its
+//! hash, whatever its type, so decoding gives back the [compacted](SparseMerkleProof::compact)
+//! proof, which verifies exactly like the original one.
+
+use alloc::{vec, vec::Vec};
+use core::fmt;
+
+use super::{
+    MalformedProofError, SparseMerkleInternalNode, SparseMerkleLeafNode, SparseMerkleNode,
+    SparseMerkleProof,
+};
+use crate::{KeyHash, SimpleHasher, ValueHash};
+
+/// The flag of proofs which have a leaf.
+const HAS_LEAF: u8 = 1;
+/// The flag of proofs whose lowest sibling which is not a placeholder is typed.
+const TYPED_SIBLING: u8 = 2;
+/// The flag of proofs whose typed sibling is a leaf, as opposed to an internal node.
+const LEAF_SIBLING: u8 = 4;
+
+impl<H: SimpleHasher> SparseMerkleProof<H> {
+    /// Returns the same proof, where every sibling except the lowest one which is not a
+    /// placeholder is replaced by its hash.
+    ///
+    /// The compacted proof verifies exactly like the original one, and is enough to verify an
+    /// update, but a [`PartialTree`](super::PartialTree) built from it covers fewer keys.
+    pub fn compact(&self) -> Self {
+        let mut typed_sibling_seen = false;
+        let siblings = self
+            .siblings()
+            .iter()
+            .map(|sibling| match sibling {
+                SparseMerkleNode::Null => SparseMerkleNode::Null,
+                _ if !typed_sibling_seen => {
+                    typed_sibling_seen = true;
+                    *sibling
+                }
+                _ => SparseMerkleNode::Untyped(sibling.hash::<H>()),
+            })
+            .collect();
+        Self::new(self.leaf(), siblings)
+    }
+
+    /// Encodes the proof with the compact encoding described in the [module](self)
+    /// documentation.
+    pub fn to_compact_bytes(&self) -> Vec<u8> {
+        let siblings = self.siblings();
+        let mut non_null = siblings
+            .iter()
+            .filter(|sibling| **sibling != SparseMerkleNode::Null);
+        let lowest = non_null.next();
+
+        let mut flags = 0;
+        if self.leaf().is_some() {
+            flags |= HAS_LEAF;
+        }
+        match lowest {
+            Some(SparseMerkleNode::Internal(_)) => flags |= TYPED_SIBLING,
+            Some(SparseMerkleNode::Leaf(_)) => flags |= TYPED_SIBLING | LEAF_SIBLING,
+            _ => {}
+        }
+
+        let mut bytes = vec![flags];
+        if let Some(leaf) = self.leaf() {
+            bytes.extend_from_slice(&leaf.key_hash.0);
+            bytes.extend_from_slice(&leaf.value_hash.0);
+        }
+        bytes.extend_from_slice(&(siblings.len() as u16).to_le_bytes());
+        push_bitmap(
+            &mut bytes,
+            siblings
+                .iter()
+                .map(|sibling| *sibling == SparseMerkleNode::Null),
+        );
+        match lowest {
+            Some(SparseMerkleNode::Internal(node)) => {
+                bytes.extend_from_slice(&node.left_child);
+                bytes.extend_from_slice(&node.right_child);
+            }
+            Some(SparseMerkleNode::Leaf(leaf)) => {
+                bytes.extend_from_slice(&leaf.key_hash.0);
+                bytes.extend_from_slice(&leaf.value_hash.0);
+            }
+            Some(sibling) => bytes.extend_from_slice(&sibling.hash::<H>()),
+            None => {}
+        }
+        for sibling in non_null {
+            bytes.extend_from_slice(&sibling.hash::<H>());
+        }
+        bytes
+    }
+
+    /// Decodes a proof encoded by [`to_compact_bytes`](Self::to_compact_bytes), which is the
+    /// [compacted](Self::compact) proof it was made from.
+    ///
+    /// Only the canonical encoding of a proof is accepted: padding bits must be zero, no bytes may
+    /// follow the proof, and untyped siblings must not have the placeholder hash.
+    pub fn from_compact_bytes(bytes: &[u8]) -> Result<Self, CompactProofError> {
+        let mut reader = Reader { bytes };
+        let flags = reader.take::<1>()?[0];
+        if flags & !(HAS_LEAF | TYPED_SIBLING | LEAF_SIBLING) != 0
+            || flags & (TYPED_SIBLING | LEAF_SIBLING) == LEAF_SIBLING
+        {
+            return Err(CompactProofError::UnknownFlags { flags });
+        }
+        let leaf = if flags & HAS_LEAF != 0 {
+            Some(reader.leaf()?)
+        } else {
+            None
+        };
+        let num_siblings = u16::from_le_bytes(reader.take()?) as usize;
+        if num_siblings > 256 {
+            return Err(CompactProofError::TooManySiblings { num_siblings });
+        }
+        let is_null = reader.bitmap(num_siblings)?;
+        if flags & TYPED_SIBLING != 0 && is_null.iter().all(|is_null| *is_null) {
+            return Err(CompactProofError::UnknownFlags { flags });
+        }
+
+        // Only the lowest sibling which is not a placeholder may be typed.
+        let mut typed = flags & TYPED_SIBLING != 0;
+        let mut siblings = Vec::with_capacity(num_siblings);
+        for is_null in is_null {
+            let sibling = if is_null {
+                SparseMerkleNode::Null
+            } else if typed {
+                typed = false;
+                if flags & LEAF_SIBLING != 0 {
+                    SparseMerkleNode::Leaf(reader.leaf()?)
+                } else {
+                    SparseMerkleNode::Internal(SparseMerkleInternalNode::new(
+                        reader.take()?,
+                        reader.take()?,
+                    ))
+                }
+            } else {
+                let hash = reader.take()?;
+                if hash == H::SPARSE_MERKLE_PLACEHOLDER_HASH {
+                    return Err(CompactProofError::UntypedPlaceholder);
+                }
+                SparseMerkleNode::Untyped(hash)
+            };
+            siblings.push(sibling);
+        }
+        if !reader.bytes.is_empty() {
+            return Err(CompactProofError::TrailingBytes {
+                len: reader.bytes.len(),
+            });
+        }
+        Self::try_new(leaf, siblings).map_err(CompactProofError::MalformedProof)
+    }
+}
+
+/// Appends the bits to `bytes`, starting from the lowest bit of each byte.
+fn push_bitmap(bytes: &mut Vec<u8>, bits: impl Iterator<Item = bool>) {
+    let mut bits = bits.peekable();
+    while bits.peek().is_some() {
+        let byte = bits
+            .by_ref()
+            .take(8)
+            .enumerate()
+            .fold(0, |byte, (i, bit)| byte | (u8::from(bit) << i));
+        bytes.push(byte);
+    }
+}
+
+/// Reads the parts of an encoded proof from the front of its bytes.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], CompactProofError> {
+        if self.bytes.len() < N {
+            return Err(CompactProofError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(N);
+        self.bytes = rest;
+        Ok(taken.try_into().expect("slice has N bytes"))
+    }
+
+    fn leaf(&mut self) -> Result<SparseMerkleLeafNode, CompactProofError> {
+        Ok(SparseMerkleLeafNode::new(
+            KeyHash(self.take()?),
+            ValueHash(self.take()?),
+        ))
+    }
+
+    fn bitmap(&mut self, len: usize) -> Result<Vec<bool>, CompactProofError> {
+        let num_bytes = len.div_ceil(8);
+        if self.bytes.len() < num_bytes {
+            return Err(CompactProofError::Truncated);
+        }
+        let (bitmap, rest) = self.bytes.split_at(num_bytes);
+        self.bytes = rest;
+        let bit = |i: usize| bitmap[i / 8] & (1 << (i % 8)) != 0;
+        if (len..num_bytes * 8).any(bit) {
+            return Err(CompactProofError::NonZeroPadding);
+        }
+        Ok((0..len).map(bit).collect())
+    }
+}
+
+/// The reason bytes were rejected by [`SparseMerkleProof::from_compact_bytes`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CompactProofError {
+    /// The bytes end in the middle of the proof.
+    Truncated,
+    /// Bytes are left after the end of the proof.
+    TrailingBytes { len: usize },
+    /// The flags byte has bits set which this crate does not know about, or which do not match the
+    /// siblings of the proof.
+    UnknownFlags { flags: u8 },
+    /// The proof has more siblings than the tree has levels.
+    TooManySiblings { num_siblings: usize },
+    /// A bitmap has bits set past its end.
+    NonZeroPadding,
+    /// An untyped sibling has the placeholder hash, instead of being marked as a placeholder.
+    UntypedPlaceholder,
+    /// The decoded parts do not make a proof, as told by [`SparseMerkleProof::try_new`].
+    MalformedProof(MalformedProofError),
+}
+
+impl fmt::Display for CompactProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompactProofError::Truncated => write!(f, "Compact proof is truncated."),
+            CompactProofError::TrailingBytes { len } => {
+                write!(f, "Compact proof is followed by {} more bytes.", len)
+            }
+            CompactProofError::UnknownFlags { flags } => {
+                write!(f, "Invalid flags {:#04x} in compact proof.", flags)
+            }
+            CompactProofError::TooManySiblings { num_siblings } => write!(
+                f,
+                "Compact proof has more than 256 ({}) siblings.",
+                num_siblings
+            ),
+            CompactProofError::NonZeroPadding => {
+                write!(f, "Compact proof has non-zero padding bits.")
+            }
+            CompactProofError::UntypedPlaceholder => write!(
+                f,
+                "Compact proof has an untyped sibling with the placeholder hash."
+            ),
+            CompactProofError::MalformedProof(error) => {
+                write!(f, "Malformed compact proof: {}", error)
+            }
+        }
+    }
+}
+
+impl core::error::Error for CompactProofError {}
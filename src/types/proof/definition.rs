@@ -4,19 +4,22 @@
 //! This module has definition of various proofs.
 use core::marker::PhantomData;
 
-use super::{SparseMerkleInternalNode, SparseMerkleLeafNode, SparseMerkleNode};
+use super::{
+    MalformedProofError, SparseMerkleInternalNode, SparseMerkleLeafNode, SparseMerkleNode,
+    VerificationError,
+};
 use crate::{
-    storage::Node,
     types::nibble::nibble_path::{skip_common_prefix, NibblePath},
-    Bytes32Ext, KeyHash, RootHash, SimpleHasher, ValueHash, SPARSE_MERKLE_PLACEHOLDER_HASH,
+    Bytes32Ext, KeyHash, RootHash, SimpleHasher, ValueHash,
 };
-use alloc::vec::Vec;
-use anyhow::{bail, ensure, format_err, Result};
+use alloc::{string::ToString, vec::Vec};
 use serde::{Deserialize, Serialize};
 
 /// A proof that can be used to authenticate an element in a Sparse Merkle Tree given trusted root
 /// hash. For example, `TransactionInfoToAccountProof` can be constructed on top of this structure.
-#[derive(Serialize, Deserialize, borsh::BorshSerialize, borsh::BorshDeserialize)]
+///
+/// Decoding a proof checks it like [`try_new`](Self::try_new) does.
+#[derive(Serialize, borsh::BorshSerialize)]
 pub struct SparseMerkleProof<H: SimpleHasher> {
     /// This proof can be used to authenticate whether a given leaf exists in the tree or not.
     ///     - If this is `Some(leaf_node)`
@@ -27,8 +30,8 @@ pub struct SparseMerkleProof<H: SimpleHasher> {
     ///           corresponding account blob.
     ///     - If this is `None`, this is also a non-inclusion proof which indicates the subtree is
     ///       empty.
-    // Prevent serde from adding a spurious Serialize/Deserialize bound on H
-    #[serde(bound(serialize = "", deserialize = ""))]
+    // Prevent serde from adding a spurious Serialize bound on H
+    #[serde(bound(serialize = ""))]
     leaf: Option<SparseMerkleLeafNode>,
 
     /// All siblings in this proof, including the default ones. Siblings are ordered from the bottom
@@ -37,10 +40,39 @@ pub struct SparseMerkleProof<H: SimpleHasher> {
     siblings: Vec<SparseMerkleNode>,
 
     /// A marker type showing which hash function is used in this proof.
-    #[borsh(bound(serialize = "", deserialize = ""))]
+    #[borsh(bound(serialize = ""))]
     phantom_hasher: PhantomData<H>,
 }
 
+/// The fields of a [`SparseMerkleProof`] as they are encoded, which only make a proof once
+/// checked by [`SparseMerkleProof::try_new`]. The marker does not depend on the hash function.
+#[derive(Deserialize, borsh::BorshDeserialize)]
+#[serde(rename = "SparseMerkleProof")]
+struct SparseMerkleProofFields {
+    leaf: Option<SparseMerkleLeafNode>,
+    siblings: Vec<SparseMerkleNode>,
+    phantom_hasher: PhantomData<()>,
+}
+
+impl<'de, H: SimpleHasher> Deserialize<'de> for SparseMerkleProof<H> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fields = SparseMerkleProofFields::deserialize(deserializer)?;
+        Self::try_new(fields.leaf, fields.siblings).map_err(serde::de::Error::custom)
+    }
+}
+
+impl<H: SimpleHasher> borsh::BorshDeserialize for SparseMerkleProof<H> {
+    fn deserialize_reader<R: borsh::io::Read>(reader: &mut R) -> borsh::io::Result<Self> {
+        let fields = SparseMerkleProofFields::deserialize_reader(reader)?;
+        Self::try_new(fields.leaf, fields.siblings).map_err(invalid_data)
+    }
+}
+
+/// Turns the reason decoded parts were rejected into the error of a failed borsh decoding.
+fn invalid_data(error: MalformedProofError) -> borsh::io::Error {
+    borsh::io::Error::new(borsh::io::ErrorKind::InvalidData, error.to_string())
+}
+
 // Deriving Debug fails since H is not Debug though phantom_hasher implements it
 // generically. Implement Debug manually as a workaround to enable Proptest
 impl<H: SimpleHasher> core::fmt::Debug for SparseMerkleProof<H> {
@@ -83,13 +115,60 @@ impl<H: SimpleHasher> SparseMerkleProof<H> {
         }
     }
 
+    /// Constructs a new `SparseMerkleProof` from a leaf and a list of siblings, ordered from the
+    /// bottom level to the root level, such as the ones of a proof made by another
+    /// implementation.
+    ///
+    /// The parts are rejected if no tree could have produced them:
+    ///
+    /// - There may be at most 256 siblings, one per level of the tree.
+    /// - The lowest sibling may not be a placeholder, as the node at the bottom of the path would
+    ///   then have taken the place of its parent.
+    /// - Untyped siblings may not have the placeholder hash.
+    /// - When the proof has a leaf, the key of every leaf sibling must share the path to that leaf
+    ///   down to the sibling, and branch off it there.
+    ///
+    /// Siblings can only be typed by giving the nodes which hash to them, so a proof built this way
+    /// proves no more than one made by this crate: it still has to be verified.
+    pub fn try_new(
+        leaf: Option<SparseMerkleLeafNode>,
+        siblings: Vec<SparseMerkleNode>,
+    ) -> Result<Self, MalformedProofError> {
+        let num_siblings = siblings.len();
+        if num_siblings > 256 {
+            return Err(MalformedProofError::TooManySiblings { num_siblings });
+        }
+        if siblings.first() == Some(&SparseMerkleNode::Null) {
+            return Err(MalformedProofError::PlaceholderAtBottom);
+        }
+        for (index, sibling) in siblings.iter().enumerate() {
+            match (sibling, leaf) {
+                (SparseMerkleNode::Untyped(hash), _)
+                    if *hash == H::SPARSE_MERKLE_PLACEHOLDER_HASH =>
+                {
+                    return Err(MalformedProofError::UntypedPlaceholder { index });
+                }
+                // The sibling hangs off the path to the leaf below the node at this depth.
+                (SparseMerkleNode::Leaf(sibling), Some(leaf))
+                    if sibling.key_hash.0.common_prefix_bits_len(&leaf.key_hash.0)
+                        != num_siblings - 1 - index =>
+                {
+                    return Err(MalformedProofError::MisplacedLeafSibling { index });
+                }
+                _ => {}
+            }
+        }
+        Ok(Self::new(leaf, siblings))
+    }
+
     /// Returns the leaf node in this proof.
     pub fn leaf(&self) -> Option<SparseMerkleLeafNode> {
         self.leaf.clone()
     }
 
-    /// Returns the list of siblings in this proof.
-    pub(crate) fn siblings(&self) -> &[SparseMerkleNode] {
+    /// Returns the list of siblings in this proof, ordered from the bottom level to the root
+    /// level.
+    pub fn siblings(&self) -> &[SparseMerkleNode] {
         &self.siblings
     }
 
@@ -104,7 +183,7 @@ impl<H: SimpleHasher> SparseMerkleProof<H> {
         expected_root_hash: RootHash,
         element_key: KeyHash,
         element_value: V,
-    ) -> Result<()> {
+    ) -> Result<(), VerificationError> {
         self.verify(expected_root_hash, element_key, Some(element_value))
     }
 
@@ -114,7 +193,7 @@ impl<H: SimpleHasher> SparseMerkleProof<H> {
         &self,
         expected_root_hash: RootHash,
         element_key: KeyHash,
-    ) -> Result<()> {
+    ) -> Result<(), VerificationError> {
         self.verify(expected_root_hash, element_key, None::<&[u8]>)
     }
 
@@ -127,50 +206,45 @@ impl<H: SimpleHasher> SparseMerkleProof<H> {
         expected_root_hash: RootHash,
         element_key: KeyHash,
         element_value: Option<V>,
-    ) -> Result<()> {
-        ensure!(
-            self.siblings.len() <= 256,
-            "Sparse Merkle Tree proof has more than {} ({}) siblings.",
-            256,
-            self.siblings.len(),
-        );
+    ) -> Result<(), VerificationError> {
+        if self.siblings.len() > 256 {
+            return Err(VerificationError::TooManySiblings {
+                num_siblings: self.siblings.len(),
+            });
+        }
+        self.check_canonical()?;
 
         match (element_value, self.leaf.clone()) {
             (Some(value), Some(leaf)) => {
                 // This is an inclusion proof, so the key and value hash provided in the proof
                 // should match element_key and element_value_hash. `siblings` should prove the
                 // route from the leaf node to the root.
-                ensure!(
-                    element_key == leaf.key_hash,
-                    "Keys do not match. Key in proof: {:?}. Expected key: {:?}.",
-                    leaf.key_hash,
-                    element_key
-                );
+                if element_key != leaf.key_hash {
+                    return Err(VerificationError::KeyMismatch {
+                        expected: element_key,
+                        actual: leaf.key_hash,
+                    });
+                }
                 let hash: ValueHash = ValueHash::with::<H>(value);
-                ensure!(
-                    hash == leaf.value_hash,
-                    "Value hashes do not match. Value hash in proof: {:?}. \
-                     Expected value hash: {:?}",
-                    leaf.value_hash,
-                    hash,
-                );
+                if hash != leaf.value_hash {
+                    return Err(VerificationError::ValueHashMismatch {
+                        expected: hash,
+                        actual: leaf.value_hash,
+                    });
+                }
             }
-            (Some(_value), None) => bail!("Expected inclusion proof. Found non-inclusion proof."),
+            (Some(_value), None) => return Err(VerificationError::ExpectedInclusionProof),
             (None, Some(leaf)) => {
                 // This is a non-inclusion proof. The proof intends to show that if a leaf node
                 // representing `element_key` is inserted, it will break a currently existing leaf
                 // node represented by `proof_key` into a branch. `siblings` should prove the
                 // route from that leaf node to the root.
-                ensure!(
-                    element_key != leaf.key_hash,
-                    "Expected non-inclusion proof, but key exists in proof.",
-                );
-                ensure!(
-                    element_key.0.common_prefix_bits_len(&leaf.key_hash.0) >= self.siblings.len(),
-                    "Key would not have ended up in the subtree where the provided key in proof \
-                     is the only existing key, if it existed. So this is not a valid \
-                     non-inclusion proof.",
-                );
+                if element_key == leaf.key_hash {
+                    return Err(VerificationError::ExpectedNonInclusionProof);
+                }
+                if element_key.0.common_prefix_bits_len(&leaf.key_hash.0) < self.siblings.len() {
+                    return Err(VerificationError::KeyOutsideLeafSubtree);
+                }
             }
             (None, None) => {
                 // This is a non-inclusion proof. The proof intends to show that if a leaf node
@@ -182,7 +256,7 @@ impl<H: SimpleHasher> SparseMerkleProof<H> {
         let current_hash = self
             .leaf
             .clone()
-            .map_or(SPARSE_MERKLE_PLACEHOLDER_HASH, |leaf| leaf.hash::<H>());
+            .map_or(H::SPARSE_MERKLE_PLACEHOLDER_HASH, |leaf| leaf.hash::<H>());
         let actual_root_hash = self
             .siblings
             .iter()
@@ -201,12 +275,12 @@ impl<H: SimpleHasher> SparseMerkleProof<H> {
                 }
             });
 
-        ensure!(
-            actual_root_hash == expected_root_hash.0,
-            "Root hashes do not match. Actual root hash: {:?}. Expected root hash: {:?}.",
-            actual_root_hash,
-            expected_root_hash.0,
-        );
+        if actual_root_hash != expected_root_hash.0 {
+            return Err(VerificationError::RootHashMismatch {
+                expected: expected_root_hash,
+                actual: RootHash(actual_root_hash),
+            });
+        }
 
         Ok(())
     }
@@ -307,7 +381,7 @@ impl<H: SimpleHasher> SparseMerkleProof<H> {
         old_root_hash: RootHash,
         new_element_key: KeyHash,
         new_element_value: Option<V>,
-    ) -> Result<RootHash> {
+    ) -> Result<RootHash, VerificationError> {
         if let Some(new_element_value) = new_element_value {
             // A value have been supplied, we need to prove that we inserted a given value at the new key
 
@@ -316,7 +390,7 @@ impl<H: SimpleHasher> SparseMerkleProof<H> {
                 // The inserted key is going to update an existing leaf
                 Some(leaf_node) => {
                     // First verify that the old merkle path is valid
-                    ensure!(self.root_hash() == old_root_hash);
+                    self.check_root_hash(old_root_hash)?;
                     if new_element_key == leaf_node.key_hash {
                         // Step 2: we compute the new Merkle path (we build a new [`SparseMerkleProof`] object)
                         // In this case the siblings are left unchanged, only the leaf value is updated
@@ -344,9 +418,7 @@ impl<H: SimpleHasher> SparseMerkleProof<H> {
 
                 // There is no leaf in the Merkle path, which means the key we are going to insert does not update an existing leaf
                 None => {
-                    ensure!(self
-                        .verify_nonexistence(old_root_hash, new_element_key)
-                        .is_ok());
+                    self.verify_nonexistence(old_root_hash, new_element_key)?;
 
                     // Step 2: we compute the new Merkle path (we build a new [`SparseMerkleProof`] object)
                     // In that case, the leaf is none so we don't need to change the siblings
@@ -365,13 +437,13 @@ impl<H: SimpleHasher> SparseMerkleProof<H> {
         } else {
             // No value supplied, we need to prove that the previous value was deleted
             if let Some(leaf_node) = self.leaf {
-                ensure!(self.root_hash() == old_root_hash);
-                ensure!(
-                    new_element_key == leaf_node.key_hash,
-                    "Key {:?} to remove doesn't match the leaf key {:?} supplied with the proof",
-                    new_element_key,
-                    leaf_node.key_hash
-                );
+                self.check_root_hash(old_root_hash)?;
+                if new_element_key != leaf_node.key_hash {
+                    return Err(VerificationError::KeyMismatch {
+                        expected: new_element_key,
+                        actual: leaf_node.key_hash,
+                    });
+                }
 
                 // Step 2: we compute the new Merkle tree path.
                 // In case of deletion, we need to rewind the nibble until we reach the first non-default hash
@@ -406,21 +478,24 @@ impl<H: SimpleHasher> SparseMerkleProof<H> {
                                         .rev()
                                         .skip(256 - remaining_siblings_len),
                                 )
-                                .fold(Node::new_null().hash::<H>(), |hash, (sibling_node, bit)| {
-                                    if bit {
-                                        SparseMerkleInternalNode::new(
-                                            sibling_node.hash::<H>(),
-                                            hash,
-                                        )
-                                        .hash::<H>()
-                                    } else {
-                                        SparseMerkleInternalNode::new(
-                                            hash,
-                                            sibling_node.hash::<H>(),
-                                        )
-                                        .hash::<H>()
-                                    }
-                                }),
+                                .fold(
+                                    H::SPARSE_MERKLE_PLACEHOLDER_HASH,
+                                    |hash, (sibling_node, bit)| {
+                                        if bit {
+                                            SparseMerkleInternalNode::new(
+                                                sibling_node.hash::<H>(),
+                                                hash,
+                                            )
+                                            .hash::<H>()
+                                        } else {
+                                            SparseMerkleInternalNode::new(
+                                                hash,
+                                                sibling_node.hash::<H>(),
+                                            )
+                                            .hash::<H>()
+                                        }
+                                    },
+                                ),
                         )
                     }
                     SparseMerkleNode::Leaf(_) => {
@@ -470,7 +545,9 @@ impl<H: SimpleHasher> SparseMerkleProof<H> {
 
                         // Step 3: we compute the new Merkle root
                     }
-                    SparseMerkleNode::Null => RootHash(SPARSE_MERKLE_PLACEHOLDER_HASH),
+                    SparseMerkleNode::Null => RootHash(H::SPARSE_MERKLE_PLACEHOLDER_HASH),
+                    // Whether the sibling gets coalesced cannot be decided from its hash.
+                    SparseMerkleNode::Untyped(_) => return Err(VerificationError::UntypedSibling),
                 };
 
                 Ok(new_merkle_hash)
@@ -486,7 +563,7 @@ impl<H: SimpleHasher> SparseMerkleProof<H> {
         let current_hash = self
             .leaf
             .clone()
-            .map_or(SPARSE_MERKLE_PLACEHOLDER_HASH, |leaf| leaf.hash::<H>());
+            .map_or(H::SPARSE_MERKLE_PLACEHOLDER_HASH, |leaf| leaf.hash::<H>());
         let actual_root_hash = self
             .siblings
             .iter()
@@ -509,6 +586,244 @@ impl<H: SimpleHasher> SparseMerkleProof<H> {
 
         RootHash(actual_root_hash)
     }
+
+    /// Checks that this proof authenticates an existing leaf under `expected_root_hash`, and
+    /// returns that leaf.
+    fn verify_neighbor(
+        &self,
+        expected_root_hash: RootHash,
+    ) -> Result<SparseMerkleLeafNode, VerificationError> {
+        if self.siblings.len() > 256 {
+            return Err(VerificationError::TooManySiblings {
+                num_siblings: self.siblings.len(),
+            });
+        }
+        let leaf = self.leaf.ok_or(VerificationError::MissingNeighborLeaf)?;
+        self.check_root_hash(expected_root_hash)?;
+        Ok(leaf)
+    }
+
+    /// Checks that the root hash computed from this proof is `expected_root_hash`.
+    fn check_root_hash(&self, expected_root_hash: RootHash) -> Result<(), VerificationError> {
+        self.check_canonical()?;
+        let actual_root_hash = self.root_hash();
+        if actual_root_hash != expected_root_hash {
+            return Err(VerificationError::RootHashMismatch {
+                expected: expected_root_hash,
+                actual: actual_root_hash,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks that the hashes of the siblings are canonical, before they are hashed.
+    fn check_canonical(&self) -> Result<(), VerificationError> {
+        self.siblings
+            .iter()
+            .try_for_each(SparseMerkleNode::check_canonical::<H>)
+    }
+
+    /// Returns `true` if every sibling lying on the side designated by `side_bit` of the path to
+    /// the leaf is a placeholder, ignoring the siblings located above `min_depth`. A sibling lies
+    /// on the right of the path when the corresponding bit of the leaf key is `0`, and on the left
+    /// when it is `1`.
+    fn siblings_are_empty_on_side(&self, side_bit: bool, min_depth: usize) -> bool {
+        let leaf_key = match self.leaf {
+            Some(leaf) => leaf.key_hash,
+            None => return false,
+        };
+        let num_siblings = self.siblings.len();
+        self.siblings
+            .iter()
+            .zip(leaf_key.0.iter_bits().rev().skip(256 - num_siblings))
+            .enumerate()
+            .all(|(i, (sibling, bit))| {
+                // Siblings are ordered from the bottom level to the root level.
+                let depth = num_siblings - 1 - i;
+                depth < min_depth || bit != side_bit || *sibling == SparseMerkleNode::Null
+            })
+    }
+}
+
+/// A proof of non-existence by exclusion between two adjacent neighbors.
+#[derive(Serialize, Deserialize, borsh::BorshSerialize, borsh::BorshDeserialize)]
+// Prevent serde from adding a spurious Serialize/Deserialize bound on H
+#[serde(bound(serialize = "", deserialize = ""))]
+pub enum ExclusionProof<H: SimpleHasher> {
+    /// The key is smaller than every key of the tree: proves the smallest existing key.
+    Leftmost {
+        #[borsh(bound(serialize = "", deserialize = ""))]
+        leftmost_right_proof: SparseMerkleProof<H>,
+    },
+    /// The key falls between two adjacent existing keys: proves both of them.
+    Middle {
+        #[borsh(bound(serialize = "", deserialize = ""))]
+        leftmost_right_proof: SparseMerkleProof<H>,
+        #[borsh(bound(serialize = "", deserialize = ""))]
+        rightmost_left_proof: SparseMerkleProof<H>,
+    },
+    /// The key is greater than every key of the tree: proves the greatest existing key.
+    Rightmost {
+        #[borsh(bound(serialize = "", deserialize = ""))]
+        rightmost_left_proof: SparseMerkleProof<H>,
+    },
+}
+
+// Manually implement Debug to circumvent [incorrect auto-bounds](https://github.com/rust-lang/rust/issues/26925)
+// TODO: Switch back to #[derive] once the perfect_derive feature lands
+impl<H: SimpleHasher> core::fmt::Debug for ExclusionProof<H> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ExclusionProof::Leftmost {
+                leftmost_right_proof,
+            } => f
+                .debug_struct("Leftmost")
+                .field("leftmost_right_proof", leftmost_right_proof)
+                .finish(),
+            ExclusionProof::Middle {
+                leftmost_right_proof,
+                rightmost_left_proof,
+            } => f
+                .debug_struct("Middle")
+                .field("leftmost_right_proof", leftmost_right_proof)
+                .field("rightmost_left_proof", rightmost_left_proof)
+                .finish(),
+            ExclusionProof::Rightmost {
+                rightmost_left_proof,
+            } => f
+                .debug_struct("Rightmost")
+                .field("rightmost_left_proof", rightmost_left_proof)
+                .finish(),
+        }
+    }
+}
+
+// Manually implement PartialEq to circumvent [incorrect auto-bounds](https://github.com/rust-lang/rust/issues/26925)
+// TODO: Switch back to #[derive] once the perfect_derive feature lands
+impl<H: SimpleHasher> PartialEq for ExclusionProof<H> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                ExclusionProof::Leftmost {
+                    leftmost_right_proof: a,
+                },
+                ExclusionProof::Leftmost {
+                    leftmost_right_proof: b,
+                },
+            ) => a == b,
+            (
+                ExclusionProof::Middle {
+                    leftmost_right_proof: a_right,
+                    rightmost_left_proof: a_left,
+                },
+                ExclusionProof::Middle {
+                    leftmost_right_proof: b_right,
+                    rightmost_left_proof: b_left,
+                },
+            ) => a_right == b_right && a_left == b_left,
+            (
+                ExclusionProof::Rightmost {
+                    rightmost_left_proof: a,
+                },
+                ExclusionProof::Rightmost {
+                    rightmost_left_proof: b,
+                },
+            ) => a == b,
+            _ => false,
+        }
+    }
+}
+
+// Manually implement Clone to circumvent [incorrect auto-bounds](https://github.com/rust-lang/rust/issues/26925)
+// TODO: Switch back to #[derive] once the perfect_derive feature lands
+impl<H: SimpleHasher> Clone for ExclusionProof<H> {
+    fn clone(&self) -> Self {
+        match self {
+            ExclusionProof::Leftmost {
+                leftmost_right_proof,
+            } => ExclusionProof::Leftmost {
+                leftmost_right_proof: leftmost_right_proof.clone(),
+            },
+            ExclusionProof::Middle {
+                leftmost_right_proof,
+                rightmost_left_proof,
+            } => ExclusionProof::Middle {
+                leftmost_right_proof: leftmost_right_proof.clone(),
+                rightmost_left_proof: rightmost_left_proof.clone(),
+            },
+            ExclusionProof::Rightmost {
+                rightmost_left_proof,
+            } => ExclusionProof::Rightmost {
+                rightmost_left_proof: rightmost_left_proof.clone(),
+            },
+        }
+    }
+}
+
+impl<H: SimpleHasher> ExclusionProof<H> {
+    /// Verifies that `key_hash` does not exist in the tree committed to by `expected_root_hash`.
+    ///
+    /// Each neighbor proof must authenticate an existing leaf under `expected_root_hash`, the
+    /// neighbors must bound `key_hash`, and they must be adjacent: every sibling between the
+    /// neighbors (or between a neighbor and the edge of the tree) must be a placeholder, so that
+    /// no other leaf can exist in the excluded range.
+    pub fn verify(
+        &self,
+        expected_root_hash: RootHash,
+        key_hash: KeyHash,
+    ) -> Result<(), VerificationError> {
+        match self {
+            ExclusionProof::Leftmost {
+                leftmost_right_proof,
+            } => {
+                let right_leaf = leftmost_right_proof.verify_neighbor(expected_root_hash)?;
+                if key_hash >= right_leaf.key_hash {
+                    return Err(VerificationError::KeyNotBetweenNeighbors);
+                }
+                if !leftmost_right_proof.siblings_are_empty_on_side(true, 0) {
+                    return Err(VerificationError::NonAdjacentNeighbors);
+                }
+            }
+            ExclusionProof::Middle {
+                leftmost_right_proof,
+                rightmost_left_proof,
+            } => {
+                let left_leaf = rightmost_left_proof.verify_neighbor(expected_root_hash)?;
+                let right_leaf = leftmost_right_proof.verify_neighbor(expected_root_hash)?;
+                if !(left_leaf.key_hash < key_hash && key_hash < right_leaf.key_hash) {
+                    return Err(VerificationError::KeyNotBetweenNeighbors);
+                }
+
+                // The two neighbors are split at the depth of their common prefix. Below that
+                // point, nothing may exist on the right of the left neighbor, nor on the left of
+                // the right neighbor.
+                let split_depth = left_leaf
+                    .key_hash
+                    .0
+                    .common_prefix_bits_len(&right_leaf.key_hash.0);
+                if rightmost_left_proof.siblings.len() <= split_depth
+                    || leftmost_right_proof.siblings.len() <= split_depth
+                    || !rightmost_left_proof.siblings_are_empty_on_side(false, split_depth + 1)
+                    || !leftmost_right_proof.siblings_are_empty_on_side(true, split_depth + 1)
+                {
+                    return Err(VerificationError::NonAdjacentNeighbors);
+                }
+            }
+            ExclusionProof::Rightmost {
+                rightmost_left_proof,
+            } => {
+                let left_leaf = rightmost_left_proof.verify_neighbor(expected_root_hash)?;
+                if left_leaf.key_hash >= key_hash {
+                    return Err(VerificationError::KeyNotBetweenNeighbors);
+                }
+                if !rightmost_left_proof.siblings_are_empty_on_side(false, 0) {
+                    return Err(VerificationError::NonAdjacentNeighbors);
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, borsh::BorshSerialize, borsh::BorshDeserialize)]
@@ -521,6 +836,11 @@ impl<H: SimpleHasher> UpdateMerkleProof<H> {
         UpdateMerkleProof(merkle_proofs)
     }
 
+    /// Returns the proofs of the updates, in the order of the updates.
+    pub fn proofs(&self) -> &[SparseMerkleProof<H>] {
+        &self.0
+    }
+
     /// Verifies an update of the [`JellyfishMerkleTree`], proving the transition from an `old_root_hash` to a `new_root_hash` ([`RootHash`])
     /// Multiple cases to handle:
     ///    - Insert a tuple `new_element_key`, `new_element_value`
@@ -538,14 +858,14 @@ impl<H: SimpleHasher> UpdateMerkleProof<H> {
         old_root_hash: RootHash,
         new_root_hash: RootHash,
         updates: impl AsRef<[(KeyHash, Option<V>)]>,
-    ) -> Result<()> {
+    ) -> Result<(), VerificationError> {
         let updates = updates.as_ref();
-        ensure!(
-            updates.len() == self.0.len(),
-            "Mismatched number of updates and proofs. Received {} proofs for {} updates",
-            self.0.len(),
-            updates.len()
-        );
+        if updates.len() != self.0.len() {
+            return Err(VerificationError::UpdateCountMismatch {
+                num_proofs: self.0.len(),
+                num_updates: updates.len(),
+            });
+        }
         let mut curr_root_hash = old_root_hash;
 
         for (merkle_proof, (new_element_key, new_element_value)) in
@@ -559,12 +879,12 @@ impl<H: SimpleHasher> UpdateMerkleProof<H> {
             )?;
         }
 
-        ensure!(
-            curr_root_hash == new_root_hash,
-            "Root hashes do not match. Actual root hash: {:?}. Expected root hash: {:?}.",
-            curr_root_hash,
-            new_root_hash,
-        );
+        if curr_root_hash != new_root_hash {
+            return Err(VerificationError::RootHashMismatch {
+                expected: new_root_hash,
+                actual: curr_root_hash,
+            });
+        }
 
         Ok(())
     }
@@ -593,7 +913,9 @@ impl<H: SimpleHasher> UpdateMerkleProof<H> {
 ///
 /// if the proof wants show that `[a, b, c, d, e]` exists in the tree, it would need the siblings
 /// `X` and `h` on the right.
-#[derive(Eq, Serialize, Deserialize, borsh::BorshSerialize, borsh::BorshDeserialize)]
+///
+/// Decoding a proof checks it like [`try_new`](Self::try_new) does.
+#[derive(Eq, Serialize, borsh::BorshSerialize)]
 pub struct SparseMerkleRangeProof<H: SimpleHasher> {
     /// The vector of siblings on the right of the path from root to last leaf. The ones near the
     /// bottom are at the beginning of the vector. In the above example, it's `[X, h]`.
@@ -601,6 +923,29 @@ pub struct SparseMerkleRangeProof<H: SimpleHasher> {
     _phantom: PhantomData<H>,
 }
 
+/// The fields of a [`SparseMerkleRangeProof`] as they are encoded, which only make a proof once
+/// checked by [`SparseMerkleRangeProof::try_new`].
+#[derive(Deserialize, borsh::BorshDeserialize)]
+#[serde(rename = "SparseMerkleRangeProof")]
+struct SparseMerkleRangeProofFields {
+    right_siblings: Vec<SparseMerkleNode>,
+    _phantom: PhantomData<()>,
+}
+
+impl<'de, H: SimpleHasher> Deserialize<'de> for SparseMerkleRangeProof<H> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fields = SparseMerkleRangeProofFields::deserialize(deserializer)?;
+        Self::try_new(fields.right_siblings).map_err(serde::de::Error::custom)
+    }
+}
+
+impl<H: SimpleHasher> borsh::BorshDeserialize for SparseMerkleRangeProof<H> {
+    fn deserialize_reader<R: borsh::io::Read>(reader: &mut R) -> borsh::io::Result<Self> {
+        let fields = SparseMerkleRangeProofFields::deserialize_reader(reader)?;
+        Self::try_new(fields.right_siblings).map_err(invalid_data)
+    }
+}
+
 // Manually implement PartialEq to circumvent [incorrect auto-bounds](https://github.com/rust-lang/rust/issues/26925)
 // TODO: Switch back to #[derive] once the perfect_derive feature lands
 impl<H: SimpleHasher> PartialEq for SparseMerkleRangeProof<H> {
@@ -640,8 +985,27 @@ impl<H: SimpleHasher> SparseMerkleRangeProof<H> {
         }
     }
 
-    /// Returns the right siblings.
-    pub(crate) fn right_siblings(&self) -> &[SparseMerkleNode] {
+    /// Constructs a new `SparseMerkleRangeProof` from the siblings on the right of the path to the
+    /// rightmost known leaf, ordered from the bottom level to the root level.
+    ///
+    /// The siblings are rejected if there are more than 256 of them, or if an untyped sibling has
+    /// the placeholder hash.
+    pub fn try_new(right_siblings: Vec<SparseMerkleNode>) -> Result<Self, MalformedProofError> {
+        if right_siblings.len() > 256 {
+            return Err(MalformedProofError::TooManySiblings {
+                num_siblings: right_siblings.len(),
+            });
+        }
+        if let Some(index) = right_siblings.iter().position(|sibling| {
+            *sibling == SparseMerkleNode::Untyped(H::SPARSE_MERKLE_PLACEHOLDER_HASH)
+        }) {
+            return Err(MalformedProofError::UntypedPlaceholder { index });
+        }
+        Ok(Self::new(right_siblings))
+    }
+
+    /// Returns the right siblings, ordered from the bottom level to the root level.
+    pub fn right_siblings(&self) -> &[SparseMerkleNode] {
         &self.right_siblings
     }
 
@@ -652,8 +1016,16 @@ impl<H: SimpleHasher> SparseMerkleRangeProof<H> {
         expected_root_hash: RootHash,
         rightmost_known_leaf: SparseMerkleLeafNode,
         left_siblings: Vec<[u8; 32]>,
-    ) -> Result<()> {
+    ) -> Result<(), VerificationError> {
         let num_siblings = left_siblings.len() + self.right_siblings.len();
+        if num_siblings > 256 {
+            return Err(VerificationError::TooManySiblings { num_siblings });
+        }
+        left_siblings
+            .iter()
+            .map(|hash| SparseMerkleNode::Untyped(*hash))
+            .chain(self.right_siblings.iter().copied())
+            .try_for_each(|sibling| sibling.check_canonical::<H>())?;
         let mut left_sibling_iter = left_siblings.iter();
         let mut right_sibling_iter = self.right_siblings().iter();
 
@@ -669,7 +1041,7 @@ impl<H: SimpleHasher> SparseMerkleRangeProof<H> {
                 (
                     *left_sibling_iter
                         .next()
-                        .ok_or_else(|| format_err!("Missing left sibling."))?,
+                        .ok_or(VerificationError::MissingSibling)?,
                     current_hash,
                 )
             } else {
@@ -677,19 +1049,19 @@ impl<H: SimpleHasher> SparseMerkleRangeProof<H> {
                     current_hash,
                     right_sibling_iter
                         .next()
-                        .ok_or_else(|| format_err!("Missing right sibling."))?
+                        .ok_or(VerificationError::MissingSibling)?
                         .hash::<H>(),
                 )
             };
             current_hash = SparseMerkleInternalNode::new(left_hash, right_hash).hash::<H>();
         }
 
-        ensure!(
-            current_hash == expected_root_hash.0,
-            "Root hashes do not match. Actual root hash: {:?}. Expected root hash: {:?}.",
-            current_hash,
-            expected_root_hash,
-        );
+        if current_hash != expected_root_hash.0 {
+            return Err(VerificationError::RootHashMismatch {
+                expected: expected_root_hash,
+                actual: RootHash(current_hash),
+            });
+        }
 
         Ok(())
     }
@@ -708,7 +1080,7 @@ mod serialization_tests {
         KeyHash, ValueHash,
     };
 
-    use super::{SparseMerkleProof, SparseMerkleRangeProof};
+    use super::{ExclusionProof, SparseMerkleProof, SparseMerkleRangeProof};
 
     fn get_test_proof() -> SparseMerkleProof<Sha256> {
         SparseMerkleProof {
@@ -754,6 +1126,34 @@ mod serialization_tests {
         assert_eq!(proof, deserialized);
     }
 
+    fn get_test_exclusion_proof() -> ExclusionProof<Sha256> {
+        ExclusionProof::Middle {
+            leftmost_right_proof: get_test_proof(),
+            rightmost_left_proof: get_test_proof(),
+        }
+    }
+
+    #[test]
+    fn test_exclusion_proof_roundtrip_serde() {
+        let proof = get_test_exclusion_proof();
+        let serialized_proof = serde_json::to_string(&proof).expect("serialization is infallible");
+        let deserialized =
+            serde_json::from_str(&serialized_proof).expect("serialized proof is valid");
+
+        assert_eq!(proof, deserialized);
+    }
+
+    #[test]
+    fn test_exclusion_proof_roundtrip_borsh() {
+        use borsh::BorshDeserialize;
+        let proof = get_test_exclusion_proof();
+        let serialized_proof = borsh::to_vec(&proof).expect("serialization is infallible");
+        let deserialized = ExclusionProof::<Sha256>::deserialize(&mut serialized_proof.as_slice())
+            .expect("serialized proof is valid");
+
+        assert_eq!(proof, deserialized);
+    }
+
     #[test]
     fn test_sparse_merkle_range_proof_roundtrip_serde() {
         let proof = get_test_range_proof();
@@ -776,3 +1176,69 @@ mod serialization_tests {
         assert_eq!(proof, deserialized);
     }
 }
+
+#[cfg(test)]
+mod verification_tests {
+    //! These tests check the typed errors reported by proof verification, which is available in
+    //! verifier-only builds where the tree itself cannot be used to produce proofs.
+
+    use sha2::Sha256;
+
+    use crate::{
+        proof::{SparseMerkleLeafNode, SparseMerkleNode, VerificationError},
+        KeyHash, RootHash, ValueHash,
+    };
+
+    use super::SparseMerkleProof;
+
+    const VALUE: &[u8] = b"value";
+
+    fn get_single_leaf_proof(key: KeyHash) -> (SparseMerkleProof<Sha256>, RootHash) {
+        let proof = SparseMerkleProof::new(
+            Some(SparseMerkleLeafNode::new(
+                key,
+                ValueHash::with::<Sha256>(VALUE),
+            )),
+            alloc::vec![SparseMerkleNode::Null],
+        );
+        let root_hash = proof.root_hash();
+        (proof, root_hash)
+    }
+
+    #[test]
+    fn test_verify_reports_typed_errors() {
+        let key = KeyHash([0u8; 32]);
+        let (proof, root_hash) = get_single_leaf_proof(key);
+
+        proof.verify_existence(root_hash, key, VALUE).unwrap();
+        assert_eq!(
+            proof.verify_existence(root_hash, key, b"other value"),
+            Err(VerificationError::ValueHashMismatch {
+                expected: ValueHash::with::<Sha256>(b"other value"),
+                actual: ValueHash::with::<Sha256>(VALUE),
+            })
+        );
+        assert_eq!(
+            proof.verify_nonexistence(root_hash, key),
+            Err(VerificationError::ExpectedNonInclusionProof)
+        );
+        assert_eq!(
+            proof.verify_nonexistence(root_hash, KeyHash([0xff; 32])),
+            Err(VerificationError::KeyOutsideLeafSubtree)
+        );
+        assert_eq!(
+            proof.verify_existence(RootHash([0u8; 32]), key, VALUE),
+            Err(VerificationError::RootHashMismatch {
+                expected: RootHash([0u8; 32]),
+                actual: root_hash,
+            })
+        );
+
+        let too_long_proof =
+            SparseMerkleProof::<Sha256>::new(None, alloc::vec![SparseMerkleNode::Null; 257]);
+        assert_eq!(
+            too_long_proof.verify_nonexistence(root_hash, key),
+            Err(VerificationError::TooManySiblings { num_siblings: 257 })
+        );
+    }
+}
@@ -0,0 +1,302 @@
+//! An optional self-describing encoding of proofs.
+//!
+//! The borsh and serde encodings of the proof types do not say which hash function or domain
+//! created them, so a proof of one tree decodes as a proof of any other one and only fails at
+//! verification, with a root hash mismatch. [`EnvelopedProof::to_envelope`] prefixes the borsh
+//! encoding of a proof with a [`ProofHeader`] identifying the proof type, the hasher and the
+//! domain, so that clients dealing with several trees can route or reject proofs before decoding
+//! them, and [`EnvelopedProof::from_envelope`] checks that header against the expected proof type.
+//!
+//! The header is laid out as follows, followed by the borsh encoding of the proof:
+//!
+//! | bytes    | field                                              |
+//! |----------|----------------------------------------------------|
+//! | `0..4`   | [`ENVELOPE_MAGIC`]                                 |
+//! | `4`      | format version, [`ENVELOPE_FORMAT_VERSION`]        |
+//! | `5`      | [`ProofKind`]                                      |
+//! | `6..38`  | [`hasher_id`] of the hasher of the proof           |
+//! | `38..70` | [`domain_id`] of the domain of the proof           |
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use super::{ExclusionProof, SparseMerkleProof, SparseMerkleRangeProof, UpdateMerkleProof};
+use crate::SimpleHasher;
+
+/// The first bytes of every proof envelope.
+pub const ENVELOPE_MAGIC: [u8; 4] = *b"JMTP";
+
+/// The version of the envelope format written by [`EnvelopedProof::to_envelope`].
+pub const ENVELOPE_FORMAT_VERSION: u8 = 1;
+
+/// The length of an encoded [`ProofHeader`].
+pub const ENVELOPE_HEADER_LEN: usize = 70;
+
+/// Returns the identifier of the hash function of `H`: its hash of `"JMT::HasherId"`.
+///
+/// Hashers which only differ by their [`Domain`](crate::domain::Domain) have the same identifier.
+pub fn hasher_id<H: SimpleHasher>() -> [u8; 32] {
+    H::hash(b"JMT::HasherId")
+}
+
+/// Returns the identifier of the domain of `H`: its hash of `"JMT::DomainId"`, followed by its
+/// leaf and internal domain separators, each prefixed with its length as a little-endian `u32`,
+/// and its placeholder hash.
+pub fn domain_id<H: SimpleHasher>() -> [u8; 32] {
+    let mut hasher = H::new();
+    hasher.update(b"JMT::DomainId");
+    for separator in [H::LEAF_DOMAIN_SEPARATOR, H::INTERNAL_DOMAIN_SEPARATOR] {
+        hasher.update(&(separator.len() as u32).to_le_bytes());
+        hasher.update(separator);
+    }
+    hasher.update(&H::SPARSE_MERKLE_PLACEHOLDER_HASH);
+    hasher.finalize()
+}
+
+/// The type of the proof in an envelope.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum ProofKind {
+    /// A [`SparseMerkleProof`].
+    SparseMerkle = 0,
+    /// An [`ExclusionProof`].
+    Exclusion = 1,
+    /// An [`UpdateMerkleProof`].
+    Update = 2,
+    /// A [`SparseMerkleRangeProof`].
+    Range = 3,
+}
+
+impl ProofKind {
+    fn from_u8(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(ProofKind::SparseMerkle),
+            1 => Some(ProofKind::Exclusion),
+            2 => Some(ProofKind::Update),
+            3 => Some(ProofKind::Range),
+            _ => None,
+        }
+    }
+}
+
+/// The header of a proof envelope, which describes the proof following it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ProofHeader {
+    /// The version of the envelope format.
+    pub format_version: u8,
+    /// The type of the proof.
+    pub kind: ProofKind,
+    /// The [`hasher_id`] of the hasher of the proof.
+    pub hasher_id: [u8; 32],
+    /// The [`domain_id`] of the domain of the proof.
+    pub domain_id: [u8; 32],
+}
+
+impl ProofHeader {
+    /// Returns the header of the proofs of type `kind` hashed with `H`.
+    pub fn new<H: SimpleHasher>(kind: ProofKind) -> Self {
+        Self {
+            format_version: ENVELOPE_FORMAT_VERSION,
+            kind,
+            hasher_id: hasher_id::<H>(),
+            domain_id: domain_id::<H>(),
+        }
+    }
+
+    /// Decodes the header at the start of `envelope`, and returns it along with the encoded proof
+    /// which follows it.
+    pub fn decode(envelope: &[u8]) -> Result<(Self, &[u8]), EnvelopeError> {
+        if envelope.len() < ENVELOPE_HEADER_LEN {
+            return Err(EnvelopeError::Truncated {
+                len: envelope.len(),
+            });
+        }
+        let (header, proof) = envelope.split_at(ENVELOPE_HEADER_LEN);
+        if header[..4] != ENVELOPE_MAGIC {
+            return Err(EnvelopeError::BadMagic);
+        }
+        let format_version = header[4];
+        if format_version != ENVELOPE_FORMAT_VERSION {
+            return Err(EnvelopeError::UnsupportedFormatVersion { format_version });
+        }
+        let kind = ProofKind::from_u8(header[5])
+            .ok_or(EnvelopeError::UnknownProofKind { tag: header[5] })?;
+        Ok((
+            Self {
+                format_version,
+                kind,
+                hasher_id: header[6..38].try_into().expect("slice has 32 bytes"),
+                domain_id: header[38..70].try_into().expect("slice has 32 bytes"),
+            },
+            proof,
+        ))
+    }
+
+    /// Encodes the header.
+    pub fn encode(&self) -> [u8; ENVELOPE_HEADER_LEN] {
+        let mut header = [0; ENVELOPE_HEADER_LEN];
+        header[..4].copy_from_slice(&ENVELOPE_MAGIC);
+        header[4] = self.format_version;
+        header[5] = self.kind as u8;
+        header[6..38].copy_from_slice(&self.hasher_id);
+        header[38..70].copy_from_slice(&self.domain_id);
+        header
+    }
+
+    /// Returns whether the proof was created by a tree hashed with `H`, in the domain of `H`.
+    pub fn is_hashed_with<H: SimpleHasher>(&self) -> bool {
+        self.hasher_id == hasher_id::<H>() && self.domain_id == domain_id::<H>()
+    }
+
+    /// Checks that this is the header of a proof of type `kind` hashed with `H`.
+    pub fn check<H: SimpleHasher>(&self, kind: ProofKind) -> Result<(), EnvelopeError> {
+        if self.kind != kind {
+            return Err(EnvelopeError::ProofKindMismatch {
+                expected: kind,
+                actual: self.kind,
+            });
+        }
+        if self.hasher_id != hasher_id::<H>() {
+            return Err(EnvelopeError::HasherMismatch {
+                expected: hasher_id::<H>(),
+                actual: self.hasher_id,
+            });
+        }
+        if self.domain_id != domain_id::<H>() {
+            return Err(EnvelopeError::DomainMismatch {
+                expected: domain_id::<H>(),
+                actual: self.domain_id,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// A proof type which can be wrapped in a self-describing envelope.
+pub trait EnvelopedProof: BorshSerialize + BorshDeserialize {
+    /// The hasher of the tree which created the proof.
+    type Hasher: SimpleHasher;
+    /// The type of the proof, as recorded in its envelope.
+    const KIND: ProofKind;
+
+    /// Returns the header of the envelopes of this proof type.
+    fn envelope_header() -> ProofHeader {
+        ProofHeader::new::<Self::Hasher>(Self::KIND)
+    }
+
+    /// Encodes the proof in an envelope.
+    fn to_envelope(&self) -> Vec<u8> {
+        let mut envelope = Self::envelope_header().encode().to_vec();
+        self.serialize(&mut envelope)
+            .expect("serialization is infallible");
+        envelope
+    }
+
+    /// Decodes a proof from an envelope, after checking that its header matches the proof type,
+    /// its hasher and its domain.
+    fn from_envelope(envelope: &[u8]) -> Result<Self, EnvelopeError> {
+        let (header, proof) = ProofHeader::decode(envelope)?;
+        header.check::<Self::Hasher>(Self::KIND)?;
+        borsh::from_slice(proof).map_err(|error| EnvelopeError::MalformedProof {
+            reason: alloc::format!("{}", error),
+        })
+    }
+}
+
+impl<H: SimpleHasher> EnvelopedProof for SparseMerkleProof<H> {
+    type Hasher = H;
+    const KIND: ProofKind = ProofKind::SparseMerkle;
+}
+
+impl<H: SimpleHasher> EnvelopedProof for ExclusionProof<H> {
+    type Hasher = H;
+    const KIND: ProofKind = ProofKind::Exclusion;
+}
+
+impl<H: SimpleHasher> EnvelopedProof for UpdateMerkleProof<H> {
+    type Hasher = H;
+    const KIND: ProofKind = ProofKind::Update;
+}
+
+impl<H: SimpleHasher> EnvelopedProof for SparseMerkleRangeProof<H> {
+    type Hasher = H;
+    const KIND: ProofKind = ProofKind::Range;
+}
+
+/// The reason a proof envelope was rejected by [`EnvelopedProof::from_envelope`] or
+/// [`ProofHeader::decode`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EnvelopeError {
+    /// The envelope is shorter than its header.
+    Truncated { len: usize },
+    /// The envelope does not start with [`ENVELOPE_MAGIC`].
+    BadMagic,
+    /// The envelope was written in a format version this crate does not know about.
+    UnsupportedFormatVersion { format_version: u8 },
+    /// The envelope holds a type of proof this crate does not know about.
+    UnknownProofKind { tag: u8 },
+    /// The envelope holds another type of proof than the expected one.
+    ProofKindMismatch {
+        expected: ProofKind,
+        actual: ProofKind,
+    },
+    /// The proof was created with another hash function than the expected one.
+    HasherMismatch {
+        expected: [u8; 32],
+        actual: [u8; 32],
+    },
+    /// The proof was created in another domain than the expected one.
+    DomainMismatch {
+        expected: [u8; 32],
+        actual: [u8; 32],
+    },
+    /// The proof following the header could not be decoded, or its parts do not make a proof, as
+    /// told by the `try_new` constructor of the proof.
+    MalformedProof { reason: String },
+}
+
+impl fmt::Display for EnvelopeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvelopeError::Truncated { len } => write!(
+                f,
+                "Proof envelope of {} bytes is shorter than its {}-byte header.",
+                len, ENVELOPE_HEADER_LEN
+            ),
+            EnvelopeError::BadMagic => write!(f, "Not a proof envelope."),
+            EnvelopeError::UnsupportedFormatVersion { format_version } => write!(
+                f,
+                "Unsupported proof envelope format version {}. Expected version {}.",
+                format_version, ENVELOPE_FORMAT_VERSION
+            ),
+            EnvelopeError::UnknownProofKind { tag } => {
+                write!(f, "Unknown proof kind {} in proof envelope.", tag)
+            }
+            EnvelopeError::ProofKindMismatch { expected, actual } => write!(
+                f,
+                "Proof kinds do not match. Proof in envelope: {:?}. Expected proof: {:?}.",
+                actual, expected
+            ),
+            EnvelopeError::HasherMismatch { expected, actual } => write!(
+                f,
+                "Hashers do not match. Hasher of proof: {}. Expected hasher: {}.",
+                hex::encode(actual),
+                hex::encode(expected)
+            ),
+            EnvelopeError::DomainMismatch { expected, actual } => write!(
+                f,
+                "Domains do not match. Domain of proof: {}. Expected domain: {}.",
+                hex::encode(actual),
+                hex::encode(expected)
+            ),
+            EnvelopeError::MalformedProof { reason } => {
+                write!(f, "Malformed proof in envelope: {}", reason)
+            }
+        }
+    }
+}
+
+impl core::error::Error for EnvelopeError {}
@@ -0,0 +1,180 @@
+// Copyright (c) The Diem Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! The error returned when a Merkle proof fails to verify.
+
+use core::fmt;
+
+use crate::{KeyHash, RootHash, ValueHash};
+
+/// The reason a proof was rejected by one of the `verify*` methods of the [proof
+/// types](crate::proof).
+///
+/// Unlike the rest of the crate, verification does not report errors through `anyhow`, so that
+/// it can be used in verifier-only builds of this crate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum VerificationError {
+    /// The proof has more siblings than the tree has levels.
+    TooManySiblings { num_siblings: usize },
+    /// The key of the leaf in the proof is not the expected key.
+    KeyMismatch { expected: KeyHash, actual: KeyHash },
+    /// The value hash of the leaf in the proof is not the hash of the expected value.
+    ValueHashMismatch {
+        expected: ValueHash,
+        actual: ValueHash,
+    },
+    /// An inclusion proof was expected, but the proof does not contain a leaf.
+    ExpectedInclusionProof,
+    /// A non-inclusion proof was expected, but the proof contains a leaf with the requested key.
+    ExpectedNonInclusionProof,
+    /// The requested key would not be located in the subtree that the proof's leaf is the
+    /// only occupant of, so that leaf does not prove anything about the key.
+    KeyOutsideLeafSubtree,
+    /// The root hash computed from the proof is not the expected root hash.
+    RootHashMismatch {
+        expected: RootHash,
+        actual: RootHash,
+    },
+    /// The proof ran out of siblings before reaching the root.
+    MissingSibling,
+    /// A proof of a neighboring leaf does not contain a leaf.
+    MissingNeighborLeaf,
+    /// The key does not lie strictly between the neighbors of an exclusion proof.
+    KeyNotBetweenNeighbors,
+    /// Another leaf may exist between the neighbors of an exclusion proof.
+    NonAdjacentNeighbors,
+    /// The path to the key goes through a part of a
+    /// [`PartialTree`](crate::proof::PartialTree) that none of its proofs reveal.
+    KeyNotCovered { key_hash: KeyHash },
+    /// The number of proofs in an update proof is not the number of updates.
+    UpdateCountMismatch {
+        num_proofs: usize,
+        num_updates: usize,
+    },
+    /// Updating the tree depends on whether a sibling is a leaf, but the proof only reveals the
+    /// hash of that sibling.
+    UntypedSibling,
+    /// A sibling hash of the proof is not a canonical encoding for the hasher, as told by
+    /// [`SimpleHasher::is_canonical_hash`](crate::SimpleHasher::is_canonical_hash).
+    NonCanonicalHash,
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerificationError::TooManySiblings { num_siblings } => write!(
+                f,
+                "Sparse Merkle Tree proof has more than 256 ({}) siblings.",
+                num_siblings
+            ),
+            VerificationError::KeyMismatch { expected, actual } => write!(
+                f,
+                "Keys do not match. Key in proof: {:?}. Expected key: {:?}.",
+                actual, expected
+            ),
+            VerificationError::ValueHashMismatch { expected, actual } => write!(
+                f,
+                "Value hashes do not match. Value hash in proof: {:?}. Expected value hash: {:?}",
+                actual, expected
+            ),
+            VerificationError::ExpectedInclusionProof => {
+                write!(f, "Expected inclusion proof. Found non-inclusion proof.")
+            }
+            VerificationError::ExpectedNonInclusionProof => {
+                write!(f, "Expected non-inclusion proof, but key exists in proof.")
+            }
+            VerificationError::KeyOutsideLeafSubtree => write!(
+                f,
+                "Key would not have ended up in the subtree where the provided key in proof is \
+                 the only existing key, if it existed. So this is not a valid non-inclusion proof."
+            ),
+            VerificationError::RootHashMismatch { expected, actual } => write!(
+                f,
+                "Root hashes do not match. Actual root hash: {:?}. Expected root hash: {:?}.",
+                actual, expected
+            ),
+            VerificationError::MissingSibling => write!(f, "Missing sibling."),
+            VerificationError::MissingNeighborLeaf => {
+                write!(f, "Neighbor proof must contain a leaf.")
+            }
+            VerificationError::KeyNotBetweenNeighbors => {
+                write!(
+                    f,
+                    "Key is not between the neighbors of the exclusion proof."
+                )
+            }
+            VerificationError::NonAdjacentNeighbors => write!(
+                f,
+                "Neighbors of the exclusion proof are not adjacent in the tree."
+            ),
+            VerificationError::KeyNotCovered { key_hash } => write!(
+                f,
+                "Key {:?} is not covered by the proofs of the partial tree.",
+                key_hash
+            ),
+            VerificationError::UpdateCountMismatch {
+                num_proofs,
+                num_updates,
+            } => write!(
+                f,
+                "Mismatched number of updates and proofs. Received {} proofs for {} updates",
+                num_proofs, num_updates
+            ),
+            VerificationError::UntypedSibling => write!(
+                f,
+                "The update depends on the type of a sibling known only by its hash."
+            ),
+            VerificationError::NonCanonicalHash => {
+                write!(f, "Proof has a sibling hash which is not canonical.")
+            }
+        }
+    }
+}
+
+impl core::error::Error for VerificationError {}
+
+/// The reason the parts of a proof were rejected by one of the `try_new` constructors of the
+/// [proof types](crate::proof).
+///
+/// These checks only reject proofs which no tree could have produced. A proof which passes them
+/// still proves nothing until it is verified against a trusted root hash.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MalformedProofError {
+    /// The proof has more siblings than the tree has levels.
+    TooManySiblings { num_siblings: usize },
+    /// The lowest sibling is a placeholder, so the node at the bottom of the path should have
+    /// taken the place of its parent.
+    PlaceholderAtBottom,
+    /// An untyped sibling has the placeholder hash, instead of being a placeholder.
+    UntypedPlaceholder { index: usize },
+    /// A leaf sibling does not branch off the path to the leaf of the proof where it is located:
+    /// its key does not share the prefix of that path.
+    MisplacedLeafSibling { index: usize },
+}
+
+impl fmt::Display for MalformedProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MalformedProofError::TooManySiblings { num_siblings } => write!(
+                f,
+                "Sparse Merkle Tree proof has more than 256 ({}) siblings.",
+                num_siblings
+            ),
+            MalformedProofError::PlaceholderAtBottom => {
+                write!(f, "The lowest sibling of the proof is a placeholder.")
+            }
+            MalformedProofError::UntypedPlaceholder { index } => {
+                write!(f, "Untyped sibling {} has the placeholder hash.", index)
+            }
+            MalformedProofError::MisplacedLeafSibling { index } => write!(
+                f,
+                "The key of leaf sibling {} does not match its position in the proof.",
+                index
+            ),
+        }
+    }
+}
+
+impl core::error::Error for MalformedProofError {}
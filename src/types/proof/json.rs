@@ -0,0 +1,294 @@
+//! A canonical JSON form of proofs, for clients written in other languages.
+//!
+//! The serde encoding of the proof types writes hashes as arrays of 32 integers, and follows the
+//! layout of the Rust types. The types of this module are a stable serde form of the proofs
+//! instead, in which every hash is a string of 64 lowercase hex digits and every node states its
+//! `type`. Serialized with `serde_json`, a [`SparseMerkleProof`] with a leaf and four siblings
+//! looks like this (with shortened hashes):
+//!
+//! ```json
+//! {
+//!   "leaf": { "key_hash": "3a7c…", "value_hash": "9f0e…" },
+//!   "siblings": [
+//!     { "type": "leaf", "key_hash": "2b11…", "value_hash": "04cd…" },
+//!     { "type": "null" },
+//!     { "type": "internal", "left_child": "77a0…", "right_child": "e3f9…" },
+//!     { "type": "untyped", "hash": "5d2c…" }
+//!   ]
+//! }
+//! ```
+//!
+//! A proof without a leaf has `"leaf": null`. Siblings are ordered from the bottom level to the
+//! root level, like in the proof types. A [`SparseMerkleRangeProof`] is an object with its
+//! `right_siblings`, an [`UpdateMerkleProof`] is an object with its `proofs`, and an
+//! [`ExclusionProof`] is an object with a `type` of `leftmost`, `middle` or `rightmost` and the
+//! proofs of its neighbors.
+//!
+//! Decoding is strict: unknown fields, uppercase hex digits and hashes of the wrong length are
+//! rejected, and proofs are checked like by [`SparseMerkleProof::try_new`].
+
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    ExclusionProof, MalformedProofError, SparseMerkleInternalNode, SparseMerkleLeafNode,
+    SparseMerkleNode, SparseMerkleProof, SparseMerkleRangeProof, UpdateMerkleProof,
+};
+use crate::{KeyHash, SimpleHasher, ValueHash};
+
+/// The JSON form of a [`SparseMerkleLeafNode`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JsonSparseMerkleLeafNode {
+    #[serde(with = "hex_hash")]
+    pub key_hash: [u8; 32],
+    #[serde(with = "hex_hash")]
+    pub value_hash: [u8; 32],
+}
+
+/// The JSON form of a [`SparseMerkleNode`], tagged with its `type`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum JsonSparseMerkleNode {
+    Null,
+    Internal {
+        #[serde(with = "hex_hash")]
+        left_child: [u8; 32],
+        #[serde(with = "hex_hash")]
+        right_child: [u8; 32],
+    },
+    Leaf {
+        #[serde(with = "hex_hash")]
+        key_hash: [u8; 32],
+        #[serde(with = "hex_hash")]
+        value_hash: [u8; 32],
+    },
+    Untyped {
+        #[serde(with = "hex_hash")]
+        hash: [u8; 32],
+    },
+}
+
+/// The JSON form of a [`SparseMerkleProof`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JsonSparseMerkleProof {
+    pub leaf: Option<JsonSparseMerkleLeafNode>,
+    pub siblings: Vec<JsonSparseMerkleNode>,
+}
+
+/// The JSON form of a [`SparseMerkleRangeProof`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JsonSparseMerkleRangeProof {
+    pub right_siblings: Vec<JsonSparseMerkleNode>,
+}
+
+/// The JSON form of an [`UpdateMerkleProof`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JsonUpdateMerkleProof {
+    pub proofs: Vec<JsonSparseMerkleProof>,
+}
+
+/// The JSON form of an [`ExclusionProof`], tagged with its `type`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum JsonExclusionProof {
+    Leftmost {
+        leftmost_right_proof: JsonSparseMerkleProof,
+    },
+    Middle {
+        leftmost_right_proof: JsonSparseMerkleProof,
+        rightmost_left_proof: JsonSparseMerkleProof,
+    },
+    Rightmost {
+        rightmost_left_proof: JsonSparseMerkleProof,
+    },
+}
+
+impl From<SparseMerkleLeafNode> for JsonSparseMerkleLeafNode {
+    fn from(leaf: SparseMerkleLeafNode) -> Self {
+        Self {
+            key_hash: leaf.key_hash.0,
+            value_hash: leaf.value_hash.0,
+        }
+    }
+}
+
+impl From<JsonSparseMerkleLeafNode> for SparseMerkleLeafNode {
+    fn from(leaf: JsonSparseMerkleLeafNode) -> Self {
+        SparseMerkleLeafNode::new(KeyHash(leaf.key_hash), ValueHash(leaf.value_hash))
+    }
+}
+
+impl From<SparseMerkleNode> for JsonSparseMerkleNode {
+    fn from(node: SparseMerkleNode) -> Self {
+        match node {
+            SparseMerkleNode::Null => JsonSparseMerkleNode::Null,
+            SparseMerkleNode::Internal(node) => JsonSparseMerkleNode::Internal {
+                left_child: node.left_child(),
+                right_child: node.right_child(),
+            },
+            SparseMerkleNode::Leaf(leaf) => JsonSparseMerkleNode::Leaf {
+                key_hash: leaf.key_hash.0,
+                value_hash: leaf.value_hash.0,
+            },
+            SparseMerkleNode::Untyped(hash) => JsonSparseMerkleNode::Untyped { hash },
+        }
+    }
+}
+
+impl From<JsonSparseMerkleNode> for SparseMerkleNode {
+    fn from(node: JsonSparseMerkleNode) -> Self {
+        match node {
+            JsonSparseMerkleNode::Null => SparseMerkleNode::Null,
+            JsonSparseMerkleNode::Internal {
+                left_child,
+                right_child,
+            } => SparseMerkleNode::Internal(SparseMerkleInternalNode::new(left_child, right_child)),
+            JsonSparseMerkleNode::Leaf {
+                key_hash,
+                value_hash,
+            } => SparseMerkleNode::Leaf(SparseMerkleLeafNode::new(
+                KeyHash(key_hash),
+                ValueHash(value_hash),
+            )),
+            JsonSparseMerkleNode::Untyped { hash } => SparseMerkleNode::Untyped(hash),
+        }
+    }
+}
+
+impl<H: SimpleHasher> From<&SparseMerkleProof<H>> for JsonSparseMerkleProof {
+    fn from(proof: &SparseMerkleProof<H>) -> Self {
+        Self {
+            leaf: proof.leaf().map(Into::into),
+            siblings: proof.siblings().iter().map(|node| (*node).into()).collect(),
+        }
+    }
+}
+
+impl<H: SimpleHasher> TryFrom<JsonSparseMerkleProof> for SparseMerkleProof<H> {
+    type Error = MalformedProofError;
+
+    fn try_from(proof: JsonSparseMerkleProof) -> Result<Self, Self::Error> {
+        SparseMerkleProof::try_new(
+            proof.leaf.map(Into::into),
+            proof.siblings.into_iter().map(Into::into).collect(),
+        )
+    }
+}
+
+impl<H: SimpleHasher> From<&SparseMerkleRangeProof<H>> for JsonSparseMerkleRangeProof {
+    fn from(proof: &SparseMerkleRangeProof<H>) -> Self {
+        Self {
+            right_siblings: proof
+                .right_siblings()
+                .iter()
+                .map(|node| (*node).into())
+                .collect(),
+        }
+    }
+}
+
+impl<H: SimpleHasher> TryFrom<JsonSparseMerkleRangeProof> for SparseMerkleRangeProof<H> {
+    type Error = MalformedProofError;
+
+    fn try_from(proof: JsonSparseMerkleRangeProof) -> Result<Self, Self::Error> {
+        SparseMerkleRangeProof::try_new(proof.right_siblings.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<H: SimpleHasher> From<&UpdateMerkleProof<H>> for JsonUpdateMerkleProof {
+    fn from(proof: &UpdateMerkleProof<H>) -> Self {
+        Self {
+            proofs: proof.proofs().iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl<H: SimpleHasher> TryFrom<JsonUpdateMerkleProof> for UpdateMerkleProof<H> {
+    type Error = MalformedProofError;
+
+    fn try_from(proof: JsonUpdateMerkleProof) -> Result<Self, Self::Error> {
+        Ok(UpdateMerkleProof::new(
+            proof
+                .proofs
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, _>>()?,
+        ))
+    }
+}
+
+impl<H: SimpleHasher> From<&ExclusionProof<H>> for JsonExclusionProof {
+    fn from(proof: &ExclusionProof<H>) -> Self {
+        match proof {
+            ExclusionProof::Leftmost {
+                leftmost_right_proof,
+            } => JsonExclusionProof::Leftmost {
+                leftmost_right_proof: leftmost_right_proof.into(),
+            },
+            ExclusionProof::Middle {
+                leftmost_right_proof,
+                rightmost_left_proof,
+            } => JsonExclusionProof::Middle {
+                leftmost_right_proof: leftmost_right_proof.into(),
+                rightmost_left_proof: rightmost_left_proof.into(),
+            },
+            ExclusionProof::Rightmost {
+                rightmost_left_proof,
+            } => JsonExclusionProof::Rightmost {
+                rightmost_left_proof: rightmost_left_proof.into(),
+            },
+        }
+    }
+}
+
+impl<H: SimpleHasher> TryFrom<JsonExclusionProof> for ExclusionProof<H> {
+    type Error = MalformedProofError;
+
+    fn try_from(proof: JsonExclusionProof) -> Result<Self, Self::Error> {
+        Ok(match proof {
+            JsonExclusionProof::Leftmost {
+                leftmost_right_proof,
+            } => ExclusionProof::Leftmost {
+                leftmost_right_proof: leftmost_right_proof.try_into()?,
+            },
+            JsonExclusionProof::Middle {
+                leftmost_right_proof,
+                rightmost_left_proof,
+            } => ExclusionProof::Middle {
+                leftmost_right_proof: leftmost_right_proof.try_into()?,
+                rightmost_left_proof: rightmost_left_proof.try_into()?,
+            },
+            JsonExclusionProof::Rightmost {
+                rightmost_left_proof,
+            } => ExclusionProof::Rightmost {
+                rightmost_left_proof: rightmost_left_proof.try_into()?,
+            },
+        })
+    }
+}
+
+/// Serializes hashes as strings of 64 lowercase hex digits.
+mod hex_hash {
+    use alloc::string::String;
+
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(hash: &[u8; 32], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(hash))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 32], D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        if hex.bytes().any(|byte| byte.is_ascii_uppercase()) {
+            return Err(D::Error::custom("hashes must be lowercase hex"));
+        }
+        let mut hash = [0; 32];
+        hex::decode_to_slice(hex, &mut hash).map_err(D::Error::custom)?;
+        Ok(hash)
+    }
+}
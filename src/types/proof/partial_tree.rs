@@ -0,0 +1,336 @@
+//! A partial view of a tree, reconstructed from proofs alone.
+
+use alloc::boxed::Box;
+use core::marker::PhantomData;
+
+use super::{
+    SparseMerkleInternalNode, SparseMerkleLeafNode, SparseMerkleNode, SparseMerkleProof,
+    VerificationError,
+};
+use crate::{KeyHash, RootHash, SimpleHasher, ValueHash};
+
+/// A node of a [`PartialTree`], in the binary view of the tree where subtrees containing zero or
+/// one leaf are replaced by a placeholder or by that leaf.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum PartialNode {
+    /// An empty subtree.
+    Empty,
+    /// A subtree containing a single leaf.
+    Leaf(SparseMerkleLeafNode),
+    /// A subtree containing at least two leaves, whose children are known.
+    Internal(Box<PartialNode>, Box<PartialNode>),
+    /// A subtree containing at least two leaves, of which only the hash is known.
+    Opaque([u8; 32]),
+    /// A non-empty subtree of which only the hash is known, which may be a single leaf.
+    Untyped([u8; 32]),
+}
+
+impl PartialNode {
+    fn from_sibling<H: SimpleHasher>(sibling: &SparseMerkleNode) -> Self {
+        match sibling {
+            SparseMerkleNode::Null => PartialNode::Empty,
+            SparseMerkleNode::Leaf(leaf) => PartialNode::Leaf(*leaf),
+            SparseMerkleNode::Internal(node) => PartialNode::Opaque(node.hash::<H>()),
+            SparseMerkleNode::Untyped(hash) => PartialNode::Untyped(*hash),
+        }
+    }
+
+    fn internal(left: PartialNode, right: PartialNode) -> Self {
+        PartialNode::Internal(Box::new(left), Box::new(right))
+    }
+
+    fn hash<H: SimpleHasher>(&self) -> [u8; 32] {
+        match self {
+            PartialNode::Empty => H::SPARSE_MERKLE_PLACEHOLDER_HASH,
+            PartialNode::Leaf(leaf) => leaf.hash::<H>(),
+            PartialNode::Internal(left, right) => {
+                SparseMerkleInternalNode::new(left.hash::<H>(), right.hash::<H>()).hash::<H>()
+            }
+            PartialNode::Opaque(hash) | PartialNode::Untyped(hash) => *hash,
+        }
+    }
+
+    /// Combines two views of the same subtree, keeping whatever either of them reveals.
+    ///
+    /// Both views are expected to have the same hash, so they can only differ in which parts
+    /// are opaque.
+    fn merge(self, other: PartialNode) -> PartialNode {
+        match (self, other) {
+            (PartialNode::Untyped(_), node) | (node, PartialNode::Untyped(_)) => node,
+            (PartialNode::Opaque(_), node) | (node, PartialNode::Opaque(_)) => node,
+            (
+                PartialNode::Internal(left, right),
+                PartialNode::Internal(other_left, other_right),
+            ) => PartialNode::internal(left.merge(*other_left), right.merge(*other_right)),
+            (node, _) => node,
+        }
+    }
+
+    fn get(&self, key_hash: KeyHash, depth: usize) -> Result<Option<ValueHash>, VerificationError> {
+        match self {
+            PartialNode::Empty => Ok(None),
+            PartialNode::Leaf(leaf) => Ok((leaf.key_hash == key_hash).then_some(leaf.value_hash)),
+            PartialNode::Internal(left, right) => {
+                if bit(key_hash, depth) {
+                    right.get(key_hash, depth + 1)
+                } else {
+                    left.get(key_hash, depth + 1)
+                }
+            }
+            PartialNode::Opaque(_) | PartialNode::Untyped(_) => {
+                Err(VerificationError::KeyNotCovered { key_hash })
+            }
+        }
+    }
+
+    fn put(
+        &mut self,
+        new_leaf: SparseMerkleLeafNode,
+        depth: usize,
+    ) -> Result<(), VerificationError> {
+        match self {
+            PartialNode::Empty => *self = PartialNode::Leaf(new_leaf),
+            PartialNode::Leaf(leaf) if leaf.key_hash == new_leaf.key_hash => *leaf = new_leaf,
+            PartialNode::Leaf(leaf) => *self = Self::split(*leaf, new_leaf, depth),
+            PartialNode::Internal(left, right) => {
+                if bit(new_leaf.key_hash, depth) {
+                    right.put(new_leaf, depth + 1)?
+                } else {
+                    left.put(new_leaf, depth + 1)?
+                }
+            }
+            PartialNode::Opaque(_) | PartialNode::Untyped(_) => {
+                return Err(VerificationError::KeyNotCovered {
+                    key_hash: new_leaf.key_hash,
+                })
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds the subtree at `depth` containing the two distinct leaves.
+    fn split(leaf: SparseMerkleLeafNode, new_leaf: SparseMerkleLeafNode, depth: usize) -> Self {
+        match (bit(leaf.key_hash, depth), bit(new_leaf.key_hash, depth)) {
+            (false, true) => Self::internal(PartialNode::Leaf(leaf), PartialNode::Leaf(new_leaf)),
+            (true, false) => Self::internal(PartialNode::Leaf(new_leaf), PartialNode::Leaf(leaf)),
+            (false, false) => {
+                Self::internal(Self::split(leaf, new_leaf, depth + 1), PartialNode::Empty)
+            }
+            (true, true) => {
+                Self::internal(PartialNode::Empty, Self::split(leaf, new_leaf, depth + 1))
+            }
+        }
+    }
+
+    /// Returns what deleting `key_hash` turns this subtree into, without deleting it, so that
+    /// [`delete`](Self::delete) fails before changing anything.
+    fn check_delete(&self, key_hash: KeyHash, depth: usize) -> Result<Deleted, VerificationError> {
+        match self {
+            PartialNode::Empty => Ok(Deleted::Unchanged),
+            PartialNode::Leaf(leaf) if leaf.key_hash == key_hash => Ok(Deleted::Empty),
+            PartialNode::Leaf(_) => Ok(Deleted::Unchanged),
+            PartialNode::Internal(left, right) => {
+                let (child, sibling) = if bit(key_hash, depth) {
+                    (right, left)
+                } else {
+                    (left, right)
+                };
+                Ok(
+                    match (child.check_delete(key_hash, depth + 1)?, sibling.as_ref()) {
+                        (Deleted::Unchanged, _) => Deleted::Unchanged,
+                        (Deleted::Empty, PartialNode::Empty) => Deleted::Empty,
+                        (Deleted::Empty, PartialNode::Leaf(_))
+                        | (Deleted::Leaf, PartialNode::Empty) => Deleted::Leaf,
+                        // The subtree is coalesced only if its sibling is a single leaf.
+                        (Deleted::Empty, PartialNode::Untyped(_)) => {
+                            return Err(VerificationError::UntypedSibling)
+                        }
+                        _ => Deleted::Internal,
+                    },
+                )
+            }
+            PartialNode::Opaque(_) | PartialNode::Untyped(_) => {
+                Err(VerificationError::KeyNotCovered { key_hash })
+            }
+        }
+    }
+
+    fn delete(&mut self, key_hash: KeyHash, depth: usize) -> Result<(), VerificationError> {
+        match self {
+            PartialNode::Empty => {}
+            PartialNode::Leaf(leaf) => {
+                if leaf.key_hash == key_hash {
+                    *self = PartialNode::Empty;
+                }
+            }
+            PartialNode::Internal(left, right) => {
+                if bit(key_hash, depth) {
+                    right.delete(key_hash, depth + 1)?
+                } else {
+                    left.delete(key_hash, depth + 1)?
+                }
+
+                // Coalesce the subtree if it is left with zero or one leaf.
+                match (left.as_ref(), right.as_ref()) {
+                    (PartialNode::Empty, PartialNode::Empty) => *self = PartialNode::Empty,
+                    (PartialNode::Empty, PartialNode::Leaf(leaf))
+                    | (PartialNode::Leaf(leaf), PartialNode::Empty) => {
+                        *self = PartialNode::Leaf(*leaf)
+                    }
+                    _ => {}
+                }
+            }
+            PartialNode::Opaque(_) | PartialNode::Untyped(_) => {
+                return Err(VerificationError::KeyNotCovered { key_hash })
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What a subtree turns into when a key is deleted from it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Deleted {
+    /// The key is not in the subtree.
+    Unchanged,
+    Empty,
+    Leaf,
+    Internal,
+}
+
+/// Returns the bit of `key_hash` that selects a child at `depth`, `true` designating the right
+/// child.
+fn bit(key_hash: KeyHash, depth: usize) -> bool {
+    key_hash.0[depth / 8] & (1 << (7 - depth % 8)) != 0
+}
+
+/// A partial view of a [`JellyfishMerkleTree`](crate::JellyfishMerkleTree), built from a trusted
+/// root hash and proofs for some keys, which can be read and updated without access to the
+/// rest of the tree.
+///
+/// The paths of all the proven keys are merged into a single sparse tree, so that keys sharing
+/// siblings can be updated together: after any sequence of [`put`](Self::put) and
+/// [`delete`](Self::delete), [`root_hash`](Self::root_hash) is the root hash the full tree would
+/// have after the same updates.
+///
+/// Any key whose path is revealed by the proofs can be read and updated, which includes every
+/// proven key. Reaching a part of the tree that none of the proofs reveal is an error, and so is
+/// a deletion which depends on the type of a sibling that the proofs only reveal the hash of, as
+/// in [compacted](SparseMerkleProof::compact) proofs.
+pub struct PartialTree<H: SimpleHasher> {
+    root: PartialNode,
+    _phantom_hasher: PhantomData<H>,
+}
+
+// Manually implement Clone to circumvent [incorrect auto-bounds](https://github.com/rust-lang/rust/issues/26925)
+// TODO: Switch back to #[derive] once the perfect_derive feature lands
+impl<H: SimpleHasher> Clone for PartialTree<H> {
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+            _phantom_hasher: Default::default(),
+        }
+    }
+}
+
+// Manually implement Debug to circumvent [incorrect auto-bounds](https://github.com/rust-lang/rust/issues/26925)
+// TODO: Switch back to #[derive] once the perfect_derive feature lands
+impl<H: SimpleHasher> core::fmt::Debug for PartialTree<H> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PartialTree")
+            .field("root", &self.root)
+            .finish()
+    }
+}
+
+impl<H: SimpleHasher> PartialTree<H> {
+    /// Builds a partial tree from `proofs` of the keys they are paired with, each of which
+    /// must be a valid inclusion or non-inclusion proof under `root_hash`.
+    pub fn new<'a>(
+        root_hash: RootHash,
+        proofs: impl IntoIterator<Item = (KeyHash, &'a SparseMerkleProof<H>)>,
+    ) -> Result<Self, VerificationError>
+    where
+        H: 'a,
+    {
+        let mut root = if root_hash.0 == H::SPARSE_MERKLE_PLACEHOLDER_HASH {
+            PartialNode::Empty
+        } else {
+            PartialNode::Opaque(root_hash.0)
+        };
+
+        for (key_hash, proof) in proofs {
+            match proof.leaf() {
+                Some(leaf) if leaf.key_hash == key_hash => {
+                    if proof.siblings().len() > 256 {
+                        return Err(VerificationError::TooManySiblings {
+                            num_siblings: proof.siblings().len(),
+                        });
+                    }
+                    let actual_root_hash = proof.root_hash();
+                    if actual_root_hash != root_hash {
+                        return Err(VerificationError::RootHashMismatch {
+                            expected: root_hash,
+                            actual: actual_root_hash,
+                        });
+                    }
+                }
+                _ => proof.verify_nonexistence(root_hash, key_hash)?,
+            }
+            root = root.merge(Self::path_from_proof(key_hash, proof));
+        }
+
+        Ok(Self {
+            root,
+            _phantom_hasher: Default::default(),
+        })
+    }
+
+    /// Returns the subtree revealed by the path of `proof`, from the root to its leaf.
+    fn path_from_proof(key_hash: KeyHash, proof: &SparseMerkleProof<H>) -> PartialNode {
+        let bottom = match proof.leaf() {
+            Some(leaf) => PartialNode::Leaf(leaf),
+            None => PartialNode::Empty,
+        };
+        let num_siblings = proof.siblings().len();
+        // Siblings are ordered from the bottom level to the root level.
+        proof
+            .siblings()
+            .iter()
+            .enumerate()
+            .fold(bottom, |node, (i, sibling)| {
+                let sibling = PartialNode::from_sibling::<H>(sibling);
+                if bit(key_hash, num_siblings - 1 - i) {
+                    PartialNode::internal(sibling, node)
+                } else {
+                    PartialNode::internal(node, sibling)
+                }
+            })
+    }
+
+    /// Returns the current root hash of the tree.
+    pub fn root_hash(&self) -> RootHash {
+        RootHash(self.root.hash::<H>())
+    }
+
+    /// Returns the hash of the value currently stored under `key_hash`, if any.
+    pub fn get(&self, key_hash: KeyHash) -> Result<Option<ValueHash>, VerificationError> {
+        self.root.get(key_hash, 0)
+    }
+
+    /// Sets the value stored under `key_hash`.
+    pub fn put(
+        &mut self,
+        key_hash: KeyHash,
+        value: impl AsRef<[u8]>,
+    ) -> Result<(), VerificationError> {
+        let new_leaf = SparseMerkleLeafNode::new(key_hash, ValueHash::with::<H>(value));
+        self.root.put(new_leaf, 0)
+    }
+
+    /// Removes the value stored under `key_hash`, if any.
+    pub fn delete(&mut self, key_hash: KeyHash) -> Result<(), VerificationError> {
+        self.root.check_delete(key_hash, 0)?;
+        self.root.delete(key_hash, 0)
+    }
+}
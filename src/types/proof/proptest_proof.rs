@@ -9,7 +9,7 @@ use proptest::{collection::vec, prelude::*};
 
 use crate::{
     types::proof::{SparseMerkleLeafNode, SparseMerkleProof, SparseMerkleRangeProof},
-    SimpleHasher,
+    KeyHash, SimpleHasher,
 };
 
 use super::SparseMerkleNode;
@@ -27,6 +27,27 @@ fn arb_sparse_merkle_sibling() -> impl Strategy<Value = SparseMerkleNode> {
     ]
 }
 
+/// Rewrites the key of every leaf sibling of a proof with a leaf so that it branches off the path
+/// to that leaf at the depth of the sibling, as decoding a proof requires.
+fn place_leaf_siblings(
+    leaf: Option<SparseMerkleLeafNode>,
+    mut siblings: Vec<SparseMerkleNode>,
+) -> Vec<SparseMerkleNode> {
+    let Some(leaf) = leaf else {
+        return siblings;
+    };
+    let num_siblings = siblings.len();
+    for (index, sibling) in siblings.iter_mut().enumerate() {
+        if let SparseMerkleNode::Leaf(sibling_leaf) = sibling {
+            let depth = num_siblings - 1 - index;
+            let mut key_hash = leaf.key_hash().0;
+            key_hash[depth / 8] ^= 0x80 >> (depth % 8);
+            *sibling_leaf = SparseMerkleLeafNode::new(KeyHash(key_hash), sibling_leaf.value_hash());
+        }
+    }
+    siblings
+}
+
 impl<H: SimpleHasher + 'static> Arbitrary for SparseMerkleProof<H> {
     type Parameters = ();
     type Strategy = BoxedStrategy<Self>;
@@ -50,7 +71,10 @@ impl<H: SimpleHasher + 'static> Arbitrary for SparseMerkleProof<H> {
                 }
             }),
         )
-            .prop_map(|(leaf, siblings)| SparseMerkleProof::new(leaf, siblings))
+            .prop_map(|(leaf, siblings)| {
+                SparseMerkleProof::try_new(leaf, place_leaf_siblings(leaf, siblings))
+                    .expect("proof is well-formed")
+            })
             .boxed()
     }
 }
@@ -0,0 +1,362 @@
+//! Protobuf messages of the proofs, for clients written in other languages.
+//!
+//! These are the [prost] messages of the `jmt.proof.v1` package, whose schema is
+//! `proto/jmt/proof/v1/proof.proto` in the repository of this crate, and they convert to and from
+//! the proof types. Encode them with [`prost::Message`]. Like the [JSON form](super::json),
+//! messages are checked like by [`SparseMerkleProof::try_new`](super::SparseMerkleProof::try_new)
+//! when converted to proofs.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::MalformedProofError;
+use crate::{KeyHash, SimpleHasher, ValueHash};
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SparseMerkleLeafNode {
+    #[prost(bytes = "vec", tag = "1")]
+    pub key_hash: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub value_hash: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SparseMerkleInternalNode {
+    #[prost(bytes = "vec", tag = "1")]
+    pub left_child: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub right_child: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SparseMerkleNode {
+    #[prost(oneof = "sparse_merkle_node::Node", tags = "1, 2, 3, 4")]
+    pub node: Option<sparse_merkle_node::Node>,
+}
+
+/// Nested messages and enums of [`SparseMerkleNode`].
+pub mod sparse_merkle_node {
+    use alloc::vec::Vec;
+
+    /// An empty subtree, whose hash is the placeholder hash.
+    #[derive(Clone, Copy, PartialEq, ::prost::Message)]
+    pub struct Null {}
+
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Node {
+        #[prost(message, tag = "1")]
+        Null(Null),
+        #[prost(message, tag = "2")]
+        Internal(super::SparseMerkleInternalNode),
+        #[prost(message, tag = "3")]
+        Leaf(super::SparseMerkleLeafNode),
+        /// A non-empty node of which only the hash is known.
+        #[prost(bytes, tag = "4")]
+        Untyped(Vec<u8>),
+    }
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SparseMerkleProof {
+    /// Absent when the proof shows that the subtree of the key is empty.
+    #[prost(message, optional, tag = "1")]
+    pub leaf: Option<SparseMerkleLeafNode>,
+    #[prost(message, repeated, tag = "2")]
+    pub siblings: Vec<SparseMerkleNode>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SparseMerkleRangeProof {
+    #[prost(message, repeated, tag = "1")]
+    pub right_siblings: Vec<SparseMerkleNode>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdateMerkleProof {
+    /// One proof per update, in the order of the updates.
+    #[prost(message, repeated, tag = "1")]
+    pub proofs: Vec<SparseMerkleProof>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExclusionProof {
+    #[prost(oneof = "exclusion_proof::Proof", tags = "1, 2, 3")]
+    pub proof: Option<exclusion_proof::Proof>,
+}
+
+/// Nested messages and enums of [`ExclusionProof`].
+pub mod exclusion_proof {
+    /// The key is smaller than every key of the tree.
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Leftmost {
+        #[prost(message, optional, tag = "1")]
+        pub leftmost_right_proof: Option<super::SparseMerkleProof>,
+    }
+
+    /// The key falls between two adjacent keys of the tree.
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Middle {
+        #[prost(message, optional, tag = "1")]
+        pub leftmost_right_proof: Option<super::SparseMerkleProof>,
+        #[prost(message, optional, tag = "2")]
+        pub rightmost_left_proof: Option<super::SparseMerkleProof>,
+    }
+
+    /// The key is greater than every key of the tree.
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Rightmost {
+        #[prost(message, optional, tag = "1")]
+        pub rightmost_left_proof: Option<super::SparseMerkleProof>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Proof {
+        #[prost(message, tag = "1")]
+        Leftmost(Leftmost),
+        #[prost(message, tag = "2")]
+        Middle(Middle),
+        #[prost(message, tag = "3")]
+        Rightmost(Rightmost),
+    }
+}
+
+/// The reason a protobuf message was rejected when converting it to a proof.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProtoProofError {
+    /// A hash is not 32 bytes long.
+    InvalidHashLength { len: usize },
+    /// A field which is required to build the proof is absent.
+    MissingField { field: &'static str },
+    /// The proof was rejected by its constructor.
+    Malformed(MalformedProofError),
+}
+
+impl From<MalformedProofError> for ProtoProofError {
+    fn from(error: MalformedProofError) -> Self {
+        ProtoProofError::Malformed(error)
+    }
+}
+
+impl fmt::Display for ProtoProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtoProofError::InvalidHashLength { len } => {
+                write!(
+                    f,
+                    "Protobuf proof has a hash of {} bytes instead of 32.",
+                    len
+                )
+            }
+            ProtoProofError::MissingField { field } => {
+                write!(f, "Protobuf proof is missing its {} field.", field)
+            }
+            ProtoProofError::Malformed(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl core::error::Error for ProtoProofError {}
+
+fn hash(bytes: &[u8]) -> Result<[u8; 32], ProtoProofError> {
+    bytes
+        .try_into()
+        .map_err(|_| ProtoProofError::InvalidHashLength { len: bytes.len() })
+}
+
+impl From<super::SparseMerkleLeafNode> for SparseMerkleLeafNode {
+    fn from(leaf: super::SparseMerkleLeafNode) -> Self {
+        Self {
+            key_hash: leaf.key_hash().0.to_vec(),
+            value_hash: leaf.value_hash().0.to_vec(),
+        }
+    }
+}
+
+impl TryFrom<SparseMerkleLeafNode> for super::SparseMerkleLeafNode {
+    type Error = ProtoProofError;
+
+    fn try_from(leaf: SparseMerkleLeafNode) -> Result<Self, Self::Error> {
+        Ok(super::SparseMerkleLeafNode::new(
+            KeyHash(hash(&leaf.key_hash)?),
+            ValueHash(hash(&leaf.value_hash)?),
+        ))
+    }
+}
+
+impl From<super::SparseMerkleNode> for SparseMerkleNode {
+    fn from(node: super::SparseMerkleNode) -> Self {
+        use sparse_merkle_node::{Node, Null};
+        let node = match node {
+            super::SparseMerkleNode::Null => Node::Null(Null {}),
+            super::SparseMerkleNode::Internal(node) => Node::Internal(SparseMerkleInternalNode {
+                left_child: node.left_child().to_vec(),
+                right_child: node.right_child().to_vec(),
+            }),
+            super::SparseMerkleNode::Leaf(leaf) => Node::Leaf(leaf.into()),
+            super::SparseMerkleNode::Untyped(hash) => Node::Untyped(hash.to_vec()),
+        };
+        Self { node: Some(node) }
+    }
+}
+
+impl TryFrom<SparseMerkleNode> for super::SparseMerkleNode {
+    type Error = ProtoProofError;
+
+    fn try_from(node: SparseMerkleNode) -> Result<Self, Self::Error> {
+        use sparse_merkle_node::Node;
+        Ok(
+            match node
+                .node
+                .ok_or(ProtoProofError::MissingField { field: "node" })?
+            {
+                Node::Null(_) => super::SparseMerkleNode::Null,
+                Node::Internal(node) => {
+                    super::SparseMerkleNode::Internal(super::SparseMerkleInternalNode::new(
+                        hash(&node.left_child)?,
+                        hash(&node.right_child)?,
+                    ))
+                }
+                Node::Leaf(leaf) => super::SparseMerkleNode::Leaf(leaf.try_into()?),
+                Node::Untyped(bytes) => super::SparseMerkleNode::Untyped(hash(&bytes)?),
+            },
+        )
+    }
+}
+
+fn nodes(nodes: Vec<SparseMerkleNode>) -> Result<Vec<super::SparseMerkleNode>, ProtoProofError> {
+    nodes.into_iter().map(TryInto::try_into).collect()
+}
+
+impl<H: SimpleHasher> From<&super::SparseMerkleProof<H>> for SparseMerkleProof {
+    fn from(proof: &super::SparseMerkleProof<H>) -> Self {
+        Self {
+            leaf: proof.leaf().map(Into::into),
+            siblings: proof.siblings().iter().map(|node| (*node).into()).collect(),
+        }
+    }
+}
+
+impl<H: SimpleHasher> TryFrom<SparseMerkleProof> for super::SparseMerkleProof<H> {
+    type Error = ProtoProofError;
+
+    fn try_from(proof: SparseMerkleProof) -> Result<Self, Self::Error> {
+        let leaf = proof.leaf.map(TryInto::try_into).transpose()?;
+        Ok(super::SparseMerkleProof::try_new(
+            leaf,
+            nodes(proof.siblings)?,
+        )?)
+    }
+}
+
+impl<H: SimpleHasher> From<&super::SparseMerkleRangeProof<H>> for SparseMerkleRangeProof {
+    fn from(proof: &super::SparseMerkleRangeProof<H>) -> Self {
+        Self {
+            right_siblings: proof
+                .right_siblings()
+                .iter()
+                .map(|node| (*node).into())
+                .collect(),
+        }
+    }
+}
+
+impl<H: SimpleHasher> TryFrom<SparseMerkleRangeProof> for super::SparseMerkleRangeProof<H> {
+    type Error = ProtoProofError;
+
+    fn try_from(proof: SparseMerkleRangeProof) -> Result<Self, Self::Error> {
+        Ok(super::SparseMerkleRangeProof::try_new(nodes(
+            proof.right_siblings,
+        )?)?)
+    }
+}
+
+impl<H: SimpleHasher> From<&super::UpdateMerkleProof<H>> for UpdateMerkleProof {
+    fn from(proof: &super::UpdateMerkleProof<H>) -> Self {
+        Self {
+            proofs: proof.proofs().iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl<H: SimpleHasher> TryFrom<UpdateMerkleProof> for super::UpdateMerkleProof<H> {
+    type Error = ProtoProofError;
+
+    fn try_from(proof: UpdateMerkleProof) -> Result<Self, Self::Error> {
+        Ok(super::UpdateMerkleProof::new(
+            proof
+                .proofs
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, _>>()?,
+        ))
+    }
+}
+
+impl<H: SimpleHasher> From<&super::ExclusionProof<H>> for ExclusionProof {
+    fn from(proof: &super::ExclusionProof<H>) -> Self {
+        use exclusion_proof::{Leftmost, Middle, Proof, Rightmost};
+        let proof = match proof {
+            super::ExclusionProof::Leftmost {
+                leftmost_right_proof,
+            } => Proof::Leftmost(Leftmost {
+                leftmost_right_proof: Some(leftmost_right_proof.into()),
+            }),
+            super::ExclusionProof::Middle {
+                leftmost_right_proof,
+                rightmost_left_proof,
+            } => Proof::Middle(Middle {
+                leftmost_right_proof: Some(leftmost_right_proof.into()),
+                rightmost_left_proof: Some(rightmost_left_proof.into()),
+            }),
+            super::ExclusionProof::Rightmost {
+                rightmost_left_proof,
+            } => Proof::Rightmost(Rightmost {
+                rightmost_left_proof: Some(rightmost_left_proof.into()),
+            }),
+        };
+        Self { proof: Some(proof) }
+    }
+}
+
+impl<H: SimpleHasher> TryFrom<ExclusionProof> for super::ExclusionProof<H> {
+    type Error = ProtoProofError;
+
+    fn try_from(proof: ExclusionProof) -> Result<Self, Self::Error> {
+        use exclusion_proof::Proof;
+        let required = |proof: Option<SparseMerkleProof>, field| {
+            proof
+                .ok_or(ProtoProofError::MissingField { field })?
+                .try_into()
+        };
+        Ok(
+            match proof
+                .proof
+                .ok_or(ProtoProofError::MissingField { field: "proof" })?
+            {
+                Proof::Leftmost(proof) => super::ExclusionProof::Leftmost {
+                    leftmost_right_proof: required(
+                        proof.leftmost_right_proof,
+                        "leftmost_right_proof",
+                    )?,
+                },
+                Proof::Middle(proof) => super::ExclusionProof::Middle {
+                    leftmost_right_proof: required(
+                        proof.leftmost_right_proof,
+                        "leftmost_right_proof",
+                    )?,
+                    rightmost_left_proof: required(
+                        proof.rightmost_left_proof,
+                        "rightmost_left_proof",
+                    )?,
+                },
+                Proof::Rightmost(proof) => super::ExclusionProof::Rightmost {
+                    rightmost_left_proof: required(
+                        proof.rightmost_left_proof,
+                        "rightmost_left_proof",
+                    )?,
+                },
+            },
+        )
+    }
+}
@@ -0,0 +1,383 @@
+//! This module implements the recording of the storage accesses made by a
+//! [`JellyfishMerkleTree`](crate::JellyfishMerkleTree), so that the same operations can later be
+//! re-executed without access to the underlying storage.
+//!
+//! Wrap the storage in a [`RecordingTreeReader`] while executing some reads and writes against the
+//! tree, then extract the resulting [`Witness`]. Handing that witness to a [`WitnessTreeReader`]
+//! lets a stateless party replay the same operations, and recompute the same root hash. The
+//! reader only accepts a witness which [verifies](Witness::verify) against a trusted root hash.
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec,
+};
+use core::cell::RefCell;
+
+use anyhow::{bail, ensure, format_err, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    node_type::{LeafNode, Node, NodeKey},
+    storage::TreeReader,
+    types::nibble::ROOT_NIBBLE_HEIGHT,
+    Bytes32Ext, KeyHash, OwnedValue, RootHash, SimpleHasher, ValueHash, Version,
+};
+
+/// A self-contained bundle of every node and value read from storage by some operations on a
+/// [`JellyfishMerkleTree`](crate::JellyfishMerkleTree).
+///
+/// Lookups of nodes or values that turned out to be missing are recorded as well, since
+/// replaying the operations depends on their absence too. So are the versions the store resolved
+/// the versions read to, as a version skipped by
+/// [`put_value_set_after`](crate::JellyfishMerkleTree::put_value_set_after) is read from the root
+/// of an earlier one, and the versions new versions were built on.
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    borsh::BorshSerialize,
+    borsh::BorshDeserialize,
+)]
+pub struct Witness {
+    pub(crate) nodes: BTreeMap<NodeKey, Option<Node>>,
+    pub(crate) values: BTreeMap<(Version, KeyHash), Option<OwnedValue>>,
+    pub(crate) resolved_versions: BTreeMap<Version, Option<Version>>,
+    pub(crate) base_versions: BTreeMap<Version, Option<Version>>,
+}
+
+impl Witness {
+    /// Returns the recorded nodes, indexed by their node keys.
+    pub fn nodes(&self) -> &BTreeMap<NodeKey, Option<Node>> {
+        &self.nodes
+    }
+
+    /// Returns the recorded values, indexed by the maximum version and the key hash they were
+    /// looked up with.
+    pub fn values(&self) -> &BTreeMap<(Version, KeyHash), Option<OwnedValue>> {
+        &self.values
+    }
+
+    /// Returns the recorded versions, indexed by the version they were resolved from. See
+    /// [`TreeReader::latest_version_at_or_before`].
+    pub fn resolved_versions(&self) -> &BTreeMap<Version, Option<Version>> {
+        &self.resolved_versions
+    }
+
+    /// Returns the recorded versions new versions were built on, indexed by the base version they
+    /// were resolved from. See [`TreeReader::latest_version_to_build_on`].
+    pub fn base_versions(&self) -> &BTreeMap<Version, Option<Version>> {
+        &self.base_versions
+    }
+
+    /// Returns `true` if nothing has been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+            && self.values.is_empty()
+            && self.resolved_versions.is_empty()
+            && self.base_versions.is_empty()
+    }
+
+    /// Adds everything recorded in `other` to `self`.
+    pub fn merge(&mut self, other: Self) {
+        self.nodes.extend(other.nodes);
+        self.values.extend(other.values);
+        self.resolved_versions.extend(other.resolved_versions);
+        self.base_versions.extend(other.base_versions);
+    }
+
+    /// Checks that the witness is part of the tree whose root at `version` has the hash
+    /// `root_hash`.
+    ///
+    /// The root is the one of the version `version` was recorded to resolve to, if any, which
+    /// must not be after it. New versions must have been built on that same root. Starting from the root, every recorded node must hash to the hash
+    /// recorded for it by its parent, and recorded nodes which cannot be reached this way are
+    /// rejected. Every recorded value must have been looked up at `version`, and must match the
+    /// leaf found by following its key hash from the root, or be missing if that key hash has no
+    /// leaf.
+    pub fn verify<H: SimpleHasher>(&self, root_hash: RootHash, version: Version) -> Result<()> {
+        for (&resolved_from, &resolved_version) in
+            self.resolved_versions.iter().chain(&self.base_versions)
+        {
+            ensure!(
+                resolved_from == version,
+                "Version {} was resolved, not version {}.",
+                resolved_from,
+                version
+            );
+            ensure!(
+                !matches!(resolved_version, Some(resolved_version) if resolved_version > version),
+                "Version {} cannot resolve to the later version {:?}.",
+                version,
+                resolved_version
+            );
+        }
+        // A version which does not resolve keeps its own root key, as when reading the tree.
+        let root_version = self
+            .resolved_versions
+            .get(&version)
+            .copied()
+            .flatten()
+            .or_else(|| self.base_versions.get(&version).copied().flatten())
+            .unwrap_or(version);
+        ensure!(
+            !matches!(
+                self.base_versions.get(&version),
+                Some(base_version) if *base_version != Some(root_version)
+            ),
+            "Version {} was built on another version than the one it resolves to.",
+            version
+        );
+        let root_key = NodeKey::new_empty_path(root_version);
+        let mut reached = BTreeSet::new();
+        let mut pending = vec![(root_key.clone(), root_hash.0)];
+        while let Some((node_key, expected_hash)) = pending.pop() {
+            let node = match self.nodes.get(&node_key) {
+                // The subtree was not read, which is fine.
+                None => continue,
+                Some(None) => bail!(
+                    "Node at {:?} is recorded as missing, but its parent references it.",
+                    node_key
+                ),
+                Some(Some(node)) => node,
+            };
+            ensure!(
+                node.hash::<H>() == expected_hash,
+                "Node at {:?} does not match the hash of its parent's reference.",
+                node_key
+            );
+            if let Node::Internal(internal_node) = node {
+                pending.extend(internal_node.children_unsorted().map(|(nibble, child)| {
+                    (
+                        node_key.gen_child_node_key(child.version, nibble),
+                        child.hash,
+                    )
+                }));
+            }
+            reached.insert(node_key);
+        }
+        for (node_key, node) in &self.nodes {
+            ensure!(
+                node.is_none() || reached.contains(node_key),
+                "Node at {:?} cannot be reached from the root at version {}.",
+                node_key,
+                version
+            );
+        }
+
+        for (&(max_version, key_hash), value) in &self.values {
+            ensure!(
+                max_version == version,
+                "Value with key hash {:?} was looked up at version {}, not at version {}.",
+                key_hash,
+                max_version,
+                version
+            );
+            let value_hash = self.find_value_hash(&root_key, key_hash)?;
+            ensure!(
+                value_hash == value.as_ref().map(ValueHash::with::<H>),
+                "Value with key hash {:?} does not match its leaf.",
+                key_hash
+            );
+        }
+        Ok(())
+    }
+
+    /// Follows `key_hash` down from the root at `root_key`, returning the value hash of its leaf if
+    /// it has one.
+    fn find_value_hash(&self, root_key: &NodeKey, key_hash: KeyHash) -> Result<Option<ValueHash>> {
+        let mut node_key = root_key.clone();
+        for depth in 0..=ROOT_NIBBLE_HEIGHT {
+            let Some(Some(node)) = self.nodes.get(&node_key) else {
+                bail!(
+                    "Node at {:?} is needed to check the value with key hash {:?}, but it is not \
+                     in the witness.",
+                    node_key,
+                    key_hash
+                );
+            };
+            match node {
+                Node::Null => return Ok(None),
+                Node::Leaf(leaf_node) => {
+                    return Ok((leaf_node.key_hash() == key_hash).then(|| leaf_node.value_hash()))
+                }
+                Node::Internal(internal_node) => {
+                    let nibble = key_hash.0.get_nibble(depth);
+                    match internal_node.child(nibble) {
+                        None => return Ok(None),
+                        Some(child) => {
+                            node_key = node_key.gen_child_node_key(child.version, nibble)
+                        }
+                    }
+                }
+            }
+        }
+        bail!("Jellyfish Merkle tree has cyclic graph inside.");
+    }
+}
+
+/// A [`TreeReader`] wrapping another one, which records every node and value read through it
+/// into a [`Witness`].
+pub struct RecordingTreeReader<R> {
+    inner: R,
+    witness: RefCell<Witness>,
+}
+
+impl<R> RecordingTreeReader<R> {
+    /// Wraps `inner`, starting with an empty witness.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            witness: Default::default(),
+        }
+    }
+
+    /// Returns the wrapped reader.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns a copy of everything recorded so far.
+    pub fn witness(&self) -> Witness {
+        self.witness.borrow().clone()
+    }
+
+    /// Returns everything recorded so far, and starts recording a new witness.
+    pub fn take_witness(&self) -> Witness {
+        self.witness.take()
+    }
+
+    /// Consumes the reader, returning the wrapped reader and everything recorded.
+    pub fn into_parts(self) -> (R, Witness) {
+        (self.inner, self.witness.into_inner())
+    }
+}
+
+impl<R: TreeReader> TreeReader for RecordingTreeReader<R> {
+    fn get_node_option(&self, node_key: &NodeKey) -> Result<Option<Node>> {
+        let node = self.inner.get_node_option(node_key)?;
+        self.witness
+            .borrow_mut()
+            .nodes
+            .insert(node_key.clone(), node.clone());
+        Ok(node)
+    }
+
+    fn get_value_option(
+        &self,
+        max_version: Version,
+        key_hash: KeyHash,
+    ) -> Result<Option<OwnedValue>> {
+        let value = self.inner.get_value_option(max_version, key_hash)?;
+        self.witness
+            .borrow_mut()
+            .values
+            .insert((max_version, key_hash), value.clone());
+        Ok(value)
+    }
+
+    fn latest_version_at_or_before(&self, version: Version) -> Result<Option<Version>> {
+        let resolved_version = self.inner.latest_version_at_or_before(version)?;
+        self.witness
+            .borrow_mut()
+            .resolved_versions
+            .insert(version, resolved_version);
+        Ok(resolved_version)
+    }
+
+    fn latest_version_to_build_on(&self, version: Version) -> Result<Option<Version>> {
+        let base_version = self.inner.latest_version_to_build_on(version)?;
+        self.witness
+            .borrow_mut()
+            .base_versions
+            .insert(version, base_version);
+        Ok(base_version)
+    }
+
+    /// Restoring a tree is not recorded, so this is passed through to the wrapped reader.
+    fn get_rightmost_leaf(&self) -> Result<Option<(NodeKey, LeafNode)>> {
+        self.inner.get_rightmost_leaf()
+    }
+}
+
+/// A [`TreeReader`] serving nodes and values exclusively from a verified [`Witness`].
+///
+/// Looking up anything that was not recorded in the witness is an error, rather than a missing
+/// node or value, so that an incomplete witness can never be mistaken for an absence.
+#[derive(Clone, Debug)]
+pub struct WitnessTreeReader {
+    witness: Witness,
+}
+
+impl WitnessTreeReader {
+    /// Creates a reader serving from `witness`, after [verifying](Witness::verify) it against the
+    /// hash `root_hash` of the root of the tree at `version`.
+    pub fn new<H: SimpleHasher>(
+        witness: Witness,
+        root_hash: RootHash,
+        version: Version,
+    ) -> Result<Self> {
+        witness.verify::<H>(root_hash, version)?;
+        Ok(Self { witness })
+    }
+
+    /// Returns the witness this reader serves from.
+    pub fn witness(&self) -> &Witness {
+        &self.witness
+    }
+}
+
+impl TreeReader for WitnessTreeReader {
+    fn get_node_option(&self, node_key: &NodeKey) -> Result<Option<Node>> {
+        self.witness
+            .nodes
+            .get(node_key)
+            .cloned()
+            .ok_or_else(|| format_err!("Node at {:?} is not in the witness.", node_key))
+    }
+
+    fn get_value_option(
+        &self,
+        max_version: Version,
+        key_hash: KeyHash,
+    ) -> Result<Option<OwnedValue>> {
+        self.witness
+            .values
+            .get(&(max_version, key_hash))
+            .cloned()
+            .ok_or_else(|| {
+                format_err!(
+                    "Value with max_version {max_version:} and key hash {key_hash:?} is not in \
+                     the witness."
+                )
+            })
+    }
+
+    fn latest_version_at_or_before(&self, version: Version) -> Result<Option<Version>> {
+        self.witness
+            .resolved_versions
+            .get(&version)
+            .copied()
+            .ok_or_else(|| format_err!("Version {} is not resolved in the witness.", version))
+    }
+
+    fn latest_version_to_build_on(&self, version: Version) -> Result<Option<Version>> {
+        self.witness
+            .base_versions
+            .get(&version)
+            .copied()
+            .ok_or_else(|| {
+                format_err!(
+                    "Version {} is not resolved as a base version in the witness.",
+                    version
+                )
+            })
+    }
+
+    fn get_rightmost_leaf(&self) -> Result<Option<(NodeKey, LeafNode)>> {
+        bail!("A witness cannot be used to restore a tree.")
+    }
+}